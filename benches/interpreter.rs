@@ -0,0 +1,68 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use slither::Agent;
+use std::time::Instant;
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("nbody", include_str!("programs/nbody.sl")),
+    ("strings", include_str!("programs/strings.sl")),
+    ("objects", include_str!("programs/objects.sl")),
+    ("properties", include_str!("programs/properties.sl")),
+    ("calls", include_str!("programs/calls.sl")),
+    ("promises", include_str!("programs/promises.sl")),
+    ("deque", include_str!("programs/deque.sl")),
+    ("scopes", include_str!("programs/scopes.sl")),
+];
+
+fn programs(c: &mut Criterion) {
+    for (name, source) in PROGRAMS {
+        // every run of a script gets a new module scope, so one agent can run
+        // the same program over and over.
+        let mut agent = Agent::new();
+        let specifier = format!("{}.sl", name);
+        c.bench_function(name, |b| {
+            b.iter(|| agent.run_to_completion(&specifier, source).unwrap())
+        });
+    }
+}
+
+// fibonacci(30) takes seconds a run, so it gets the fewest samples criterion
+// allows.
+fn fibonacci(c: &mut Criterion) {
+    let mut agent = Agent::new();
+    let source = include_str!("programs/fibonacci.sl");
+    c.bench_function("fibonacci", |b| {
+        b.iter(|| agent.run_to_completion("fibonacci.sl", source).unwrap())
+    });
+}
+
+// the first agent of a thread creates the intrinsics, which the agents after
+// it share.
+fn agents(c: &mut Criterion) {
+    c.bench_function("first agent", |b| {
+        b.iter_custom(|iters| {
+            (0..iters)
+                .map(|_| {
+                    std::thread::spawn(|| {
+                        let start = Instant::now();
+                        let agent = Agent::new();
+                        let elapsed = start.elapsed();
+                        drop(agent);
+                        elapsed
+                    })
+                    .join()
+                    .unwrap()
+                })
+                .sum()
+        })
+    });
+    let _first = Agent::new();
+    c.bench_function("later agent", |b| b.iter_with_large_drop(Agent::new));
+}
+
+criterion_group!(benches, programs, agents);
+criterion_group! {
+    name = slow;
+    config = Criterion::default().sample_size(10);
+    targets = fibonacci
+}
+criterion_main!(benches, slow);
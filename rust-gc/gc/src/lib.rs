@@ -86,6 +86,40 @@ impl<T: Trace> Gc<T> {
     }
 }
 
+impl<T: Trace> Gc<T> {
+    /// The pointer this `Gc` holds, with whether it is rooted in the lowest
+    /// bit, so it can be packed into another word.
+    pub fn bits(this: &Self) -> usize {
+        this.ptr_root.get().as_ptr() as usize
+    }
+
+    /// Consumes the `Gc`, returning its bits. To avoid a leak they must be
+    /// turned back into a `Gc` with [`Gc::from_bits`][from_bits].
+    ///
+    /// [from_bits]: struct.Gc.html#method.from_bits
+    pub fn into_bits(this: Self) -> usize {
+        let bits = Gc::bits(&this);
+        mem::forget(this);
+        bits
+    }
+
+    /// Constructs a `Gc` from bits returned by [`Gc::into_bits`][into_bits]
+    /// or [`Gc::bits`][bits], including whether it is rooted.
+    ///
+    /// This function is unsafe for the same reasons as `Gc::from_raw`, and
+    /// also because the `Gc` must not be dropped or have its rooting changed
+    /// unless it owns the bits.
+    ///
+    /// [into_bits]: struct.Gc.html#method.into_bits
+    /// [bits]: struct.Gc.html#method.bits
+    pub unsafe fn from_bits(bits: usize) -> Self {
+        Gc {
+            ptr_root: Cell::new(NonNull::new_unchecked(bits as *mut GcBox<T>)),
+            marker: PhantomData,
+        }
+    }
+}
+
 /// Returns the given pointer with its root bit cleared.
 unsafe fn clear_root_bit<T: ?Sized + Trace>(ptr: NonNull<GcBox<T>>) -> NonNull<GcBox<T>> {
     let mut ptr = ptr.as_ptr();
@@ -512,6 +546,43 @@ impl<T: Trace + ?Sized> GcCell<T> {
             }
         }
     }
+
+    /// Mutably borrows the wrapped value for the duration of `f`, without
+    /// rooting its contents the way `borrow_mut` does. Rooting visits every
+    /// value in the cell, so this is what large collections want for small
+    /// changes.
+    ///
+    /// `f` is passed whether the contents are rooted, which is the case
+    /// exactly when the cell itself is.
+    ///
+    /// # Safety
+    ///
+    /// `f` must not allocate with `Gc::new`, as a collection would not trace
+    /// the contents while they are borrowed. When the contents aren't rooted,
+    /// `f` must unroot values it moves into the cell and root values it moves
+    /// out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    #[inline]
+    pub unsafe fn with_unrooted_mut<R>(&self, f: impl FnOnce(&mut T, bool) -> R) -> R {
+        struct Release<'a>(&'a Cell<BorrowFlag>);
+
+        impl Drop for Release<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get().set_unused());
+            }
+        }
+
+        if self.flags.get().borrowed() != BorrowState::Unused {
+            panic!("GcCell<T> already borrowed");
+        }
+        self.flags.set(self.flags.get().set_writing());
+        let _release = Release(&self.flags);
+
+        f(&mut *self.cell.get(), self.flags.get().rooted())
+    }
 }
 
 impl<T: Trace + ?Sized> Finalize for GcCell<T> {}
@@ -0,0 +1,130 @@
+//! Argument checking for builtin functions, so that every builtin reports a
+//! wrong or missing argument the same way:
+//!
+//! ```text
+//! Promise: argument 1 must be a function, received number
+//! Promise: argument 1 must be a function, received nothing
+//! ```
+//!
+//! Most builtins use `check_args!`, which checks each argument against a
+//! kind and evaluates to a tuple of the converted values:
+//!
+//! ```ignore
+//! let (target, count): (Value, f64) = check_args!(agent, args, "describe", [any, number]);
+//! ```
+//!
+//! The kinds are the functions in this module which take an argument index.
+//! They are `#[track_caller]`, so the `nativeFrame` of an error they return
+//! points at the builtin rather than at this module.
+
+use crate::agent::Agent;
+use crate::value::{Value, ValueKind};
+
+macro_rules! check_args {
+    ($agent:expr, $args:expr, $name:expr, [$($kind:ident),* $(,)?]) => {{
+        let mut _index = 0;
+        ($({
+            let value = $crate::args::$kind($agent, &$args, $name, _index)?;
+            _index += 1;
+            value
+        },)*)
+    }};
+}
+
+/// The error for a value which is not of the `expected` kind. `position`
+/// says which value it is, like "argument 2" or "receiver", and `received`
+/// is None when an argument was not passed at all.
+#[track_caller]
+pub fn type_error(
+    agent: &Agent,
+    name: &str,
+    position: &str,
+    expected: &str,
+    received: Option<&Value>,
+) -> Value {
+    let received = match received {
+        Some(value) => value.type_of(),
+        None => "nothing",
+    };
+    Value::new_error(
+        agent,
+        &format!(
+            "{}: {} must be {}, received {}",
+            name, position, expected, received
+        ),
+    )
+}
+
+#[track_caller]
+fn check<T>(
+    agent: &Agent,
+    args: &[Value],
+    name: &str,
+    index: usize,
+    expected: &str,
+    convert: impl FnOnce(&Value) -> Option<T>,
+) -> Result<T, Value> {
+    let value = args.get(index);
+    match value.and_then(convert) {
+        Some(value) => Ok(value),
+        None => Err(type_error(
+            agent,
+            name,
+            &format!("argument {}", index + 1),
+            expected,
+            value,
+        )),
+    }
+}
+
+/// Any value, which must be passed even if it is null.
+#[track_caller]
+pub fn any(agent: &Agent, args: &[Value], name: &str, index: usize) -> Result<Value, Value> {
+    check(agent, args, name, index, "a value", |v| Some(v.clone()))
+}
+
+/// Any value, or null if it was not passed.
+pub fn optional(_agent: &Agent, args: &[Value], _name: &str, index: usize) -> Result<Value, Value> {
+    Ok(args.get(index).cloned().unwrap_or(Value::Null))
+}
+
+// no builtin takes a number which must not be converted at the moment
+#[allow(dead_code)]
+#[track_caller]
+pub fn number(agent: &Agent, args: &[Value], name: &str, index: usize) -> Result<f64, Value> {
+    check(agent, args, name, index, "a number", |v| match v.kind() {
+        ValueKind::Number(n) => Some(n),
+        _ => None,
+    })
+}
+
+#[track_caller]
+pub fn string(agent: &Agent, args: &[Value], name: &str, index: usize) -> Result<String, Value> {
+    check(agent, args, name, index, "a string", |v| match v.kind() {
+        ValueKind::String(s) => Some(s.clone()),
+        _ => None,
+    })
+}
+
+/// An object of any kind, including functions.
+#[track_caller]
+pub fn object(agent: &Agent, args: &[Value], name: &str, index: usize) -> Result<Value, Value> {
+    check(agent, args, name, index, "an object", |v| {
+        if v.is_object() {
+            Some(v.clone())
+        } else {
+            None
+        }
+    })
+}
+
+#[track_caller]
+pub fn function(agent: &Agent, args: &[Value], name: &str, index: usize) -> Result<Value, Value> {
+    check(agent, args, name, index, "a function", |v| {
+        if v.type_of() == "function" {
+            Some(v.clone())
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,836 @@
+//! The parse tree as JSON, for tools outside the crate. Every node is an
+//! object with a `type`, the name of its `Node` variant, and its children
+//! by name. Operators are their variant names, function kinds a list of
+//! flags, and numbers which JSON can't hold (`NaN` and the infinities) are
+//! strings. Statements carry a `start` and an `end`, each a `line`,
+//! `column` and char `index` in the source, from the positions their block
+//! keeps. Expressions carry none, as the parser folds constants, rewrites
+//! pipelines and drops dead branches while it builds them, so the tree only
+//! knows where its statements are, which is where errors are reported.
+
+use crate::parser::{FunctionKind, Node, Operator, Scope, ScopeKind, SourcePosition, SourceSpan};
+use indexmap::IndexMap;
+use num::BigInt;
+use serde_json::{Map, Value};
+
+const OPERATORS: &[Operator] = &[
+    Operator::Add,
+    Operator::AddAssign,
+    Operator::Sub,
+    Operator::SubAssign,
+    Operator::Mul,
+    Operator::MulAssign,
+    Operator::Pow,
+    Operator::PowAssign,
+    Operator::Div,
+    Operator::DivAssign,
+    Operator::Mod,
+    Operator::ModAssign,
+    Operator::LeftShift,
+    Operator::RightShift,
+    Operator::UnsignedRightShift,
+    Operator::UnsignedRightShiftAssign,
+    Operator::GreaterThan,
+    Operator::GreaterThanOrEqual,
+    Operator::Not,
+    Operator::LessThan,
+    Operator::LessThanOrEqual,
+    Operator::BitwiseAND,
+    Operator::BitwiseOR,
+    Operator::BitwiseXOR,
+    Operator::BitwiseNOT,
+    Operator::LogicalAND,
+    Operator::LogicalOR,
+    Operator::Assign,
+    Operator::Equal,
+    Operator::NotEqual,
+    Operator::Typeof,
+    Operator::Void,
+    Operator::Delete,
+    Operator::Has,
+    Operator::Range,
+    Operator::RangeInclusive,
+];
+
+impl Node {
+    pub fn to_json(&self) -> String {
+        to_value(self).to_string()
+    }
+
+    pub fn from_json(json: &str) -> Result<Node, String> {
+        let value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        from_value(&value)
+    }
+}
+
+fn object(kind: &str, fields: Vec<(&str, Value)>) -> Value {
+    let mut map = Map::new();
+    map.insert("type".to_string(), Value::from(kind));
+    for (name, value) in fields {
+        map.insert(name.to_string(), value);
+    }
+    Value::Object(map)
+}
+
+fn nodes(list: &[Node]) -> Value {
+    Value::Array(list.iter().map(to_value).collect())
+}
+
+fn optional(node: &Option<Box<Node>>) -> Value {
+    node.as_ref().map_or(Value::Null, |n| to_value(n))
+}
+
+fn position(position: &SourcePosition) -> Value {
+    let mut map = Map::new();
+    map.insert("index".to_string(), Value::from(position.index));
+    map.insert("line".to_string(), Value::from(position.line));
+    map.insert("column".to_string(), Value::from(position.column));
+    Value::Object(map)
+}
+
+fn strings(list: &[String]) -> Value {
+    Value::Array(list.iter().map(|s| Value::from(s.as_str())).collect())
+}
+
+fn optional_string(s: &Option<String>) -> Value {
+    s.as_ref().map_or(Value::Null, |s| Value::from(s.as_str()))
+}
+
+fn number(n: f64) -> Value {
+    if n.is_finite() {
+        Value::from(n)
+    } else {
+        Value::from(crate::num_util::to_string(n))
+    }
+}
+
+fn operator(op: Operator) -> Value {
+    Value::from(format!("{:?}", op))
+}
+
+fn function_kind(kind: FunctionKind) -> Value {
+    Value::Array(kind.names().map(Value::from).collect())
+}
+
+fn to_value(node: &Node) -> Value {
+    match node {
+        Node::NullLiteral => object("NullLiteral", vec![]),
+        Node::TrueLiteral => object("TrueLiteral", vec![]),
+        Node::FalseLiteral => object("FalseLiteral", vec![]),
+        Node::NumberLiteral(n) => object("NumberLiteral", vec![("value", number(*n))]),
+        Node::BigIntLiteral(n) => {
+            object("BigIntLiteral", vec![("value", Value::from(n.to_string()))])
+        }
+        Node::StringLiteral(s) => object("StringLiteral", vec![("value", Value::from(s.as_str()))]),
+        Node::SymbolLiteral(s) => object("SymbolLiteral", vec![("name", Value::from(s.as_str()))]),
+        Node::RegexLiteral(s) => object("RegexLiteral", vec![("pattern", Value::from(s.as_str()))]),
+        Node::ObjectLiteral(list) => object("ObjectLiteral", vec![("properties", nodes(list))]),
+        Node::ArrayLiteral(list) => object("ArrayLiteral", vec![("elements", nodes(list))]),
+        Node::TupleLiteral(list) => object("TupleLiteral", vec![("elements", nodes(list))]),
+        Node::TemplateLiteral(quasis, expressions) => object(
+            "TemplateLiteral",
+            vec![
+                ("quasis", strings(quasis)),
+                ("expressions", nodes(expressions)),
+            ],
+        ),
+        Node::Identifier(name) => object("Identifier", vec![("name", Value::from(name.as_str()))]),
+        Node::Block(scope, body) => {
+            // a list rather than an object, to keep the order of declaration
+            let declarations = scope
+                .bindings
+                .iter()
+                .map(|(name, mutable)| {
+                    let mut map = Map::new();
+                    map.insert("name".to_string(), Value::from(name.as_str()));
+                    map.insert("mutable".to_string(), Value::from(*mutable));
+                    Value::Object(map)
+                })
+                .collect();
+            // a block changed since it was parsed may not know where its
+            // statements are
+            let spans = if scope.positions.len() == body.len() {
+                &scope.positions[..]
+            } else {
+                &[]
+            };
+            let statements = body
+                .iter()
+                .enumerate()
+                .map(|(i, statement)| {
+                    let mut value = to_value(statement);
+                    if let (Some(span), Value::Object(map)) = (spans.get(i), &mut value) {
+                        map.insert("start".to_string(), position(&span.start));
+                        map.insert("end".to_string(), position(&span.end));
+                    }
+                    value
+                })
+                .collect();
+            let kind = match scope.kind {
+                ScopeKind::TopLevel => "TopLevel",
+                ScopeKind::Block => "Block",
+            };
+            object(
+                "Block",
+                vec![
+                    ("scope", Value::from(kind)),
+                    ("declarations", Value::Array(declarations)),
+                    ("body", Value::Array(statements)),
+                ],
+            )
+        }
+        Node::IfStatement(test, consequent, alternate) => object(
+            "IfStatement",
+            vec![
+                ("test", to_value(test)),
+                ("consequent", to_value(consequent)),
+                ("alternate", optional(alternate)),
+            ],
+        ),
+        Node::ConditionalExpression(test, consequent, alternate) => object(
+            "ConditionalExpression",
+            vec![
+                ("test", to_value(test)),
+                ("consequent", to_value(consequent)),
+                ("alternate", to_value(alternate)),
+            ],
+        ),
+        Node::WhileLoop(test, body) => object(
+            "WhileLoop",
+            vec![("test", to_value(test)), ("body", to_value(body))],
+        ),
+        Node::ForLoop(is_await, binding, iterable, body) => object(
+            "ForLoop",
+            vec![
+                ("await", Value::from(*is_await)),
+                ("binding", Value::from(binding.as_str())),
+                ("iterable", to_value(iterable)),
+                ("body", to_value(body)),
+            ],
+        ),
+        Node::ExpressionStatement(expression) => object(
+            "ExpressionStatement",
+            vec![("expression", to_value(expression))],
+        ),
+        Node::UnaryExpression(op, argument) => object(
+            "UnaryExpression",
+            vec![
+                ("operator", operator(*op)),
+                ("argument", to_value(argument)),
+            ],
+        ),
+        Node::BinaryExpression(op, left, right) => object(
+            "BinaryExpression",
+            vec![
+                ("operator", operator(*op)),
+                ("left", to_value(left)),
+                ("right", to_value(right)),
+            ],
+        ),
+        Node::ParenthesizedExpression(expression) => object(
+            "ParenthesizedExpression",
+            vec![("expression", to_value(expression))],
+        ),
+        Node::YieldExpression(argument) => {
+            object("YieldExpression", vec![("argument", optional(argument))])
+        }
+        Node::AwaitExpression(argument) => {
+            object("AwaitExpression", vec![("argument", to_value(argument))])
+        }
+        Node::ThisExpression => object("ThisExpression", vec![]),
+        Node::ImportMeta => object("ImportMeta", vec![]),
+        Node::NewExpression(expression) => {
+            object("NewExpression", vec![("expression", to_value(expression))])
+        }
+        Node::MatchExpression(discriminant, arms) => object(
+            "MatchExpression",
+            vec![
+                ("discriminant", to_value(discriminant)),
+                ("arms", nodes(arms)),
+            ],
+        ),
+        Node::MatchArm(pattern, consequent) => object(
+            "MatchArm",
+            vec![
+                ("pattern", to_value(pattern)),
+                ("consequent", to_value(consequent)),
+            ],
+        ),
+        Node::ObjectPattern(properties, rest) => {
+            let properties = properties
+                .iter()
+                .map(|(key, value)| {
+                    let mut map = Map::new();
+                    map.insert("key".to_string(), Value::from(key.as_str()));
+                    map.insert("value".to_string(), to_value(value));
+                    Value::Object(map)
+                })
+                .collect();
+            object(
+                "ObjectPattern",
+                vec![
+                    ("properties", Value::Array(properties)),
+                    ("rest", Value::from(*rest)),
+                ],
+            )
+        }
+        Node::ArrayPattern(elements, rest) => object(
+            "ArrayPattern",
+            vec![("elements", nodes(elements)), ("rest", Value::from(*rest))],
+        ),
+        Node::TuplePattern(elements) => object("TuplePattern", vec![("elements", nodes(elements))]),
+        Node::MemberExpression(base, property) => object(
+            "MemberExpression",
+            vec![
+                ("object", to_value(base)),
+                ("property", Value::from(property.as_str())),
+            ],
+        ),
+        Node::ComputedMemberExpression(base, property) => object(
+            "ComputedMemberExpression",
+            vec![("object", to_value(base)), ("property", to_value(property))],
+        ),
+        Node::CallExpression(callee, arguments) => object(
+            "CallExpression",
+            vec![
+                ("callee", to_value(callee)),
+                ("arguments", nodes(arguments)),
+            ],
+        ),
+        Node::TailCallExpression(callee, arguments) => object(
+            "TailCallExpression",
+            vec![
+                ("callee", to_value(callee)),
+                ("arguments", nodes(arguments)),
+            ],
+        ),
+        Node::FunctionExpression(kind, name, params, body) => object(
+            "FunctionExpression",
+            vec![
+                ("kind", function_kind(*kind)),
+                ("name", optional_string(name)),
+                ("params", nodes(params)),
+                ("body", to_value(body)),
+            ],
+        ),
+        Node::FunctionDeclaration(kind, name, params, body) => object(
+            "FunctionDeclaration",
+            vec![
+                ("kind", function_kind(*kind)),
+                ("name", Value::from(name.as_str())),
+                ("params", nodes(params)),
+                ("body", to_value(body)),
+            ],
+        ),
+        Node::ArrowFunctionExpression(kind, params, body) => object(
+            "ArrowFunctionExpression",
+            vec![
+                ("kind", function_kind(*kind)),
+                ("params", nodes(params)),
+                ("body", to_value(body)),
+            ],
+        ),
+        Node::ClassExpression(name, extends, members) => object(
+            "ClassExpression",
+            vec![
+                ("name", Value::from(name.as_str())),
+                ("extends", optional(extends)),
+                ("members", nodes(members)),
+            ],
+        ),
+        Node::ClassDeclaration(name, extends, members) => object(
+            "ClassDeclaration",
+            vec![
+                ("name", Value::from(name.as_str())),
+                ("extends", optional(extends)),
+                ("members", nodes(members)),
+            ],
+        ),
+        Node::LexicalInitialization(name, init) => object(
+            "LexicalInitialization",
+            vec![
+                ("name", Value::from(name.as_str())),
+                ("init", to_value(init)),
+            ],
+        ),
+        Node::ReturnStatement(argument) => {
+            object("ReturnStatement", vec![("argument", optional(argument))])
+        }
+        Node::ThrowStatement(argument) => {
+            object("ThrowStatement", vec![("argument", to_value(argument))])
+        }
+        Node::BreakStatement(label) => {
+            object("BreakStatement", vec![("label", optional_string(label))])
+        }
+        Node::ContinueStatement(label) => {
+            object("ContinueStatement", vec![("label", optional_string(label))])
+        }
+        Node::LabeledStatement(label, body) => object(
+            "LabeledStatement",
+            vec![
+                ("label", Value::from(label.as_str())),
+                ("body", to_value(body)),
+            ],
+        ),
+        Node::TryStatement(block, binding, handler, finalizer) => object(
+            "TryStatement",
+            vec![
+                ("block", to_value(block)),
+                ("binding", optional_string(binding)),
+                ("handler", optional(handler)),
+                ("finalizer", optional(finalizer)),
+            ],
+        ),
+        Node::ImportDeclaration(specifier) => object(
+            "ImportDeclaration",
+            vec![("specifier", Value::from(specifier.as_str()))],
+        ),
+        Node::ImportNamedDeclaration(specifier, names) => object(
+            "ImportNamedDeclaration",
+            vec![
+                ("specifier", Value::from(specifier.as_str())),
+                ("names", strings(names)),
+            ],
+        ),
+        Node::ImportDefaultDeclaration(specifier, name, kind) => object(
+            "ImportDefaultDeclaration",
+            vec![
+                ("specifier", Value::from(specifier.as_str())),
+                ("name", Value::from(name.as_str())),
+                ("kind", optional_string(kind)),
+            ],
+        ),
+        Node::ImportStandardDeclaration(module, names) => object(
+            "ImportStandardDeclaration",
+            vec![
+                ("module", Value::from(module.as_str())),
+                ("names", strings(names)),
+            ],
+        ),
+        Node::ExportDeclaration(declaration) => object(
+            "ExportDeclaration",
+            vec![("declaration", to_value(declaration))],
+        ),
+        Node::Initializer(target, init) => object(
+            "Initializer",
+            vec![("target", to_value(target)), ("init", to_value(init))],
+        ),
+        Node::RestElement(name) => {
+            object("RestElement", vec![("name", Value::from(name.as_str()))])
+        }
+        Node::SpreadElement(argument) => {
+            object("SpreadElement", vec![("argument", to_value(argument))])
+        }
+        Node::ObjectSpread(argument) => {
+            object("ObjectSpread", vec![("argument", to_value(argument))])
+        }
+        Node::ErrorNode => object("ErrorNode", vec![]),
+    }
+}
+
+// the fields of one node, for reading them back with errors which say where
+// the JSON went wrong.
+struct Fields<'a> {
+    kind: &'a str,
+    value: &'a Value,
+}
+
+impl<'a> Fields<'a> {
+    fn get(&self, name: &str) -> Result<&'a Value, String> {
+        self.value
+            .get(name)
+            .ok_or_else(|| format!("{} is missing `{}`", self.kind, name))
+    }
+
+    fn invalid(&self, name: &str) -> String {
+        format!("{} has an invalid `{}`", self.kind, name)
+    }
+
+    fn node(&self, name: &str) -> Result<Box<Node>, String> {
+        Ok(Box::new(from_value(self.get(name)?)?))
+    }
+
+    fn optional(&self, name: &str) -> Result<Option<Box<Node>>, String> {
+        match self.get(name)? {
+            Value::Null => Ok(None),
+            v => Ok(Some(Box::new(from_value(v)?))),
+        }
+    }
+
+    fn nodes(&self, name: &str) -> Result<Vec<Node>, String> {
+        match self.get(name)? {
+            Value::Array(list) => list.iter().map(from_value).collect(),
+            _ => Err(self.invalid(name)),
+        }
+    }
+
+    fn string(&self, name: &str) -> Result<String, String> {
+        match self.get(name)? {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(self.invalid(name)),
+        }
+    }
+
+    fn optional_string(&self, name: &str) -> Result<Option<String>, String> {
+        match self.get(name)? {
+            Value::Null => Ok(None),
+            Value::String(s) => Ok(Some(s.clone())),
+            _ => Err(self.invalid(name)),
+        }
+    }
+
+    fn strings(&self, name: &str) -> Result<Vec<String>, String> {
+        match self.get(name)? {
+            Value::Array(list) => list
+                .iter()
+                .map(|s| match s {
+                    Value::String(s) => Ok(s.clone()),
+                    _ => Err(self.invalid(name)),
+                })
+                .collect(),
+            _ => Err(self.invalid(name)),
+        }
+    }
+
+    fn boolean(&self, name: &str) -> Result<bool, String> {
+        self.get(name)?.as_bool().ok_or_else(|| self.invalid(name))
+    }
+
+    fn number(&self, name: &str) -> Result<f64, String> {
+        match self.get(name)? {
+            Value::Number(n) => n.as_f64().ok_or_else(|| self.invalid(name)),
+            Value::String(s) if s == "NaN" => Ok(std::f64::NAN),
+            Value::String(s) if s == "Infinity" => Ok(std::f64::INFINITY),
+            Value::String(s) if s == "-Infinity" => Ok(std::f64::NEG_INFINITY),
+            _ => Err(self.invalid(name)),
+        }
+    }
+
+    fn operator(&self) -> Result<Operator, String> {
+        let name = self.string("operator")?;
+        OPERATORS
+            .iter()
+            .find(|op| format!("{:?}", op) == name)
+            .cloned()
+            .ok_or_else(|| self.invalid("operator"))
+    }
+
+    fn function_kind(&self) -> Result<FunctionKind, String> {
+        let mut bits = 0;
+        for flag in self.strings("kind")? {
+            match FunctionKind::NAMES.iter().find(|(name, _)| *name == flag) {
+                Some((_, kind)) => bits |= kind.bits(),
+                None => return Err(self.invalid("kind")),
+            }
+        }
+        FunctionKind::from_bits(bits).ok_or_else(|| self.invalid("kind"))
+    }
+
+    // the statements of a block, and where they are if every one of them
+    // says so
+    fn statements(&self) -> Result<(Vec<Node>, Vec<SourceSpan>), String> {
+        let list = match self.get("body")? {
+            Value::Array(list) => list,
+            _ => return Err(self.invalid("body")),
+        };
+        let mut statements = Vec::new();
+        let mut spans = Vec::new();
+        for statement in list {
+            statements.push(from_value(statement)?);
+            let position = |name| {
+                let field = |field| statement.get(name)?.get(field)?.as_u64();
+                match (field("index"), field("line"), field("column")) {
+                    (Some(index), Some(line), Some(column)) => Some(SourcePosition {
+                        index: index as usize,
+                        line: line as usize,
+                        column: column as usize,
+                    }),
+                    _ => None,
+                }
+            };
+            match (position("start"), position("end")) {
+                (Some(start), Some(end)) => spans.push(SourceSpan { start, end }),
+                (None, None)
+                    if statement.get("start").is_none() && statement.get("end").is_none() => {}
+                _ => return Err(self.invalid("body")),
+            }
+        }
+        if !spans.is_empty() && spans.len() != statements.len() {
+            return Err(self.invalid("body"));
+        }
+        Ok((statements, spans))
+    }
+
+    fn scope(&self, positions: Vec<SourceSpan>) -> Result<Scope, String> {
+        let kind = match self.string("scope")?.as_str() {
+            "TopLevel" => ScopeKind::TopLevel,
+            "Block" => ScopeKind::Block,
+            _ => return Err(self.invalid("scope")),
+        };
+        let mut bindings = IndexMap::new();
+        match self.get("declarations")? {
+            Value::Array(list) => {
+                for declaration in list {
+                    let name = declaration.get("name").and_then(Value::as_str);
+                    let mutable = declaration.get("mutable").and_then(Value::as_bool);
+                    match (name, mutable) {
+                        (Some(name), Some(mutable)) => {
+                            bindings.insert(name.to_string(), mutable);
+                        }
+                        _ => return Err(self.invalid("declarations")),
+                    }
+                }
+            }
+            _ => return Err(self.invalid("declarations")),
+        }
+        Ok(Scope {
+            kind,
+            bindings,
+            positions,
+        })
+    }
+
+    fn properties(&self) -> Result<IndexMap<String, Node>, String> {
+        let mut properties = IndexMap::new();
+        match self.get("properties")? {
+            Value::Array(list) => {
+                for property in list {
+                    match (
+                        property.get("key").and_then(Value::as_str),
+                        property.get("value"),
+                    ) {
+                        (Some(key), Some(value)) => {
+                            properties.insert(key.to_string(), from_value(value)?);
+                        }
+                        _ => return Err(self.invalid("properties")),
+                    }
+                }
+            }
+            _ => return Err(self.invalid("properties")),
+        }
+        Ok(properties)
+    }
+}
+
+fn from_value(value: &Value) -> Result<Node, String> {
+    let kind = match value.get("type") {
+        Some(Value::String(kind)) => kind.as_str(),
+        _ => return Err(format!("not a node: {}", value)),
+    };
+    let f = Fields { kind, value };
+    Ok(match kind {
+        "NullLiteral" => Node::NullLiteral,
+        "TrueLiteral" => Node::TrueLiteral,
+        "FalseLiteral" => Node::FalseLiteral,
+        "NumberLiteral" => Node::NumberLiteral(f.number("value")?),
+        "BigIntLiteral" => match f.string("value")?.parse::<BigInt>() {
+            Ok(n) => Node::BigIntLiteral(n),
+            Err(_) => return Err(f.invalid("value")),
+        },
+        "StringLiteral" => Node::StringLiteral(f.string("value")?),
+        "SymbolLiteral" => Node::SymbolLiteral(f.string("name")?),
+        "RegexLiteral" => Node::RegexLiteral(f.string("pattern")?),
+        "ObjectLiteral" => Node::ObjectLiteral(f.nodes("properties")?),
+        "ArrayLiteral" => Node::ArrayLiteral(f.nodes("elements")?),
+        "TupleLiteral" => Node::TupleLiteral(f.nodes("elements")?),
+        "TemplateLiteral" => Node::TemplateLiteral(f.strings("quasis")?, f.nodes("expressions")?),
+        "Identifier" => Node::Identifier(f.string("name")?),
+        "Block" => {
+            let (body, positions) = f.statements()?;
+            Node::Block(f.scope(positions)?, body)
+        }
+        "IfStatement" => Node::IfStatement(
+            f.node("test")?,
+            f.node("consequent")?,
+            f.optional("alternate")?,
+        ),
+        "ConditionalExpression" => Node::ConditionalExpression(
+            f.node("test")?,
+            f.node("consequent")?,
+            f.node("alternate")?,
+        ),
+        "WhileLoop" => Node::WhileLoop(f.node("test")?, f.node("body")?),
+        "ForLoop" => Node::ForLoop(
+            f.boolean("await")?,
+            f.string("binding")?,
+            f.node("iterable")?,
+            f.node("body")?,
+        ),
+        "ExpressionStatement" => Node::ExpressionStatement(f.node("expression")?),
+        "UnaryExpression" => Node::UnaryExpression(f.operator()?, f.node("argument")?),
+        "BinaryExpression" => {
+            Node::BinaryExpression(f.operator()?, f.node("left")?, f.node("right")?)
+        }
+        "ParenthesizedExpression" => Node::ParenthesizedExpression(f.node("expression")?),
+        "YieldExpression" => Node::YieldExpression(f.optional("argument")?),
+        "AwaitExpression" => Node::AwaitExpression(f.node("argument")?),
+        "ThisExpression" => Node::ThisExpression,
+        "ImportMeta" => Node::ImportMeta,
+        "NewExpression" => Node::NewExpression(f.node("expression")?),
+        "MatchExpression" => Node::MatchExpression(f.node("discriminant")?, f.nodes("arms")?),
+        "MatchArm" => Node::MatchArm(f.node("pattern")?, f.node("consequent")?),
+        "ObjectPattern" => Node::ObjectPattern(f.properties()?, f.boolean("rest")?),
+        "ArrayPattern" => Node::ArrayPattern(f.nodes("elements")?, f.boolean("rest")?),
+        "TuplePattern" => Node::TuplePattern(f.nodes("elements")?),
+        "MemberExpression" => Node::MemberExpression(f.node("object")?, f.string("property")?),
+        "ComputedMemberExpression" => {
+            Node::ComputedMemberExpression(f.node("object")?, f.node("property")?)
+        }
+        "CallExpression" => Node::CallExpression(f.node("callee")?, f.nodes("arguments")?),
+        "TailCallExpression" => Node::TailCallExpression(f.node("callee")?, f.nodes("arguments")?),
+        "FunctionExpression" => Node::FunctionExpression(
+            f.function_kind()?,
+            f.optional_string("name")?,
+            f.nodes("params")?,
+            f.node("body")?,
+        ),
+        "FunctionDeclaration" => Node::FunctionDeclaration(
+            f.function_kind()?,
+            f.string("name")?,
+            f.nodes("params")?,
+            f.node("body")?,
+        ),
+        "ArrowFunctionExpression" => {
+            Node::ArrowFunctionExpression(f.function_kind()?, f.nodes("params")?, f.node("body")?)
+        }
+        "ClassExpression" => Node::ClassExpression(
+            f.string("name")?,
+            f.optional("extends")?,
+            f.nodes("members")?,
+        ),
+        "ClassDeclaration" => Node::ClassDeclaration(
+            f.string("name")?,
+            f.optional("extends")?,
+            f.nodes("members")?,
+        ),
+        "LexicalInitialization" => Node::LexicalInitialization(f.string("name")?, f.node("init")?),
+        "ReturnStatement" => Node::ReturnStatement(f.optional("argument")?),
+        "ThrowStatement" => Node::ThrowStatement(f.node("argument")?),
+        "BreakStatement" => Node::BreakStatement(f.optional_string("label")?),
+        "ContinueStatement" => Node::ContinueStatement(f.optional_string("label")?),
+        "LabeledStatement" => Node::LabeledStatement(f.string("label")?, f.node("body")?),
+        "TryStatement" => Node::TryStatement(
+            f.node("block")?,
+            f.optional_string("binding")?,
+            f.optional("handler")?,
+            f.optional("finalizer")?,
+        ),
+        "ImportDeclaration" => Node::ImportDeclaration(f.string("specifier")?),
+        "ImportNamedDeclaration" => {
+            Node::ImportNamedDeclaration(f.string("specifier")?, f.strings("names")?)
+        }
+        "ImportDefaultDeclaration" => Node::ImportDefaultDeclaration(
+            f.string("specifier")?,
+            f.string("name")?,
+            f.optional_string("kind")?,
+        ),
+        "ImportStandardDeclaration" => {
+            Node::ImportStandardDeclaration(f.string("module")?, f.strings("names")?)
+        }
+        "ExportDeclaration" => Node::ExportDeclaration(f.node("declaration")?),
+        "Initializer" => Node::Initializer(f.node("target")?, f.node("init")?),
+        "RestElement" => Node::RestElement(f.string("name")?),
+        "SpreadElement" => Node::SpreadElement(f.node("argument")?),
+        "ObjectSpread" => Node::ObjectSpread(f.node("argument")?),
+        "ErrorNode" => Node::ErrorNode,
+        _ => return Err(format!("unknown node type `{}`", kind)),
+    })
+}
+
+#[test]
+fn test_json_round_trip() {
+    let sources = [
+        include_str!("../test/exports.sl"),
+        include_str!("../test/fib.sl"),
+        include_str!("../test/finally.sl"),
+        include_str!("../test/module.sl"),
+        include_str!("../test/test.sl"),
+        "import './a'; import b from './b'; import c from './c' as json; const o = { a: 1, [b]: 2, c() { return this; } };",
+        "o.a; o[b]; new A(1); typeof o; delete o.a; void 0; ~o; -o;",
+        "const t = `x ${o} y`; const s = :sym; const r = /a+b/; const big = 12345678901234567890n;",
+        "const n = 0 / 0; const i = 1 / 0; const j = -1 / 0; const f = 1.5e-7;",
+        "let a = 1; a += 2; a **= 2; a = a < 1 ? a >= 2 : a != 3 && !a || a has 'x';",
+        "gen function g() { yield; yield 1; } async function f() { await g(); }",
+        "const h = async (x, ...y) => y; const k = (a) => k(a);",
+        "const v = match x { 1 => 'a', { a, b: [c, ...] } => c, (d, _) => d, [e, f] => { e; } };",
+        "async function l(y) { outer: for await x in y { if x { break outer; } else { continue; } } }",
+        "try { throw 1; } catch e { o; } finally { o; } try { o; } catch { o; }",
+        "class A extends B { constructor(a = 1) { this.a = a; } m() { return this.a; } }",
+        "const C = class C {}; for i in 0..10 { (i..=5, i); f(...i); }",
+        "const o = { ...a, b: 1, ...{ c } };",
+    ];
+    for source in sources.iter() {
+        let ast = crate::Parser::parse(source).unwrap();
+        let json = ast.to_json();
+        // NaN isn't equal to itself, so compare what the trees print as
+        let parsed = Node::from_json(&json).unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", ast));
+        assert_eq!(parsed.to_json(), json);
+    }
+    let (ast, _) = crate::Parser::parse_with_recovery("1 +;\nlet a = 2;");
+    assert_eq!(Node::from_json(&ast.to_json()), Ok(ast));
+}
+
+#[test]
+fn test_json_snapshot() {
+    let ast = crate::Parser::parse("const add = async (a, b = 1) => a + b;\nadd(2);").unwrap();
+    // serde_json writes the keys of an object sorted
+    assert_eq!(
+        ast.to_json(),
+        concat!(
+            r#"{"body":[{"end":{"column":39,"index":38,"line":1},"init":{"body":{"body":[{"argument":"#,
+            r#"{"left":{"name":"a","type":"Identifier"},"operator":"Add","right":{"name":"b","#,
+            r#""type":"Identifier"},"type":"BinaryExpression"},"end":{"column":38,"index":37,"line":1},"#,
+            r#""start":{"column":33,"index":32,"line":1},"type":"ReturnStatement"}],"declarations":[],"#,
+            r#""scope":"Block","type":"Block"},"kind":["async","arrow"],"params":[{"name":"a","#,
+            r#""type":"Identifier"},{"init":{"type":"NumberLiteral","value":1.0},"target":{"name":"b","#,
+            r#""type":"Identifier"},"type":"Initializer"}],"type":"ArrowFunctionExpression"},"name":"add","#,
+            r#""start":{"column":1,"index":0,"line":1},"type":"LexicalInitialization"},"#,
+            r#"{"end":{"column":8,"index":46,"line":2},"expression":{"arguments":[{"type":"NumberLiteral","#,
+            r#""value":2.0}],"callee":{"name":"add","type":"Identifier"},"type":"CallExpression"},"#,
+            r#""start":{"column":1,"index":39,"line":2},"type":"ParenthesizedExpression"}],"#,
+            r#""declarations":[{"mutable":false,"name":"add"}],"scope":"TopLevel","type":"Block"}"#,
+        )
+    );
+    assert!(Node::from_json(r#"{"type":"Nope"}"#).is_err());
+    assert!(Node::from_json(r#"{"type":"BinaryExpression","operator":"Add"}"#).is_err());
+    assert!(Node::from_json(r#"{"type":"Identifier","name":1}"#).is_err());
+}
+
+#[test]
+fn test_json_statement_spans() {
+    let source = "let a = `x${1}\ny`\nconst r = /a+/;\nif a {\n  a;\n}";
+    let ast = crate::Parser::parse(source).unwrap();
+    let json: Value = serde_json::from_str(&ast.to_json()).unwrap();
+    let text = |statement: &Value| {
+        let index = |name: &str| statement[name]["index"].as_u64().unwrap() as usize;
+        source
+            .chars()
+            .skip(index("start"))
+            .take(index("end") - index("start"))
+            .collect::<String>()
+    };
+    let body = json["body"].as_array().unwrap();
+    assert_eq!(text(&body[0]), "let a = `x${1}\ny`");
+    assert_eq!(text(&body[1]), "const r = /a+/;");
+    assert_eq!(text(&body[2]), "if a {\n  a;\n}");
+    assert_eq!(body[2]["end"]["line"], 6);
+    assert_eq!(body[2]["end"]["column"], 2);
+    assert_eq!(text(&body[2]["consequent"]["body"][0]), "a;");
+
+    // a block's statements all say where they are, or none of them do
+    let spans = r#"{"column":1,"index":0,"line":1}"#;
+    let block = |first: &str, second: &str| {
+        let statement = |span| format!(r#"{{"type":"NullLiteral"{}}}"#, span);
+        format!(
+            r#"{{"type":"Block","scope":"TopLevel","declarations":[],"body":[{},{}]}}"#,
+            statement(first),
+            statement(second)
+        )
+    };
+    let both = format!(r#","start":{},"end":{}"#, spans, spans);
+    assert!(Node::from_json(&block("", "")).is_ok());
+    assert!(Node::from_json(&block(&both, &both)).is_ok());
+    assert!(Node::from_json(&block(&both, "")).is_err());
+    assert!(Node::from_json(&block(&format!(r#","start":{}"#, spans), "")).is_err());
+}
@@ -8,6 +8,7 @@ fn main() {
         .args_from_usage(
             r#"
         [FILENAME]           'File to run'
+        [ARGS]...            'Arguments passed to the script'
         -d, --disassemble    'Print disassembly instead of running'
         -e, --eval=[code]    'Code to eval inline'
         "#,
@@ -28,25 +29,37 @@ fn main() {
         disassemble(source.as_str());
     } else if matches.is_present("eval") {
         let mut agent = Agent::new();
+        agent.set_process_env(std::env::vars().collect());
         let value = agent.run("eval", source.as_str());
         agent.run_jobs();
         match value {
             Ok(v) => println!("{}", Value::inspect(&agent, &v)),
             Err(e) => println!("Uncaught Exception: {}", Value::inspect(&agent, &e)),
         };
+        if let Some(code) = agent.exit_code() {
+            std::process::exit(code);
+        }
     } else {
         let filename = matches.value_of("FILENAME").unwrap();
         let referrer = std::env::current_dir().unwrap().join("slither");
         let referrer = referrer.to_str().unwrap();
 
         let mut agent = Agent::new();
+        agent.set_process_env(std::env::vars().collect());
+        if let Some(args) = matches.values_of("ARGS") {
+            agent.set_process_args(args.map(String::from).collect());
+        }
         agent.import(filename, referrer).unwrap();
         agent.run_jobs();
+        if let Some(code) = agent.exit_code() {
+            std::process::exit(code);
+        }
     }
 }
 
 fn start_repl() {
     let mut agent = Agent::new();
+    agent.set_process_env(std::env::vars().collect());
 
     agent.set_uncaught_exception_handler(|agent: &Agent, v: Value| {
         println!("Uncaught Exception: {}", Value::inspect(agent, &v));
@@ -65,12 +78,12 @@ fn start_repl() {
                     Err(e) => match Parser::parse((line + ";").as_str()) {
                         Ok(a) => a,
                         Err(_) => {
-                            println!("Uncaught Exception: {:?}", e);
+                            println!("Uncaught Exception: {}", e);
                             continue;
                         }
                     },
                 };
-                let index = agent.assembler.assemble(&ast);
+                let index = agent.assembler.assemble("repl", &ast);
                 let mut interpreter = Interpreter::new(index, context.clone());
                 let value = interpreter.run(&agent).unwrap();
                 agent.run_jobs();
@@ -0,0 +1,282 @@
+use crate::interpreter::Context;
+use crate::intrinsics::promise::{new_promise_capability, promise_resolve_i};
+use crate::value::{ObjectKey, ObjectKind, ValueKind};
+use crate::{Agent, Value};
+use std::collections::HashMap;
+
+// an error named AssertionError, carrying the values which were compared.
+fn assertion_error(
+    agent: &Agent,
+    message: &str,
+    actual: Value,
+    expected: Value,
+) -> Result<Value, Value> {
+    let e = Value::new_error(agent, message);
+    e.set(agent, ObjectKey::NAME, Value::from("AssertionError"))?;
+    e.set(agent, ObjectKey::new(agent, "actual"), actual)?;
+    e.set(agent, ObjectKey::new(agent, "expected"), expected)?;
+    Ok(e)
+}
+
+fn message_or(message: Value, default: String) -> String {
+    match message.kind() {
+        ValueKind::String(s) => s.clone(),
+        _ => default,
+    }
+}
+
+fn assert(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (condition, message) = check_args!(agent, args, "assert", [any, optional]);
+    if condition.to_bool() {
+        return Ok(Value::Null);
+    }
+    let message = message_or(message, "assertion failed".to_string());
+    Err(assertion_error(
+        agent,
+        &message,
+        condition,
+        Value::from(true),
+    )?)
+}
+
+fn fail(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (message,) = check_args!(agent, args, "fail", [optional]);
+    let message = message_or(message, "failed".to_string());
+    Err(assertion_error(agent, &message, Value::Null, Value::Null)?)
+}
+
+fn push_key(path: &str, key: &ObjectKey) -> String {
+    match key {
+        ObjectKey::Number(n) => format!("{}[{}]", path, n),
+        ObjectKey::Interned(..) => format!("{}.{}", path, key),
+        ObjectKey::Symbol(s) => format!("{}[{}]", path, s),
+    }
+}
+
+// the first place `actual` and `expected` differ, as the path to it and the
+// values found there, or None if they are equal. Tuples and numbers compare
+// as they do with `==`, except that NaN equals itself. Arrays and ordinary
+// objects compare by their prototype and own properties, and anything else
+// by identity. `seen` holds the pairs of objects being compared, so that
+// cycles end.
+#[allow(clippy::type_complexity)]
+fn difference(
+    agent: &Agent,
+    actual: &Value,
+    expected: &Value,
+    path: String,
+    seen: &mut Vec<(Value, Value)>,
+) -> Result<Option<(String, Option<Value>, Option<Value>)>, Value> {
+    let differs = Ok(Some((
+        path.clone(),
+        Some(actual.clone()),
+        Some(expected.clone()),
+    )));
+    match (actual.kind(), expected.kind()) {
+        (ValueKind::Number(a), ValueKind::Number(b)) if a.is_nan() && b.is_nan() => Ok(None),
+        (ValueKind::Tuple(a), ValueKind::Tuple(b)) => {
+            if a.len() != b.len() {
+                return differs;
+            }
+            for (i, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+                let path = push_key(&path, &ObjectKey::from(i));
+                if let Some(d) = difference(agent, a, b, path, seen)? {
+                    return Ok(Some(d));
+                }
+            }
+            Ok(None)
+        }
+        (ValueKind::Object(a), ValueKind::Object(b)) => {
+            if actual == expected || seen.iter().any(|(sa, sb)| sa == actual && sb == expected) {
+                return Ok(None);
+            }
+            match (&a.kind, &b.kind) {
+                (ObjectKind::Ordinary, ObjectKind::Ordinary) => {}
+                (ObjectKind::Array(a), ObjectKind::Array(b)) => {
+                    let (a, b) = (a.borrow().len(), b.borrow().len());
+                    if a != b {
+                        return Ok(Some((
+                            push_key(&path, &ObjectKey::LENGTH),
+                            Some(Value::from(a as f64)),
+                            Some(Value::from(b as f64)),
+                        )));
+                    }
+                }
+                _ => return differs,
+            }
+            if actual.get_prototype_of(agent)? != expected.get_prototype_of(agent)? {
+                return differs;
+            }
+            seen.push((actual.clone(), expected.clone()));
+            let expected_keys = expected.keys(agent)?;
+            for key in actual.keys(agent)? {
+                let path = push_key(&path, &key);
+                let value = actual.get(agent, key.clone())?;
+                if !expected_keys.contains(&key) {
+                    return Ok(Some((path, Some(value), None)));
+                }
+                let other = expected.get(agent, key)?;
+                if let Some(d) = difference(agent, &value, &other, path, seen)? {
+                    return Ok(Some(d));
+                }
+            }
+            let actual_keys = actual.keys(agent)?;
+            for key in expected_keys {
+                if !actual_keys.contains(&key) {
+                    let value = expected.get(agent, key.clone())?;
+                    return Ok(Some((push_key(&path, &key), None, Some(value))));
+                }
+            }
+            seen.pop();
+            Ok(None)
+        }
+        _ if actual == expected => Ok(None),
+        _ => differs,
+    }
+}
+
+fn equal(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (actual, expected, message) = check_args!(agent, args, "equal", [any, any, optional]);
+    let (path, a, b) = match difference(agent, &actual, &expected, String::new(), &mut Vec::new())?
+    {
+        Some(d) => d,
+        None => return Ok(Value::Null),
+    };
+    let describe = |v: Option<Value>| match v {
+        Some(v) => Value::inspect(agent, &v),
+        None => "(missing)".to_string(),
+    };
+    let location = if path.is_empty() {
+        String::new()
+    } else {
+        format!(" at {}", path)
+    };
+    let message = format!(
+        "{}{}\n- actual: {}\n+ expected: {}",
+        message_or(message, "values are not equal".to_string()),
+        location,
+        describe(a),
+        describe(b),
+    );
+    Err(assertion_error(agent, &message, actual, expected)?)
+}
+
+// whether the thrown `error` satisfies `matcher`: anything if there is no
+// matcher, a message containing it if it is a string, or a truthy result if
+// it is a predicate.
+fn check_matcher(agent: &Agent, name: &str, error: &Value, matcher: &Value) -> Result<(), Value> {
+    let matches = match matcher.kind() {
+        ValueKind::Null => true,
+        ValueKind::String(s) => {
+            let message = match error.kind() {
+                ValueKind::String(m) => m.clone(),
+                _ if error.is_object() => {
+                    match error.get(agent, ObjectKey::new(agent, "message"))?.kind() {
+                        ValueKind::String(m) => m.clone(),
+                        _ => String::new(),
+                    }
+                }
+                _ => String::new(),
+            };
+            message.contains(s.as_str())
+        }
+        _ if matcher.type_of() == "function" => matcher
+            .call(agent, Value::Null, vec![error.clone()])?
+            .to_bool(),
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                &format!("{}: matcher must be a string or a function", name),
+            ))
+        }
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(assertion_error(
+            agent,
+            &format!("error does not match: {}", Value::inspect(agent, error)),
+            error.clone(),
+            matcher.clone(),
+        )?)
+    }
+}
+
+fn throws(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (f, matcher) = check_args!(agent, args, "throws", [function, optional]);
+    match f.call(agent, Value::Null, Vec::new()) {
+        Ok(value) => Err(assertion_error(
+            agent,
+            "expected function to throw",
+            value,
+            matcher,
+        )?),
+        Err(e) => {
+            check_matcher(agent, "throws", &e, &matcher)?;
+            Ok(e)
+        }
+    }
+}
+
+fn rejects_on_fulfilled(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let value = args.get(0).cloned().unwrap_or(Value::Null);
+    Err(assertion_error(
+        agent,
+        "expected promise to reject",
+        value,
+        ctx.callee().get_slot("matcher"),
+    )?)
+}
+
+fn rejects_on_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let e = args.get(0).cloned().unwrap_or(Value::Null);
+    check_matcher(agent, "rejects", &e, &ctx.callee().get_slot("matcher"))?;
+    Ok(e)
+}
+
+// a promise which fulfills with the reason the promise returned by `f`
+// rejects with, and rejects with an AssertionError if it fulfills instead.
+fn rejects(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (f, matcher) = check_args!(agent, args, "rejects", [function, optional]);
+    let constructor = agent.intrinsics.promise.clone();
+    let promise = match f.call(agent, Value::Null, Vec::new()) {
+        Ok(value) => promise_resolve_i(agent, constructor, value)?,
+        Err(e) => {
+            let capability = new_promise_capability(agent, constructor)?;
+            capability
+                .get_slot("reject")
+                .call(agent, Value::Null, vec![e])?;
+            capability
+        }
+    };
+    let on_fulfilled = Value::new_builtin_function(agent, rejects_on_fulfilled);
+    on_fulfilled.set_slot("matcher", matcher.clone());
+    let on_rejected = Value::new_builtin_function(agent, rejects_on_rejected);
+    on_rejected.set_slot("matcher", matcher);
+    promise.get(agent, ObjectKey::new(agent, "then"))?.call(
+        agent,
+        promise,
+        vec![on_fulfilled, on_rejected],
+    )
+}
+
+pub fn create(agent: &Agent) -> HashMap<String, Value> {
+    let mut module = HashMap::new();
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            module.insert(
+                $name.to_string(),
+                Value::new_named_builtin_function(agent, $fn, $name, $length),
+            );
+        };
+    }
+
+    method!("assert", assert, 2);
+    method!("equal", equal, 3);
+    method!("throws", throws, 2);
+    method!("rejects", rejects, 2);
+    method!("fail", fail, 1);
+
+    module
+}
@@ -0,0 +1,289 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::bytes_prototype::{encode_hex, to_bytes};
+use crate::value::{Value, ValueKind};
+use std::collections::HashMap;
+use std::io::Read;
+
+const MAX_RANDOM_BYTES: usize = 65536;
+
+// Append the length-suffixed padding shared by md5, sha1 and sha256.
+fn pad(data: &[u8], big_endian: bool) -> Vec<u8> {
+    let mut message = data.to_vec();
+    let bits = (data.len() as u64).wrapping_mul(8);
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    if big_endian {
+        message.extend_from_slice(&bits.to_be_bytes());
+    } else {
+        message.extend_from_slice(&bits.to_le_bytes());
+    }
+    message
+}
+
+#[rustfmt::skip]
+const SHA256_K: [u32; 64] = [
+    0x428a_2f98, 0x7137_4491, 0xb5c0_fbcf, 0xe9b5_dba5, 0x3956_c25b, 0x59f1_11f1, 0x923f_82a4, 0xab1c_5ed5,
+    0xd807_aa98, 0x1283_5b01, 0x2431_85be, 0x550c_7dc3, 0x72be_5d74, 0x80de_b1fe, 0x9bdc_06a7, 0xc19b_f174,
+    0xe49b_69c1, 0xefbe_4786, 0x0fc1_9dc6, 0x240c_a1cc, 0x2de9_2c6f, 0x4a74_84aa, 0x5cb0_a9dc, 0x76f9_88da,
+    0x983e_5152, 0xa831_c66d, 0xb003_27c8, 0xbf59_7fc7, 0xc6e0_0bf3, 0xd5a7_9147, 0x06ca_6351, 0x1429_2967,
+    0x27b7_0a85, 0x2e1b_2138, 0x4d2c_6dfc, 0x5338_0d13, 0x650a_7354, 0x766a_0abb, 0x81c2_c92e, 0x9272_2c85,
+    0xa2bf_e8a1, 0xa81a_664b, 0xc24b_8b70, 0xc76c_51a3, 0xd192_e819, 0xd699_0624, 0xf40e_3585, 0x106a_a070,
+    0x19a4_c116, 0x1e37_6c08, 0x2748_774c, 0x34b0_bcb5, 0x391c_0cb3, 0x4ed8_aa4a, 0x5b9c_ca4f, 0x682e_6ff3,
+    0x748f_82ee, 0x78a5_636f, 0x84c8_7814, 0x8cc7_0208, 0x90be_fffa, 0xa450_6ceb, 0xbef9_a3f7, 0xc671_78f2,
+];
+
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    let mut h: [u32; 8] = [
+        0x6a09_e667,
+        0xbb67_ae85,
+        0x3c6e_f372,
+        0xa54f_f53a,
+        0x510e_527f,
+        0x9b05_688c,
+        0x1f83_d9ab,
+        0x5be0_cd19,
+    ];
+    for chunk in pad(data, true).chunks(64) {
+        let mut w = [0u32; 64];
+        for (word, bytes) in w.iter_mut().zip(chunk.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let mut v = h;
+        for (k, word) in SHA256_K.iter().zip(w.iter()) {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7]
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*k)
+                .wrapping_add(*word);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [
+                t1.wrapping_add(t2),
+                v[0],
+                v[1],
+                v[2],
+                v[3].wrapping_add(t1),
+                v[4],
+                v[5],
+                v[6],
+            ];
+        }
+        for (a, b) in h.iter_mut().zip(v.iter()) {
+            *a = a.wrapping_add(*b);
+        }
+    }
+    h.iter().flat_map(|x| x.to_be_bytes().to_vec()).collect()
+}
+
+pub fn sha1(data: &[u8]) -> Vec<u8> {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    for chunk in pad(data, true).chunks(64) {
+        let mut w = [0u32; 80];
+        for (word, bytes) in w.iter_mut().zip(chunk.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let mut v = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((v[1] & v[2]) | (!v[1] & v[3]), 0x5a82_7999),
+                20..=39 => (v[1] ^ v[2] ^ v[3], 0x6ed9_eba1),
+                40..=59 => ((v[1] & v[2]) | (v[1] & v[3]) | (v[2] & v[3]), 0x8f1b_bcdc),
+                _ => (v[1] ^ v[2] ^ v[3], 0xca62_c1d6),
+            };
+            let t = v[0]
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(v[4])
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            v = [t, v[0], v[1].rotate_left(30), v[2], v[3]];
+        }
+        for (a, b) in h.iter_mut().zip(v.iter()) {
+            *a = a.wrapping_add(*b);
+        }
+    }
+    h.iter().flat_map(|x| x.to_be_bytes().to_vec()).collect()
+}
+
+const MD5_S: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+pub fn md5(data: &[u8]) -> Vec<u8> {
+    // K[i] = floor(abs(sin(i + 1)) * 2^32)
+    let k: Vec<u32> = (0..64)
+        .map(|i| (f64::from(i + 1).sin().abs() * 4_294_967_296.0) as u32)
+        .collect();
+    let mut h: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for chunk in pad(data, false).chunks(64) {
+        let mut m = [0u32; 16];
+        for (word, bytes) in m.iter_mut().zip(chunk.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        let mut v = h;
+        for (i, (k, shift)) in k.iter().zip(MD5_S.iter()).enumerate() {
+            let (f, g) = match i {
+                0..=15 => ((v[1] & v[2]) | (!v[1] & v[3]), i),
+                16..=31 => ((v[3] & v[1]) | (!v[3] & v[2]), (5 * i + 1) % 16),
+                32..=47 => (v[1] ^ v[2] ^ v[3], (3 * i + 5) % 16),
+                _ => (v[2] ^ (v[1] | !v[3]), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(v[0]).wrapping_add(*k).wrapping_add(m[g]);
+            v = [v[3], v[1].wrapping_add(f.rotate_left(*shift)), v[1], v[2]];
+        }
+        for (a, b) in h.iter_mut().zip(v.iter()) {
+            *a = a.wrapping_add(*b);
+        }
+    }
+    h.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect()
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut key = if key.len() > 64 {
+        sha256(key)
+    } else {
+        key.to_vec()
+    };
+    key.resize(64, 0);
+    let mut inner: Vec<u8> = key.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(data);
+    let mut outer: Vec<u8> = key.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend(sha256(&inner));
+    sha256(&outer)
+}
+
+fn random_fill(buf: &mut [u8]) -> std::io::Result<()> {
+    std::fs::File::open("/dev/urandom")?.read_exact(buf)
+}
+
+// Strings are hashed as their utf-8 encoding.
+fn data_arg(agent: &Agent, value: Option<&Value>, name: &str) -> Result<Vec<u8>, Value> {
+    match value.map(Value::kind) {
+        Some(ValueKind::String(s)) => Ok(s.as_bytes().to_vec()),
+        _ => match value.and_then(to_bytes) {
+            Some(b) => Ok(b),
+            None => Err(Value::new_error(
+                agent,
+                &format!("{} must be a string or Bytes", name),
+            )),
+        },
+    }
+}
+
+fn sha256_(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let data = data_arg(agent, args.get(0), "data")?;
+    Ok(Value::new_buffer_from_vec(agent, sha256(&data)))
+}
+
+fn sha1_(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let data = data_arg(agent, args.get(0), "data")?;
+    Ok(Value::new_buffer_from_vec(agent, sha1(&data)))
+}
+
+fn md5_(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let data = data_arg(agent, args.get(0), "data")?;
+    Ok(Value::new_buffer_from_vec(agent, md5(&data)))
+}
+
+fn hmac_sha256_(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let key = data_arg(agent, args.get(0), "key")?;
+    let data = data_arg(agent, args.get(1), "data")?;
+    Ok(Value::new_buffer_from_vec(agent, hmac_sha256(&key, &data)))
+}
+
+fn random_bytes(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let n = match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "size must be a non-negative integer",
+            ));
+        }
+    };
+    if n > MAX_RANDOM_BYTES {
+        return Err(Value::new_error(
+            agent,
+            &format!("size must be at most {}", MAX_RANDOM_BYTES),
+        ));
+    }
+    let mut buf = vec![0; n];
+    match random_fill(&mut buf) {
+        Ok(()) => Ok(Value::new_buffer_from_vec(agent, buf)),
+        Err(e) => Err(Value::new_error(agent, &format!("{}", e))),
+    }
+}
+
+fn uuid(agent: &Agent, _: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let mut b = [0u8; 16];
+    if let Err(e) = random_fill(&mut b) {
+        return Err(Value::new_error(agent, &format!("{}", e)));
+    }
+    b[6] = (b[6] & 0x0f) | 0x40; // version 4
+    b[8] = (b[8] & 0x3f) | 0x80; // variant 10xx
+    Ok(Value::from(format!(
+        "{}-{}-{}-{}-{}",
+        encode_hex(&b[0..4]),
+        encode_hex(&b[4..6]),
+        encode_hex(&b[6..8]),
+        encode_hex(&b[8..10]),
+        encode_hex(&b[10..16]),
+    )))
+}
+
+pub fn create(agent: &Agent) -> HashMap<String, Value> {
+    let mut module = HashMap::new();
+
+    module.insert(
+        "sha256".to_string(),
+        Value::new_named_builtin_function(agent, sha256_, "sha256", 1),
+    );
+    // sha1 and md5 are broken, they exist for checksums and legacy protocols.
+    module.insert(
+        "sha1".to_string(),
+        Value::new_named_builtin_function(agent, sha1_, "sha1", 1),
+    );
+    module.insert(
+        "md5".to_string(),
+        Value::new_named_builtin_function(agent, md5_, "md5", 1),
+    );
+    module.insert(
+        "hmacSha256".to_string(),
+        Value::new_named_builtin_function(agent, hmac_sha256_, "hmacSha256", 2),
+    );
+    module.insert(
+        "randomBytes".to_string(),
+        Value::new_named_builtin_function(agent, random_bytes, "randomBytes", 1),
+    );
+    module.insert(
+        "uuid".to_string(),
+        Value::new_named_builtin_function(agent, uuid, "uuid", 0),
+    );
+
+    module
+}
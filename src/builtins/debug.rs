@@ -1,4 +1,6 @@
 use crate::interpreter::Context;
+use crate::tracer::{ChromeTracer, FlatProfiler, Tracer};
+use crate::value::ValueKind;
 use crate::{Agent, Value};
 use std::collections::HashMap;
 
@@ -11,11 +13,48 @@ fn print(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
     Ok(Value::Null)
 }
 
+// starts tracing with the built in tracer named by the argument, "flat" by
+// default. stopProfile returns its report.
+fn start_profile(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (kind,) = check_args!(agent, args, "startProfile", [optional]);
+    if agent.tracing() {
+        return Err(Value::new_error(agent, "startProfile: already tracing"));
+    }
+    let tracer: Box<dyn Tracer> = match kind.kind() {
+        ValueKind::Null => Box::new(FlatProfiler::new()),
+        ValueKind::String(s) if s == "flat" => Box::new(FlatProfiler::new()),
+        ValueKind::String(s) if s == "chrome" => Box::new(ChromeTracer::new()),
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "startProfile: argument 1 must be 'flat' or 'chrome'",
+            ))
+        }
+    };
+    agent.set_tracer(tracer);
+    Ok(Value::Null)
+}
+
+fn stop_profile(agent: &Agent, _args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    match agent.take_tracer() {
+        Some(tracer) => tracer.report(agent),
+        None => Err(Value::new_error(agent, "stopProfile: not tracing")),
+    }
+}
+
 pub fn create(agent: &Agent) -> HashMap<String, Value> {
     let mut module = HashMap::new();
     module.insert(
         "print".to_string(),
-        Value::new_builtin_function(agent, print),
+        Value::new_named_builtin_function(agent, print, "print", 1),
+    );
+    module.insert(
+        "startProfile".to_string(),
+        Value::new_named_builtin_function(agent, start_profile, "startProfile", 0),
+    );
+    module.insert(
+        "stopProfile".to_string(),
+        Value::new_named_builtin_function(agent, stop_profile, "stopProfile", 0),
     );
 
     module
@@ -1,7 +1,8 @@
 use crate::agent::{Agent, MioMapType};
 use crate::interpreter::Context;
 use crate::intrinsics::promise::new_promise_capability;
-use crate::value::{ObjectKey, Value};
+use crate::intrinsics::text_encoding::{buffer_source_bytes, new_uint8_array};
+use crate::value::{ObjectKey, Value, ValueKind};
 use lazy_static::lazy_static;
 use mio::{PollOpt, Ready, Registration, Token};
 use std::collections::HashMap;
@@ -13,7 +14,10 @@ lazy_static! {
 
 pub enum FsResponse {
     Read(String),
+    ReadBytes(Vec<u8>),
+    Directory(Vec<String>),
     Metadata(std::fs::Metadata),
+    Stat(std::fs::Metadata),
     Exists(bool),
     Success,
     Error(String),
@@ -28,11 +32,40 @@ pub fn handle(agent: &Agent, token: Token, promise: Value) {
                 .call(agent, promise, vec![Value::from(s)])
                 .unwrap();
         }
+        FsResponse::ReadBytes(bytes) => {
+            let settled = match new_uint8_array(agent, bytes) {
+                Ok(bytes) => promise
+                    .get_slot("resolve")
+                    .call(agent, promise, vec![bytes]),
+                Err(e) => promise.get_slot("reject").call(agent, promise, vec![e]),
+            };
+            settled.unwrap();
+        }
+        FsResponse::Directory(names) => {
+            let names = names.into_iter().map(Value::from).collect();
+            promise
+                .get_slot("resolve")
+                .call(
+                    agent,
+                    promise,
+                    vec![Value::new_array_from_vec(agent, names)],
+                )
+                .unwrap();
+        }
+        FsResponse::Stat(m) => {
+            let stat = create_stat(agent, &m);
+            promise
+                .get_slot("resolve")
+                .call(agent, promise, vec![stat])
+                .unwrap();
+        }
         FsResponse::Metadata(m) => {
             let o = Value::new_object(agent.intrinsics.object_prototype.clone());
             macro_rules! p {
                 ($target:expr, $name:expr, $value:expr) => {
-                    $target.set(agent, ObjectKey::from($name), $value).unwrap();
+                    $target
+                        .set(agent, ObjectKey::new(agent, $name), $value)
+                        .unwrap();
                 };
             }
             let ft = m.file_type();
@@ -96,91 +129,125 @@ pub fn handle(agent: &Agent, token: Token, promise: Value) {
     }
 }
 
-fn read_file(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(filename)) = args.get(0) {
-        let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+// runs `f` on the thread pool, settling the returned promise with its
+// response from the event loop.
+fn spawn<F>(agent: &Agent, f: F) -> Result<Value, Value>
+where
+    F: FnOnce() -> FsResponse + Send + 'static,
+{
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+
+    let (registration, set_readiness) = Registration::new2();
+    let token = agent.mio_token();
+
+    agent
+        .mio
+        .register(&registration, token, Ready::readable(), PollOpt::edge())
+        .unwrap();
+    agent
+        .mio_map
+        .borrow_mut()
+        .insert(token, MioMapType::FS(registration, promise.clone()));
+
+    agent.pool.execute(move || {
+        let response = f();
+        RESPONSES.lock().unwrap().insert(token, response);
+        set_readiness.set_readiness(Ready::readable()).unwrap();
+    });
+
+    Ok(promise)
+}
 
-        let (registration, set_readiness) = Registration::new2();
-        let token = agent.mio_token();
+fn respond<T>(result: std::io::Result<T>, f: impl FnOnce(T) -> FsResponse) -> FsResponse {
+    match result {
+        Ok(v) => f(v),
+        Err(e) => FsResponse::Error(format!("{}", e)),
+    }
+}
 
-        agent
-            .mio
-            .register(&registration, token, Ready::readable(), PollOpt::edge())
-            .unwrap();
-        agent
-            .mio_map
-            .borrow_mut()
-            .insert(token, MioMapType::FS(registration, promise.clone()));
+fn path_arg(agent: &Agent, args: &[Value]) -> Result<String, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(path)) => Ok(path.clone()),
+        _ => Err(Value::new_error(agent, "path must be a string")),
+    }
+}
 
-        let filename = filename.to_string();
-        agent
-            .pool
-            .execute(move || match std::fs::read_to_string(filename) {
-                Ok(s) => {
-                    RESPONSES.lock().unwrap().insert(token, FsResponse::Read(s));
-                    set_readiness.set_readiness(Ready::readable()).unwrap();
-                }
-                Err(e) => {
-                    RESPONSES
-                        .lock()
-                        .unwrap()
-                        .insert(token, FsResponse::Error(format!("{}", e)));
-                    set_readiness.set_readiness(Ready::readable()).unwrap();
-                }
-            });
+// file contents are utf-8 text unless the encoding is 'bytes', which gives a
+// Uint8Array instead.
+fn read_as_bytes(agent: &Agent, encoding: Option<&Value>) -> Result<bool, Value> {
+    match encoding.map(Value::kind) {
+        None | Some(ValueKind::Null) => Ok(false),
+        Some(ValueKind::String(e)) => match e.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(false),
+            "bytes" => Ok(true),
+            _ => Err(Value::new_error(
+                agent,
+                &format!("unsupported encoding '{}'", e),
+            )),
+        },
+        Some(_) => Err(Value::new_error(agent, "encoding must be a string")),
+    }
+}
 
-        Ok(promise)
+fn read_file(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let path = path_arg(agent, &args)?;
+    if read_as_bytes(agent, args.get(1))? {
+        spawn(agent, move || {
+            respond(std::fs::read(path), FsResponse::ReadBytes)
+        })
     } else {
-        Err(Value::new_error(agent, "filename must be a string"))
+        spawn(agent, move || {
+            respond(std::fs::read_to_string(path), FsResponse::Read)
+        })
     }
 }
 
-fn write_file(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(filename)) = args.get(0) {
-        if let Some(Value::String(contents)) = args.get(1) {
-            let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
-
-            let (registration, set_readiness) = Registration::new2();
-            let token = agent.mio_token();
-
-            agent
-                .mio
-                .register(&registration, token, Ready::readable(), PollOpt::edge())
-                .unwrap();
-            agent
-                .mio_map
-                .borrow_mut()
-                .insert(token, MioMapType::FS(registration, promise.clone()));
+// strings are written as utf-8, anything holding bytes is written as is.
+fn contents_arg(agent: &Agent, args: &[Value]) -> Result<Vec<u8>, Value> {
+    match args.get(1).map(Value::kind) {
+        Some(ValueKind::String(s)) => Ok(s.clone().into_bytes()),
+        Some(_) => buffer_source_bytes(agent, &args[1]),
+        None => Err(Value::new_error(
+            agent,
+            "contents must be a string or bytes",
+        )),
+    }
+}
 
-            let filename = filename.to_string();
-            let contents = contents.to_string();
-            agent
-                .pool
-                .execute(move || match std::fs::write(filename, contents) {
-                    Ok(()) => {
-                        RESPONSES.lock().unwrap().insert(token, FsResponse::Success);
-                        set_readiness.set_readiness(Ready::readable()).unwrap();
-                    }
-                    Err(e) => {
-                        RESPONSES
-                            .lock()
-                            .unwrap()
-                            .insert(token, FsResponse::Error(format!("{}", e)));
-                        set_readiness.set_readiness(Ready::readable()).unwrap();
-                    }
-                });
+fn append_option(agent: &Agent, args: &[Value]) -> Result<bool, Value> {
+    match args.get(2).map(Value::kind) {
+        None | Some(ValueKind::Null) => Ok(false),
+        Some(_) if args[2].type_of() == "object" => Ok(args[2]
+            .get(agent, ObjectKey::new(agent, "append"))?
+            .to_bool()),
+        Some(_) => Err(Value::new_error(agent, "options must be an object")),
+    }
+}
 
-            Ok(promise)
-        } else {
-            Err(Value::new_error(agent, "contents must be a string"))
-        }
+fn write(path: String, contents: Vec<u8>, append: bool) -> std::io::Result<()> {
+    if append {
+        use std::io::Write;
+        std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)?
+            .write_all(&contents)
     } else {
-        Err(Value::new_error(agent, "filename must be a string"))
+        std::fs::write(path, contents)
     }
 }
 
+fn write_file(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let path = path_arg(agent, &args)?;
+    let contents = contents_arg(agent, &args)?;
+    let append = append_option(agent, &args)?;
+    spawn(agent, move || {
+        respond(write(path, contents, append), |_| FsResponse::Success)
+    })
+}
+
 fn remove_file(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(filename)) = args.get(0) {
+    if let Some(ValueKind::String(filename)) = args.get(0).map(Value::kind) {
         let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
         let (registration, set_readiness) = Registration::new2();
@@ -219,7 +286,7 @@ fn remove_file(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Va
 }
 
 fn get_metadata(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(filename)) = args.get(0) {
+    if let Some(ValueKind::String(filename)) = args.get(0).map(Value::kind) {
         let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
         let (registration, set_readiness) = Registration::new2();
@@ -261,8 +328,8 @@ fn get_metadata(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, V
 }
 
 fn copy(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(from)) = args.get(0) {
-        if let Some(Value::String(to)) = args.get(1) {
+    if let Some(ValueKind::String(from)) = args.get(0).map(Value::kind) {
+        if let Some(ValueKind::String(to)) = args.get(1).map(Value::kind) {
             let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
             let (registration, set_readiness) = Registration::new2();
@@ -303,8 +370,8 @@ fn copy(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
 }
 
 fn move_(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(from)) = args.get(0) {
-        if let Some(Value::String(to)) = args.get(1) {
+    if let Some(ValueKind::String(from)) = args.get(0).map(Value::kind) {
+        if let Some(ValueKind::String(to)) = args.get(1).map(Value::kind) {
             let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
             let (registration, set_readiness) = Registration::new2();
@@ -359,8 +426,8 @@ fn symlink(from: String, to: String) -> std::io::Result<()> {
 }
 
 fn create_symlink(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(from)) = args.get(0) {
-        if let Some(Value::String(to)) = args.get(1) {
+    if let Some(ValueKind::String(from)) = args.get(0).map(Value::kind) {
+        if let Some(ValueKind::String(to)) = args.get(1).map(Value::kind) {
             let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
             let (registration, set_readiness) = Registration::new2();
@@ -401,7 +468,7 @@ fn create_symlink(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value,
 }
 
 fn exists(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(filename)) = args.get(0) {
+    if let Some(ValueKind::String(filename)) = args.get(0).map(Value::kind) {
         let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
         let (registration, set_readiness) = Registration::new2();
@@ -433,7 +500,7 @@ fn exists(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value>
 }
 
 fn create_directory(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(filename)) = args.get(0) {
+    if let Some(ValueKind::String(filename)) = args.get(0).map(Value::kind) {
         let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
         let (registration, set_readiness) = Registration::new2();
@@ -472,7 +539,7 @@ fn create_directory(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Valu
 }
 
 fn remove_directory(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    if let Some(Value::String(filename)) = args.get(0) {
+    if let Some(ValueKind::String(filename)) = args.get(0).map(Value::kind) {
         let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
 
         let (registration, set_readiness) = Registration::new2();
@@ -510,26 +577,141 @@ fn remove_directory(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Valu
     }
 }
 
+fn read_file_sync(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let path = path_arg(agent, &args)?;
+    let error = |e: std::io::Error| Value::new_error(agent, &format!("{}", e));
+    if read_as_bytes(agent, args.get(1))? {
+        new_uint8_array(agent, std::fs::read(path).map_err(error)?)
+    } else {
+        Ok(Value::from(std::fs::read_to_string(path).map_err(error)?))
+    }
+}
+
+fn write_file_sync(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let path = path_arg(agent, &args)?;
+    let contents = contents_arg(agent, &args)?;
+    let append = append_option(agent, &args)?;
+    match write(path, contents, append) {
+        Ok(()) => Ok(Value::Null),
+        Err(e) => Err(Value::new_error(agent, &format!("{}", e))),
+    }
+}
+
+fn read_directory(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let path = path_arg(agent, &args)?;
+    spawn(agent, move || {
+        let names = std::fs::read_dir(path).and_then(|entries| {
+            let mut names = entries
+                .map(|entry| entry.map(|e| e.file_name().to_string_lossy().into_owned()))
+                .collect::<std::io::Result<Vec<String>>>()?;
+            names.sort();
+            Ok(names)
+        });
+        respond(names, FsResponse::Directory)
+    })
+}
+
+fn stat_is(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() != "object" || !this.has_slot("stat type") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    let kind = ctx.callee().get_slot("stat type");
+    Ok(Value::from(this.get_slot("stat type") == kind))
+}
+
+fn create_stat(agent: &Agent, m: &std::fs::Metadata) -> Value {
+    let stat = Value::new_custom_object(agent.intrinsics.object_prototype.clone());
+    let ft = m.file_type();
+    let kind = if ft.is_file() {
+        "file"
+    } else if ft.is_dir() {
+        "directory"
+    } else if ft.is_symlink() {
+        "symlink"
+    } else {
+        "other"
+    };
+    stat.set_slot("stat type", Value::from(kind));
+    let mtime = m
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
+        .map_or(Value::Null, |d| Value::from(d.as_millis() as f64));
+    stat.set(
+        agent,
+        ObjectKey::new(agent, "size"),
+        Value::from(m.len() as f64),
+    )
+    .unwrap();
+    stat.set(agent, ObjectKey::new(agent, "mtime"), mtime)
+        .unwrap();
+    for (name, kind) in &[("isFile", "file"), ("isDirectory", "directory")] {
+        let f = Value::new_named_builtin_function(agent, stat_is, name, 0);
+        f.set_slot("stat type", Value::from(*kind));
+        stat.set(agent, ObjectKey::new(agent, *name), f).unwrap();
+    }
+    stat
+}
+
+fn stat(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let path = path_arg(agent, &args)?;
+    spawn(agent, move || {
+        respond(std::fs::metadata(path), FsResponse::Stat)
+    })
+}
+
+fn make_directory(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let path = path_arg(agent, &args)?;
+    let recursive = match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => false,
+        Some(_) if args[1].type_of() == "object" => args[1]
+            .get(agent, ObjectKey::new(agent, "recursive"))?
+            .to_bool(),
+        Some(_) => return Err(Value::new_error(agent, "options must be an object")),
+    };
+    spawn(agent, move || {
+        let result = if recursive {
+            std::fs::create_dir_all(path)
+        } else {
+            std::fs::create_dir(path)
+        };
+        respond(result, |_| FsResponse::Success)
+    })
+}
+
 pub fn create(agent: &Agent) -> HashMap<String, Value> {
     let mut module = HashMap::new();
 
     macro_rules! method {
-        ($name:expr, $fn:ident) => {
-            module.insert($name.to_string(), Value::new_builtin_function(agent, $fn));
+        ($name:expr, $fn:ident, $length:expr) => {
+            module.insert(
+                $name.to_string(),
+                Value::new_named_builtin_function(agent, $fn, $name, $length),
+            );
         };
     }
-    method!("readFile", read_file);
-    method!("writeFile", write_file);
-    method!("removeFile", remove_file);
-    method!("getMetadata", get_metadata);
-    method!("copy", copy);
-    method!("move", move_);
-    method!("createSymbolicLink", create_symlink);
-    method!("exists", exists);
+    method!("readFile", read_file, 1);
+    method!("writeFile", write_file, 2);
+    method!("readFileSync", read_file_sync, 1);
+    method!("writeFileSync", write_file_sync, 2);
+    method!("removeFile", remove_file, 1);
+    method!("getMetadata", get_metadata, 1);
+    method!("copy", copy, 2);
+    method!("move", move_, 2);
+    method!("createSymbolicLink", create_symlink, 2);
+    method!("exists", exists, 1);
     // watch
-    method!("createDirectory", create_directory);
-    method!("removeDirectory", remove_directory);
-    // readDirectory
+    method!("createDirectory", create_directory, 1);
+    method!("removeDirectory", remove_directory, 1);
+    method!("readDirectory", read_directory, 1);
+
+    // names familiar from node
+    method!("readdir", read_directory, 1);
+    method!("stat", stat, 1);
+    method!("mkdir", make_directory, 1);
+    method!("unlink", remove_file, 1);
+    method!("rename", move_, 2);
 
     module
 }
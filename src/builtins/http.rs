@@ -0,0 +1,681 @@
+use crate::agent::{Agent, MioMapType};
+use crate::builtins::timers;
+use crate::interpreter::Context;
+use crate::intrinsics::abort_controller::{abort_reason, on_abort, signal_option};
+use crate::intrinsics::bytes_prototype::to_bytes;
+use crate::intrinsics::promise::new_promise_capability;
+use crate::value::{ObjectKey, Value, ValueKind};
+use mio::{net::TcpStream, PollOpt, Ready, Registration, Token};
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::Duration;
+
+const DEFAULT_MAX_REDIRECTS: usize = 20;
+
+#[derive(Debug, Clone)]
+struct Url {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    fn parse(s: &str) -> Result<Url, String> {
+        // the path goes into the request line as it is
+        if s.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(format!("invalid url '{}'", s));
+        }
+        let rest = if s.starts_with("http://") {
+            &s[7..]
+        } else if s.starts_with("https://") {
+            return Err("https is not supported".to_string());
+        } else {
+            return Err(format!("invalid url '{}'", s));
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(i) => match authority[i + 1..].parse::<u16>() {
+                Ok(port) => (&authority[..i], port),
+                Err(_) => return Err(format!("invalid port in url '{}'", s)),
+            },
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("invalid url '{}'", s));
+        }
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Resolve the target of a `Location` header against this url.
+    fn join(&self, location: &str) -> Result<Url, String> {
+        if location.contains("://") {
+            Url::parse(location)
+        } else if location.starts_with('/') {
+            Ok(Url {
+                path: location.to_string(),
+                ..self.clone()
+            })
+        } else {
+            let dir = match self.path.rfind('/') {
+                Some(i) => &self.path[..=i],
+                None => "/",
+            };
+            Ok(Url {
+                path: format!("{}{}", dir, location),
+                ..self.clone()
+            })
+        }
+    }
+
+    fn authority(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "http://{}{}", self.authority(), self.path)
+    }
+}
+
+struct Response {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// whether `s` can be a method or a header name. anything else, a line break
+// most of all, could end the line early and smuggle in another header or
+// request.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_graphic() && !b"\"(),/:;<=>?@[\\]{}".contains(&b))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+// Returns None if more data is needed, `eof` means no more data will come.
+fn parse_response(buf: &[u8], eof: bool) -> Result<Option<Response>, String> {
+    let head_end = match find(buf, b"\r\n\r\n") {
+        Some(i) => i,
+        None if eof => return Err("connection closed before headers were received".to_string()),
+        None => return Ok(None),
+    };
+    let head = match std::str::from_utf8(&buf[..head_end]) {
+        Ok(s) => s,
+        Err(_) => return Err("headers are not valid utf-8".to_string()),
+    };
+    let mut lines = head.split("\r\n");
+
+    let status_line = lines.next().unwrap();
+    let mut parts = status_line.splitn(3, ' ');
+    match parts.next() {
+        Some(v) if v.starts_with("HTTP/1.") => {}
+        _ => return Err(format!("invalid status line '{}'", status_line)),
+    }
+    let status = match parts.next().map(str::parse::<u16>) {
+        Some(Ok(s)) if s >= 100 && s < 600 => s,
+        _ => return Err(format!("invalid status code in '{}'", status_line)),
+    };
+    let reason = parts.next().unwrap_or("").to_string();
+
+    let mut headers = Vec::new();
+    for line in lines {
+        match line.find(':') {
+            Some(i) if i > 0 => headers.push((
+                line[..i].trim().to_lowercase(),
+                line[i + 1..].trim().to_string(),
+            )),
+            _ => return Err(format!("invalid header '{}'", line)),
+        }
+    }
+    let mut response = Response {
+        status,
+        reason,
+        headers,
+        body: Vec::new(),
+    };
+
+    let rest = &buf[head_end + 4..];
+    let chunked = match response.header("transfer-encoding") {
+        Some(te) => te.to_lowercase().contains("chunked"),
+        None => false,
+    };
+    if chunked {
+        let mut pos = 0;
+        loop {
+            let line_end = match find(&rest[pos..], b"\r\n") {
+                Some(i) => pos + i,
+                None if eof => return Err("incomplete chunked body".to_string()),
+                None => return Ok(None),
+            };
+            let size = std::str::from_utf8(&rest[pos..line_end])
+                .ok()
+                .and_then(|s| usize::from_str_radix(s.split(';').next().unwrap().trim(), 16).ok());
+            let size = match size {
+                Some(s) => s,
+                None => return Err("invalid chunk size".to_string()),
+            };
+            if size == 0 {
+                break;
+            }
+            let data_start = line_end + 2;
+            if rest.len() < data_start + size + 2 {
+                if eof {
+                    return Err("incomplete chunked body".to_string());
+                }
+                return Ok(None);
+            }
+            if rest[data_start + size..data_start + size + 2] != b"\r\n"[..] {
+                return Err("chunk is missing its terminator".to_string());
+            }
+            response
+                .body
+                .extend_from_slice(&rest[data_start..data_start + size]);
+            pos = data_start + size + 2;
+        }
+    } else if let Some(length) = response.header("content-length") {
+        let length = match length.parse::<usize>() {
+            Ok(l) => l,
+            Err(_) => return Err(format!("invalid content-length '{}'", length)),
+        };
+        if rest.len() < length {
+            if eof {
+                return Err(format!(
+                    "expected {} bytes of body but got {}",
+                    length,
+                    rest.len()
+                ));
+            }
+            return Ok(None);
+        }
+        response.body = rest[..length].to_vec();
+    } else if eof || status == 204 || status == 304 {
+        response.body = rest.to_vec();
+    } else {
+        return Ok(None);
+    }
+    Ok(Some(response))
+}
+
+/// An in-flight request, the same promise is carried across redirects.
+#[derive(Finalize)]
+pub struct Request {
+    // None until the host is resolved, when the address arrives from the
+    // thread pool through `resolving`
+    stream: Option<TcpStream>,
+    resolving: Option<(Registration, Receiver<std::io::Result<SocketAddr>>)>,
+    url: Url,
+    method: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    unwritten: Vec<u8>,
+    received: Vec<u8>,
+    redirects: usize,
+    max_redirects: usize,
+    timer: Option<Token>,
+    promise: Value,
+}
+
+unsafe impl gc::Trace for Request {
+    custom_trace!(this, {
+        mark(&this.promise);
+    });
+}
+
+impl std::fmt::Debug for Request {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "Request({} {})", self.method, self.url)
+    }
+}
+
+impl Request {
+    fn serialize(&self) -> Vec<u8> {
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+            self.method,
+            self.url.path,
+            self.url.authority()
+        );
+        for (name, value) in &self.headers {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+        if !self.body.is_empty() || self.method == "POST" || self.method == "PUT" {
+            head += &format!("Content-Length: {}\r\n", self.body.len());
+        }
+        head += "\r\n";
+        let mut out = head.into_bytes();
+        out.extend_from_slice(&self.body);
+        out
+    }
+}
+
+fn lookup(host: &str, port: u16) -> std::io::Result<SocketAddr> {
+    let addr = (host, port).to_socket_addrs()?.next();
+    match addr {
+        Some(addr) => Ok(addr),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("could not resolve {}", host),
+        )),
+    }
+}
+
+// looks the host up on the thread pool, as a slow name server would hold up
+// the event loop. `handle` connects once the address arrives.
+fn resolve(agent: &Agent, mut request: Request) {
+    let (registration, set_readiness) = Registration::new2();
+    let token = agent.mio_token();
+    if let Err(e) = agent
+        .mio
+        .register(&registration, token, Ready::readable(), PollOpt::edge())
+    {
+        reject(agent, &request, &format!("{}", e));
+        return;
+    }
+    let (sender, receiver) = channel();
+    let host = request.url.host.clone();
+    let port = request.url.port;
+    agent.pool.execute(move || {
+        // the request may have been aborted or timed out by now
+        let _ = sender.send(lookup(&host, port));
+        let _ = set_readiness.set_readiness(Ready::readable());
+    });
+    request.stream = None;
+    request.resolving = Some((registration, receiver));
+    agent
+        .mio_map
+        .borrow_mut()
+        .insert(token, MioMapType::Http(request));
+}
+
+fn reject(agent: &Agent, request: &Request, message: &str) {
+    reject_with(agent, request, Value::new_error(agent, message));
+}
+
+fn reject_with(agent: &Agent, request: &Request, reason: Value) {
+    if let Some(timer) = request.timer {
+        timers::clear(agent, timer);
+    }
+    request
+        .promise
+        .get_slot("reject")
+        .call(agent, Value::Null, vec![reason])
+        .unwrap();
+}
+
+fn start(agent: &Agent, mut request: Request) {
+    request.unwritten = request.serialize();
+    request.received = Vec::new();
+    let token = agent.mio_token();
+    if let Err(e) = agent.mio.register(
+        request.stream.as_ref().unwrap(),
+        token,
+        Ready::readable() | Ready::writable(),
+        PollOpt::edge(),
+    ) {
+        reject(agent, &request, &format!("{}", e));
+        return;
+    }
+    agent
+        .mio_map
+        .borrow_mut()
+        .insert(token, MioMapType::Http(request));
+}
+
+fn create_response(agent: &Agent, url: &Url, response: Response) -> Result<Value, Value> {
+    let r = Value::new_custom_object(agent.intrinsics.http_response_prototype.clone());
+    r.set(
+        agent,
+        ObjectKey::new(agent, "status"),
+        Value::from(f64::from(response.status)),
+    )?;
+    r.set(
+        agent,
+        ObjectKey::new(agent, "statusText"),
+        Value::from(response.reason),
+    )?;
+    r.set(
+        agent,
+        ObjectKey::new(agent, "url"),
+        Value::from(url.to_string()),
+    )?;
+    let headers = Value::new_object(agent.intrinsics.object_prototype.clone());
+    for (name, value) in response.headers {
+        headers.set(agent, ObjectKey::new(agent, &name), Value::from(value))?;
+    }
+    r.set(agent, ObjectKey::new(agent, "headers"), headers)?;
+    r.set_slot(
+        "http response body",
+        Value::new_buffer_from_vec(agent, response.body),
+    );
+    Ok(r)
+}
+
+fn finish(agent: &Agent, mut request: Request, response: Response) {
+    match response.status {
+        301 | 302 | 303 | 307 | 308 if response.header("location").is_some() => {
+            if request.redirects >= request.max_redirects {
+                reject(agent, &request, "too many redirects");
+                return;
+            }
+            let url = match request.url.join(response.header("location").unwrap()) {
+                Ok(url) => url,
+                Err(e) => {
+                    reject(agent, &request, &e);
+                    return;
+                }
+            };
+            // like browsers, everything but 307 and 308 is followed with a GET
+            if response.status != 307 && response.status != 308 && request.method != "HEAD" {
+                request.method = "GET".to_string();
+                request.body = Vec::new();
+            }
+            request.url = url;
+            request.redirects += 1;
+            resolve(agent, request);
+        }
+        _ => {
+            if let Some(timer) = request.timer {
+                timers::clear(agent, timer);
+            }
+            match create_response(agent, &request.url, response) {
+                Ok(r) => request
+                    .promise
+                    .get_slot("resolve")
+                    .call(agent, Value::Null, vec![r])
+                    .unwrap(),
+                Err(e) => request
+                    .promise
+                    .get_slot("reject")
+                    .call(agent, Value::Null, vec![e])
+                    .unwrap(),
+            };
+        }
+    }
+}
+
+pub fn handle(agent: &Agent, token: Token, mut request: Request) {
+    if let Some((registration, receiver)) = request.resolving.take() {
+        match receiver.try_recv() {
+            Ok(Ok(addr)) => match TcpStream::connect(&addr) {
+                Ok(stream) => {
+                    request.stream = Some(stream);
+                    start(agent, request);
+                }
+                Err(e) => reject(agent, &request, &format!("{}", e)),
+            },
+            Ok(Err(e)) => reject(agent, &request, &format!("{}", e)),
+            Err(TryRecvError::Empty) => {
+                request.resolving = Some((registration, receiver));
+                agent
+                    .mio_map
+                    .borrow_mut()
+                    .insert(token, MioMapType::Http(request));
+            }
+            Err(TryRecvError::Disconnected) => reject(
+                agent,
+                &request,
+                &format!("could not resolve {}", request.url.host),
+            ),
+        }
+        return;
+    }
+
+    match request.stream.as_ref().unwrap().take_error() {
+        Ok(Some(e)) | Err(e) => {
+            reject(agent, &request, &format!("{}", e));
+            return;
+        }
+        Ok(None) => {}
+    }
+
+    while !request.unwritten.is_empty() {
+        match request.stream.as_mut().unwrap().write(&request.unwritten) {
+            Ok(n) => {
+                request.unwritten.drain(..n);
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            // not connected yet, there will be another event once it is
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotConnected => break,
+            Err(e) => {
+                reject(agent, &request, &format!("{}", e));
+                return;
+            }
+        }
+    }
+
+    let mut eof = false;
+    let mut buf = [0; 4096];
+    loop {
+        match request.stream.as_mut().unwrap().read(&mut buf) {
+            Ok(0) => {
+                eof = true;
+                break;
+            }
+            Ok(n) => request.received.extend_from_slice(&buf[..n]),
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotConnected => break,
+            Err(e) => {
+                reject(agent, &request, &format!("{}", e));
+                return;
+            }
+        }
+    }
+
+    match parse_response(&request.received, eof) {
+        Ok(Some(response)) => finish(agent, request, response),
+        Ok(None) => {
+            agent
+                .mio_map
+                .borrow_mut()
+                .insert(token, MioMapType::Http(request));
+        }
+        Err(e) => reject(agent, &request, &format!("http parse error: {}", e)),
+    }
+}
+
+// removes the request which settles `promise` from the event loop, dropping
+// it closes the connection. Returns None if it has already settled.
+fn take_request(agent: &Agent, promise: &Value) -> Option<Request> {
+    let token = agent
+        .mio_map
+        .borrow()
+        .iter()
+        .find(|(_, entry)| match entry {
+            MioMapType::Http(r) => r.promise == *promise,
+            _ => false,
+        })
+        .map(|(token, _)| *token)?;
+    match agent.mio_map.borrow_mut().remove(&token) {
+        Some(MioMapType::Http(request)) => Some(request),
+        _ => None,
+    }
+}
+
+fn on_timeout(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let promise = ctx.callee().get_slot("http promise");
+    if let Some(mut request) = take_request(agent, &promise) {
+        request.timer = None;
+        reject(agent, &request, "request timed out");
+    }
+    Ok(Value::Null)
+}
+
+fn on_abort_request(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let promise = ctx.callee().get_slot("http promise");
+    if let Some(request) = take_request(agent, &promise) {
+        let reason = args.get(0).cloned().unwrap_or(Value::Null);
+        reject_with(agent, &request, reason);
+    }
+    Ok(Value::Null)
+}
+
+fn fetch(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let url = match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => Url::parse(s).map_err(|e| Value::new_error(agent, &e))?,
+        _ => return Err(Value::new_error(agent, "url must be a string")),
+    };
+
+    let mut method = "GET".to_string();
+    let mut headers = Vec::new();
+    let mut body = Vec::new();
+    let mut timeout = None;
+    let mut max_redirects = DEFAULT_MAX_REDIRECTS;
+    let signal = signal_option(agent, args.get(1))?;
+    match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => {}
+        Some(_) if args[1].type_of() == "object" => {
+            let options = &args[1];
+            match options.get(agent, ObjectKey::new(agent, "method"))?.kind() {
+                ValueKind::Null => {}
+                ValueKind::String(m) if is_token(m) => method = m.to_uppercase(),
+                ValueKind::String(m) => {
+                    return Err(Value::new_error(agent, &format!("invalid method '{}'", m)));
+                }
+                _ => return Err(Value::new_error(agent, "method must be a string")),
+            }
+            let h = options.get(agent, ObjectKey::new(agent, "headers"))?;
+            match h.kind() {
+                ValueKind::Null => {}
+                _ if h.type_of() == "object" => {
+                    for key in h.keys(agent)? {
+                        let name = key.to_string();
+                        if !is_token(&name) {
+                            return Err(Value::new_error(
+                                agent,
+                                &format!("invalid header name '{}'", name),
+                            ));
+                        }
+                        match h.get(agent, key)?.kind() {
+                            ValueKind::String(v)
+                                if v.contains(|c| c == '\r' || c == '\n' || c == '\0') =>
+                            {
+                                return Err(Value::new_error(
+                                    agent,
+                                    &format!("invalid value for header '{}'", name),
+                                ));
+                            }
+                            ValueKind::String(v) => headers.push((name, v.clone())),
+                            _ => {
+                                return Err(Value::new_error(
+                                    agent,
+                                    "header values must be strings",
+                                ));
+                            }
+                        }
+                    }
+                }
+                _ => return Err(Value::new_error(agent, "headers must be an object")),
+            }
+            let b = options.get(agent, ObjectKey::new(agent, "body"))?;
+            match b.kind() {
+                ValueKind::Null => {}
+                ValueKind::String(s) => body = s.clone().into_bytes(),
+                _ => match to_bytes(&b) {
+                    Some(b) => body = b,
+                    None => return Err(Value::new_error(agent, "body must be a string or bytes")),
+                },
+            }
+            match options.get(agent, ObjectKey::new(agent, "timeout"))?.kind() {
+                ValueKind::Null => {}
+                // a timeout longer than any timer can wait never fires
+                ValueKind::Number(n) if n > timers::MAX_DURATION => {}
+                ValueKind::Number(n) if n >= 0.0 => timeout = Some(Duration::from_millis(n as u64)),
+                _ => {
+                    return Err(Value::new_error(
+                        agent,
+                        "timeout must be a non-negative number",
+                    ));
+                }
+            }
+            match options
+                .get(agent, ObjectKey::new(agent, "maxRedirects"))?
+                .kind()
+            {
+                ValueKind::Null => {}
+                // Infinity saturates, allowing any number
+                ValueKind::Number(n) if n >= 0.0 => max_redirects = n as usize,
+                _ => {
+                    return Err(Value::new_error(
+                        agent,
+                        "maxRedirects must be a non-negative number",
+                    ));
+                }
+            }
+        }
+        _ => return Err(Value::new_error(agent, "options must be an object")),
+    }
+
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    if let Some(reason) = signal.as_ref().and_then(abort_reason) {
+        promise
+            .get_slot("reject")
+            .call(agent, Value::Null, vec![reason])?;
+        return Ok(promise);
+    }
+    if let Some(signal) = signal {
+        let callback = Value::new_builtin_function(agent, on_abort_request);
+        callback.set_slot("http promise", promise.clone());
+        on_abort(&signal, callback);
+    }
+    let timer = timeout.map(|duration| {
+        let callback = Value::new_builtin_function(agent, on_timeout);
+        callback.set_slot("http promise", promise.clone());
+        timers::set(agent, callback, duration)
+    });
+    resolve(
+        agent,
+        Request {
+            stream: None,
+            resolving: None,
+            url,
+            method,
+            headers,
+            body,
+            unwritten: Vec::new(),
+            received: Vec::new(),
+            redirects: 0,
+            max_redirects,
+            timer,
+            promise: promise.clone(),
+        },
+    );
+    Ok(promise)
+}
+
+pub fn create(agent: &Agent) -> HashMap<String, Value> {
+    let mut module = HashMap::new();
+    module.insert(
+        "fetch".to_string(),
+        Value::new_named_builtin_function(agent, fetch, "fetch", 2),
+    );
+
+    module
+}
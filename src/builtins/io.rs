@@ -0,0 +1,309 @@
+use crate::agent::{Agent, MioMapType};
+use crate::interpreter::Context;
+use crate::intrinsics::promise::new_promise_capability;
+use crate::value::{ObjectKey, ValueKind};
+use crate::Value;
+use mio::{PollOpt, Ready, Registration, SetReadiness, Token};
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+enum Line {
+    Line(String),
+    End,
+    Error(String),
+}
+
+/// The lines read from stdin by the reader thread. Sending wakes up the mio
+/// loop of the agent.
+struct Lines {
+    lines: Mutex<VecDeque<Line>>,
+    readiness: SetReadiness,
+}
+
+impl Lines {
+    fn send(&self, line: Line) {
+        self.lines.lock().unwrap().push_back(line);
+        self.readiness
+            .set_readiness(Ready::readable())
+            .expect("failed to set stdin readiness");
+    }
+}
+
+/// The reader of stdin for one agent, started by the first read. It stays in
+/// the mio map once started, but only keeps the agent alive while a read is
+/// waiting for a line.
+#[derive(Finalize)]
+pub struct Stdin {
+    registration: Registration,
+    lines: Arc<Lines>,
+    object: Value,
+}
+
+unsafe impl gc::Trace for Stdin {
+    custom_trace!(this, {
+        mark(&this.object);
+    });
+}
+
+impl std::fmt::Debug for Stdin {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "Stdin")
+    }
+}
+
+impl Stdin {
+    pub fn waiting(&self) -> bool {
+        match self.object.get_slot("stdin queue").kind() {
+            ValueKind::List(queue) => !queue.borrow().is_empty(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// a line without its line break. input which ends without one, like after a
+// ctrl-d in the middle of a line, is still a line.
+fn read_lines(mut source: Box<dyn Read + Send>, lines: Arc<Lines>) {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        match source.read(&mut chunk) {
+            Ok(0) => {
+                if !buffer.is_empty() {
+                    lines.send(Line::Line(String::from_utf8_lossy(&buffer).into_owned()));
+                }
+                lines.send(Line::End);
+                return;
+            }
+            Ok(n) => {
+                buffer.extend_from_slice(&chunk[..n]);
+                while let Some(i) = buffer.iter().position(|b| *b == b'\n') {
+                    let mut line = buffer.drain(..=i).collect::<Vec<u8>>();
+                    line.pop();
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    lines.send(Line::Line(String::from_utf8_lossy(&line).into_owned()));
+                }
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                lines.send(Line::Error(e.to_string()));
+                return;
+            }
+        }
+    }
+}
+
+// start the reader thread if this is the first read, then hand out whatever
+// lines are already there.
+fn start(agent: &Agent, stdin: &Value) {
+    let token = if stdin.has_slot("stdin token") {
+        match stdin.get_slot("stdin token").kind() {
+            ValueKind::Number(n) => Token(n as usize),
+            _ => unreachable!(),
+        }
+    } else {
+        let (registration, readiness) = Registration::new2();
+        let lines = Arc::new(Lines {
+            lines: Mutex::new(VecDeque::new()),
+            readiness,
+        });
+        let token = agent.mio_token();
+        agent
+            .mio
+            .register(&registration, token, Ready::readable(), PollOpt::edge())
+            .unwrap();
+        stdin.set_slot("stdin token", Value::from(token.0 as f64));
+        {
+            let source = agent.take_stdin();
+            let lines = lines.clone();
+            std::thread::spawn(move || read_lines(source, lines));
+        }
+        agent.mio_map.borrow_mut().insert(
+            token,
+            MioMapType::Stdin(Stdin {
+                registration,
+                lines,
+                object: stdin.clone(),
+            }),
+        );
+        token
+    };
+    let entry = agent.mio_map.borrow_mut().remove(&token);
+    if let Some(MioMapType::Stdin(reader)) = entry {
+        handle(agent, token, reader);
+    }
+}
+
+// settle a pending read. `next` reads want an iterator result, `readLine`
+// reads want the line, or null at the end.
+fn settle(agent: &Agent, read: Value, line: Result<Option<String>, Value>) {
+    let (promise, iterating) = match read.kind() {
+        ValueKind::Tuple(items) => (items[0].clone(), items[1] == Value::from(true)),
+        _ => unreachable!(),
+    };
+    let (resolution, value) = match line {
+        Ok(line) => {
+            let done = line.is_none();
+            let line = line.map(Value::from).unwrap_or(Value::Null);
+            let value = if iterating {
+                Value::new_iter_result(agent, line, done).unwrap()
+            } else {
+                line
+            };
+            ("resolve", value)
+        }
+        Err(e) => ("reject", e),
+    };
+    promise
+        .get_slot(resolution)
+        .call(agent, Value::Null, vec![value])
+        .unwrap();
+}
+
+pub fn handle(agent: &Agent, token: Token, reader: Stdin) {
+    let queue = match reader.object.get_slot("stdin queue").kind() {
+        ValueKind::List(queue) => (*queue).clone(),
+        _ => unreachable!(),
+    };
+    // clear readiness first so that a line sent while draining produces a
+    // new event.
+    reader
+        .lines
+        .readiness
+        .set_readiness(Ready::empty())
+        .unwrap();
+    loop {
+        if queue.borrow().is_empty() {
+            break;
+        }
+        let line = reader.lines.lines.lock().unwrap().pop_front();
+        match line {
+            Some(Line::Line(line)) => {
+                let read = queue.borrow_mut().pop_front().unwrap();
+                settle(agent, read, Ok(Some(line)));
+            }
+            Some(Line::End) => {
+                reader.object.set_slot("stdin done", Value::Null);
+                break;
+            }
+            Some(Line::Error(e)) => {
+                let e = Value::new_error(agent, &e);
+                reader.object.set_slot("stdin done", e);
+                break;
+            }
+            None => break,
+        }
+    }
+    if reader.object.has_slot("stdin done") {
+        let end = reader.object.get_slot("stdin done");
+        let reads = queue.borrow_mut().drain(..).collect::<Vec<Value>>();
+        for read in reads {
+            settle(agent, read, finished(&end));
+        }
+    }
+    agent
+        .mio_map
+        .borrow_mut()
+        .insert(token, MioMapType::Stdin(reader));
+}
+
+// what every read gets once stdin has ended or failed
+fn finished(end: &Value) -> Result<Option<String>, Value> {
+    match end.kind() {
+        ValueKind::Null => Ok(None),
+        _ => Err(end.clone()),
+    }
+}
+
+fn read(agent: &Agent, stdin: &Value, iterating: bool) -> Result<Value, Value> {
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    let read = Value::Tuple(vec![promise.clone(), Value::from(iterating)]);
+    if stdin.has_slot("stdin done") {
+        settle(agent, read, finished(&stdin.get_slot("stdin done")));
+        return Ok(promise);
+    }
+    match stdin.get_slot("stdin queue").kind() {
+        ValueKind::List(queue) => queue.borrow_mut().push_back(read),
+        _ => unreachable!(),
+    }
+    start(agent, stdin);
+    Ok(promise)
+}
+
+fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() != "object" || !this.has_slot("stdin queue") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    read(agent, &this, true)
+}
+
+fn read_line(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (prompt,) = check_args!(agent, args, "readLine", [optional]);
+    match prompt.kind() {
+        ValueKind::Null => {}
+        ValueKind::String(s) => agent.output(s),
+        _ => {
+            return Err(crate::args::type_error(
+                agent,
+                "readLine",
+                "argument 1",
+                "a string",
+                Some(&prompt),
+            ))
+        }
+    }
+    read(agent, &ctx.callee().get_slot("io stdin"), false)
+}
+
+fn write_stdout(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (s,) = check_args!(agent, args, "stdout.write", [string]);
+    agent.output(&s);
+    Ok(Value::Null)
+}
+
+fn write_stderr(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let (s,) = check_args!(agent, args, "stderr.write", [string]);
+    agent.output_error(&s);
+    Ok(Value::Null)
+}
+
+fn stream(agent: &Agent, write: fn(&Agent, Vec<Value>, &Context) -> Result<Value, Value>) -> Value {
+    let stream = Value::new_object(agent.intrinsics.object_prototype.clone());
+    stream
+        .set(
+            agent,
+            ObjectKey::new(agent, "write"),
+            Value::new_named_builtin_function(agent, write, "write", 1),
+        )
+        .unwrap();
+    stream
+}
+
+pub fn create(agent: &Agent) -> HashMap<String, Value> {
+    let mut module = HashMap::new();
+
+    // stdin is an async iterator of its lines
+    let proto = Value::new_object(agent.intrinsics.async_iterator_prototype.clone());
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+    let stdin = Value::new_custom_object(proto);
+    stdin.set_slot("stdin queue", Value::new_list());
+    module.insert("stdin".to_string(), stdin.clone());
+
+    let f = Value::new_named_builtin_function(agent, read_line, "readLine", 1);
+    f.set_slot("io stdin", stdin);
+    module.insert("readLine".to_string(), f);
+
+    module.insert("stdout".to_string(), stream(agent, write_stdout));
+    module.insert("stderr".to_string(), stream(agent, write_stderr));
+
+    module
+}
@@ -1,6 +1,6 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
-use crate::value::Value;
+use crate::value::{Value, ValueKind};
 use std::collections::HashMap;
 
 fn min(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
@@ -10,7 +10,7 @@ fn min(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
 
     let mut numbers = Vec::new();
     for arg in args {
-        if let Value::Number(n) = arg {
+        if let ValueKind::Number(n) = arg.kind() {
             numbers.push(n);
         } else {
             return Err(Value::new_error(agent, "argument must be a number"));
@@ -29,7 +29,7 @@ fn max(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
 
     let mut numbers = Vec::new();
     for arg in args {
-        if let Value::Number(n) = arg {
+        if let ValueKind::Number(n) = arg.kind() {
             numbers.push(n);
         } else {
             return Err(Value::new_error(agent, "argument must be a number"));
@@ -44,8 +44,14 @@ fn max(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
 pub fn create(agent: &Agent) -> HashMap<String, Value> {
     let mut module = HashMap::new();
 
-    module.insert("min".to_string(), Value::new_builtin_function(agent, min));
-    module.insert("max".to_string(), Value::new_builtin_function(agent, max));
+    module.insert(
+        "min".to_string(),
+        Value::new_named_builtin_function(agent, min, "min", 2),
+    );
+    module.insert(
+        "max".to_string(),
+        Value::new_named_builtin_function(agent, max, "max", 2),
+    );
 
     macro_rules! C {
         ($n:ident) => {
@@ -2,22 +2,35 @@ use crate::agent::Agent;
 use crate::value::Value;
 use std::collections::HashMap;
 
+mod assert;
 mod r#async;
+mod crypto;
 mod debug;
 pub mod fs;
+pub mod http;
+pub mod io;
 mod math;
 pub mod net;
-mod timers;
+pub mod process;
+pub mod time;
+pub mod timers;
+pub mod worker;
 
-pub fn create(agent: &Agent) -> HashMap<String, HashMap<String, Value>> {
-    let mut builtins = HashMap::new();
-
-    builtins.insert("debug".to_string(), debug::create(agent));
-    builtins.insert("timers".to_string(), timers::create(agent));
-    builtins.insert("fs".to_string(), fs::create(agent));
-    builtins.insert("net".to_string(), net::create(agent));
-    builtins.insert("math".to_string(), math::create(agent));
-    builtins.insert("async".to_string(), r#async::create(agent));
-
-    builtins
+pub fn create(agent: &Agent, name: &str) -> Option<HashMap<String, Value>> {
+    match name {
+        "assert" => Some(assert::create(agent)),
+        "debug" => Some(debug::create(agent)),
+        "crypto" => Some(crypto::create(agent)),
+        "timers" => Some(timers::create(agent)),
+        "time" => Some(time::create(agent)),
+        "fs" => Some(fs::create(agent)),
+        "net" => Some(net::create(agent)),
+        "http" => Some(http::create(agent)),
+        "io" => Some(io::create(agent)),
+        "math" => Some(math::create(agent)),
+        "async" => Some(r#async::create(agent)),
+        "process" => Some(process::create(agent)),
+        "worker" => Some(worker::create(agent)),
+        _ => None,
+    }
 }
@@ -1,14 +1,17 @@
 use crate::agent::{Agent, MioMapType};
 use crate::interpreter::Context;
+use crate::intrinsics::abort_controller::{abort_reason, on_abort, signal_option};
 use crate::intrinsics::promise::{new_promise_capability, promise_resolve_i};
-use crate::value::Value;
+use crate::value::{ObjectKey, Value, ValueKind};
 use crate::IntoValue;
 use mio::{
     net::{TcpListener, TcpStream},
     PollOpt, Ready, Token,
 };
+use net2::TcpBuilder;
 use std::collections::HashMap;
 use std::io::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 #[derive(Debug, Finalize)]
 pub enum Net {
@@ -26,14 +29,16 @@ unsafe impl gc::Trace for Net {
 }
 
 fn get_or_create_resolve(kind: &str, agent: &Agent, target: Value, value: Value, done: bool) {
-    if let Value::List(queue) = target.get_slot(&format!("net {} queue", kind)) {
+    if let ValueKind::List(queue) = target.get_slot(&format!("net {} queue", kind)).kind() {
         let value = Value::new_iter_result(agent, value, done).unwrap();
         if let Some(promise) = queue.borrow_mut().pop_front() {
             promise
                 .get_slot("resolve")
                 .call(agent, Value::Null, vec![value])
                 .unwrap();
-        } else if let Value::List(buffer) = target.get_slot(&format!("net {} buffer", kind)) {
+        } else if let ValueKind::List(buffer) =
+            target.get_slot(&format!("net {} buffer", kind)).kind()
+        {
             buffer.borrow_mut().push_back(
                 promise_resolve_i(agent, agent.intrinsics.promise.clone(), value).unwrap(),
             );
@@ -46,13 +51,15 @@ fn get_or_create_resolve(kind: &str, agent: &Agent, target: Value, value: Value,
 }
 
 fn get_or_create_reject(kind: &str, agent: &Agent, target: Value, value: Value) {
-    if let Value::List(queue) = target.get_slot(&format!("net {} queue", kind)) {
+    if let ValueKind::List(queue) = target.get_slot(&format!("net {} queue", kind)).kind() {
         if let Some(promise) = queue.borrow_mut().pop_front() {
             promise
                 .get_slot("reject")
                 .call(agent, Value::Null, vec![value])
                 .unwrap();
-        } else if let Value::List(buffer) = target.get_slot(&format!("net {} buffer", kind)) {
+        } else if let ValueKind::List(buffer) =
+            target.get_slot(&format!("net {} buffer", kind)).kind()
+        {
             let p = new_promise_capability(agent, agent.intrinsics.promise.clone()).unwrap();
             p.get_slot("reject")
                 .call(agent, Value::Null, vec![value])
@@ -100,9 +107,14 @@ pub fn handle(agent: &Agent, token: Token, net: Net) {
                         get_or_create_resolve("client", agent, client, Value::Null, true);
                         return;
                     }
+                    // the peer wrote and closed before this event was seen, and
+                    // with edge triggering there won't be another one for the
+                    // close, so the client is done now.
                     Ok(_) => {
                         let r = Value::new_buffer_from_vec(agent, buf);
                         get_or_create_resolve("client", agent, client.clone(), r, false);
+                        get_or_create_resolve("client", agent, client, Value::Null, true);
+                        return;
                     }
                     Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                         let r = Value::new_buffer_from_vec(agent, buf);
@@ -149,54 +161,213 @@ pub fn handle(agent: &Agent, token: Token, net: Net) {
     }
 }
 
+// closes the socket and rejects any reads which are still waiting.
+fn abort_client(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let client = ctx.callee().get_slot("net client");
+    if let ValueKind::Number(t) = client.get_slot("net client token").kind() {
+        agent.mio_map.borrow_mut().remove(&Token(t as usize));
+    }
+    let reason = args.get(0).cloned().unwrap_or(Value::Null);
+    if let ValueKind::List(queue) = client.get_slot("net client queue").kind() {
+        let waiting = queue.borrow_mut().drain(..).collect::<Vec<Value>>();
+        for promise in waiting {
+            promise
+                .get_slot("reject")
+                .call(agent, Value::Null, vec![reason.clone()])?;
+        }
+    }
+    Ok(Value::Null)
+}
+
+/// `connect(address, { signal }?)`, aborting the signal closes the client.
 fn connect(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    match args.get(0).unwrap_or(&Value::Null) {
-        Value::String(addr) => {
-            let addr: std::net::SocketAddr = match addr.parse() {
+    let signal = signal_option(agent, args.get(1))?;
+    if let Some(reason) = signal.as_ref().and_then(abort_reason) {
+        return Err(reason);
+    }
+    let client = match args.get(0).unwrap_or(&Value::Null).kind() {
+        ValueKind::String(addr) => {
+            let addr: SocketAddr = match addr.parse() {
                 Ok(v) => v,
                 Err(e) => return Err(e.into_value(agent)),
             };
             match TcpStream::connect(&addr) {
-                Ok(v) => create_client(agent, v),
-                Err(e) => Err(e.into_value(agent)),
+                Ok(v) => create_client(agent, v)?,
+                Err(e) => return Err(e.into_value(agent)),
             }
         }
-        _ => Err(Value::new_error(agent, "address must be a string")),
+        _ => return Err(Value::new_error(agent, "address must be a string")),
+    };
+    if let Some(signal) = signal {
+        let callback = Value::new_builtin_function(agent, abort_client);
+        callback.set_slot("net client", client.clone());
+        on_abort(&signal, callback);
     }
+    Ok(client)
 }
 
-fn listen(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
-    match args.get(0).unwrap_or(&Value::Null) {
-        Value::String(addr) => {
-            let addr: std::net::SocketAddr = match addr.parse() {
-                Ok(v) => v,
-                Err(e) => return Err(e.into_value(agent)),
-            };
-            let listener = match TcpListener::bind(&addr) {
-                Ok(v) => v,
-                Err(e) => return Err(e.into_value(agent)),
-            };
-            let token = agent.mio_token();
-            match agent
-                .mio
-                .register(&listener, token, Ready::all(), PollOpt::edge())
-            {
-                Ok(_) => {
-                    let server =
-                        Value::new_custom_object(agent.intrinsics.net_server_prototype.clone());
-                    server.set_slot("net server buffer", Value::new_list());
-                    server.set_slot("net server queue", Value::new_list());
-                    server.set_slot("net server token", Value::from(token.0 as f64));
-                    agent.mio_map.borrow_mut().insert(
-                        token,
-                        MioMapType::Net(Net::Server(listener, server.clone())),
-                    );
-                    Ok(server)
+// the socket options of a listener.
+struct ListenOptions {
+    addr: SocketAddr,
+    backlog: i32,
+    reuse_addr: bool,
+    reuse_port: bool,
+}
+
+fn number_option(agent: &Agent, options: &Value, name: &str) -> Result<Option<f64>, Value> {
+    match options.get(agent, ObjectKey::new(agent, name))?.kind() {
+        ValueKind::Null => Ok(None),
+        ValueKind::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(Some(n)),
+        _ => Err(Value::new_error(
+            agent,
+            &format!("{} must be a non-negative integer", name),
+        )),
+    }
+}
+
+fn boolean_option(
+    agent: &Agent,
+    options: &Value,
+    name: &str,
+    default: bool,
+) -> Result<bool, Value> {
+    match options.get(agent, ObjectKey::new(agent, name))?.kind() {
+        ValueKind::Null => Ok(default),
+        ValueKind::Boolean(b) => Ok(b),
+        _ => Err(Value::new_error(
+            agent,
+            &format!("{} must be a boolean", name),
+        )),
+    }
+}
+
+// `address` is either a string like "127.0.0.1:8080" or an object of
+// `{ port, host, backlog, reuseAddr, reusePort }`, where only the port is
+// required. The address is reused by default, as it was by `TcpListener::bind`.
+fn listen_options(agent: &Agent, address: &Value) -> Result<ListenOptions, Value> {
+    let mut options = ListenOptions {
+        addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+        backlog: 128,
+        reuse_addr: true,
+        reuse_port: false,
+    };
+    match address.kind() {
+        ValueKind::String(addr) => match addr.parse() {
+            Ok(addr) => options.addr = addr,
+            Err(e) => return Err(e.into_value(agent)),
+        },
+        _ if address.type_of() == "object" => {
+            match number_option(agent, address, "port")? {
+                Some(port) if port <= f64::from(u16::MAX) => options.addr.set_port(port as u16),
+                _ => {
+                    return Err(Value::new_error(
+                        agent,
+                        "port must be a number from 0 to 65535",
+                    ))
                 }
-                Err(e) => Err(e.into_value(agent)),
             }
+            match address.get(agent, ObjectKey::new(agent, "host"))?.kind() {
+                ValueKind::Null => {}
+                ValueKind::String(host) => match host.parse() {
+                    Ok(ip) => options.addr.set_ip(ip),
+                    Err(e) => return Err(e.into_value(agent)),
+                },
+                _ => return Err(Value::new_error(agent, "host must be a string")),
+            }
+            if let Some(backlog) = number_option(agent, address, "backlog")? {
+                options.backlog = backlog.min(f64::from(i32::MAX)) as i32;
+            }
+            options.reuse_addr = boolean_option(agent, address, "reuseAddr", true)?;
+            options.reuse_port = boolean_option(agent, address, "reusePort", false)?;
+        }
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "address must be a string or an object",
+            ))
+        }
+    }
+    Ok(options)
+}
+
+fn bind(options: &ListenOptions) -> std::io::Result<TcpListener> {
+    let builder = match options.addr {
+        SocketAddr::V4(..) => TcpBuilder::new_v4()?,
+        SocketAddr::V6(..) => TcpBuilder::new_v6()?,
+    };
+    builder.reuse_address(options.reuse_addr)?;
+    #[cfg(unix)]
+    {
+        use net2::unix::UnixTcpBuilderExt;
+        builder.reuse_port(options.reuse_port)?;
+    }
+    builder.bind(options.addr)?;
+    TcpListener::from_std(builder.listen(options.backlog)?)
+}
+
+// an error for a socket which couldn't be set up, with the number the OS gave
+// the error as its `code`.
+fn os_error(agent: &Agent, e: &std::io::Error) -> Value {
+    let error = e.into_value(agent);
+    if let Some(code) = e.raw_os_error() {
+        error
+            .set(
+                agent,
+                ObjectKey::new(agent, "code"),
+                Value::from(f64::from(code)),
+            )
+            .unwrap();
+    }
+    error
+}
+
+fn create_server(agent: &Agent, listener: TcpListener) -> std::io::Result<Value> {
+    let addr = listener.local_addr()?;
+    let token = agent.mio_token();
+    agent
+        .mio
+        .register(&listener, token, Ready::all(), PollOpt::edge())?;
+    let server = Value::new_custom_object(agent.intrinsics.net_server_prototype.clone());
+    server.set_slot("net server buffer", Value::new_list());
+    server.set_slot("net server queue", Value::new_list());
+    server.set_slot("net server token", Value::from(token.0 as f64));
+    let address = Value::new_object(agent.intrinsics.object_prototype.clone());
+    address
+        .set(
+            agent,
+            ObjectKey::new(agent, "host"),
+            Value::from(addr.ip().to_string()),
+        )
+        .unwrap();
+    address
+        .set(
+            agent,
+            ObjectKey::new(agent, "port"),
+            Value::from(f64::from(addr.port())),
+        )
+        .unwrap();
+    server.set_slot("net server address", address);
+    agent.mio_map.borrow_mut().insert(
+        token,
+        MioMapType::Net(Net::Server(listener, server.clone())),
+    );
+    Ok(server)
+}
+
+/// `listen(address)`, a promise for a server listening on `address`, which
+/// rejects if the socket can't be bound. Every server has its own token, so
+/// an agent can listen on any number of addresses at once.
+fn listen(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let options = listen_options(agent, args.get(0).unwrap_or(&Value::Null))?;
+    match bind(&options).and_then(|listener| create_server(agent, listener)) {
+        Ok(server) => promise_resolve_i(agent, agent.intrinsics.promise.clone(), server),
+        Err(e) => {
+            let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+            promise
+                .get_slot("reject")
+                .call(agent, Value::Null, vec![os_error(agent, &e)])?;
+            Ok(promise)
         }
-        _ => Err(Value::new_error(agent, "address must be a string")),
     }
 }
 
@@ -204,11 +375,11 @@ pub fn create(agent: &Agent) -> HashMap<String, Value> {
     let mut module = HashMap::new();
     module.insert(
         "connect".to_string(),
-        Value::new_builtin_function(agent, connect),
+        Value::new_named_builtin_function(agent, connect, "connect", 2),
     );
     module.insert(
         "listen".to_string(),
-        Value::new_builtin_function(agent, listen),
+        Value::new_named_builtin_function(agent, listen, "listen", 1),
     );
 
     module
@@ -0,0 +1,164 @@
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, ValueKind};
+use crate::{Agent, Value};
+use std::collections::HashMap;
+
+fn cwd(agent: &Agent, _: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    match std::env::current_dir() {
+        Ok(p) => Ok(Value::from(p.to_string_lossy().to_string())),
+        Err(e) => Err(Value::new_error(agent, &format!("{}", e))),
+    }
+}
+
+fn exit(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if n.fract() == 0.0 => agent.exit(n as i32),
+        None | Some(ValueKind::Null) => agent.exit(0),
+        _ => return Err(Value::new_error(agent, "exit code must be an integer")),
+    }
+    Ok(Value::Null)
+}
+
+fn remaining_budget(agent: &Agent, _: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    Ok(match agent.remaining_budget() {
+        Some(n) => Value::from(n as f64),
+        None => Value::Null,
+    })
+}
+
+fn read_only_trap(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    match ctx.callee().get_slot("read only name").kind() {
+        ValueKind::String(name) => Err(Value::new_error(agent, &format!("{} is read-only", name))),
+        _ => unreachable!(),
+    }
+}
+
+// wraps `target` so that assigning or deleting properties throws.
+fn read_only(agent: &Agent, target: Value, name: &str) -> Value {
+    target.prevent_extensions(agent).unwrap();
+    let handler = Value::new_object(Value::Null);
+    for trap in &["set", "deleteProperty"] {
+        let f = Value::new_builtin_function(agent, read_only_trap);
+        f.set_slot("read only name", Value::from(name));
+        handler.set(agent, ObjectKey::new(agent, *trap), f).unwrap();
+    }
+    Value::new_proxy(target, handler)
+}
+
+// frozen rather than wrapped by `read_only`, so it is still an array.
+fn argv(agent: &Agent) -> Value {
+    let args = agent
+        .process_args()
+        .iter()
+        .map(|a| Value::from(a.as_str()))
+        .collect();
+    let argv = Value::new_array_from_vec(agent, args);
+    argv.freeze(agent).unwrap();
+    argv
+}
+
+fn env(agent: &Agent) -> Value {
+    let env = Value::new_object(agent.intrinsics.object_prototype.clone());
+    for (key, value) in agent.process_env() {
+        env.set(
+            agent,
+            ObjectKey::new(agent, key.as_str()),
+            Value::from(value.as_str()),
+        )
+        .unwrap();
+    }
+    read_only(agent, env, "process.env")
+}
+
+/// Create the `process` global, which has the same information as
+/// standard:process.
+///
+/// `process.exit(code)` doesn't end the process right away, as an embedder
+/// may run other agents in it. It records the code, see `Agent::exit_code`,
+/// and stops the event loop: the rest of the running script still runs, but
+/// no job after it does. The `slither` binary then exits with the code.
+pub fn create_global(agent: &Agent) -> Value {
+    let process = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! value {
+        ($name:expr, $value:expr) => {
+            process
+                .set(agent, ObjectKey::new(agent, $name), $value)
+                .unwrap();
+        };
+    }
+
+    value!("argv", argv(agent));
+    value!("env", env(agent));
+    value!(
+        "cwd",
+        Value::new_named_builtin_function(agent, cwd, "cwd", 0)
+    );
+    value!(
+        "exit",
+        Value::new_named_builtin_function(agent, exit, "exit", 1)
+    );
+    value!(
+        "remainingBudget",
+        Value::new_named_builtin_function(agent, remaining_budget, "remainingBudget", 0)
+    );
+    value!("version", Value::from(env!("CARGO_PKG_VERSION")));
+    value!("platform", Value::from(std::env::consts::OS));
+    value!("arch", Value::from(std::env::consts::ARCH));
+
+    process
+}
+
+/// Called when the embedder changes the arguments or environment after the
+/// `process` global was created.
+pub fn update_global(agent: &Agent) {
+    if let Ok(process) = agent
+        .intrinsics
+        .global_object
+        .get(agent, ObjectKey::new(agent, "process"))
+    {
+        if process.type_of() == "object" {
+            let _ = process.set(agent, ObjectKey::new(agent, "argv"), argv(agent));
+            let _ = process.set(agent, ObjectKey::new(agent, "env"), env(agent));
+        }
+    }
+}
+
+pub fn create(agent: &Agent) -> HashMap<String, Value> {
+    let mut module = HashMap::new();
+
+    let args = agent
+        .process_args()
+        .iter()
+        .map(|a| Value::from(a.as_str()))
+        .collect();
+    module.insert("args".to_string(), Value::new_array_from_vec(agent, args));
+
+    let env = Value::new_object(agent.intrinsics.object_prototype.clone());
+    for (key, value) in agent.process_env() {
+        env.set(
+            agent,
+            ObjectKey::new(agent, key.as_str()),
+            Value::from(value.as_str()),
+        )
+        .unwrap();
+    }
+    module.insert("env".to_string(), env);
+
+    module.insert(
+        "cwd".to_string(),
+        Value::new_named_builtin_function(agent, cwd, "cwd", 0),
+    );
+    module.insert(
+        "exit".to_string(),
+        Value::new_named_builtin_function(agent, exit, "exit", 1),
+    );
+    module.insert(
+        "remainingBudget".to_string(),
+        Value::new_named_builtin_function(agent, remaining_budget, "remainingBudget", 0),
+    );
+    module.insert("platform".to_string(), Value::from(std::env::consts::OS));
+    module.insert("arch".to_string(), Value::from(std::env::consts::ARCH));
+
+    module
+}
@@ -0,0 +1,395 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, Value, ValueKind};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+lazy_static! {
+    static ref START: Instant = Instant::now();
+}
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+// Days since 1970-01-01 for a proleptic gregorian date, see
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Broken down time, in whatever offset it was created with.
+struct Components {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    ms: i64,
+}
+
+impl Components {
+    fn from_ms(ms: i64) -> Components {
+        let days = ms.div_euclid(MS_PER_DAY);
+        let rem = ms.rem_euclid(MS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        Components {
+            year,
+            month,
+            day,
+            hour: rem / 3_600_000,
+            minute: rem / 60_000 % 60,
+            second: rem / 1000 % 60,
+            ms: rem % 1000,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.month < 1 || self.month > 12 {
+            return Err(format!("month {} is out of range", self.month));
+        }
+        if self.day < 1 || self.day > days_in_month(self.year, self.month) {
+            return Err(format!(
+                "day {} is out of range for {}-{:02}",
+                self.day, self.year, self.month
+            ));
+        }
+        if self.hour < 0 || self.hour > 23 {
+            return Err(format!("hour {} is out of range", self.hour));
+        }
+        if self.minute < 0 || self.minute > 59 {
+            return Err(format!("minute {} is out of range", self.minute));
+        }
+        if self.second < 0 || self.second > 59 {
+            return Err(format!("second {} is out of range", self.second));
+        }
+        if self.ms < 0 || self.ms > 999 {
+            return Err(format!("ms {} is out of range", self.ms));
+        }
+        Ok(())
+    }
+
+    fn to_ms(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * MS_PER_DAY
+            + self.hour * 3_600_000
+            + self.minute * 60_000
+            + self.second * 1000
+            + self.ms
+    }
+}
+
+/// Create a Time object. `offset` is the fixed offset from UTC in minutes
+/// which the accessors and `toISOString` use.
+pub fn create_time(agent: &Agent, ms: i64, offset: i64) -> Result<Value, Value> {
+    let t = Value::new_custom_object(agent.intrinsics.time_prototype.clone());
+    t.set_slot("time value", Value::from(ms as f64));
+    t.set_slot("time offset", Value::from(offset as f64));
+
+    let c = Components::from_ms(ms + offset * 60_000);
+    macro_rules! accessor {
+        ($name:expr, $value:expr) => {
+            t.set(
+                agent,
+                ObjectKey::new(agent, $name),
+                Value::from($value as f64),
+            )?;
+        };
+    }
+    accessor!("year", c.year);
+    accessor!("month", c.month);
+    accessor!("day", c.day);
+    accessor!("hour", c.hour);
+    accessor!("minute", c.minute);
+    accessor!("second", c.second);
+    accessor!("ms", c.ms);
+    accessor!("offset", offset);
+    Ok(t)
+}
+
+/// The `(ms, offset)` of a Time object.
+pub fn time_value(agent: &Agent, value: &Value) -> Result<(i64, i64), Value> {
+    if !value.has_slot("time value") {
+        return Err(Value::new_error(agent, "value is not a Time"));
+    }
+    match (
+        value.get_slot("time value").kind(),
+        value.get_slot("time offset").kind(),
+    ) {
+        (ValueKind::Number(ms), ValueKind::Number(offset)) => Ok((ms as i64, offset as i64)),
+        _ => unreachable!(),
+    }
+}
+
+pub fn format_iso(ms: i64, offset: i64) -> String {
+    let c = Components::from_ms(ms + offset * 60_000);
+    let date = if c.year >= 0 && c.year <= 9999 {
+        format!("{:04}-{:02}-{:02}", c.year, c.month, c.day)
+    } else {
+        format!("{:+07}-{:02}-{:02}", c.year, c.month, c.day)
+    };
+    let zone = if offset == 0 {
+        "Z".to_string()
+    } else {
+        let sign = if offset < 0 { '-' } else { '+' };
+        format!("{}{:02}:{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+    };
+    format!(
+        "{}T{:02}:{:02}:{:02}.{:03}{}",
+        date, c.hour, c.minute, c.second, c.ms, zone
+    )
+}
+
+// `YYYY-MM-DD[THH:MM[:SS[.sss]]][Z|+HH:MM|-HH:MM]`
+fn parse_iso(s: &str) -> Result<(i64, i64), String> {
+    fn number(s: &str, field: &str) -> Result<i64, String> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("invalid {} '{}'", field, s));
+        }
+        Ok(s.parse::<i64>().unwrap())
+    }
+
+    if !s.is_ascii() {
+        return Err("unexpected non-ascii character".to_string());
+    }
+    if s.len() < 10 || s.as_bytes()[4] != b'-' || s.as_bytes()[7] != b'-' {
+        return Err("expected a date like YYYY-MM-DD".to_string());
+    }
+    let mut c = Components {
+        year: number(&s[0..4], "year")?,
+        month: number(&s[5..7], "month")?,
+        day: number(&s[8..10], "day")?,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        ms: 0,
+    };
+    let mut rest = &s[10..];
+    let mut offset = 0;
+
+    if rest.starts_with('T') {
+        let time_end = rest
+            .find(|c| c == 'Z' || c == '+' || c == '-')
+            .unwrap_or_else(|| rest.len());
+        let time = &rest[1..time_end];
+        let (hms, fraction) = match time.find('.') {
+            Some(i) => (&time[..i], Some(&time[i + 1..])),
+            None => (time, None),
+        };
+        let mut parts = hms.split(':');
+        c.hour = number(parts.next().unwrap_or(""), "hour")?;
+        c.minute = number(parts.next().unwrap_or(""), "minute")?;
+        if let Some(second) = parts.next() {
+            c.second = number(second, "second")?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("invalid time '{}'", time));
+        }
+        if let Some(fraction) = fraction {
+            if fraction.len() > 3 {
+                return Err(format!("invalid ms '{}'", fraction));
+            }
+            // '.5' is 500ms
+            c.ms = number(fraction, "ms")? * 10i64.pow(3 - fraction.len() as u32);
+        }
+        rest = &rest[time_end..];
+    } else if !rest.is_empty() {
+        return Err(format!("unexpected '{}'", rest));
+    }
+
+    if rest == "Z" {
+        rest = "";
+    } else if rest.starts_with('+') || rest.starts_with('-') {
+        if rest.len() != 6 || rest.as_bytes()[3] != b':' {
+            return Err(format!("invalid offset '{}'", rest));
+        }
+        let hours = number(&rest[1..3], "offset")?;
+        let minutes = number(&rest[4..6], "offset")?;
+        if hours > 23 || minutes > 59 {
+            return Err(format!("invalid offset '{}'", rest));
+        }
+        offset = hours * 60 + minutes;
+        if rest.starts_with('-') {
+            offset = -offset;
+        }
+        rest = "";
+    }
+    if !rest.is_empty() {
+        return Err(format!("unexpected '{}'", rest));
+    }
+
+    c.validate()?;
+    Ok((c.to_ms() - offset * 60_000, offset))
+}
+
+fn integer(agent: &Agent, value: Value, name: &str, default: i64) -> Result<i64, Value> {
+    match value.kind() {
+        ValueKind::Null => Ok(default),
+        ValueKind::Number(n) if n.fract() == 0.0 && n.is_finite() => Ok(n as i64),
+        _ => Err(Value::new_error(
+            agent,
+            &format!("{} must be an integer", name),
+        )),
+    }
+}
+
+/// Accepts a number of milliseconds, or an object with any of `days`,
+/// `hours`, `minutes`, `seconds` and `ms`.
+pub fn duration_ms(agent: &Agent, value: &Value) -> Result<i64, Value> {
+    match value.kind() {
+        ValueKind::Number(_) => integer(agent, value.clone(), "duration", 0),
+        _ if value.type_of() == "object" => {
+            let mut ms = 0;
+            for (name, scale) in &[
+                ("days", MS_PER_DAY),
+                ("hours", 3_600_000),
+                ("minutes", 60_000),
+                ("seconds", 1000),
+                ("ms", 1),
+            ] {
+                ms += integer(
+                    agent,
+                    value.get(agent, ObjectKey::new(agent, *name))?,
+                    name,
+                    0,
+                )? * scale;
+            }
+            Ok(ms)
+        }
+        _ => Err(Value::new_error(
+            agent,
+            "duration must be a number or an object",
+        )),
+    }
+}
+
+fn now_ms() -> i64 {
+    let d = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is before the unix epoch");
+    (d.as_secs() * 1000 + u64::from(d.subsec_millis())) as i64
+}
+
+fn now(_: &Agent, _: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    Ok(Value::from(now_ms() as f64))
+}
+
+fn instant(_: &Agent, _: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let d = START.elapsed();
+    Ok(Value::from(
+        d.as_secs() as f64 * 1000.0 + f64::from(d.subsec_nanos()) / 1_000_000.0,
+    ))
+}
+
+fn parse(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => match parse_iso(s) {
+            Ok((ms, offset)) => create_time(agent, ms, offset),
+            Err(e) => Err(Value::new_error(
+                agent,
+                &format!("invalid iso date '{}': {}", s, e),
+            )),
+        },
+        _ => Err(Value::new_error(agent, "argument must be a string")),
+    }
+}
+
+fn time(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => create_time(agent, now_ms(), 0),
+        Some(ValueKind::Number(_)) => {
+            let ms = integer(agent, args[0].clone(), "time", 0)?;
+            let offset = integer(
+                agent,
+                args.get(1).cloned().unwrap_or(Value::Null),
+                "offset",
+                0,
+            )?;
+            create_time(agent, ms, offset)
+        }
+        Some(_) if args[0].type_of() == "object" => {
+            macro_rules! field {
+                ($name:expr, $default:expr) => {
+                    integer(
+                        agent,
+                        args[0].get(agent, ObjectKey::new(agent, $name))?,
+                        $name,
+                        $default,
+                    )?
+                };
+            }
+            let c = Components {
+                year: field!("year", 1970),
+                month: field!("month", 1),
+                day: field!("day", 1),
+                hour: field!("hour", 0),
+                minute: field!("minute", 0),
+                second: field!("second", 0),
+                ms: field!("ms", 0),
+            };
+            let offset = field!("offset", 0);
+            if let Err(e) = c.validate() {
+                return Err(Value::new_error(agent, &e));
+            }
+            create_time(agent, c.to_ms() - offset * 60_000, offset)
+        }
+        _ => Err(Value::new_error(
+            agent,
+            "argument must be a number or an object",
+        )),
+    }
+}
+
+pub fn create(agent: &Agent) -> HashMap<String, Value> {
+    let mut module = HashMap::new();
+    module.insert(
+        "now".to_string(),
+        Value::new_named_builtin_function(agent, now, "now", 0),
+    );
+    module.insert(
+        "instant".to_string(),
+        Value::new_named_builtin_function(agent, instant, "instant", 0),
+    );
+    module.insert(
+        "parseISO".to_string(),
+        Value::new_named_builtin_function(agent, parse, "parseISO", 1),
+    );
+
+    let time = Value::new_named_builtin_function(agent, time, "Time", 2);
+    time.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.time_prototype.clone(),
+    )
+    .unwrap();
+    module.insert("Time".to_string(), time);
+
+    module
+}
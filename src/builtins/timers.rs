@@ -1,9 +1,11 @@
 use crate::agent::{Agent, MioMapType};
 use crate::interpreter::Context;
+use crate::intrinsics::abort_controller::{abort_reason, on_abort, signal_option};
+use crate::intrinsics::promise::new_promise_capability;
 use crate::linked_list::LinkedList;
-use crate::value::Value;
+use crate::value::{ObjectKey, Value, ValueKind};
 use lazy_static::lazy_static;
-use mio::{PollOpt, Ready, Registration, SetReadiness};
+use mio::{PollOpt, Ready, Registration, SetReadiness, Token};
 use num::ToPrimitive;
 use std::collections::HashMap;
 use std::sync::Mutex;
@@ -25,17 +27,28 @@ impl TimerList {
 lazy_static! {
     static ref TIMERS: Mutex<LinkedList<TimerList>> = Mutex::new(LinkedList::new());
     static ref THREAD: std::thread::JoinHandle<()> = std::thread::spawn(move || loop {
-        let mut timers = TIMERS.lock().unwrap();
-        if let Some(list) = timers.cursor().next() {
-            if Instant::now() >= list.instant {
-                while let Some(r) = list.timers.pop_front() {
-                    r.set_readiness(Ready::readable())
-                        .expect("failed to set timer readiness");
+        // the lock must not be held while parked, or nothing could be inserted.
+        let wait = {
+            let mut timers = TIMERS.lock().unwrap();
+            match timers.front_mut() {
+                Some(list) => {
+                    let now = Instant::now();
+                    if now >= list.instant {
+                        while let Some(r) = list.timers.pop_front() {
+                            r.set_readiness(Ready::readable())
+                                .expect("failed to set timer readiness");
+                        }
+                        timers.pop_front();
+                        continue;
+                    }
+                    Some(list.instant - now)
                 }
-                timers.pop_front();
+                None => None,
             }
-        } else {
-            std::thread::park();
+        };
+        match wait {
+            Some(d) => std::thread::park_timeout(d),
+            None => std::thread::park(),
         }
     });
 }
@@ -61,42 +74,168 @@ fn insert(instant: Instant, timer: SetReadiness) {
     timers.push_back(TimerList::new(instant, timer));
 }
 
-fn create_timeout(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
-    let callback = args.get(0).unwrap_or(&Value::Null);
+fn schedule(agent: &Agent, token: Token, callback: Value, duration: Duration, repeat: bool) {
+    let (registration, set_readiness) = Registration::new2();
+
+    agent
+        .mio
+        .register(&registration, token, Ready::readable(), PollOpt::edge())
+        .unwrap();
+    let interval = if repeat { Some(duration) } else { None };
+    agent
+        .mio_map
+        .borrow_mut()
+        .insert(token, MioMapType::Timer(registration, callback, interval));
+
+    insert(Instant::now() + duration, set_readiness);
+    THREAD.thread().unpark();
+}
+
+// the longest a timer can wait, about 24.8 days, so that its deadline can
+// always be represented.
+pub const MAX_DURATION: f64 = 2_147_483_647.0;
+
+fn to_duration(agent: &Agent, duration: Option<&Value>) -> Result<Duration, Value> {
+    match duration.unwrap_or(&Value::from(0)).kind() {
+        ValueKind::Number(n) if n > MAX_DURATION => {
+            let e = Value::new_error(agent, "duration is too long");
+            e.set(agent, ObjectKey::NAME, Value::from("RangeError"))?;
+            Err(e)
+        }
+        ValueKind::Number(n) if n >= 0.0 => Ok(Duration::from_millis(n as u64)),
+        _ => Err(Value::new_error(
+            agent,
+            "duration must be a non-negative number",
+        )),
+    }
+}
+
+fn callback_and_duration(agent: &Agent, args: &[Value]) -> Result<(Value, Duration), Value> {
+    let callback = args.get(0).cloned().unwrap_or(Value::Null);
     if callback.type_of() != "function" {
         return Err(Value::new_error(agent, "callback must be a function"));
     }
-    match args.get(1).unwrap_or(&Value::Null) {
-        Value::Number(n) => {
-            let end = Instant::now() + Duration::from_millis(n.to_u64().unwrap());
-
-            let (registration, set_readiness) = Registration::new2();
-            let token = agent.mio_token();
-
-            agent
-                .mio
-                .register(&registration, token, Ready::readable(), PollOpt::edge())
-                .unwrap();
-            agent
-                .mio_map
-                .borrow_mut()
-                .insert(token, MioMapType::Timer(registration, callback.clone()));
-
-            insert(end, set_readiness);
-            THREAD.thread().unpark();
-
-            // TODO: return object with cancel()
+    Ok((callback, to_duration(agent, args.get(1))?))
+}
+
+/// Called by the event loop when a timer fires, interval timers are
+/// scheduled again with the same token so their handle stays valid.
+pub fn handle(agent: &Agent, token: Token, callback: Value, interval: Option<Duration>) {
+    if let Some(duration) = interval {
+        schedule(agent, token, callback.clone(), duration, true);
+    }
+    agent.enqueue_job(call_timer_job, vec![callback]);
+}
+
+fn call_timer_job(agent: &Agent, args: Vec<Value>) -> Result<(), Value> {
+    args[0].call(agent, Value::Null, Vec::new())?;
+    Ok(())
+}
+
+fn create_timeout(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (callback, duration) = callback_and_duration(agent, &args)?;
+    schedule(agent, agent.mio_token(), callback, duration, false);
+
+    // TODO: return object with cancel()
+    Ok(Value::Null)
+}
+
+pub fn set_timeout(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (callback, duration) = callback_and_duration(agent, &args)?;
+    let token = agent.mio_token();
+    schedule(agent, token, callback, duration, false);
+    Ok(Value::from(token.0 as f64))
+}
+
+pub fn set_interval(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (callback, duration) = callback_and_duration(agent, &args)?;
+    let token = agent.mio_token();
+    schedule(agent, token, callback, duration, true);
+    Ok(Value::from(token.0 as f64))
+}
+
+/// Call `callback` once after `duration`, for use by other builtins.
+pub fn set(agent: &Agent, callback: Value, duration: Duration) -> Token {
+    let token = agent.mio_token();
+    schedule(agent, token, callback, duration, false);
+    token
+}
+
+/// Cancel a timer created by `set`, if it has not fired yet.
+pub fn clear(agent: &Agent, token: Token) {
+    let mut map = agent.mio_map.borrow_mut();
+    if let Some(MioMapType::Timer(..)) = map.get(&token) {
+        // dropping the registration means it will never fire
+        map.remove(&token);
+    }
+}
+
+/// Used for both `clearTimeout` and `clearInterval`.
+pub fn clear_timer(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) => {
+            clear(agent, Token(n.to_usize().unwrap_or(std::usize::MAX)));
             Ok(Value::Null)
         }
-        _ => Err(Value::new_error(agent, "duration must be a number")),
+        None | Some(ValueKind::Null) => Ok(Value::Null),
+        _ => Err(Value::new_error(agent, "invalid timer handle")),
     }
 }
 
+fn resolve_sleep(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let promise = ctx.callee().get_slot("sleep promise");
+    promise
+        .get_slot("resolve")
+        .call(agent, Value::Null, vec![Value::Null])
+}
+
+// the timer is dropped straight away, so an aborted sleep does not keep the
+// event loop alive.
+fn abort_sleep(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = ctx.callee();
+    if let ValueKind::Number(n) = f.get_slot("sleep timer").kind() {
+        clear(agent, Token(n as usize));
+    }
+    let reason = args.get(0).cloned().unwrap_or(Value::Null);
+    f.get_slot("sleep promise")
+        .get_slot("reject")
+        .call(agent, Value::Null, vec![reason])
+}
+
+/// `sleep(duration, { signal }?)` returns a promise which resolves after
+/// `duration` milliseconds, or rejects with the abort reason of `signal`.
+fn sleep(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let duration = to_duration(agent, args.get(0))?;
+    let signal = signal_option(agent, args.get(1))?;
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    if let Some(reason) = signal.as_ref().and_then(abort_reason) {
+        promise
+            .get_slot("reject")
+            .call(agent, Value::Null, vec![reason])?;
+        return Ok(promise);
+    }
+
+    let callback = Value::new_builtin_function(agent, resolve_sleep);
+    callback.set_slot("sleep promise", promise.clone());
+    let token = set(agent, callback, duration);
+    if let Some(signal) = signal {
+        let callback = Value::new_builtin_function(agent, abort_sleep);
+        callback.set_slot("sleep promise", promise.clone());
+        callback.set_slot("sleep timer", Value::from(token.0 as f64));
+        on_abort(&signal, callback);
+    }
+    Ok(promise)
+}
+
 pub fn create(agent: &Agent) -> HashMap<String, Value> {
     let mut module = HashMap::new();
     module.insert(
         "createTimeout".to_string(),
-        Value::new_builtin_function(agent, create_timeout),
+        Value::new_named_builtin_function(agent, create_timeout, "createTimeout", 2),
+    );
+    module.insert(
+        "sleep".to_string(),
+        Value::new_named_builtin_function(agent, sleep, "sleep", 1),
     );
 
     module
@@ -0,0 +1,369 @@
+use crate::agent::{Agent, AgentOptions, MioMapType};
+use crate::interpreter::Context;
+use crate::intrinsics::promise::{new_promise_capability, promise_resolve_i};
+use crate::value::{data_clone_error, CloneData, ObjectKey, Value, ValueKind};
+use mio::{PollOpt, Ready, Registration, SetReadiness, Token};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+enum Message {
+    Value(CloneData),
+    Ready,
+    Error(CloneData),
+    Exit,
+}
+
+/// One direction of the connection between two agents. Sending wakes up the
+/// mio loop of the receiving agent.
+struct Inbox {
+    messages: Mutex<VecDeque<Message>>,
+    readiness: SetReadiness,
+}
+
+impl Inbox {
+    fn new() -> (Registration, Arc<Inbox>) {
+        let (registration, readiness) = Registration::new2();
+        let inbox = Inbox {
+            messages: Mutex::new(VecDeque::new()),
+            readiness,
+        };
+        (registration, Arc::new(inbox))
+    }
+
+    fn send(&self, message: Message) {
+        self.messages.lock().unwrap().push_back(message);
+        self.readiness
+            .set_readiness(Ready::readable())
+            .expect("failed to set worker readiness");
+    }
+}
+
+/// The end of a worker connection owned by one agent.
+#[derive(Finalize)]
+pub struct Worker {
+    registration: Registration,
+    inbox: Arc<Inbox>,
+    outbox: Arc<Inbox>,
+    // only the parent end can interrupt the other agent.
+    interrupt: Option<Arc<AtomicBool>>,
+    object: Value,
+}
+
+unsafe impl gc::Trace for Worker {
+    custom_trace!(this, {
+        mark(&this.object);
+    });
+}
+
+impl std::fmt::Debug for Worker {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "Worker")
+    }
+}
+
+struct Endpoint {
+    registration: Registration,
+    inbox: Arc<Inbox>,
+    outbox: Arc<Inbox>,
+}
+
+thread_local! {
+    // set on worker threads before the agent is created, picked up when the
+    // script imports standard:worker.
+    static PARENT: RefCell<Option<Endpoint>> = RefCell::new(None);
+}
+
+fn clone_or_inspect(agent: &Agent, value: &Value) -> CloneData {
+    CloneData::new(value).unwrap_or_else(|_| CloneData::String(Value::inspect(agent, value)))
+}
+
+fn resolve_next(agent: &Agent, messages: &Value, value: Value, done: bool) {
+    let value = Value::new_iter_result(agent, value, done).unwrap();
+    if let ValueKind::List(queue) = messages.get_slot("worker queue").kind() {
+        if let Some(promise) = queue.borrow_mut().pop_front() {
+            promise
+                .get_slot("resolve")
+                .call(agent, Value::Null, vec![value])
+                .unwrap();
+        } else if let ValueKind::List(buffer) = messages.get_slot("worker buffer").kind() {
+            buffer.borrow_mut().push_back(
+                promise_resolve_i(agent, agent.intrinsics.promise.clone(), value).unwrap(),
+            );
+        } else {
+            unreachable!();
+        }
+    } else {
+        unreachable!();
+    }
+}
+
+fn reject_next(agent: &Agent, messages: &Value, value: Value) {
+    if let ValueKind::List(queue) = messages.get_slot("worker queue").kind() {
+        if let Some(promise) = queue.borrow_mut().pop_front() {
+            promise
+                .get_slot("reject")
+                .call(agent, Value::Null, vec![value])
+                .unwrap();
+        } else if let ValueKind::List(buffer) = messages.get_slot("worker buffer").kind() {
+            let p = new_promise_capability(agent, agent.intrinsics.promise.clone()).unwrap();
+            p.get_slot("reject")
+                .call(agent, Value::Null, vec![value])
+                .unwrap();
+            buffer.borrow_mut().push_back(p);
+        } else {
+            unreachable!();
+        }
+    } else {
+        unreachable!();
+    }
+}
+
+/// Settle every pending and future iteration of `messages` as done.
+fn finish(agent: &Agent, object: &Value) {
+    let messages = object.get_slot("worker messages");
+    if messages.get_slot("worker done") == Value::from(true) {
+        return;
+    }
+    messages.set_slot("worker done", Value::from(true));
+    if let ValueKind::List(queue) = messages.get_slot("worker queue").kind() {
+        while !queue.borrow().is_empty() {
+            resolve_next(agent, &messages, Value::Null, true);
+        }
+    }
+    let ready = object.get_slot("worker ready");
+    if ready.get_slot("promise state") == Value::from("pending") {
+        let e = Value::new_error(agent, "worker exited before it was ready");
+        ready
+            .get_slot("reject")
+            .call(agent, Value::Null, vec![e])
+            .unwrap();
+    }
+}
+
+fn register(
+    agent: &Agent,
+    registration: Registration,
+    inbox: Arc<Inbox>,
+    outbox: Arc<Inbox>,
+    interrupt: Option<Arc<AtomicBool>>,
+) -> Value {
+    let token = agent.mio_token();
+    agent
+        .mio
+        .register(&registration, token, Ready::readable(), PollOpt::edge())
+        .unwrap();
+
+    let messages = Value::new_custom_object(agent.intrinsics.worker_messages_prototype.clone());
+    messages.set_slot("worker queue", Value::new_list());
+    messages.set_slot("worker buffer", Value::new_list());
+    messages.set_slot("worker done", Value::from(false));
+
+    let ready = new_promise_capability(agent, agent.intrinsics.promise.clone()).unwrap();
+    // scripts which never wait for the worker to be ready don't hear about
+    // it failing to start through `ready`
+    ready.set_slot("promise handled", Value::from(true));
+
+    let object = Value::new_custom_object(agent.intrinsics.worker_prototype.clone());
+    object.set_slot("worker token", Value::from(token.0 as f64));
+    object.set_slot("worker messages", messages.clone());
+    object.set_slot("worker ready", ready.clone());
+    object
+        .set(agent, ObjectKey::new(agent, "messages"), messages)
+        .unwrap();
+    object
+        .set(agent, ObjectKey::new(agent, "ready"), ready)
+        .unwrap();
+
+    // messages may have arrived before this end was registered
+    if !inbox.messages.lock().unwrap().is_empty() {
+        inbox.readiness.set_readiness(Ready::readable()).unwrap();
+    }
+
+    agent.mio_map.borrow_mut().insert(
+        token,
+        MioMapType::Worker(Worker {
+            registration,
+            inbox,
+            outbox,
+            interrupt,
+            object: object.clone(),
+        }),
+    );
+
+    object
+}
+
+pub fn handle(agent: &Agent, token: Token, worker: Worker) {
+    let messages = worker.object.get_slot("worker messages");
+    // clear readiness first so that anything sent while draining the inbox
+    // produces a new event.
+    worker
+        .inbox
+        .readiness
+        .set_readiness(Ready::empty())
+        .unwrap();
+    loop {
+        let message = worker.inbox.messages.lock().unwrap().pop_front();
+        match message {
+            Some(Message::Value(data)) => {
+                let value = data.to_value(agent);
+                resolve_next(agent, &messages, value, false);
+            }
+            Some(Message::Ready) => {
+                worker
+                    .object
+                    .get_slot("worker ready")
+                    .get_slot("resolve")
+                    .call(agent, Value::Null, vec![Value::Null])
+                    .unwrap();
+            }
+            Some(Message::Error(data)) => {
+                let e = data.to_value(agent);
+                let ready = worker.object.get_slot("worker ready");
+                if ready.get_slot("promise state") == Value::from("pending") {
+                    ready
+                        .get_slot("reject")
+                        .call(agent, Value::Null, vec![e])
+                        .unwrap();
+                } else {
+                    reject_next(agent, &messages, e);
+                }
+            }
+            Some(Message::Exit) => {
+                finish(agent, &worker.object);
+                return;
+            }
+            None => break,
+        }
+    }
+    agent
+        .mio_map
+        .borrow_mut()
+        .insert(token, MioMapType::Worker(worker));
+}
+
+fn run_worker(
+    filename: String,
+    endpoint: Endpoint,
+    interrupt: Arc<AtomicBool>,
+    options: AgentOptions,
+) {
+    let outbox = endpoint.outbox.clone();
+    PARENT.with(|p| p.replace(Some(endpoint)));
+
+    let mut agent = Agent::new_with_options(options);
+    agent.interrupt = interrupt;
+    {
+        let outbox = outbox.clone();
+        agent.set_uncaught_exception_handler(move |agent: &Agent, e: Value| {
+            outbox.send(Message::Error(clone_or_inspect(agent, &e)));
+        });
+    }
+
+    match agent.import(&filename, &filename) {
+        Ok(_) => {
+            outbox.send(Message::Ready);
+            agent.run_jobs();
+        }
+        Err(e) => {
+            outbox.send(Message::Error(clone_or_inspect(&agent, &e)));
+        }
+    }
+    outbox.send(Message::Exit);
+}
+
+fn worker(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let filename = match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => s,
+        _ => return Err(Value::new_error(agent, "filename must be a string")),
+    };
+    let filename = match std::fs::canonicalize(filename) {
+        Ok(p) => p.to_str().unwrap().to_string(),
+        Err(e) => return Err(Value::new_error(agent, &format!("{}", e))),
+    };
+
+    let (parent_registration, parent_inbox) = Inbox::new();
+    let (worker_registration, worker_inbox) = Inbox::new();
+    let interrupt = Arc::new(AtomicBool::new(false));
+
+    let endpoint = Endpoint {
+        registration: worker_registration,
+        inbox: worker_inbox.clone(),
+        outbox: parent_inbox.clone(),
+    };
+    {
+        let interrupt = interrupt.clone();
+        let options = agent.options();
+        std::thread::spawn(move || run_worker(filename, endpoint, interrupt, options));
+    }
+
+    Ok(register(
+        agent,
+        parent_registration,
+        parent_inbox,
+        worker_inbox,
+        Some(interrupt),
+    ))
+}
+
+pub fn post_message(agent: &Agent, this: &Value, value: &Value) -> Result<(), Value> {
+    let token = match this.get_slot("worker token").kind() {
+        ValueKind::Number(n) => Token(n as usize),
+        _ => unreachable!(),
+    };
+    let data = match CloneData::new(value) {
+        Ok(d) => d,
+        Err(path) => return Err(data_clone_error(agent, &path)),
+    };
+    match agent.mio_map.borrow().get(&token) {
+        Some(MioMapType::Worker(w)) => {
+            w.outbox.send(Message::Value(data));
+            Ok(())
+        }
+        _ => Err(Value::new_error(agent, "worker has been terminated")),
+    }
+}
+
+pub fn terminate(agent: &Agent, this: &Value) {
+    let token = match this.get_slot("worker token").kind() {
+        ValueKind::Number(n) => Token(n as usize),
+        _ => unreachable!(),
+    };
+    let entry = agent.mio_map.borrow_mut().remove(&token);
+    if let Some(MioMapType::Worker(w)) = entry {
+        if let Some(interrupt) = &w.interrupt {
+            interrupt.store(true, Ordering::SeqCst);
+        }
+        // wakes the other agent up, and tells it no more messages are coming.
+        w.outbox.send(Message::Exit);
+    }
+    finish(agent, this);
+}
+
+pub fn create(agent: &Agent) -> HashMap<String, Value> {
+    let mut module = HashMap::new();
+
+    let constructor = Value::new_named_builtin_function(agent, worker, "Worker", 1);
+    constructor
+        .set(
+            agent,
+            ObjectKey::new(agent, "prototype"),
+            agent.intrinsics.worker_prototype.clone(),
+        )
+        .unwrap();
+    module.insert("Worker".to_string(), constructor);
+
+    let parent = match PARENT.with(|p| p.borrow_mut().take()) {
+        Some(Endpoint {
+            registration,
+            inbox,
+            outbox,
+        }) => register(agent, registration, inbox, outbox, None),
+        None => Value::Null,
+    };
+    module.insert("parent".to_string(), parent);
+
+    module
+}
@@ -0,0 +1,153 @@
+//! Property names are interned by the agent, so a string key is a pointer
+//! which is cheap to copy, compare and hash, and looking up a name only
+//! hashes it the first time it is turned into a key. A name stays interned
+//! only while some key uses it, so scripts which make keys as they go don't
+//! grow the interner without bound. Two agents intern names apart, so keys
+//! are written out as names when values move between agents (see
+//! `CloneData`), except for the names of the intrinsics, which the agents
+//! sharing them share too.
+
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+// names the objects themselves check for, which are the same in every agent
+// so no agent is needed to make their keys.
+const PREDEFINED: &[&str] = &["length", "name"];
+
+// the interner forgets names no key uses once it has this many, and then
+// each time it has doubled since.
+const MIN_PURGE: usize = 1024;
+
+#[derive(Clone)]
+pub enum InternedString {
+    Predefined(&'static str),
+    Name(Rc<str>),
+}
+
+impl InternedString {
+    pub const LENGTH: InternedString = InternedString::Predefined("length");
+    pub const NAME: InternedString = InternedString::Predefined("name");
+}
+
+impl Deref for InternedString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            InternedString::Predefined(s) => s,
+            InternedString::Name(s) => s,
+        }
+    }
+}
+
+// an interner hands out one `Rc` per name, so names are the same when their
+// pointers are.
+impl PartialEq for InternedString {
+    fn eq(&self, other: &InternedString) -> bool {
+        match (self, other) {
+            (InternedString::Predefined(a), InternedString::Predefined(b)) => a == b,
+            (InternedString::Name(a), InternedString::Name(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for InternedString {}
+
+impl Hash for InternedString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            InternedString::Predefined(s) => s.hash(state),
+            InternedString::Name(s) => (Rc::as_ptr(s) as *const u8 as usize).hash(state),
+        }
+    }
+}
+
+impl std::fmt::Debug for InternedString {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl gc::Finalize for InternedString {}
+
+unsafe impl gc::Trace for InternedString {
+    gc::unsafe_empty_trace!();
+}
+
+pub struct Interner {
+    // names interned by the agent which created the intrinsics, which stay
+    // interned for as long as the agents sharing them
+    shared: Rc<HashSet<Rc<str>>>,
+    names: HashSet<Rc<str>>,
+    purge_at: usize,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::with_shared(Rc::new(HashSet::new()))
+    }
+
+    /// An interner which gives the names in `shared` the keys the interner
+    /// they were frozen from did, see `freeze`.
+    pub fn with_shared(shared: Rc<HashSet<Rc<str>>>) -> Interner {
+        Interner {
+            shared,
+            names: HashSet::new(),
+            purge_at: MIN_PURGE,
+        }
+    }
+
+    pub fn intern(&mut self, name: &str) -> InternedString {
+        if let Some(s) = PREDEFINED.iter().find(|s| **s == name) {
+            return InternedString::Predefined(s);
+        }
+        if let Some(s) = self.shared.get(name).or_else(|| self.names.get(name)) {
+            return InternedString::Name(s.clone());
+        }
+        if self.names.len() >= self.purge_at {
+            // only the interner has these, so no key can be compared to them
+            self.names.retain(|s| Rc::strong_count(s) > 1);
+            self.purge_at = MIN_PURGE.max(self.names.len() * 2);
+        }
+        let s = Rc::<str>::from(name);
+        self.names.insert(s.clone());
+        InternedString::Name(s)
+    }
+
+    /// Keep every name interned so far for as long as this interner, and
+    /// return them to share with interners made `with_shared`.
+    pub fn freeze(&mut self) -> Rc<HashSet<Rc<str>>> {
+        let mut shared = (*self.shared).clone();
+        shared.extend(self.names.drain());
+        self.shared = Rc::new(shared);
+        self.shared.clone()
+    }
+}
+
+#[test]
+fn test_interner() {
+    let mut first = Interner::new();
+    let length = first.intern("length");
+    let kept = first.intern("kept");
+    assert_eq!(length, InternedString::LENGTH);
+    assert_eq!(first.intern("kept"), kept);
+    assert_eq!(&*kept, "kept");
+
+    // names no key uses are forgotten as more are interned
+    for i in 0..MIN_PURGE * 4 {
+        first.intern(&format!("key {}", i));
+    }
+    assert!(first.names.len() <= MIN_PURGE * 2);
+    assert_eq!(first.intern("kept"), kept);
+
+    // frozen names are shared, the names interned after are not
+    let mut second = Interner::with_shared(first.freeze());
+    let later = first.intern("later");
+    assert_eq!(second.intern("kept"), kept);
+    assert_eq!(second.intern("length"), length);
+    assert_ne!(second.intern("later"), later);
+    assert_eq!(&*second.intern("later"), "later");
+}
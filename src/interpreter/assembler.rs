@@ -1,7 +1,11 @@
 use crate::interpreter::{Op, REGISTER_COUNT};
-use crate::parser::{FunctionKind, Node, Operator, Scope, ScopeKind};
+use crate::parser::{FunctionKind, Node, Operator, Scope, ScopeKind, SourcePosition};
 use crate::runtime::RuntimeFunction;
+use crate::tracer::Span;
 use byteorder::{LittleEndian, WriteBytesExt};
+use indexmap::IndexMap;
+use num::BigInt;
+use std::collections::HashMap;
 
 struct Register {
     id: u32,
@@ -58,22 +62,106 @@ impl Drop for Label {
     }
 }
 
+// the names a match pattern binds, in order
+fn pattern_bindings<'a>(pattern: &'a Node, bindings: &mut Vec<&'a str>) {
+    match pattern {
+        Node::Identifier(binding) if binding != "_" => bindings.push(binding),
+        Node::ObjectPattern(patterns, _) => {
+            for pattern in patterns.values() {
+                pattern_bindings(pattern, bindings);
+            }
+        }
+        Node::ArrayPattern(patterns, _) | Node::TuplePattern(patterns) => {
+            for pattern in patterns {
+                pattern_bindings(pattern, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+// where each statement of a block starts, unless the block wasn't parsed
+// from source, or was and has been changed since.
+fn statement_positions(scope: &Scope, stmts: &[Node]) -> Vec<Option<SourcePosition>> {
+    if scope.positions.len() == stmts.len() {
+        scope
+            .positions
+            .iter()
+            .map(|span| Some(span.start))
+            .collect()
+    } else {
+        vec![None; stmts.len()]
+    }
+}
+
+fn has_spread(args: &[Node]) -> bool {
+    args.iter().any(|a| match a {
+        Node::SpreadElement(..) => true,
+        _ => false,
+    })
+}
+
 pub struct AssemblerFunctionInfo {
     pub kind: FunctionKind,
     pub name: Option<String>,
     pub parameters: Vec<String>,
+    /// Whether the last parameter collects the remaining arguments.
+    pub rest: bool,
+    /// Number of parameters before the first default or rest parameter.
+    pub length: usize,
     pub position: usize,
+    /// Where the body ends, for tracing.
+    pub end: usize,
+}
+
+// where `break` and `continue` jump to, and how much they have to unwind
+struct LoopTarget {
+    label: Option<String>,
+    break_label: *mut Label,
+    continue_label: *mut Label,
+    scope_depth: usize,
+    try_depth: usize,
+    // register holding the iterator of a for loop, and whether it is async
+    iterator: Option<(u32, bool)>,
+}
+
+// a finally clause around the code being assembled. break, continue and
+// return leave the try block with a jump, so they run the clause first.
+struct FinallyTarget {
+    clause: *const Node,
+    // how many loops are around the try statement
+    loops: usize,
+    scope_depth: usize,
+    try_depth: usize,
 }
 
 pub struct Assembler {
     pub code: Vec<u8>,
     pub string_table: Vec<String>,
     pub function_info: Vec<AssemblerFunctionInfo>,
+    // the code of each statement, ordered by where it starts
+    statements: Vec<Span>,
+    // where the code from each `assemble` starts, and the file it is from
+    files: Vec<(usize, String)>,
+    // the source position of the code from each offset on, until the next
+    positions: Vec<(usize, SourcePosition)>,
+    // the position of the statement being assembled
+    position: Option<SourcePosition>,
     register_index: u32,
     register_max: u32,
-    break_label: Option<*mut Label>,
-    continue_label: Option<*mut Label>,
-    throw_label: Option<*mut Label>,
+    loops: Vec<LoopTarget>,
+    finally: Vec<FinallyTarget>,
+    pending_label: Option<String>,
+    scope_depth: usize,
+    try_depth: usize,
+    // the catch clause a `throw` can jump straight to, and the scope depth
+    // of its try statement
+    throw_label: Option<(*mut Label, usize)>,
+    // the bindings of each scope the code being assembled runs in, innermost
+    // last, with their index in the scope, which is the order they are
+    // created in. the scope of a script, module or REPL isn't here, as it
+    // gets bindings this can't see, like imports and earlier REPL lines.
+    scopes: Vec<HashMap<String, u32>>,
 }
 
 impl Assembler {
@@ -82,16 +170,26 @@ impl Assembler {
             code: Vec::new(),
             string_table: Vec::new(),
             function_info: Vec::new(),
+            statements: Vec::new(),
+            files: Vec::new(),
+            positions: Vec::new(),
+            position: None,
             register_index: 0,
             register_max: REGISTER_COUNT as u32,
-            break_label: None,
-            continue_label: None,
+            loops: Vec::new(),
+            finally: Vec::new(),
+            pending_label: None,
+            scope_depth: 0,
+            try_depth: 0,
             throw_label: None,
+            scopes: Vec::new(),
         }
     }
 
-    pub fn assemble(&mut self, ast: &Node) -> usize {
+    pub fn assemble(&mut self, filename: &str, ast: &Node) -> usize {
         let start = self.code.len();
+        self.files.push((start, filename.to_string()));
+        self.position = None;
         self.visit(ast);
         self.push_op(Op::End);
         start
@@ -103,6 +201,7 @@ impl Assembler {
             Node::TrueLiteral => self.visit_true(),
             Node::FalseLiteral => self.visit_false(),
             Node::NumberLiteral(n) => self.visit_number(*n),
+            Node::BigIntLiteral(n) => self.visit_bigint(n),
             Node::StringLiteral(s) => self.visit_string(s),
             Node::SymbolLiteral(s) => self.visit_symbol(s),
             Node::RegexLiteral(r) => self.visit_regex(r),
@@ -129,6 +228,7 @@ impl Assembler {
             Node::YieldExpression(expr) => self.visit_yield(expr),
             Node::AwaitExpression(expr) => self.visit_await(expr),
             Node::ThisExpression => self.visit_this(),
+            Node::ImportMeta => self.visit_identifier("import.meta"),
             Node::NewExpression(target) => self.visit_new(target),
             Node::MemberExpression(target, key) => self.visit_member_expression(target, key),
             Node::ComputedMemberExpression(target, expr) => {
@@ -154,8 +254,9 @@ impl Assembler {
             Node::LexicalInitialization(var, expr) => self.visit_lexical_initialization(var, expr),
             Node::ReturnStatement(expr) => self.visit_return(expr),
             Node::ThrowStatement(expr) => self.visit_throw(expr),
-            Node::BreakStatement => self.visit_break(),
-            Node::ContinueStatement => self.visit_continue(),
+            Node::BreakStatement(label) => self.visit_break(label),
+            Node::ContinueStatement(label) => self.visit_continue(label),
+            Node::LabeledStatement(label, body) => self.visit_labeled(label, body),
             Node::TryStatement(tryc, binding, catch, finally) => {
                 self.visit_try(tryc, binding, catch, finally)
             }
@@ -167,9 +268,16 @@ impl Assembler {
                 self.load_null();
             }
             Node::ExportDeclaration(decl) => self.visit_export(decl),
-            Node::Initializer(..) => unreachable!(),
+            Node::Initializer(..)
+            | Node::RestElement(..)
+            | Node::SpreadElement(..)
+            | Node::ObjectSpread(..) => unreachable!(),
             Node::MatchArm(..) => unreachable!(),
-            Node::ObjectPattern(..) | Node::ArrayPattern(..) => unreachable!(),
+            // only made when parsing with recovery, which is never assembled
+            Node::ErrorNode => unreachable!(),
+            Node::ObjectPattern(..) | Node::ArrayPattern(..) | Node::TuplePattern(..) => {
+                unreachable!()
+            }
         }
     }
 
@@ -189,6 +297,12 @@ impl Assembler {
         self.load_f64(n);
     }
 
+    fn visit_bigint(&mut self, n: &BigInt) {
+        let id = self.string_id(&n.to_string());
+        self.push_op(Op::LoadBigInt);
+        self.push_u32(id);
+    }
+
     fn visit_string(&mut self, s: &str) {
         self.load_string(s);
     }
@@ -206,6 +320,12 @@ impl Assembler {
     fn visit_array(&mut self, exprs: &[Node]) {
         let rscope = RegisterScope::new(self);
         let array = rscope.register();
+        if has_spread(exprs) {
+            // the indices after a spread element aren't known
+            self.build_argument_array(exprs, &array);
+            self.load_accumulator_with_register(&array);
+            return;
+        }
         self.push_op(Op::CreateEmptyArray);
         self.store_accumulator_in_register(&array);
         for (idx, expr) in exprs.iter().enumerate() {
@@ -237,15 +357,22 @@ impl Assembler {
         self.push_op(Op::CreateEmptyObject);
         self.store_accumulator_in_register(&obj);
         for init in inits {
-            if let Node::Initializer(name, value) = init {
-                self.visit(name);
-                self.store_accumulator_in_register(&key);
-                self.visit(value);
-                self.push_op(Op::StoreInObjectLiteral);
-                self.push_u32(obj.id);
-                self.push_u32(key.id);
-            } else {
-                unreachable!();
+            match init {
+                Node::Initializer(name, value) => {
+                    // each key is evaluated once, right before its value.
+                    self.visit(name);
+                    self.store_accumulator_in_register(&key);
+                    self.visit(value);
+                    self.push_op(Op::StoreInObjectLiteral);
+                    self.push_u32(obj.id);
+                    self.push_u32(key.id);
+                }
+                Node::ObjectSpread(expr) => {
+                    self.visit(expr);
+                    self.push_op(Op::SpreadIntoObject);
+                    self.push_u32(obj.id);
+                }
+                _ => unreachable!(),
             }
         }
         self.load_accumulator_with_register(&obj);
@@ -299,15 +426,49 @@ impl Assembler {
         }
     }
 
+    // how many scopes out `name` is declared and its index in that scope,
+    // unless it isn't in any of `scopes` and has to be looked up by name.
+    fn resolve(&self, name: &str) -> Option<(u32, u32)> {
+        self.scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| scope.get(name).map(|slot| (depth as u32, *slot)))
+    }
+
     fn visit_identifier(&mut self, name: &str) {
-        self.push_op(Op::ResolveIdentifier);
-        let id = self.string_id(name);
-        self.push_u32(id);
+        match self.resolve(name) {
+            Some((depth, slot)) => {
+                self.push_op(Op::ResolveSlot);
+                self.push_u32(depth);
+                self.push_u32(slot);
+            }
+            None => {
+                self.push_op(Op::ResolveIdentifier);
+                let id = self.string_id(name);
+                self.push_u32(id);
+            }
+        }
+    }
+
+    fn assign_identifier(&mut self, name: &str) {
+        match self.resolve(name) {
+            Some((depth, slot)) => {
+                self.push_op(Op::AssignSlot);
+                self.push_u32(depth);
+                self.push_u32(slot);
+            }
+            None => {
+                self.push_op(Op::AssignIdentifier);
+                let id = self.string_id(name);
+                self.push_u32(id);
+            }
+        }
     }
 
     fn visit_block(&mut self, scope: &Scope, stmts: &[Node]) {
         if !scope.bindings.is_empty() && scope.kind != ScopeKind::TopLevel {
-            self.push_op(Op::EnterScope);
+            self.enter_scope();
         }
         for (name, mutable) in &scope.bindings {
             self.lexical_declaration(name, *mutable);
@@ -315,11 +476,95 @@ impl Assembler {
         if stmts.is_empty() {
             self.load_null();
         }
-        for stmt in stmts {
-            self.visit(stmt);
+        for (stmt, position) in stmts.iter().zip(statement_positions(scope, stmts)) {
+            self.visit_statement_at(stmt, position);
         }
         if !scope.bindings.is_empty() && scope.kind != ScopeKind::TopLevel {
-            self.push_op(Op::ExitScope);
+            self.exit_scope();
+        }
+    }
+
+    // the code of a statement is attributed to where it is in the source,
+    // and the code after it to the statement around it again.
+    fn visit_statement_at(&mut self, stmt: &Node, position: Option<SourcePosition>) {
+        let outer = self.position;
+        if let Some(position) = position {
+            self.mark_position(position);
+        }
+        self.visit_statement(stmt);
+        if let Some(outer) = outer {
+            self.mark_position(outer);
+        }
+        self.position = outer;
+    }
+
+    fn mark_position(&mut self, position: SourcePosition) {
+        self.position = Some(position);
+        let start = self.code.len();
+        match self.positions.last_mut() {
+            Some((pc, last)) if *pc == start => *last = position,
+            _ => self.positions.push((start, position)),
+        }
+    }
+
+    /// The file and source position of the statement whose code includes
+    /// `pc`, when it was assembled from source.
+    pub fn position_at(&self, pc: usize) -> Option<(&str, SourcePosition)> {
+        let file = match self.files.binary_search_by_key(&pc, |(start, _)| *start) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let (start, filename) = &self.files[file];
+        let index = match self.positions.binary_search_by_key(&pc, |(pc, _)| *pc) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let (offset, position) = self.positions[index];
+        if offset < *start {
+            return None;
+        }
+        Some((filename.as_str(), position))
+    }
+
+    // records where the statement's code is for tracers. a block starting
+    // with a statement starts at the same place, so only the outer one is kept.
+    fn visit_statement(&mut self, stmt: &Node) {
+        let start = self.code.len();
+        match self.statements.last() {
+            Some(span) if span.start == start => self.visit(stmt),
+            _ => {
+                let index = self.statements.len();
+                self.statements.push(Span { start, end: start });
+                self.visit(stmt);
+                self.statements[index].end = self.code.len();
+            }
+        }
+    }
+
+    /// The statement whose code starts at `pc`.
+    pub fn statement_at(&self, pc: usize) -> Option<Span> {
+        self.statements
+            .binary_search_by_key(&pc, |span| span.start)
+            .ok()
+            .map(|index| self.statements[index])
+    }
+
+    /// The body of the function whose code starts at `position`.
+    pub fn function_span(&self, position: usize) -> Span {
+        match self
+            .function_info
+            .binary_search_by_key(&position, |info| info.position)
+        {
+            Ok(index) => Span {
+                start: position,
+                end: self.function_info[index].end,
+            },
+            Err(..) => Span {
+                start: position,
+                end: position,
+            },
         }
     }
 
@@ -336,14 +581,18 @@ impl Assembler {
 
     fn visit_conditional(&mut self, test: &Node, consequent: &Node, alternative: &Node) {
         let mut alt = self.label();
+        let mut end = self.label();
         self.visit(test);
         self.jump_if_false(&mut alt);
         self.visit(consequent);
+        self.jump(&mut end);
         self.mark(&mut alt);
         self.visit(alternative);
+        self.mark(&mut end);
     }
 
     fn visit_while(&mut self, test: &Node, body: &Node) {
+        let label = self.pending_label.take();
         let rscope = RegisterScope::new(self);
         let result = rscope.register();
 
@@ -355,14 +604,10 @@ impl Assembler {
         self.mark(&mut head);
         self.visit(test);
         self.jump_if_false(&mut end);
-        let pbl = self.break_label;
-        self.break_label = Some(&mut end as *mut Label);
-        let pcl = self.continue_label;
-        self.continue_label = Some(&mut head as *mut Label);
+        self.push_loop(label, &mut end, &mut head);
         self.visit(body);
         self.store_accumulator_in_register(&result);
-        self.break_label = pbl;
-        self.continue_label = pcl;
+        self.loops.pop();
         self.jump(&mut head);
         self.mark(&mut end);
         self.load_accumulator_with_register(&result);
@@ -380,12 +625,26 @@ impl Assembler {
         if result.done {
           jump end
         }
-        BINDING = result.value
-        BODY
+        try {
+          { // a fresh scope per iteration
+            const BINDING = result.value
+            BODY
+          }
+        } catch or generator return {
+          (await) IteratorClose(iterator)
+          rethrow
+        }
         jump head
         end:
+
+        leaving the loop through break or return also closes the iterator,
+        see unwind_to and visit_return.
+
+        since BINDING lives in the scope entered for each iteration, closures
+        created in BODY capture the value from their own iteration.
         */
 
+        let label = self.pending_label.take();
         let mut head = self.label();
         let mut end = self.label();
 
@@ -416,38 +675,79 @@ impl Assembler {
         self.load_named_property("done");
         self.jump_if_true(&mut end);
 
+        self.push_loop(label, &mut end, &mut head);
+        self.loops.last_mut().unwrap().iterator = Some((iterator.id, r#async));
+
+        let mut handler = self.label();
+        self.push_op(Op::PushFinally);
+        self.jmp(&mut handler);
+        let ptl = self.throw_label.take();
+        self.try_depth += 1;
+
         self.load_accumulator_with_register(&result);
         self.load_named_property("value");
 
-        self.push_op(Op::EnterScope);
+        self.enter_scope();
 
         self.lexical_declaration(binding, false);
         self.lexical_initialization(binding);
 
-        let pbl = self.break_label;
-        self.break_label = Some(&mut end as *mut Label);
-        let pcl = self.continue_label;
-        self.continue_label = Some(&mut head as *mut Label);
-
         self.visit(body);
 
-        self.break_label = pbl;
-        self.continue_label = pcl;
+        self.exit_scope();
 
-        self.push_op(Op::ExitScope);
+        self.try_depth -= 1;
+        self.throw_label = ptl;
+        self.push_op(Op::PopTry);
+
+        self.loops.pop();
 
         self.jump(&mut head);
 
+        // errors from `return()` are dropped in favour of the error (or
+        // generator return) which is already unwinding the loop.
+        self.mark(&mut handler);
+        let value = rscope.register();
+        let returning = rscope.register();
+        self.push_op(Op::SaveCompletion);
+        self.push_u32(value.id);
+        self.push_u32(returning.id);
+        self.close_iterator(iterator.id, r#async, true);
+        self.push_op(Op::RestoreCompletion);
+        self.push_u32(value.id);
+        self.push_u32(returning.id);
+        self.push_op(Op::ThrowDynamic);
+
         self.mark(&mut end);
         self.load_accumulator_with_register(&body_result);
     }
 
+    fn close_iterator(&mut self, iterator: u32, r#async: bool, swallow: bool) {
+        self.push_op(if r#async {
+            Op::AsyncIteratorClose
+        } else {
+            Op::IteratorClose
+        });
+        self.push_u32(iterator);
+        self.push_u8(swallow as u8);
+    }
+
     fn visit_expression_statement(&mut self, expr: &Node) {
         self.visit(expr);
     }
 
     fn visit_unary(&mut self, op: Operator, expr: &Node) {
-        self.visit(expr);
+        if op == Operator::Delete {
+            self.visit_delete(expr);
+            return;
+        }
+        if let (Operator::Typeof, Node::Identifier(name)) = (op, expr) {
+            self.push_op(Op::ResolveIdentifierForTypeof);
+            let id = self.string_id(name);
+            self.push_u32(id);
+        } else {
+            self.visit(expr);
+        }
         match op {
             Operator::Not => self.push_op(Op::LNOT),
             Operator::BitwiseNOT => self.push_op(Op::BitNOT),
@@ -458,6 +758,26 @@ impl Assembler {
         }
     }
 
+    fn visit_delete(&mut self, expr: &Node) {
+        let rscope = RegisterScope::new(self);
+        let obj = rscope.register();
+        match expr {
+            Node::MemberExpression(base, name) => {
+                self.visit(base);
+                self.store_accumulator_in_register(&obj);
+                self.load_string(name);
+            }
+            Node::ComputedMemberExpression(base, key) => {
+                self.visit(base);
+                self.store_accumulator_in_register(&obj);
+                self.visit(key);
+            }
+            _ => unreachable!(),
+        }
+        self.push_op(Op::DeleteProperty);
+        self.push_u32(obj.id);
+    }
+
     fn visit_binary(&mut self, op: Operator, lhs: &Node, rhs: &Node) {
         if op == Operator::LogicalAND {
             let mut end = self.label();
@@ -482,10 +802,8 @@ impl Assembler {
         if op == Operator::Assign {
             match lhs {
                 Node::Identifier(s) => {
-                    self.visit(rhs);
-                    self.push_op(Op::AssignIdentifier);
-                    let id = self.string_id(s);
-                    self.push_u32(id);
+                    self.visit_named(rhs, s);
+                    self.assign_identifier(s);
                 }
                 Node::MemberExpression(base, name) => {
                     let obj = rscope.register();
@@ -526,6 +844,9 @@ impl Assembler {
             Operator::BitwiseAND => self.push_op(Op::BitAND),
             Operator::LeftShift => self.push_op(Op::ShiftLeft),
             Operator::RightShift => self.push_op(Op::ShiftRight),
+            Operator::UnsignedRightShift | Operator::UnsignedRightShiftAssign => {
+                self.push_op(Op::ShiftRightUnsigned)
+            }
             Operator::GreaterThan => self.push_op(Op::GreaterThan),
             Operator::LessThan => self.push_op(Op::LessThan),
             Operator::GreaterThanOrEqual => self.push_op(Op::GreaterThanOrEqual),
@@ -533,6 +854,8 @@ impl Assembler {
             Operator::Equal => self.push_op(Op::Eq),
             Operator::NotEqual => self.push_op(Op::Neq),
             Operator::Has => self.push_op(Op::HasProperty),
+            Operator::Range => self.push_op(Op::CreateRange),
+            Operator::RangeInclusive => self.push_op(Op::CreateInclusiveRange),
             _ => unreachable!(),
         }
         self.push_u32(lhsr.id);
@@ -544,12 +867,9 @@ impl Assembler {
             | Operator::MulAssign
             | Operator::DivAssign
             | Operator::ModAssign
-            | Operator::PowAssign => match lhs {
-                Node::Identifier(s) => {
-                    self.push_op(Op::AssignIdentifier);
-                    let id = self.string_id(s);
-                    self.push_u32(id);
-                }
+            | Operator::PowAssign
+            | Operator::UnsignedRightShiftAssign => match lhs {
+                Node::Identifier(s) => self.assign_identifier(s),
                 Node::MemberExpression(base, name) => {
                     let value = rscope.register();
                     let obj = rscope.register();
@@ -641,6 +961,18 @@ impl Assembler {
             }
         };
 
+        if has_spread(args) {
+            let array = rscope.register();
+            self.build_argument_array(args, &array);
+
+            // CallWithArray <receiver> <callee> <argument array>
+            self.push_op(Op::CallWithArray);
+            self.push_u32(receiver.id);
+            self.push_u32(callee.id);
+            self.push_u32(array.id);
+            return;
+        }
+
         let rarg = self.register_index;
         for arg in args {
             let reg = rscope.register();
@@ -664,6 +996,16 @@ impl Assembler {
                 let callee = rscope.register();
                 self.store_accumulator_in_register(&callee);
 
+                if has_spread(args) {
+                    let array = rscope.register();
+                    self.build_argument_array(args, &array);
+
+                    self.push_op(Op::ConstructWithArray);
+                    self.push_u32(callee.id);
+                    self.push_u32(array.id);
+                    return;
+                }
+
                 let rarg = self.register_index;
                 for arg in args {
                     let reg = rscope.register();
@@ -683,6 +1025,24 @@ impl Assembler {
         }
     }
 
+    fn build_argument_array(&mut self, args: &[Node], array: &Register) {
+        self.push_op(Op::CreateEmptyArray);
+        self.store_accumulator_in_register(array);
+        for arg in args {
+            match arg {
+                Node::SpreadElement(expr) => {
+                    self.visit(expr);
+                    self.push_op(Op::SpreadIntoArray);
+                }
+                _ => {
+                    self.visit(arg);
+                    self.push_op(Op::AppendToArray);
+                }
+            }
+            self.push_u32(array.id);
+        }
+    }
+
     fn visit_function_expression(
         &mut self,
         kind: FunctionKind,
@@ -728,12 +1088,13 @@ impl Assembler {
         self.push_op(Op::NewFunction);
         let info = AssemblerFunctionInfo {
             position: self.code.len() + 9,
+            end: 0,
             kind,
             name,
             parameters: params
                 .iter()
                 .map(|n: &Node| match n {
-                    Node::Identifier(s) => s.to_string(),
+                    Node::Identifier(s) | Node::RestElement(s) => s.to_string(),
                     Node::Initializer(s, ..) => {
                         if let Node::Identifier(s) = &**s {
                             s.to_string()
@@ -744,15 +1105,56 @@ impl Assembler {
                     _ => unreachable!(),
                 })
                 .collect::<Vec<String>>(),
+            rest: match params.last() {
+                Some(Node::RestElement(..)) => true,
+                _ => false,
+            },
+            length: params
+                .iter()
+                .take_while(|n| match n {
+                    Node::Identifier(..) => true,
+                    _ => false,
+                })
+                .count(),
         };
+        // the function keeps a scope of its own around the one it is created
+        // in, and each call gets a scope inside that for the parameters,
+        // `arguments` and, unless there are defaults, the body.
+        let mut call_scope = HashMap::new();
+        for param in &info.parameters {
+            let slot = call_scope.len() as u32;
+            call_scope.entry(param.clone()).or_insert(slot);
+        }
+        let simple = &info.parameters[..info.parameters.len() - info.rest as usize];
+        if kind & FunctionKind::Arrow != FunctionKind::Arrow
+            && !simple.iter().any(|p| p == "arguments")
+        {
+            let slot = call_scope.len() as u32;
+            call_scope.insert("arguments".to_string(), slot);
+        }
         let id = self.function_info.len();
         self.function_info.push(info);
         self.push_u32(id as u32); // 4
         self.jump(&mut end); // 5
 
+        // loops outside of the function can't be targeted from its body
+        let loops = std::mem::replace(&mut self.loops, Vec::new());
+        let finally = std::mem::replace(&mut self.finally, Vec::new());
+        let scope_depth = std::mem::replace(&mut self.scope_depth, 0);
+        let try_depth = std::mem::replace(&mut self.try_depth, 0);
+        let throw_label = self.throw_label.take();
+        let scopes = self.scopes.len();
+        self.scopes.push(HashMap::new());
+        self.scopes.push(call_scope);
+
         if let Node::Block(scope, stmts) = body {
+            // defaults are evaluated left to right on every call, in the
+            // scope holding the parameters. an argument which is missing is
+            // empty, so passing null explicitly doesn't trigger the default.
+            let mut defaults = false;
             for param in params {
                 if let Node::Initializer(name, init) = param {
+                    defaults = true;
                     if let Node::Identifier(name) = &**name {
                         let mut label = self.label();
                         self.visit_identifier(name);
@@ -765,12 +1167,21 @@ impl Assembler {
                     }
                 }
             }
+            // generators stop here when they are called, so their defaults
+            // are evaluated by the call and the body by the first `next()`.
+            if kind & FunctionKind::Generator == FunctionKind::Generator {
+                self.push_op(Op::Suspend);
+            }
+            // closures created by defaults can't see declarations in the body
+            if defaults {
+                self.enter_scope();
+            }
             for (name, mutable) in &scope.bindings {
                 self.lexical_declaration(name, *mutable);
             }
             let mut needs_return = true;
-            for stmt in stmts {
-                self.visit(stmt);
+            for (stmt, position) in stmts.iter().zip(statement_positions(scope, stmts)) {
+                self.visit_statement_at(stmt, position);
                 if let Node::ReturnStatement(..) = stmt {
                     needs_return = false;
                     break;
@@ -783,6 +1194,14 @@ impl Assembler {
             unreachable!();
         }
 
+        self.scopes.truncate(scopes);
+        self.loops = loops;
+        self.finally = finally;
+        self.scope_depth = scope_depth;
+        self.try_depth = try_depth;
+        self.throw_label = throw_label;
+
+        self.function_info[id].end = self.code.len();
         self.mark(&mut end);
     }
 
@@ -832,7 +1251,16 @@ impl Assembler {
                 unreachable!();
             }
         } else {
-            self.load_empty();
+            // a class without a constructor gets one which does nothing
+            let body = Node::Block(
+                Scope {
+                    kind: ScopeKind::Block,
+                    bindings: IndexMap::new(),
+                    positions: Vec::new(),
+                },
+                Vec::new(),
+            );
+            self.build_function(FunctionKind::Normal, Some(name.to_string()), &[], &body);
         }
         self.store_accumulator_in_register(&class);
 
@@ -865,15 +1293,39 @@ impl Assembler {
     }
 
     fn visit_lexical_initialization(&mut self, name: &str, init: &Node) {
-        self.visit(init);
+        self.visit_named(init, name);
         self.lexical_initialization(name);
     }
 
+    // anonymous functions take the name of the binding they are assigned to
+    fn visit_named(&mut self, node: &Node, name: &str) {
+        match node {
+            Node::FunctionExpression(kind, None, args, body)
+            | Node::ArrowFunctionExpression(kind, args, body) => {
+                self.build_function(*kind, Some(name.to_string()), args, body);
+            }
+            _ => self.visit(node),
+        }
+    }
+
     fn visit_return(&mut self, expr: &Option<Box<Node>>) {
-        if let Some(expr) = expr {
-            self.visit(expr);
-        } else {
-            self.load_null();
+        let cleanup = !self.finally.is_empty() || self.loops.iter().any(|l| l.iterator.is_some());
+        match expr.as_ref().map(|e| &**e) {
+            // a tail call would leave before the cleanup runs, or before a
+            // catch clause could see what it throws
+            Some(Node::TailCallExpression(callee, args)) if cleanup || self.try_depth > 0 => {
+                self.visit_call(callee, args, false)
+            }
+            Some(expr) => self.visit(expr),
+            None => self.load_null(),
+        }
+        if cleanup {
+            let rscope = RegisterScope::new(self);
+            let value = rscope.register();
+            self.store_accumulator_in_register(&value);
+            let (mut scope_depth, mut try_depth) = self.unwind(0, true);
+            self.run_finally(0, &mut scope_depth, &mut try_depth);
+            self.load_accumulator_with_register(&value);
         }
         self.push_op(Op::Return);
     }
@@ -881,7 +1333,10 @@ impl Assembler {
     fn visit_throw(&mut self, expr: &Node) {
         self.visit(expr);
         self.push_op(Op::SetException);
-        if let Some(throw_label) = self.throw_label {
+        if let Some((throw_label, scope_depth)) = self.throw_label {
+            for _ in scope_depth..self.scope_depth {
+                self.push_op(Op::ExitScope);
+            }
             self.push_op(Op::PopTry);
             unsafe {
                 self.jump(&mut *throw_label);
@@ -891,18 +1346,125 @@ impl Assembler {
         }
     }
 
-    fn visit_break(&mut self) {
+    fn push_loop(&mut self, label: Option<String>, end: &mut Label, head: &mut Label) {
+        self.loops.push(LoopTarget {
+            label,
+            break_label: end as *mut Label,
+            continue_label: head as *mut Label,
+            scope_depth: self.scope_depth,
+            try_depth: self.try_depth,
+            iterator: None,
+        });
+    }
+
+    fn visit_labeled(&mut self, label: &str, body: &Node) {
+        self.pending_label = Some(label.to_string());
+        self.visit(body);
+    }
+
+    // leave every scope and try block entered since the loop at `index`
+    // started, running the finally clauses and closing the iterators of the
+    // for loops being left. the iterator of the loop at `index` itself is
+    // only closed if `leave` is set. returns the depths left at.
+    fn unwind(&mut self, index: usize, leave: bool) -> (usize, usize) {
+        let mut scope_depth = self.scope_depth;
+        let mut try_depth = self.try_depth;
+        for i in (index..self.loops.len()).rev() {
+            self.run_finally(i + 1, &mut scope_depth, &mut try_depth);
+            for _ in self.loops[i].scope_depth..scope_depth {
+                self.push_op(Op::ExitScope);
+            }
+            for _ in self.loops[i].try_depth..try_depth {
+                self.push_op(Op::PopTry);
+            }
+            scope_depth = self.loops[i].scope_depth;
+            try_depth = self.loops[i].try_depth;
+            if let Some((iterator, r#async)) = self.loops[i].iterator {
+                if i > index || leave {
+                    self.close_iterator(iterator, r#async, false);
+                }
+            }
+        }
+        (scope_depth, try_depth)
+    }
+
+    // inline the finally clauses, innermost first, of the try statements
+    // directly inside the innermost `loops` loops.
+    fn run_finally(&mut self, loops: usize, scope_depth: &mut usize, try_depth: &mut usize) {
+        for index in (0..self.finally.len()).rev() {
+            if self.finally[index].loops != loops {
+                continue;
+            }
+            let target = &self.finally[index];
+            let (clause, target_scope_depth, target_try_depth) =
+                (target.clause, target.scope_depth, target.try_depth);
+            for _ in target_scope_depth..*scope_depth {
+                self.push_op(Op::ExitScope);
+            }
+            for _ in target_try_depth..*try_depth {
+                self.push_op(Op::PopTry);
+            }
+            *scope_depth = target_scope_depth;
+            *try_depth = target_try_depth;
+
+            // the clause is assembled as if it were where its try statement
+            // is, so it can't see the loops or finally clauses being left.
+            let finally = self.finally.split_off(index);
+            let inner = self.loops.split_off(loops);
+            let left = self.scopes.len() - (self.scope_depth - *scope_depth);
+            let scopes = self.scopes.split_off(left);
+            let saved_scope_depth = std::mem::replace(&mut self.scope_depth, *scope_depth);
+            let saved_try_depth = std::mem::replace(&mut self.try_depth, *try_depth);
+            let throw_label = self.throw_label.take();
+            self.visit(unsafe { &*clause });
+            self.finally.extend(finally);
+            self.loops.extend(inner);
+            self.scopes.extend(scopes);
+            self.scope_depth = saved_scope_depth;
+            self.try_depth = saved_try_depth;
+            self.throw_label = throw_label;
+        }
+    }
+
+    fn unwind_to(&mut self, label: &Option<String>, leave: bool) -> &LoopTarget {
+        let index = match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|l| l.label.as_ref() == Some(label))
+                .unwrap(),
+            None => self.loops.len() - 1,
+        };
+        self.unwind(index, leave);
+        &self.loops[index]
+    }
+
+    fn visit_break(&mut self, label: &Option<String>) {
+        let target = self.unwind_to(label, true).break_label;
         unsafe {
-            self.jump(&mut *self.break_label.unwrap());
+            self.jump(&mut *target);
         }
     }
 
-    fn visit_continue(&mut self) {
+    fn visit_continue(&mut self, label: &Option<String>) {
+        let target = self.unwind_to(label, false).continue_label;
         unsafe {
-            self.jump(&mut *self.continue_label.unwrap());
+            self.jump(&mut *target);
         }
     }
 
+    fn enter_scope(&mut self) {
+        self.push_op(Op::EnterScope);
+        self.scope_depth += 1;
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.push_op(Op::ExitScope);
+        self.scope_depth -= 1;
+        self.scopes.pop();
+    }
+
     fn visit_try(
         &mut self,
         tryc: &Node,
@@ -910,24 +1472,70 @@ impl Assembler {
         catchc: &Option<Box<Node>>,
         finallyc: &Option<Box<Node>>,
     ) {
+        /*
+        try { TRY } catch BINDING { CATCH } finally { FINALLY }
+
+        @=>
+
+        PushFinally finally
+        PushTry catch
+        TRY
+        PopTry
+        jump after_catch
+        catch:
+        BINDING = GetException()
+        CATCH
+        after_catch:
+        PopTry
+        FINALLY
+        jump end
+        finally:
+        SaveCompletion
+        FINALLY
+        RestoreCompletion
+        ThrowDynamic
+        end:
+        */
+
         let mut catch = self.label();
+        let mut after_catch = self.label();
         let mut finally = self.label();
-
-        self.push_op(Op::PushTry);
-        self.jmp(&mut catch); // interpreter eats this for the try_stack
+        let mut end = self.label();
 
         let ptl = self.throw_label;
-        self.throw_label = Some(&mut catch as *mut Label);
-        self.visit(tryc);
-        self.throw_label = ptl;
 
-        self.push_op(Op::PopTry);
+        if let Some(finallyc) = finallyc {
+            self.finally.push(FinallyTarget {
+                clause: &**finallyc as *const Node,
+                loops: self.loops.len(),
+                scope_depth: self.scope_depth,
+                try_depth: self.try_depth,
+            });
+            self.push_op(Op::PushFinally);
+            self.jmp(&mut finally);
+            self.try_depth += 1;
+            // a throw has to run the finally clause on its way out
+            self.throw_label = None;
+        }
 
-        self.mark(&mut catch);
         if let Some(catchc) = catchc {
+            self.push_op(Op::PushTry);
+            self.jmp(&mut catch); // interpreter eats this for the try_stack
+
+            let catch_ptl = self.throw_label;
+            self.throw_label = Some((&mut catch as *mut Label, self.scope_depth));
+            self.try_depth += 1;
+            self.visit(tryc);
+            self.try_depth -= 1;
+            self.throw_label = catch_ptl;
+
+            self.push_op(Op::PopTry);
+            self.jump(&mut after_catch);
+
+            self.mark(&mut catch);
             let mut exit = false;
             if let Some(binding) = binding {
-                self.push_op(Op::EnterScope);
+                self.enter_scope();
                 exit = true;
                 self.lexical_declaration(binding, false);
                 self.push_op(Op::GetException);
@@ -937,16 +1545,42 @@ impl Assembler {
             }
             self.visit(catchc);
             if exit {
-                self.push_op(Op::ExitScope);
+                self.exit_scope();
             }
+
+            self.mark(&mut after_catch);
+        } else {
+            self.visit(tryc);
         }
 
-        self.mark(&mut finally);
+        self.throw_label = ptl;
+
         if let Some(finallyc) = finallyc {
+            self.finally.pop();
+            self.try_depth -= 1;
+            self.push_op(Op::PopTry);
             self.visit(finallyc);
+            self.jump(&mut end);
+
+            self.mark(&mut finally);
+            let rscope = RegisterScope::new(self);
+            let value = rscope.register();
+            let returning = rscope.register();
+            self.push_op(Op::SaveCompletion);
+            self.push_u32(value.id);
+            self.push_u32(returning.id);
+            self.visit(finallyc);
+            self.push_op(Op::RestoreCompletion);
+            self.push_u32(value.id);
+            self.push_u32(returning.id);
+            self.push_op(Op::ThrowDynamic);
+
+            self.mark(&mut end);
         }
     }
 
+    // arms are tried in order, each testing the structure of the value and
+    // then binding, in a scope of its own, the names its pattern introduces.
     fn visit_match(&mut self, expr: &Node, arms: &[Node]) {
         let mut end = self.label();
 
@@ -957,88 +1591,120 @@ impl Assembler {
         self.store_accumulator_in_register(&value);
 
         for arm in arms {
-            if let Node::MatchArm(test, consequent) = arm {
-                let mut next = self.label();
-                match &**test {
-                    Node::Identifier(binding) => {
-                        self.push_op(Op::EnterScope);
+            if let Node::MatchArm(pattern, consequent) = arm {
+                let mut bindings = Vec::new();
+                pattern_bindings(pattern, &mut bindings);
+                let mut fail = self.label();
+                if !bindings.is_empty() {
+                    self.enter_scope();
+                    for binding in &bindings {
                         self.lexical_declaration(binding, false);
-                        self.load_accumulator_with_register(&value);
-                        self.lexical_initialization(binding);
-                        self.visit(consequent);
-                        self.push_op(Op::ExitScope);
-                    }
-                    Node::StringLiteral(..) | Node::NumberLiteral(..) => {
-                        self.visit(test);
-                        self.push_op(Op::Eq);
-                        self.push_u32(value.id);
-                        self.jump_if_false(&mut next);
-                        self.visit(consequent);
-                    }
-                    Node::ObjectPattern(patterns, wildcard) => {
-                        self.push_op(Op::EnterScope);
-                        if *wildcard {
-                            for (binding, _pattern) in patterns {
-                                self.load_string(binding);
-                                self.push_op(Op::HasProperty); // TODO: replace with equality for pattern
-                                self.push_u32(value.id);
-                                self.jump_if_false(&mut next);
-                                self.lexical_declaration(binding, false);
-                                self.load_accumulator_with_register(&value);
-                                self.load_named_property(binding);
-                                self.lexical_initialization(binding);
-                            }
-                        } else {
-                            let mut head = self.label();
-
-                            self.load_accumulator_with_register(&value);
-                            self.call_runtime(RuntimeFunction::ObjectKeys);
-                            let keys = rscope.register();
-                            self.store_accumulator_in_register(&keys);
-
-                            let keys_length = rscope.register();
-                            self.call_runtime(RuntimeFunction::ListLength);
-                            self.store_accumulator_in_register(&keys_length);
-
-                            self.load_f64(patterns.len() as f64);
-                            self.push_op(Op::Eq);
-                            self.push_u32(keys_length.id);
-                            self.jump_if_false(&mut next);
-
-                            self.mark(&mut head);
-
-                            self.load_accumulator_with_register(&keys);
-                            self.call_runtime(RuntimeFunction::ListPopFront);
-                            self.jump_if_empty(&mut next);
-
-                            self.push_op(Op::HasProperty); // TODO: replace with equality for pattern
-                            self.push_u32(value.id);
-                            self.jump_if_false(&mut next);
-                            /*
-                            self.lexical_declaration(binding, false);
-                            self.load_accumulator_with_register(&value);
-                            self.load_named_property(binding);
-                            self.lexical_initialization(binding);
-                            */
-                            self.jump(&mut head);
-                        }
-                        self.visit(consequent);
-                        self.push_op(Op::ExitScope);
                     }
-                    Node::ArrayPattern(_patterns, _wildcard) => unreachable!(),
-                    _ => unreachable!(),
+                }
+                self.match_pattern(pattern, &value, &mut fail);
+                self.visit(consequent);
+                if !bindings.is_empty() {
+                    self.exit_scope();
                 }
                 self.jump(&mut end);
-                self.mark(&mut next);
+                self.mark(&mut fail);
+                if !bindings.is_empty() {
+                    self.push_op(Op::ExitScope);
+                }
             } else {
                 unreachable!();
             }
         }
 
-        self.load_null();
+        self.load_accumulator_with_register(&value);
+        self.call_runtime(RuntimeFunction::ThrowNoMatch);
         self.mark(&mut end);
     }
 
+    // jump to `fail` unless the value in `value` matches `pattern`,
+    // initializing the bindings of the pattern as it goes.
+    fn match_pattern(&mut self, pattern: &Node, value: &Register, fail: &mut Label) {
+        match pattern {
+            Node::Identifier(binding) => {
+                if binding != "_" {
+                    self.load_accumulator_with_register(value);
+                    self.lexical_initialization(binding);
+                }
+            }
+            Node::TuplePattern(patterns) => {
+                self.load_accumulator_with_register(value);
+                self.call_runtime(RuntimeFunction::TupleLength);
+                self.match_length(patterns.len(), false, fail);
+                self.match_elements(patterns, value, fail);
+            }
+            Node::ArrayPattern(patterns, wildcard) => {
+                self.load_accumulator_with_register(value);
+                self.call_runtime(RuntimeFunction::ArrayLength);
+                self.match_length(patterns.len(), *wildcard, fail);
+                self.match_elements(patterns, value, fail);
+            }
+            Node::ObjectPattern(patterns, wildcard) => {
+                self.load_accumulator_with_register(value);
+                self.call_runtime(RuntimeFunction::IsObject);
+                self.jump_if_false(fail);
+                if !*wildcard {
+                    // without `...` the pattern has to name every property
+                    self.load_accumulator_with_register(value);
+                    self.call_runtime(RuntimeFunction::ObjectKeys);
+                    self.call_runtime(RuntimeFunction::ListLength);
+                    self.match_length(patterns.len(), false, fail);
+                }
+                let rscope = RegisterScope::new(self);
+                let property = rscope.register();
+                for (key, pattern) in patterns {
+                    self.load_string(key);
+                    self.push_op(Op::HasProperty);
+                    self.push_u32(value.id);
+                    self.jump_if_false(fail);
+                    self.load_accumulator_with_register(value);
+                    self.load_named_property(key);
+                    self.store_accumulator_in_register(&property);
+                    self.match_pattern(pattern, &property, fail);
+                }
+            }
+            // literals
+            _ => {
+                self.visit(pattern);
+                self.push_op(Op::Eq);
+                self.push_u32(value.id);
+                self.jump_if_false(fail);
+            }
+        }
+    }
+
+    // jump to `fail` unless the length in the accumulator is `count`, or at
+    // least that with a wildcard. a length of -1 is
+    // never matched, it means the value wasn't the right kind.
+    fn match_length(&mut self, count: usize, wildcard: bool, fail: &mut Label) {
+        let rscope = RegisterScope::new(self);
+        let length = rscope.register();
+        self.store_accumulator_in_register(&length);
+        self.load_f64(count as f64);
+        self.push_op(if wildcard {
+            Op::GreaterThanOrEqual
+        } else {
+            Op::Eq
+        });
+        self.push_u32(length.id);
+        self.jump_if_false(fail);
+    }
+
+    fn match_elements(&mut self, patterns: &[Node], value: &Register, fail: &mut Label) {
+        let rscope = RegisterScope::new(self);
+        let element = rscope.register();
+        for (i, pattern) in patterns.iter().enumerate() {
+            self.load_f64(i as f64);
+            self.load_computed_property(value);
+            self.store_accumulator_in_register(&element);
+            self.match_pattern(pattern, &element, fail);
+        }
+    }
+
     fn visit_export(&mut self, decl: &Node) {
         self.visit(decl);
     }
@@ -1098,11 +1764,6 @@ impl Assembler {
         self.jmp(label);
     }
 
-    fn jump_if_empty(&mut self, label: &mut Label) {
-        self.push_op(Op::JumpIfEmpty);
-        self.jmp(label);
-    }
-
     fn jump_if_not_empty(&mut self, label: &mut Label) {
         self.push_op(Op::JumpIfNotEmpty);
         self.jmp(label);
@@ -1188,6 +1849,10 @@ impl Assembler {
     }
 
     fn lexical_declaration(&mut self, name: &str, mutable: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let slot = scope.len() as u32;
+            scope.entry(name.to_string()).or_insert(slot);
+        }
         self.push_op(Op::LexicalDeclaration);
         let id = self.string_id(name);
         self.push_u32(id);
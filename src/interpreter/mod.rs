@@ -1,13 +1,26 @@
+//! Scripts are compiled to bytecode once, when they are loaded. `Assembler`
+//! walks the AST and appends the ops defined by `Op` below to one code buffer
+//! shared by every script and module in the agent, and a function value only
+//! records where its body starts, so calling a function again never walks or
+//! compiles anything. `Interpreter` runs the code as an accumulator machine
+//! with `REGISTER_COUNT` registers per frame, and `disassemble` prints it.
+
+use crate::intrinsics::bigint;
+use crate::intrinsics::promise::promise_resolve_i;
+use crate::intrinsics::range::create_range;
 use crate::module::Module;
-use crate::num_util::{f64_band, f64_bnot, f64_bor, f64_bxor, f64_shl, f64_shr};
+use crate::num_util::{f64_band, f64_bnot, f64_bor, f64_bxor, f64_shl, f64_shr, f64_ushr};
 use crate::parser::FunctionKind;
 use crate::runtime::RuntimeFunction;
-use crate::value::{ObjectKey, ObjectKind};
+use crate::value::{check_arity, declare_arguments, ObjectKey, ObjectKind, ValueKind};
 use crate::{Agent, Value};
 use byteorder::{LittleEndian, ReadBytesExt};
 use gc::{Gc, GcCell};
 use indexmap::IndexMap;
+use num::BigInt;
 use std::ops::{Div, Mul, Rem, Sub};
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
 
 #[allow(dead_code)]
 pub enum AccumulatorUse {
@@ -40,14 +53,18 @@ macro_rules! OPS {
             (LoadF64, AccumulatorUse::Write, OpArg::F64),
             (LoadString, AccumulatorUse::Write, OpArg::String),
             (LoadSymbol, AccumulatorUse::Write, OpArg::String),
+            (LoadBigInt, AccumulatorUse::Write, OpArg::String),
 
             (BuildRegex, AccumulatorUse::Write, OpArg::String),
             (CreateEmptyArray, AccumulatorUse::Write),
             (StoreInArrayLiteral, AccumulatorUse::Read, OpArg::Register, OpArg::U32),
+            (AppendToArray, AccumulatorUse::Read, OpArg::Register),
+            (SpreadIntoArray, AccumulatorUse::Read, OpArg::Register),
             (CreateEmptyTuple, AccumulatorUse::Write),
             (StoreInTuple, AccumulatorUse::Read, OpArg::Register),
             (CreateEmptyObject, AccumulatorUse::Write),
             (StoreInObjectLiteral, AccumulatorUse::Read, OpArg::Register, OpArg::Register),
+            (SpreadIntoObject, AccumulatorUse::Read, OpArg::Register),
             (NewFunction, AccumulatorUse::ReadWrite, OpArg::FunctionInfo),
             (FinishClass, AccumulatorUse::ReadWrite, OpArg::Register, OpArg::Register, OpArg::String),
 
@@ -60,16 +77,21 @@ macro_rules! OPS {
             (LexicalInitialization, AccumulatorUse::ReadWrite, OpArg::String),
             (OverwriteBinding, AccumulatorUse::Read, OpArg::String),
             (ResolveIdentifier, AccumulatorUse::Write, OpArg::String),
+            (ResolveIdentifierForTypeof, AccumulatorUse::Write, OpArg::String),
             (AssignIdentifier, AccumulatorUse::Read, OpArg::String),
+            (ResolveSlot, AccumulatorUse::Write, OpArg::U32, OpArg::U32),
+            (AssignSlot, AccumulatorUse::Read, OpArg::U32, OpArg::U32),
 
             (GetThis, AccumulatorUse::Write),
 
             (Call, AccumulatorUse::ReadWrite, OpArg::Register, OpArg::Register, OpArg::Register, OpArg::U8),
             (TailCall, AccumulatorUse::ReadWrite, OpArg::Register, OpArg::Register, OpArg::Register, OpArg::U8),
+            (CallWithArray, AccumulatorUse::ReadWrite, OpArg::Register, OpArg::Register, OpArg::Register),
             (CallRuntime, AccumulatorUse::ReadWrite, OpArg::RuntimeFunction),
 
             (Construct, AccumulatorUse::ReadWrite),
             (ConstructWithArgs, AccumulatorUse::ReadWRite, OpArg::Register, OpArg::Register, OpArg::U8),
+            (ConstructWithArray, AccumulatorUse::ReadWrite, OpArg::Register, OpArg::Register),
 
             (EnterScope, AccumulatorUse::None),
             (ExitScope, AccumulatorUse::None),
@@ -77,21 +99,25 @@ macro_rules! OPS {
             (Jump, AccumulatorUse::None, OpArg::Position),
             (JumpIfTrue, AccumulatorUse::Read, OpArg::Position),
             (JumpIfFalse, AccumulatorUse::Read, OpArg::Position),
-            (JumpIfEmpty, AccumulatorUse::Read, OpArg::Position),
             (JumpIfNotEmpty, AccumulatorUse::Read, OpArg::Position),
 
             (PushTry, AccumulatorUse::None,OpArg::Position),
+            (PushFinally, AccumulatorUse::None, OpArg::Position),
             (PopTry, AccumulatorUse::None),
             (ThrowDynamic, AccumulatorUse::None),
             (SetException, AccumulatorUse::Read),
             (GetException, AccumulatorUse::Write),
             (ClearException, AccumulatorUse::None),
+            (SaveCompletion, AccumulatorUse::None, OpArg::Register, OpArg::Register),
+            (RestoreCompletion, AccumulatorUse::None, OpArg::Register, OpArg::Register),
 
             (Suspend, AccumulatorUse::Write),
             (Return, AccumulatorUse::Write),
 
             (IteratorNext, AccumulatorUse::ReadWrite, OpArg::Register),
             (AsyncIteratorNext, AccumulatorUse::ReadWrite, OpArg::Register),
+            (IteratorClose, AccumulatorUse::None, OpArg::Register, OpArg::Boolean),
+            (AsyncIteratorClose, AccumulatorUse::Write, OpArg::Register, OpArg::Boolean),
 
             (LoadAccumulatorFromRegister, AccumulatorUse::Write, OpArg::Register),
             (StoreAccumulatorInRegister, AccumulatorUse::Read, OpArg::Register),
@@ -107,11 +133,15 @@ macro_rules! OPS {
             (BitAND, AccumulatorUse::ReadWrite, OpArg::Register),
             (ShiftLeft, AccumulatorUse::ReadWrite, OpArg::Register),
             (ShiftRight, AccumulatorUse::ReadWrite, OpArg::Register),
+            (ShiftRightUnsigned, AccumulatorUse::ReadWrite, OpArg::Register),
             (GreaterThan, AccumulatorUse::ReadWrite, OpArg::Register),
             (LessThan, AccumulatorUse::ReadWrite, OpArg::Register),
             (GreaterThanOrEqual, AccumulatorUse::ReadWrite, OpArg::Register),
             (LessThanOrEqual, AccumulatorUse::ReadWrite, OpArg::Register),
             (HasProperty, AccumulatorUse::ReadWrite, OpArg::Register),
+            (CreateRange, AccumulatorUse::ReadWrite, OpArg::Register),
+            (CreateInclusiveRange, AccumulatorUse::ReadWrite, OpArg::Register),
+            (DeleteProperty, AccumulatorUse::ReadWrite, OpArg::Register),
             (Eq, AccumulatorUse::ReadWrite, OpArg::Register),
             (Neq, AccumulatorUse::ReadWrite, OpArg::Register),
             (LNOT, AccumulatorUse::ReadWrite),
@@ -160,10 +190,43 @@ struct Binding {
     module: Option<Gc<GcCell<Module>>>,
 }
 
+impl Binding {
+    fn value(&self, agent: &Agent, name: &str) -> Result<Value, Value> {
+        match self {
+            Binding { value: Some(v), .. } => Ok(v.clone()),
+            Binding {
+                module: Some(m), ..
+            } => m.borrow().context.borrow().scope.borrow().get(agent, name),
+            _ => Err(uninitialized(agent, name)),
+        }
+    }
+
+    fn assign(&mut self, agent: &Agent, name: &str, value: Value) -> Result<(), Value> {
+        // imports are constant, even though the value lives in the module
+        if self.module.is_some() || (!self.mutable && self.value.is_some()) {
+            Err(Value::new_error(
+                agent,
+                &format!("cannot reassign constant binding `{}`", name),
+            ))
+        } else if self.value.is_none() {
+            Err(uninitialized(agent, name))
+        } else {
+            self.value = Some(value);
+            Ok(())
+        }
+    }
+}
+
+/// Bindings are kept in the order they are created, so code which knows the
+/// layout of the scopes it runs in can find a binding by how many scopes out
+/// it is and its index there (see `ResolveSlot`) rather than by its name.
 #[derive(Trace, Finalize, Debug)]
 pub struct Scope {
     parent: Option<Gc<GcCell<Scope>>>,
     bindings: IndexMap<String, Binding>,
+    // the global object, for the root scope, whose own properties are the
+    // global bindings
+    object: Option<Value>,
     pub this: Option<Value>,
 }
 
@@ -172,6 +235,35 @@ impl Scope {
         Gc::new(GcCell::new(Scope {
             parent,
             bindings: IndexMap::new(),
+            object: None,
+            this: None,
+        }))
+    }
+
+    /// A root scope which keeps its bindings as the properties of `global`,
+    /// so that `globalThis.x` and `x` are always the same value. `undefined`,
+    /// `NaN` and `Infinity` are constant bindings of the scope instead, so
+    /// assigning to them throws.
+    pub fn new_global(global: Value) -> Gc<GcCell<Scope>> {
+        let mut bindings = IndexMap::new();
+        for (name, value) in [
+            ("undefined", Value::Null),
+            ("NaN", Value::from(std::f64::NAN)),
+            ("Infinity", Value::from(std::f64::INFINITY)),
+        ] {
+            bindings.insert(
+                name.to_string(),
+                Binding {
+                    mutable: false,
+                    value: Some(value),
+                    module: None,
+                },
+            );
+        }
+        Gc::new(GcCell::new(Scope {
+            parent: None,
+            bindings,
+            object: Some(global),
             this: None,
         }))
     }
@@ -224,39 +316,81 @@ impl Scope {
         self.bindings.get_mut(name).unwrap().value = Some(value);
     }
 
+    pub fn get_own(&self, name: &str) -> Option<Value> {
+        self.bindings.get(name).and_then(|b| b.value.clone())
+    }
+
     fn get(&self, agent: &Agent, name: &str) -> Result<Value, Value> {
         match self.bindings.get(name) {
-            Some(Binding { value: Some(v), .. }) => Ok(v.clone()),
-            Some(Binding {
-                module: Some(m), ..
-            }) => m.borrow().context.borrow().scope.borrow().get(agent, name),
-            Some(..) => Err(Value::new_error(agent, "Reference error")),
-            None => match self.parent {
-                Some(ref parent) => parent.borrow().get(agent, name),
-                None => Err(Value::new_error(agent, "Reference error")),
+            Some(b) => b.value(agent, name),
+            None => match (&self.parent, &self.object) {
+                (Some(parent), _) => parent.borrow().get(agent, name),
+                (None, Some(global))
+                    if global.has_own_property(agent, ObjectKey::new(agent, name))? =>
+                {
+                    global.get(agent, ObjectKey::new(agent, name))
+                }
+                (None, _) => Err(undefined(agent, name)),
             },
         }
     }
 
+    fn has_binding(&self, agent: &Agent, name: &str) -> Result<bool, Value> {
+        if self.bindings.contains_key(name) {
+            Ok(true)
+        } else {
+            match (&self.parent, &self.object) {
+                (Some(parent), _) => parent.borrow().has_binding(agent, name),
+                (None, Some(global)) => global.has_own_property(agent, ObjectKey::new(agent, name)),
+                (None, None) => Ok(false),
+            }
+        }
+    }
+
     fn set(&mut self, agent: &Agent, name: &str, value: Value) -> Result<(), Value> {
         match self.bindings.get_mut(name) {
-            Some(b) => {
-                if b.value.is_none() {
-                    Err(Value::new_error(agent, "Reference error"))
-                } else if !b.mutable {
-                    Err(Value::new_error(agent, "cannot reassign constant binding"))
-                } else {
-                    b.value = Some(value);
+            Some(b) => b.assign(agent, name, value),
+            None => match (&self.parent, &self.object) {
+                (Some(parent), _) => parent.borrow_mut().set(agent, name, value),
+                (None, Some(global))
+                    if global.has_own_property(agent, ObjectKey::new(agent, name))? =>
+                {
+                    global.set(agent, ObjectKey::new(agent, name), value)?;
                     Ok(())
                 }
-            }
-            None => match self.parent {
-                Some(ref parent) => parent.borrow_mut().set(agent, name, value),
-                None => Err(Value::new_error(agent, "Reference error")),
+                (None, _) => Err(undefined(agent, name)),
             },
         }
     }
 
+    fn get_slot(&self, agent: &Agent, depth: u32, slot: u32) -> Result<Value, Value> {
+        match &self.parent {
+            Some(parent) if depth > 0 => parent.borrow().get_slot(agent, depth - 1, slot),
+            _ => {
+                let (name, binding) = self.bindings.get_index(slot as usize).unwrap();
+                binding.value(agent, name)
+            }
+        }
+    }
+
+    fn set_slot(
+        &mut self,
+        agent: &Agent,
+        depth: u32,
+        slot: u32,
+        value: Value,
+    ) -> Result<(), Value> {
+        match &self.parent {
+            Some(parent) if depth > 0 => {
+                parent.borrow_mut().set_slot(agent, depth - 1, slot, value)
+            }
+            _ => {
+                let (name, binding) = self.bindings.get_index_mut(slot as usize).unwrap();
+                binding.assign(agent, name, value)
+            }
+        }
+    }
+
     pub fn get_this(&self, agent: &Agent) -> Result<Value, Value> {
         match self.this {
             Some(ref t) => Ok(t.clone()),
@@ -268,12 +402,78 @@ impl Scope {
     }
 }
 
+// bindings are created when their scope is entered but can't be used until
+// their declaration has run.
+fn uninitialized(agent: &Agent, name: &str) -> Value {
+    Value::new_error(
+        agent,
+        &format!("cannot access `{}` before initialization", name),
+    )
+}
+
+fn undefined(agent: &Agent, name: &str) -> Value {
+    Value::new_error(agent, &format!("`{}` is not defined", name))
+}
+
+// an error is given the file, line and column of the statement running when
+// it is first thrown. errors from builtins are thrown by the call to them,
+// and rethrowing an error leaves it where it was first thrown.
+fn locate_error(agent: &Agent, pc: usize, error: &Value) {
+    match error.kind() {
+        ValueKind::Object(o) => match o.kind {
+            ObjectKind::Ordinary => {}
+            _ => return,
+        },
+        _ => return,
+    }
+    let mut prototype = error.get_prototype_of(agent).unwrap();
+    loop {
+        match prototype.kind() {
+            ValueKind::Object(..) if prototype == agent.intrinsics.error_prototype => break,
+            ValueKind::Object(..) => prototype = prototype.get_prototype_of(agent).unwrap(),
+            _ => return,
+        }
+    }
+    if let Ok(true) = error.has_own_property(agent, ObjectKey::new(agent, "fileName")) {
+        return;
+    }
+    if let Some((filename, position)) = agent.assembler.position_at(pc) {
+        let _ = error.set(
+            agent,
+            ObjectKey::new(agent, "fileName"),
+            Value::from(filename),
+        );
+        let _ = error.set(
+            agent,
+            ObjectKey::new(agent, "line"),
+            Value::from(position.line as f64),
+        );
+        let _ = error.set(
+            agent,
+            ObjectKey::new(agent, "column"),
+            Value::from(position.column as f64),
+        );
+    }
+}
+
+#[derive(Trace, Finalize, Debug)]
+struct TryHandler {
+    pc: usize,
+    // finally handlers also run when a generator is returned from
+    finally: bool,
+    // the scope the try statement is in, which the handler runs in however
+    // many scopes inside it the completion came from
+    scope: Gc<GcCell<Scope>>,
+}
+
 #[derive(Trace, Finalize, Debug)]
 pub struct Context {
     pub scope: Gc<GcCell<Scope>>,
     pub interpreter: Option<Interpreter>,
     pub function: Option<Value>,
-    try_stack: Vec<usize>,
+    /// The constructor `new` was applied to, if this call came from `new`.
+    pub new_target: Option<Value>,
+    try_stack: Vec<TryHandler>,
 }
 
 impl Context {
@@ -282,9 +482,38 @@ impl Context {
             scope,
             interpreter: None,
             function: None,
+            new_target: None,
             try_stack: Vec::new(),
         }))
     }
+
+    /// The receiver of the running function.
+    pub fn this(&self, agent: &Agent) -> Result<Value, Value> {
+        self.scope.borrow().get_this(agent)
+    }
+
+    /// The running function, which for builtins holds their slots.
+    pub fn callee(&self) -> Value {
+        self.function.clone().unwrap_or(Value::Null)
+    }
+
+    /// The constructor `new` was applied to, or null for a plain call.
+    pub fn new_target(&self) -> Value {
+        self.new_target.clone().unwrap_or(Value::Null)
+    }
+
+    // pops try handlers until one which applies to the completion being
+    // unwound is found, a generator return only stops at finally handlers.
+    // the scopes entered since its try statement are left.
+    fn pop_handler(&mut self, returning: bool) -> Option<usize> {
+        while let Some(handler) = self.try_stack.pop() {
+            if handler.finally || !returning {
+                self.scope = handler.scope.clone();
+                return Some(handler.pc);
+            }
+        }
+        None
+    }
 }
 
 #[derive(Debug, Trace, Finalize)]
@@ -331,6 +560,15 @@ impl std::ops::Index<usize> for Registers {
     }
 }
 
+impl std::ops::Index<std::ops::Range<usize>> for Registers {
+    type Output = [Value];
+
+    #[inline]
+    fn index(&self, index: std::ops::Range<usize>) -> &[Value] {
+        &self.registers[index]
+    }
+}
+
 impl std::ops::IndexMut<usize> for Registers {
     #[inline]
     fn index_mut(&mut self, index: usize) -> &mut Value {
@@ -342,6 +580,9 @@ impl std::ops::IndexMut<usize> for Registers {
 pub struct Interpreter {
     pub accumulator: Value,
     pub exception: Option<Value>,
+    /// Set when `exception` holds the value a generator is returning with
+    /// rather than an error, see `Generator.prototype.return`.
+    pub returning: bool,
     pc: usize,
     context: Vec<Gc<GcCell<Context>>>,
     positions: Vec<usize>,
@@ -353,6 +594,7 @@ impl Interpreter {
         Interpreter {
             accumulator: Value::Empty,
             exception: None,
+            returning: false,
             pc,
             context: vec![ctx],
             positions: Vec::new(),
@@ -377,6 +619,9 @@ impl Interpreter {
             () => {
                 self.context.pop().unwrap();
                 self.registers = *self.registers.last.take().unwrap();
+                if agent.tracing() {
+                    agent.trace_exit();
+                }
             };
         }
 
@@ -408,49 +653,78 @@ impl Interpreter {
             }};
         }
 
+        // bigints only combine with bigints, and only for operators given a
+        // function for them.
         macro_rules! num_binop_num {
-            ($fn:expr) => {{
+            ($fn:expr) => {
+                num_binop_num!($fn, bigint::unsupported)
+            };
+            ($fn:expr, $bigfn:expr) => {{
                 let lhsid = read_u32!() as usize;
-                match self.registers[lhsid] {
-                    Value::Number(ln) => match self.accumulator {
-                        Value::Number(rn) => {
-                            self.accumulator = Value::from($fn(ln, rn));
-                        }
-                        _ => handle!(Err(Value::new_error(agent, "rhs must be a number"))),
-                    },
-                    _ => handle!(Err(Value::new_error(agent, "lhs must be a number"))),
-                }
+                let result = match (self.registers[lhsid].kind(), self.accumulator.kind()) {
+                    (ValueKind::Number(ln), ValueKind::Number(rn)) => Ok(Value::from($fn(ln, rn))),
+                    (ValueKind::BigInt(ln), ValueKind::BigInt(rn)) => $bigfn(agent, &**ln, &**rn),
+                    (ValueKind::Number(..), ValueKind::BigInt(..))
+                    | (ValueKind::BigInt(..), ValueKind::Number(..)) => {
+                        Err(bigint::mixed_error(agent))
+                    }
+                    (ValueKind::Number(..), _) | (ValueKind::BigInt(..), _) => {
+                        Err(Value::new_error(agent, "rhs must be a number"))
+                    }
+                    _ => Err(Value::new_error(agent, "lhs must be a number")),
+                };
+                self.accumulator = handle!(result);
             }};
         }
 
         macro_rules! num_binop_bool {
             ($fn:expr) => {{
                 let lhsid = read_u32!() as usize;
-                match self.registers[lhsid] {
-                    Value::Number(ln) => match self.accumulator {
-                        Value::Number(rn) => {
-                            self.accumulator = Value::from($fn(&ln, &rn));
-                        }
-                        _ => handle!(Err(Value::new_error(agent, "rhs must be a number"))),
-                    },
-                    _ => handle!(Err(Value::new_error(agent, "lhs must be a number"))),
-                }
+                let result = match (self.registers[lhsid].kind(), self.accumulator.kind()) {
+                    (ValueKind::Number(ln), ValueKind::Number(rn)) => {
+                        Ok(Value::from($fn(&ln, &rn)))
+                    }
+                    (ValueKind::BigInt(ln), ValueKind::BigInt(rn)) => Ok(Value::from($fn(ln, rn))),
+                    (ValueKind::Number(..), ValueKind::BigInt(..))
+                    | (ValueKind::BigInt(..), ValueKind::Number(..)) => {
+                        Err(bigint::mixed_error(agent))
+                    }
+                    (ValueKind::Number(..), _) | (ValueKind::BigInt(..), _) => {
+                        Err(Value::new_error(agent, "rhs must be a number"))
+                    }
+                    _ => Err(Value::new_error(agent, "lhs must be a number")),
+                };
+                self.accumulator = handle!(result);
             }};
         }
 
+        // checked on jumps and calls, so that any loop or recursion will see it.
+        macro_rules! check_interrupt {
+            () => {
+                if agent.interrupt.load(Ordering::Relaxed) {
+                    return Ok(Err(Value::new_error(agent, "agent was interrupted")));
+                }
+                if let Err(e) = agent.count_instruction() {
+                    return Ok(Err(e));
+                }
+            };
+        }
+
         if self.exception.is_some() {
             loop {
                 match self.context.last() {
-                    None => return Ok(Err(self.exception.take().unwrap())),
+                    None => return Ok(self.completion()),
                     Some(context) => {
-                        if let Some(pc) = context.borrow_mut().try_stack.pop() {
+                        if let Some(pc) = context.borrow_mut().pop_handler(self.returning) {
                             self.pc = pc;
+                            break;
                         }
                     }
                 }
                 if self.context.len() == 1 {
-                    return Ok(Err(self.exception.take().unwrap()));
+                    return Ok(self.completion());
                 } else {
+                    self.positions.pop();
                     pop_context!();
                 }
             }
@@ -462,14 +736,20 @@ impl Interpreter {
                     match $ex {
                         Ok(v) => v,
                         Err(e) => {
+                            // the pc is past the op which threw, but not past
+                            // its statement
+                            locate_error(agent, self.pc - 1, &e);
                             self.exception = Some(e);
+                            self.returning = false;
                             loop {
                                 match self.context.last() {
                                     None => {
                                         break 'main;
                                     }
                                     Some(context) => {
-                                        if let Some(pc) = context.borrow_mut().try_stack.pop() {
+                                        if let Some(pc) =
+                                            context.borrow_mut().pop_handler(self.returning)
+                                        {
                                             self.pc = pc;
                                             continue 'main;
                                         }
@@ -478,6 +758,7 @@ impl Interpreter {
                                 if self.context.len() == 1 {
                                     break 'main;
                                 } else {
+                                    self.positions.pop();
                                     pop_context!();
                                 }
                             }
@@ -489,6 +770,9 @@ impl Interpreter {
             if self.pc >= agent.assembler.code.len() {
                 break;
             }
+            if agent.tracing() {
+                agent.trace_statement(self.pc);
+            }
             let op = agent.assembler.code[self.pc].into();
             self.pc += 1;
 
@@ -523,6 +807,12 @@ impl Interpreter {
                     let sym = Value::new_well_known_symbol(name);
                     self.accumulator = sym;
                 }
+                Op::LoadBigInt => {
+                    let sid = read_u32!() as usize;
+                    let digits = agent.assembler.string_table[sid].as_bytes();
+                    let n = BigInt::parse_bytes(digits, 10).unwrap();
+                    self.accumulator = Value::BigInt(Rc::new(n));
+                }
                 Op::BuildRegex => {
                     let pid = read_u32!() as usize;
                     let pattern = &agent.assembler.string_table[pid];
@@ -531,8 +821,7 @@ impl Interpreter {
                 }
                 Op::LoadNamedProperty => {
                     let sid = read_u32!() as usize;
-                    let key = agent.assembler.string_table[sid].as_str();
-                    let key = ObjectKey::from(key);
+                    let key = agent.string_key(sid);
                     self.accumulator = handle!(self.accumulator.get(agent, key));
                 }
                 Op::LoadComputedProperty => {
@@ -543,7 +832,7 @@ impl Interpreter {
                 Op::StoreNamedProperty => {
                     let oid = read_u32!() as usize;
                     let sid = read_u32!() as usize;
-                    let key = ObjectKey::from(agent.assembler.string_table[sid].as_str());
+                    let key = agent.string_key(sid);
                     handle!(self.registers[oid].set(agent, key, self.accumulator.clone()));
                 }
                 Op::StoreComputedProperty => {
@@ -561,13 +850,29 @@ impl Interpreter {
                     self.registers[rid] = self.accumulator.clone();
                 }
                 Op::SetException => {
+                    locate_error(agent, self.pc - 1, &self.accumulator);
                     self.exception = Some(std::mem::replace(&mut self.accumulator, Value::Empty));
+                    self.returning = false;
                 }
                 Op::GetException => {
                     self.accumulator = self.exception.take().unwrap();
                 }
                 Op::ClearException => {
                     self.exception = None;
+                    self.returning = false;
+                }
+                Op::SaveCompletion => {
+                    let vid = read_u32!() as usize;
+                    let rid = read_u32!() as usize;
+                    self.registers[vid] = self.exception.take().unwrap();
+                    self.registers[rid] = Value::from(self.returning);
+                    self.returning = false;
+                }
+                Op::RestoreCompletion => {
+                    let vid = read_u32!() as usize;
+                    let rid = read_u32!() as usize;
+                    self.exception = Some(self.registers[vid].clone());
+                    self.returning = self.registers[rid] == Value::from(true);
                 }
                 Op::EnterScope => {
                     let mut context = self.context.last().unwrap().borrow_mut();
@@ -630,6 +935,19 @@ impl Interpreter {
                         .get(agent, name);
                     self.accumulator = handle!(r);
                 }
+                Op::ResolveIdentifierForTypeof => {
+                    // an undeclared identifier is null rather than an error,
+                    // but bindings in their TDZ still throw.
+                    let sid = read_u32!() as usize;
+                    let name = &agent.assembler.string_table[sid];
+                    let scope = self.context.last().unwrap().borrow().scope.clone();
+                    let r = match scope.borrow().has_binding(agent, name) {
+                        Ok(true) => scope.borrow().get(agent, name),
+                        Ok(false) => Ok(Value::Null),
+                        Err(e) => Err(e),
+                    };
+                    self.accumulator = handle!(r);
+                }
                 Op::AssignIdentifier => {
                     let sid = read_u32!() as usize;
                     let name = &agent.assembler.string_table[sid];
@@ -643,6 +961,32 @@ impl Interpreter {
                         .set(agent, name, self.accumulator.clone());
                     handle!(r);
                 }
+                Op::ResolveSlot => {
+                    let depth = read_u32!();
+                    let slot = read_u32!();
+                    let r = self
+                        .context
+                        .last()
+                        .unwrap()
+                        .borrow()
+                        .scope
+                        .borrow()
+                        .get_slot(agent, depth, slot);
+                    self.accumulator = handle!(r);
+                }
+                Op::AssignSlot => {
+                    let depth = read_u32!();
+                    let slot = read_u32!();
+                    let r = self
+                        .context
+                        .last()
+                        .unwrap()
+                        .borrow()
+                        .scope
+                        .borrow_mut()
+                        .set_slot(agent, depth, slot, self.accumulator.clone());
+                    handle!(r);
+                }
                 Op::GetThis => {
                     let r = self
                         .context
@@ -679,7 +1023,27 @@ impl Interpreter {
                     let callee = std::mem::replace(&mut self.registers[cid], Value::Empty);
                     self.accumulator = handle!(callee.construct(agent, args, callee.clone()));
                 }
+                Op::ConstructWithArray => {
+                    let cid = read_u32!() as usize; // callee
+                    let aid = read_u32!() as usize; // argument array
+
+                    let args = array_items(&self.registers[aid]).borrow().clone();
+                    let callee = std::mem::replace(&mut self.registers[cid], Value::Empty);
+                    self.accumulator = handle!(callee.construct(agent, args, callee.clone()));
+                }
+                Op::CallWithArray => {
+                    check_interrupt!();
+                    let rid = read_u32!() as usize; // receiver
+                    let cid = read_u32!() as usize; // callee
+                    let aid = read_u32!() as usize; // argument array
+
+                    let args = array_items(&self.registers[aid]).borrow().clone();
+                    let callee = std::mem::replace(&mut self.registers[cid], Value::Empty);
+                    let receiver = std::mem::replace(&mut self.registers[rid], Value::Empty);
+                    self.accumulator = handle!(callee.call(agent, receiver, args));
+                }
                 Op::Call | Op::TailCall => {
+                    check_interrupt!();
                     let rid = read_u32!() as usize; // receiver
                     let cid = read_u32!() as usize; // callee
                     let sargid = read_u32!() as usize; // first argument register
@@ -699,8 +1063,8 @@ impl Interpreter {
                         };
                     }
 
-                    match callee {
-                        Value::Object(ref o) => match &o.kind {
+                    match callee.kind() {
+                        ValueKind::Object(o) => match &o.kind {
                             ObjectKind::BytecodeFunction { kind, .. }
                                 if *kind & FunctionKind::Normal != FunctionKind::Normal =>
                             {
@@ -712,21 +1076,41 @@ impl Interpreter {
                             ObjectKind::BytecodeFunction {
                                 position,
                                 parameters,
+                                rest,
                                 scope,
                                 kind,
-                                ..
+                                name,
+                                length,
                             } => {
+                                handle!(check_arity(agent, name, *length, argc));
                                 let scope = Scope::new(Some(scope.clone()));
                                 let ctx = Context::new(scope.clone());
                                 for (i, param) in parameters.iter().enumerate() {
                                     handle!(scope.borrow_mut().create(agent, param, false));
-                                    let value = if i >= argc {
+                                    let value = if *rest && i == parameters.len() - 1 {
+                                        let start = sargid + i.min(argc);
+                                        Value::new_array_from_vec(
+                                            agent,
+                                            self.registers[start..sargid + argc].to_vec(),
+                                        )
+                                    } else if i >= argc {
                                         Value::Empty
                                     } else {
                                         self.registers[sargid + i].clone()
                                     };
                                     scope.borrow_mut().initialize(param, value);
                                 }
+                                if *kind & FunctionKind::Arrow != FunctionKind::Arrow {
+                                    let args = self.registers[sargid..sargid + argc].to_vec();
+                                    let simple = &parameters[..parameters.len() - *rest as usize];
+                                    handle!(declare_arguments(
+                                        agent,
+                                        &scope,
+                                        args,
+                                        callee.clone(),
+                                        simple
+                                    ));
+                                }
                                 if *kind & FunctionKind::Arrow == FunctionKind::Arrow {
                                     // FIXME: doesn't have `this` vs inherited `this` needs to be clarified
                                 } else if self.registers[rid].type_of() == "null" {
@@ -735,12 +1119,21 @@ impl Interpreter {
                                     let r = handle!(self.registers[rid].to_object(agent));
                                     scope.borrow_mut().this = Some(r);
                                 }
-                                if op == Op::TailCall {
+                                // the frame this interpreter was entered with
+                                // has nothing under it to return to, so it
+                                // isn't replaced
+                                if op == Op::TailCall && !self.positions.is_empty() {
                                     pop_context!();
                                 } else {
                                     self.positions.push(self.pc);
                                 }
                                 push_context!(ctx);
+                                if agent.tracing() {
+                                    agent.trace_enter(
+                                        name,
+                                        Some(agent.assembler.function_span(*position)),
+                                    );
+                                }
                                 self.pc = *position;
                             }
                             _ => handle!(Err(Value::new_error(agent, "value is not a function"))),
@@ -764,22 +1157,50 @@ impl Interpreter {
                 }
                 Op::IteratorNext => {
                     let iid = read_u32!() as usize;
-                    if let Value::Iterator(iterator, next) = &self.registers[iid] {
-                        self.accumulator = handle!(next.call(agent, (**iterator).clone(), vec![]));
+                    if let ValueKind::Iterator(iterator, next) = self.registers[iid].kind() {
+                        self.accumulator = handle!(next.call(agent, iterator.clone(), vec![]));
                     } else {
                         unreachable!()
                     }
                 }
                 Op::AsyncIteratorNext => {
                     let iid = read_u32!() as usize;
-                    if let Value::Iterator(iterator, next) = &self.registers[iid] {
-                        let promise = handle!(next.call(agent, (**iterator).clone(), vec![]));
+                    if let ValueKind::Iterator(iterator, next) = self.registers[iid].kind() {
+                        let promise = handle!(next.call(agent, iterator.clone(), vec![]));
                         return Err(SuspendValue(promise));
                     } else {
                         unreachable!()
                     }
                 }
+                Op::IteratorClose => {
+                    let iid = read_u32!() as usize;
+                    let swallow = read_u8!() == 1;
+                    let r = iterator_return(agent, &self.registers[iid]);
+                    if !swallow {
+                        handle!(r);
+                    }
+                }
+                Op::AsyncIteratorClose => {
+                    let iid = read_u32!() as usize;
+                    let swallow = read_u8!() == 1;
+                    let r = iterator_return(agent, &self.registers[iid]);
+                    let promise = if swallow {
+                        match r {
+                            Ok(promise) if promise != Value::Empty => {
+                                handle!(ignore_rejection(agent, promise))
+                            }
+                            _ => continue 'main,
+                        }
+                    } else {
+                        match handle!(r) {
+                            promise if promise == Value::Empty => continue 'main,
+                            promise => promise,
+                        }
+                    };
+                    return Err(SuspendValue(promise));
+                }
                 Op::Jump => {
+                    check_interrupt!();
                     let position = read_u32!() as usize;
                     self.pc = position;
                 }
@@ -795,26 +1216,21 @@ impl Interpreter {
                         self.pc = position;
                     }
                 }
-                Op::JumpIfEmpty => {
-                    let position = read_u32!() as usize;
-                    if self.accumulator == Value::Empty {
-                        self.pc = position;
-                    }
-                }
                 Op::JumpIfNotEmpty => {
                     let position = read_u32!() as usize;
                     if self.accumulator != Value::Empty {
                         self.pc = position;
                     }
                 }
-                Op::PushTry => {
-                    let pos = read_u32!() as usize;
-                    self.context
-                        .last()
-                        .unwrap()
-                        .borrow_mut()
-                        .try_stack
-                        .push(pos);
+                Op::PushTry | Op::PushFinally => {
+                    let pc = read_u32!() as usize;
+                    let mut context = self.context.last().unwrap().borrow_mut();
+                    let scope = context.scope.clone();
+                    context.try_stack.push(TryHandler {
+                        pc,
+                        finally: op == Op::PushFinally,
+                        scope,
+                    });
                 }
                 Op::PopTry => {
                     self.context.last().unwrap().borrow_mut().try_stack.pop();
@@ -827,7 +1243,7 @@ impl Interpreter {
                                 break 'main;
                             }
                             Some(context) => {
-                                if let Some(pc) = context.borrow_mut().try_stack.pop() {
+                                if let Some(pc) = context.borrow_mut().pop_handler(self.returning) {
                                     self.pc = pc;
                                     continue 'main;
                                 }
@@ -836,6 +1252,7 @@ impl Interpreter {
                         if self.context.len() == 1 {
                             break 'main;
                         } else {
+                            self.positions.pop();
                             pop_context!();
                         }
                     }
@@ -849,12 +1266,22 @@ impl Interpreter {
                     let key = ObjectKey::from(idx);
                     handle!(self.registers[aid].set(agent, key, self.accumulator.clone()));
                 }
+                Op::AppendToArray => {
+                    let aid = read_u32!() as usize;
+                    let value = std::mem::replace(&mut self.accumulator, Value::Empty);
+                    array_items(&self.registers[aid]).borrow_mut().push(value);
+                }
+                Op::SpreadIntoArray => {
+                    let aid = read_u32!() as usize;
+                    let r = spread_into(agent, &self.accumulator, &self.registers[aid]);
+                    handle!(r);
+                }
                 Op::CreateEmptyTuple => {
                     self.accumulator = Value::new_tuple();
                 }
                 Op::StoreInTuple => {
                     let tid = read_u32!() as usize;
-                    if let Value::Tuple(items) = &mut self.registers[tid] {
+                    if let Some(items) = self.registers[tid].tuple_items_mut() {
                         items.push(std::mem::replace(&mut self.accumulator, Value::Empty));
                     } else {
                         unreachable!();
@@ -869,6 +1296,11 @@ impl Interpreter {
                     let key = handle!(self.registers[kid].to_object_key(agent));
                     handle!(self.registers[oid].set(agent, key, self.accumulator.clone()));
                 }
+                Op::SpreadIntoObject => {
+                    let oid = read_u32!() as usize;
+                    let r = spread_into_object(agent, &self.accumulator, &self.registers[oid]);
+                    handle!(r);
+                }
                 Op::NewFunction => {
                     let id = read_u32!() as usize;
                     let info = &agent.assembler.function_info[id];
@@ -884,50 +1316,50 @@ impl Interpreter {
                     let nid = read_u32!() as usize;
 
                     let name = agent.assembler.string_table[nid].as_str();
-                    handle!(self.registers[cid].set(
-                        agent,
-                        ObjectKey::from("name"),
-                        Value::from(name)
-                    ));
+                    handle!(self.registers[cid].set(agent, ObjectKey::NAME, Value::from(name)));
                     if self.registers[eid] != Value::Empty {
                         // FIXME: self.registers[cid].set_prototype(self.registers[eid]);
                     }
                 }
                 Op::Add => {
                     let lhsid = read_u32!() as usize;
-                    match self.registers[lhsid] {
-                        Value::Number(ln) => match self.accumulator {
-                            Value::Number(rn) => {
-                                self.accumulator = Value::from(ln + rn);
-                            }
-                            _ => handle!(Err(Value::new_error(agent, "rhs must be a number"))),
-                        },
-                        Value::String(ref ls) => match self.accumulator {
-                            Value::String(ref rs) => {
-                                self.accumulator = Value::from(format!("{}{}", ls, rs));
-                            }
-                            _ => handle!(Err(Value::new_error(agent, "rhs must be a string"))),
-                        },
-                        _ => handle!(Err(Value::new_error(
-                            agent,
-                            "lhs must be a number or string"
-                        ))),
-                    }
+                    let result = match (self.registers[lhsid].kind(), self.accumulator.kind()) {
+                        (ValueKind::Number(ln), ValueKind::Number(rn)) => Ok(Value::from(ln + rn)),
+                        (ValueKind::BigInt(ln), ValueKind::BigInt(rn)) => {
+                            bigint::add(agent, ln, rn)
+                        }
+                        (ValueKind::Number(..), ValueKind::BigInt(..))
+                        | (ValueKind::BigInt(..), ValueKind::Number(..)) => {
+                            Err(bigint::mixed_error(agent))
+                        }
+                        (ValueKind::Number(..), _) | (ValueKind::BigInt(..), _) => {
+                            Err(Value::new_error(agent, "rhs must be a number"))
+                        }
+                        (ValueKind::String(ls), ValueKind::String(rs)) => {
+                            Ok(Value::from(format!("{}{}", ls, rs)))
+                        }
+                        (ValueKind::String(..), _) => {
+                            Err(Value::new_error(agent, "rhs must be a string"))
+                        }
+                        _ => Err(Value::new_error(agent, "lhs must be a number or string")),
+                    };
+                    self.accumulator = handle!(result);
                 }
-                Op::Sub => num_binop_num!(f64::sub),
-                Op::Mul => num_binop_num!(f64::mul),
-                Op::Div => num_binop_num!(f64::div),
-                Op::Mod => num_binop_num!(f64::rem),
-                Op::Pow => num_binop_num!(f64::powf),
+                Op::Sub => num_binop_num!(f64::sub, bigint::sub),
+                Op::Mul => num_binop_num!(f64::mul, bigint::mul),
+                Op::Div => num_binop_num!(f64::div, bigint::div),
+                Op::Mod => num_binop_num!(f64::rem, bigint::rem),
+                Op::Pow => num_binop_num!(f64::powf, bigint::pow),
                 Op::BitOR => num_binop_num!(f64_bor),
                 Op::BitXOR => num_binop_num!(f64_bxor),
                 Op::BitAND => num_binop_num!(f64_band),
                 Op::ShiftLeft => num_binop_num!(f64_shl),
                 Op::ShiftRight => num_binop_num!(f64_shr),
-                Op::GreaterThan => num_binop_bool!(f64::gt),
-                Op::LessThan => num_binop_bool!(f64::lt),
-                Op::GreaterThanOrEqual => num_binop_bool!(f64::ge),
-                Op::LessThanOrEqual => num_binop_bool!(f64::le),
+                Op::ShiftRightUnsigned => num_binop_num!(f64_ushr),
+                Op::GreaterThan => num_binop_bool!(PartialOrd::gt),
+                Op::LessThan => num_binop_bool!(PartialOrd::lt),
+                Op::GreaterThanOrEqual => num_binop_bool!(PartialOrd::ge),
+                Op::LessThanOrEqual => num_binop_bool!(PartialOrd::le),
                 Op::HasProperty => {
                     let lhsid = read_u32!() as usize;
                     let target = handle!(self.registers[lhsid].to_object(agent));
@@ -935,6 +1367,30 @@ impl Interpreter {
                     let r = handle!(target.has(agent, key));
                     self.accumulator = Value::from(r);
                 }
+                Op::CreateRange => {
+                    let lhsid = read_u32!() as usize;
+                    self.accumulator = handle!(create_range(
+                        agent,
+                        &self.registers[lhsid],
+                        &self.accumulator,
+                        false
+                    ));
+                }
+                Op::CreateInclusiveRange => {
+                    let lhsid = read_u32!() as usize;
+                    self.accumulator = handle!(create_range(
+                        agent,
+                        &self.registers[lhsid],
+                        &self.accumulator,
+                        true
+                    ));
+                }
+                Op::DeleteProperty => {
+                    let oid = read_u32!() as usize;
+                    let key = handle!(self.accumulator.to_object_key(agent));
+                    let r = handle!(self.registers[oid].delete(agent, key));
+                    self.accumulator = Value::from(r);
+                }
                 Op::Eq => {
                     let lhsid = read_u32!() as usize;
                     self.accumulator = Value::from(self.registers[lhsid] == self.accumulator);
@@ -946,8 +1402,8 @@ impl Interpreter {
                 Op::LNOT => {
                     self.accumulator = Value::from(!self.accumulator.to_bool());
                 }
-                Op::BitNOT => match self.accumulator {
-                    Value::Number(n) => {
+                Op::BitNOT => match self.accumulator.kind() {
+                    ValueKind::Number(n) => {
                         self.accumulator = Value::from(f64_bnot(n));
                     }
                     _ => handle!(Err(Value::new_error(agent, "operand must be a number"))),
@@ -958,18 +1414,105 @@ impl Interpreter {
                 Op::Void => {
                     self.accumulator = Value::Null;
                 }
-                Op::UnSub => match self.accumulator {
-                    Value::Number(n) => {
+                Op::UnSub => match self.accumulator.kind() {
+                    ValueKind::Number(n) => {
                         self.accumulator = Value::from(-n);
                     }
+                    ValueKind::BigInt(n) => {
+                        self.accumulator = Value::BigInt(Rc::new(-&**n));
+                    }
                     _ => handle!(Err(Value::new_error(agent, "operand must be a number"))),
                 },
             }
         }
 
-        Ok(match self.exception.take() {
+        Ok(self.completion())
+    }
+
+    fn completion(&mut self) -> Result<Value, Value> {
+        match self.exception.take() {
+            Some(v) if self.returning => {
+                self.returning = false;
+                Ok(v)
+            }
             Some(e) => Err(e),
             None => Ok(self.accumulator.clone()),
-        })
+        }
+    }
+}
+
+// argument arrays are only ever created by CreateEmptyArray
+fn array_items(array: &Value) -> &GcCell<Vec<Value>> {
+    match array.kind() {
+        ValueKind::Object(o) => match &o.into_ref().kind {
+            ObjectKind::Array(items) => items,
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn spread_into(agent: &Agent, iterable: &Value, array: &Value) -> Result<(), Value> {
+    if let ValueKind::Iterator(iterator, next) = iterable.to_iterator(agent)?.kind() {
+        loop {
+            let result = next.call(agent, iterator.clone(), vec![])?;
+            if result.get(agent, ObjectKey::new(agent, "done"))?.to_bool() {
+                break;
+            }
+            let value = result.get(agent, ObjectKey::new(agent, "value"))?;
+            array_items(array).borrow_mut().push(value);
+        }
+    }
+    Ok(())
+}
+
+// copies the own properties of `source` onto `object`, reading each one once
+// with `get` so that proxy traps run as the copy is made. Strings copy each of
+// their characters by index, and other primitives (including null) have no
+// properties to copy.
+fn spread_into_object(agent: &Agent, source: &Value, object: &Value) -> Result<(), Value> {
+    match source.kind() {
+        ValueKind::Object(..) | ValueKind::Tuple(..) => {
+            for key in source.keys(agent)? {
+                let value = source.get(agent, key.clone())?;
+                object.set(agent, key, value)?;
+            }
+        }
+        ValueKind::String(s) => {
+            for (i, c) in s.chars().enumerate() {
+                object.set(agent, ObjectKey::from(i), Value::from(c.to_string()))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// calls the `return` method of an iterator stored by GetIterator or
+// GetAsyncIterator, returns empty if the iterator doesn't have one.
+fn iterator_return(agent: &Agent, iterator: &Value) -> Result<Value, Value> {
+    if let ValueKind::Iterator(iterator, _) = iterator.kind() {
+        let f = iterator.get(agent, ObjectKey::new(agent, "return"))?;
+        if f == Value::Null {
+            Ok(Value::Empty)
+        } else {
+            f.call(agent, iterator.clone(), vec![])
+        }
+    } else {
+        unreachable!()
     }
 }
+
+fn ignore(_: &Agent, _: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    Ok(Value::Null)
+}
+
+fn ignore_rejection(agent: &Agent, value: Value) -> Result<Value, Value> {
+    let promise = promise_resolve_i(agent, agent.intrinsics.promise.clone(), value)?;
+    let ignore = Value::new_builtin_function(agent, ignore);
+    promise.get(agent, ObjectKey::new(agent, "then"))?.call(
+        agent,
+        promise,
+        vec![ignore.clone(), ignore],
+    )
+}
@@ -0,0 +1,165 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, Value, ValueKind};
+
+fn is_signal(value: &Value) -> bool {
+    value.type_of() == "object" && value.has_slot("abort signal callbacks")
+}
+
+/// Read the `signal` option of an async operation. Returns None if there is
+/// no signal.
+pub fn signal_option(agent: &Agent, options: Option<&Value>) -> Result<Option<Value>, Value> {
+    let signal = match options {
+        Some(options) if options.type_of() == "object" => {
+            options.get(agent, ObjectKey::new(agent, "signal"))?
+        }
+        _ => return Ok(None),
+    };
+    match signal.kind() {
+        ValueKind::Null => Ok(None),
+        _ if is_signal(&signal) => Ok(Some(signal)),
+        _ => Err(Value::new_error(agent, "signal must be an AbortSignal")),
+    }
+}
+
+/// The reason a signal was aborted with, or None if it has not been.
+pub fn abort_reason(signal: &Value) -> Option<Value> {
+    if signal.get_slot("abort signal aborted") == Value::from(true) {
+        Some(signal.get_slot("abort signal reason"))
+    } else {
+        None
+    }
+}
+
+/// Call `callback` with the reason once `signal` is aborted. Nothing is
+/// called if the signal was already aborted, callers check that first.
+pub fn on_abort(signal: &Value, callback: Value) {
+    if let ValueKind::List(callbacks) = signal.get_slot("abort signal callbacks").kind() {
+        callbacks.borrow_mut().push_back(callback);
+    }
+}
+
+fn abort_controller(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let prototype = ctx
+        .callee()
+        .get(agent, ObjectKey::new(agent, "prototype"))?;
+
+    let signal = Value::new_custom_object(agent.intrinsics.abort_signal_prototype.clone());
+    signal.set_slot("abort signal aborted", Value::from(false));
+    signal.set_slot("abort signal reason", Value::Null);
+    signal.set_slot("abort signal callbacks", Value::new_list());
+    signal.set(agent, ObjectKey::new(agent, "aborted"), Value::from(false))?;
+    signal.set(agent, ObjectKey::new(agent, "reason"), Value::Null)?;
+
+    let controller = Value::new_custom_object(prototype);
+    controller.set_slot("abort controller signal", signal.clone());
+    controller.set(agent, ObjectKey::new(agent, "signal"), signal)?;
+    Ok(controller)
+}
+
+// aborting twice does nothing, the callbacks only ever run once.
+fn abort(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() != "object" || !this.has_slot("abort controller signal") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    let signal = this.get_slot("abort controller signal");
+    if abort_reason(&signal).is_some() {
+        return Ok(Value::Null);
+    }
+
+    let reason = match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => {
+            let e = Value::new_error(agent, "the operation was aborted");
+            e.set(agent, ObjectKey::NAME, Value::from("AbortError"))?;
+            e
+        }
+        Some(_) => args[0].clone(),
+    };
+    signal.set_slot("abort signal aborted", Value::from(true));
+    signal.set_slot("abort signal reason", reason.clone());
+    signal.set(agent, ObjectKey::new(agent, "aborted"), Value::from(true))?;
+    signal.set(agent, ObjectKey::new(agent, "reason"), reason.clone())?;
+
+    let callbacks = match signal.get_slot("abort signal callbacks").kind() {
+        ValueKind::List(callbacks) => callbacks.borrow_mut().drain(..).collect::<Vec<Value>>(),
+        _ => unreachable!(),
+    };
+    for callback in callbacks {
+        if let Err(e) = callback.call(agent, signal.clone(), vec![reason.clone()]) {
+            agent.uncaught_exception(e);
+        }
+    }
+    Ok(Value::Null)
+}
+
+fn this_signal(agent: &Agent, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if is_signal(&this) {
+        Ok(this)
+    } else {
+        Err(Value::new_error(agent, "invalid receiver"))
+    }
+}
+
+fn signal_on_abort(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let signal = this_signal(agent, ctx)?;
+    let callback = match args.get(0) {
+        Some(f) if f.type_of() == "function" => f.clone(),
+        _ => return Err(Value::new_error(agent, "callback must be a function")),
+    };
+    match abort_reason(&signal) {
+        Some(reason) => {
+            callback.call(agent, signal, vec![reason])?;
+        }
+        None => on_abort(&signal, callback),
+    }
+    Ok(Value::Null)
+}
+
+fn throw_if_aborted(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    match abort_reason(&this_signal(agent, ctx)?) {
+        Some(reason) => Err(reason),
+        None => Ok(Value::Null),
+    }
+}
+
+pub fn create_abort_signal_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("onAbort", signal_on_abort, 1);
+    method!("throwIfAborted", throw_if_aborted, 0);
+
+    proto
+}
+
+pub fn create_abort_controller(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "abort"),
+            Value::new_named_builtin_function(agent, abort, "abort", 1),
+        )
+        .unwrap();
+
+    let c = Value::new_named_builtin_function(agent, abort_controller, "AbortController", 0);
+    c.set(agent, ObjectKey::new(agent, "prototype"), proto.clone())
+        .unwrap();
+    proto
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
@@ -0,0 +1,284 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::bytes_prototype::{relative_index, zeroed_bytes};
+use crate::intrinsics::typed_array::TypedArrayKind;
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
+use gc::GcCell;
+
+/// The bytes of an ArrayBuffer, or None if `value` is not one. A detached
+/// buffer has no bytes.
+pub fn to_array_buffer(value: &Value) -> Option<&GcCell<Vec<u8>>> {
+    if let ValueKind::Object(o) = value.kind() {
+        if let ObjectKind::ArrayBuffer { data, .. } = &o.into_ref().kind {
+            return Some(data);
+        }
+    }
+    None
+}
+
+fn this_array_buffer(agent: &Agent, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    match this.kind() {
+        ValueKind::Object(o) => match &o.kind {
+            ObjectKind::ArrayBuffer { detached, .. } if *detached.borrow() => {
+                Err(Value::new_error(agent, "ArrayBuffer is detached"))
+            }
+            ObjectKind::ArrayBuffer { .. } => Ok(this),
+            _ => Err(Value::new_error(agent, "invalid receiver")),
+        },
+        _ => Err(Value::new_error(agent, "invalid receiver")),
+    }
+}
+
+fn byte_length_arg(agent: &Agent, value: Option<&Value>) -> Result<usize, Value> {
+    match value.map(Value::kind) {
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        _ => Err(Value::new_error(
+            agent,
+            "byteLength must be a non-negative integer",
+        )),
+    }
+}
+
+fn array_buffer(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let byte_length = match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => 0,
+        _ => byte_length_arg(agent, args.get(0))?,
+    };
+    let data = zeroed_bytes(agent, byte_length)?;
+    Ok(Value::new_array_buffer(agent, data))
+}
+
+fn slice(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = this_array_buffer(agent, ctx)?;
+    let data = to_array_buffer(&this).unwrap().borrow();
+    let start = relative_index(agent, args.get(0), data.len(), 0)?;
+    let end = relative_index(agent, args.get(1), data.len(), data.len())?;
+    let slice = if start < end {
+        data[start..end].to_vec()
+    } else {
+        Vec::new()
+    };
+    Ok(Value::new_array_buffer(agent, slice))
+}
+
+// moves the bytes into a new buffer, optionally resized, leaving this one
+// detached. views of this buffer see a length of 0 from then on.
+fn transfer(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = this_array_buffer(agent, ctx)?;
+    // the resized bytes are allocated first, so this buffer is left as it
+    // was if they can't be.
+    let resized = match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => None,
+        _ => Some(zeroed_bytes(agent, byte_length_arg(agent, args.get(0))?)?),
+    };
+    let data = if let ValueKind::Object(o) = this.kind() {
+        if let ObjectKind::ArrayBuffer { data, detached } = &o.kind {
+            *detached.borrow_mut() = true;
+            std::mem::take(&mut *data.borrow_mut())
+        } else {
+            unreachable!();
+        }
+    } else {
+        unreachable!();
+    };
+    match resized {
+        Some(mut resized) => {
+            let n = data.len().min(resized.len());
+            resized[..n].copy_from_slice(&data[..n]);
+            Ok(Value::new_array_buffer(agent, resized))
+        }
+        None => Ok(Value::new_array_buffer(agent, data)),
+    }
+}
+
+pub fn create_array_buffer_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("slice", slice, 2);
+    method!("transfer", transfer, 0);
+
+    proto
+}
+
+pub fn create_array_buffer(agent: &Agent) -> Value {
+    let c = Value::new_named_builtin_function(agent, array_buffer, "ArrayBuffer", 1);
+    c.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.array_buffer_prototype.clone(),
+    )
+    .unwrap();
+    agent
+        .intrinsics
+        .array_buffer_prototype
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
+
+fn data_view(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let buffer = match args.get(0) {
+        Some(buffer) if to_array_buffer(buffer).is_some() => buffer.clone(),
+        _ => return Err(Value::new_error(agent, "buffer must be an ArrayBuffer")),
+    };
+    let byte_length = to_array_buffer(&buffer).unwrap().borrow().len();
+    let offset = match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => 0,
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "byteOffset must be a non-negative integer",
+            ))
+        }
+    };
+    if offset > byte_length {
+        return Err(Value::new_error(agent, "byteOffset is out of bounds"));
+    }
+    let length = match args.get(2).map(Value::kind) {
+        None | Some(ValueKind::Null) => byte_length - offset,
+        _ => byte_length_arg(agent, args.get(2))?,
+    };
+    if offset + length > byte_length {
+        return Err(Value::new_error(agent, "byteLength is out of bounds"));
+    }
+
+    let view = Value::new_custom_object(agent.intrinsics.data_view_prototype.clone());
+    view.set_slot("data view buffer", buffer.clone());
+    view.set_slot("data view offset", Value::from(offset as f64));
+    view.set_slot("data view length", Value::from(length as f64));
+    view.set(agent, ObjectKey::new(agent, "buffer"), buffer)?;
+    view.set(
+        agent,
+        ObjectKey::new(agent, "byteOffset"),
+        Value::from(offset as f64),
+    )?;
+    view.set(
+        agent,
+        ObjectKey::new(agent, "byteLength"),
+        Value::from(length as f64),
+    )?;
+    Ok(view)
+}
+
+// resolves the byte range that a getX or setX call touches, checking it
+// against the bounds of the view.
+fn view_range(
+    agent: &Agent,
+    ctx: &Context,
+    index: Option<&Value>,
+) -> Result<(Value, usize, TypedArrayKind), Value> {
+    let view = ctx.this(agent)?;
+    if view.type_of() != "object" || !view.has_slot("data view buffer") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    let kind = match ctx.callee().get_slot("data view kind").kind() {
+        ValueKind::String(name) => TypedArrayKind::from_name(name).unwrap(),
+        _ => unreachable!(),
+    };
+    let (offset, length) = match (
+        view.get_slot("data view offset").kind(),
+        view.get_slot("data view length").kind(),
+    ) {
+        (ValueKind::Number(offset), ValueKind::Number(length)) => {
+            (offset as usize, length as usize)
+        }
+        _ => unreachable!(),
+    };
+    let index = match index.map(Value::kind) {
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "byteOffset must be a non-negative integer",
+            ))
+        }
+    };
+    let buffer = view.get_slot("data view buffer");
+    let byte_length = to_array_buffer(&buffer).unwrap().borrow().len();
+    if index + kind.size() > length || offset + length > byte_length {
+        return Err(Value::new_error(agent, "byteOffset is out of bounds"));
+    }
+    Ok((buffer, offset + index, kind))
+}
+
+fn get(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (buffer, start, kind) = view_range(agent, ctx, args.get(0))?;
+    let little_endian = args.get(1).map_or(false, Value::to_bool);
+    let mut bytes = to_array_buffer(&buffer).unwrap().borrow()[start..start + kind.size()].to_vec();
+    if !little_endian {
+        bytes.reverse();
+    }
+    Ok(Value::from(kind.decode(&bytes)))
+}
+
+fn set(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (buffer, start, kind) = view_range(agent, ctx, args.get(0))?;
+    let n = match args.get(1).map(Value::kind) {
+        Some(ValueKind::Number(n)) => n,
+        _ => return Err(Value::new_error(agent, "value must be a number")),
+    };
+    let little_endian = args.get(2).map_or(false, Value::to_bool);
+    let mut bytes = vec![0; kind.size()];
+    kind.encode(n, &mut bytes);
+    if !little_endian {
+        bytes.reverse();
+    }
+    to_array_buffer(&buffer).unwrap().borrow_mut()[start..start + kind.size()]
+        .copy_from_slice(&bytes);
+    Ok(Value::Null)
+}
+
+pub fn create_data_view_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    // getInt8, setInt8, getUint8, ... share one implementation each, told
+    // apart by the element kind stored on the function.
+    for kind in TypedArrayKind::ALL.iter() {
+        let element = kind.name().trim_end_matches("Array");
+        let getter_name = format!("get{}", element);
+        let getter = Value::new_named_builtin_function(agent, get, &getter_name, 1);
+        getter.set_slot("data view kind", Value::from(kind.name()));
+        proto
+            .set(agent, ObjectKey::new(agent, &getter_name), getter)
+            .unwrap();
+
+        let setter_name = format!("set{}", element);
+        let setter = Value::new_named_builtin_function(agent, set, &setter_name, 2);
+        setter.set_slot("data view kind", Value::from(kind.name()));
+        proto
+            .set(agent, ObjectKey::new(agent, &setter_name), setter)
+            .unwrap();
+    }
+
+    proto
+}
+
+pub fn create_data_view(agent: &Agent) -> Value {
+    let c = Value::new_named_builtin_function(agent, data_view, "DataView", 1);
+    c.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.data_view_prototype.clone(),
+    )
+    .unwrap();
+    agent
+        .intrinsics
+        .data_view_prototype
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
@@ -1,9 +1,9 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, Value};
+use crate::value::{ObjectKey, Value, ValueKind};
 
 fn next(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let o = ctx.scope.borrow().get_this(agent)?;
+    let o = ctx.this(agent)?;
     if o.type_of() != "object" {
         return Err(Value::new_error(agent, "invalid receiver"));
     }
@@ -11,12 +11,15 @@ fn next(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value>
     if a == Value::Null {
         return Value::new_iter_result(agent, Value::Null, true);
     }
-    let index = if let Value::Number(n) = o.get_slot("array iterator next index") {
+    let index = if let ValueKind::Number(n) = o.get_slot("array iterator next index").kind() {
         n
     } else {
         unreachable!();
     };
-    let len = if let Value::Number(n) = a.get(agent, Value::from("length").to_object_key(agent)?)? {
+    let len = if let ValueKind::Number(n) = a
+        .get(agent, Value::from("length").to_object_key(agent)?)?
+        .kind()
+    {
         n
     } else {
         return Err(Value::new_error(agent, "invalid array length"));
@@ -36,8 +39,8 @@ pub fn create_array_iterator_prototype(agent: &Agent) -> Value {
     proto
         .set(
             agent,
-            ObjectKey::from("next"),
-            Value::new_builtin_function(agent, next),
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
         )
         .unwrap();
 
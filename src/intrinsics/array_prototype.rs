@@ -1,11 +1,15 @@
 use crate::interpreter::Context;
+use crate::intrinsics::bytes_prototype::relative_index;
 use crate::sort::merge_sort;
-use crate::value::{ObjectKey, ObjectKind};
+use crate::value::{ObjectKey, ObjectKind, ValueKind};
 use crate::{Agent, Value};
 
 fn user_sort(agent: &Agent, f: &Value, a: &Value, b: &Value) -> Result<std::cmp::Ordering, Value> {
-    match f.call(agent, Value::Null, vec![a.clone(), b.clone()])? {
-        Value::Number(n) => {
+    match f
+        .call(agent, Value::Null, vec![a.clone(), b.clone()])?
+        .kind()
+    {
+        ValueKind::Number(n) => {
             if n == 0.0 {
                 Ok(std::cmp::Ordering::Equal)
             } else if n > 0.0 {
@@ -20,28 +24,45 @@ fn user_sort(agent: &Agent, f: &Value, a: &Value, b: &Value) -> Result<std::cmp:
     }
 }
 
-fn builtin_sort(agent: &Agent, a: &Value, b: &Value) -> Result<std::cmp::Ordering, Value> {
-    match a.partial_cmp(b) {
-        Some(o) => Ok(o),
-        None => Err(Value::new_error(agent, "invalid ordering")),
+// a stable sort, by `compare` or else by the values as strings. nulls always
+// go last, without being passed to `compare`.
+fn sort_values(agent: &Agent, values: &[Value], compare: &Value) -> Result<Vec<Value>, Value> {
+    let mut sorted = values
+        .iter()
+        .filter(|v| **v != Value::Null)
+        .cloned()
+        .collect::<Vec<Value>>();
+    let nulls = values.len() - sorted.len();
+    match compare.kind() {
+        ValueKind::Null => {
+            let mut keyed = Vec::with_capacity(sorted.len());
+            for value in sorted.drain(..) {
+                match value.to_string(agent)?.kind() {
+                    ValueKind::String(key) => keyed.push((key.clone(), value)),
+                    _ => unreachable!(),
+                }
+            }
+            merge_sort(&mut keyed, |a, b| -> Result<bool, Value> { Ok(a.0 < b.0) })?;
+            sorted.extend(keyed.into_iter().map(|(_, value)| value));
+        }
+        _ => merge_sort(&mut sorted, |a, b| -> Result<bool, Value> {
+            Ok(user_sort(agent, compare, a, b)? == std::cmp::Ordering::Less)
+        })?,
     }
+    sorted.resize(sorted.len() + nulls, Value::Null);
+    Ok(sorted)
 }
 
+// the array is only changed once the sort is done, so a comparator which
+// throws leaves it as it was.
 fn sort(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    match ctx.scope.borrow().get_this(agent)? {
-        Value::Object(o) => match &o.kind {
+    match ctx.this(agent)?.kind() {
+        ValueKind::Object(o) => match &o.kind {
             ObjectKind::Array(values) => {
-                match args.get(0).unwrap_or(&Value::Null) {
-                    Value::Null => {
-                        merge_sort(&mut values.borrow_mut(), |a, b| -> Result<bool, Value> {
-                            Ok(builtin_sort(agent, a, b)? == std::cmp::Ordering::Less)
-                        })?
-                    }
-                    v => merge_sort(&mut values.borrow_mut(), |a, b| -> Result<bool, Value> {
-                        Ok(user_sort(agent, v, a, b)? == std::cmp::Ordering::Less)
-                    })?,
-                };
-                Ok(ctx.scope.borrow().get_this(agent)?)
+                let unsorted = values.borrow().clone();
+                let sorted = sort_values(agent, &unsorted, args.get(0).unwrap_or(&Value::Null))?;
+                *values.borrow_mut() = sorted;
+                Ok(ctx.this(agent)?)
             }
             _ => Err(Value::new_error(agent, "invalid receiver")),
         },
@@ -49,22 +70,92 @@ fn sort(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value>
     }
 }
 
+// a copy of the values of the receiver, for the methods which return a
+// changed copy instead of changing the array.
+fn this_values(agent: &Agent, ctx: &Context) -> Result<Vec<Value>, Value> {
+    match ctx.this(agent)?.kind() {
+        ValueKind::Object(o) => match &o.kind {
+            ObjectKind::Array(values) => Ok(values.borrow().clone()),
+            _ => Err(Value::new_error(agent, "invalid receiver")),
+        },
+        _ => Err(Value::new_error(agent, "invalid receiver")),
+    }
+}
+
+fn to_sorted(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let values = this_values(agent, ctx)?;
+    let sorted = sort_values(agent, &values, args.get(0).unwrap_or(&Value::Null))?;
+    Ok(Value::new_array_from_vec(agent, sorted))
+}
+
+fn to_reversed(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mut values = this_values(agent, ctx)?;
+    values.reverse();
+    Ok(Value::new_array_from_vec(agent, values))
+}
+
+// `start` counts from the end when negative. without a `deleteCount`
+// everything from `start` on is removed.
+fn to_spliced(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mut values = this_values(agent, ctx)?;
+    let start = relative_index(agent, args.get(0), values.len(), 0)?;
+    let delete_count = match args.get(1).map(Value::kind) {
+        None if args.is_empty() => 0,
+        None | Some(ValueKind::Null) => values.len() - start,
+        Some(ValueKind::Number(n)) if n.fract() == 0.0 => {
+            n.max(0.0).min((values.len() - start) as f64) as usize
+        }
+        _ => return Err(Value::new_error(agent, "deleteCount must be an integer")),
+    };
+    let items = args.into_iter().skip(2);
+    values.splice(start..start + delete_count, items);
+    Ok(Value::new_array_from_vec(agent, values))
+}
+
+fn with(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mut values = this_values(agent, ctx)?;
+    let index = match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if n.fract() == 0.0 => {
+            let index = if n < 0.0 { values.len() as f64 + n } else { n };
+            if index < 0.0 || index >= values.len() as f64 {
+                let e = Value::new_error(agent, "index is out of range");
+                e.set(agent, ObjectKey::NAME, Value::from("RangeError"))?;
+                return Err(e);
+            }
+            index as usize
+        }
+        _ => return Err(Value::new_error(agent, "index must be an integer")),
+    };
+    values[index] = args.get(1).cloned().unwrap_or(Value::Null);
+    Ok(Value::new_array_from_vec(agent, values))
+}
+
 fn iterator(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
     let it = Value::new_custom_object(agent.intrinsics.array_iterator_prototype.clone());
     it.set_slot("array iterator next index", Value::from(0));
-    it.set_slot("iterated object", ctx.scope.borrow().get_this(agent)?);
+    it.set_slot("iterated object", ctx.this(agent)?);
     Ok(it)
 }
 
 pub fn create_array_prototype(agent: &Agent) -> Value {
     let p = Value::new_object(agent.intrinsics.object_prototype.clone());
 
-    p.set(
-        agent,
-        ObjectKey::from("sort"),
-        Value::new_builtin_function(agent, sort),
-    )
-    .unwrap();
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            p.set(
+                agent,
+                ObjectKey::new(agent, $name),
+                Value::new_named_builtin_function(agent, $fn, $name, $length),
+            )
+            .unwrap();
+        };
+    }
+
+    method!("sort", sort, 1);
+    method!("toSorted", to_sorted, 1);
+    method!("toReversed", to_reversed, 0);
+    method!("toSpliced", to_spliced, 2);
+    method!("with", with, 2);
 
     p.set(
         agent,
@@ -0,0 +1,505 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::promise::{new_promise_capability, promise_resolve_i};
+use crate::value::{ObjectKey, Value, ValueKind};
+
+// The async helpers. The lazy ones (map, filter, take, drop and flatMap)
+// return an async iterator on this prototype, the others (forEach, some,
+// every, find, reduce and toArray) drive the same state machine and settle a
+// single promise. Every value from the wrapped iterator and every callback
+// result is awaited before moving on. Calls to `next` are answered in order
+// and at most one of them is worked on at a time.
+
+type Callback = fn(&Agent, Vec<Value>, &Context) -> Result<Value, Value>;
+
+fn is_lazy(kind: &str) -> bool {
+    match kind {
+        "map" | "filter" | "take" | "drop" | "flatMap" => true,
+        _ => false,
+    }
+}
+
+fn kind(it: &Value) -> String {
+    match it.get_slot("helper kind").kind() {
+        ValueKind::String(kind) => kind.clone(),
+        _ => unreachable!(),
+    }
+}
+
+fn count(it: &Value) -> f64 {
+    match it.get_slot("helper count").kind() {
+        ValueKind::Number(n) => n,
+        _ => unreachable!(),
+    }
+}
+
+fn queue_len(it: &Value) -> usize {
+    match it.get_slot("helper queue").kind() {
+        ValueKind::List(queue) => queue.borrow().len(),
+        _ => unreachable!(),
+    }
+}
+
+// resolves `value` and calls `on_fulfilled` or `on_rejected` with it once it
+// settles. both callbacks find the helper in their "helper iterator" slot.
+fn await_value(
+    agent: &Agent,
+    it: &Value,
+    value: Value,
+    on_fulfilled: Callback,
+    on_rejected: Callback,
+) -> Result<(), Value> {
+    let promise = promise_resolve_i(agent, agent.intrinsics.promise.clone(), value)?;
+    let on_fulfilled = Value::new_builtin_function(agent, on_fulfilled);
+    on_fulfilled.set_slot("helper iterator", it.clone());
+    let on_rejected = Value::new_builtin_function(agent, on_rejected);
+    on_rejected.set_slot("helper iterator", it.clone());
+    promise.get(agent, ObjectKey::new(agent, "then"))?.call(
+        agent,
+        promise,
+        vec![on_fulfilled, on_rejected],
+    )?;
+    Ok(())
+}
+
+fn this_helper(ctx: &Context) -> Value {
+    ctx.callee().get_slot("helper iterator")
+}
+
+// answers the oldest pending request and starts on the next one.
+fn settle(agent: &Agent, it: &Value, result: Result<Value, Value>) -> Result<(), Value> {
+    let promise = match it.get_slot("helper queue").kind() {
+        ValueKind::List(queue) => queue.borrow_mut().pop_front().unwrap(),
+        _ => unreachable!(),
+    };
+    it.set_slot("helper busy", Value::from(false));
+    match result {
+        Ok(v) => promise
+            .get_slot("resolve")
+            .call(agent, Value::Null, vec![v])?,
+        Err(e) => promise
+            .get_slot("reject")
+            .call(agent, Value::Null, vec![e])?,
+    };
+    if queue_len(it) > 0 {
+        start(agent, it)?;
+    }
+    Ok(())
+}
+
+fn settle_value(agent: &Agent, it: &Value, value: Value) -> Result<(), Value> {
+    let result = Value::new_iter_result(agent, value, false)?;
+    settle(agent, it, Ok(result))
+}
+
+fn start(agent: &Agent, it: &Value) -> Result<(), Value> {
+    it.set_slot("helper busy", Value::from(true));
+    if it.get_slot("helper done") == Value::from(true) {
+        let result = Value::new_iter_result(agent, Value::Null, true)?;
+        return settle(agent, it, Ok(result));
+    }
+    if it.get_slot("helper returning") == Value::from(true) {
+        // a flatMap's inner iterator is closed without waiting for it.
+        if let ValueKind::Iterator(iterator, _) = it.get_slot("helper inner").kind() {
+            let _ = iterator
+                .get(agent, ObjectKey::new(agent, "return"))
+                .and_then(|f| {
+                    if f == Value::Null {
+                        Ok(Value::Null)
+                    } else {
+                        f.call(agent, iterator.clone(), vec![])
+                    }
+                });
+        }
+        let result = Value::new_iter_result(agent, Value::Null, true)?;
+        return close(agent, it, Ok(result));
+    }
+    if kind(it) == "take" {
+        let remaining = count(it);
+        if remaining == 0.0 {
+            let result = Value::new_iter_result(agent, Value::Null, true)?;
+            return close(agent, it, Ok(result));
+        }
+        it.set_slot("helper count", Value::from(remaining - 1.0));
+    }
+    pull(agent, it)
+}
+
+// asks the inner iterator of a flatMap, or else the wrapped iterator, for
+// its next result.
+fn pull(agent: &Agent, it: &Value) -> Result<(), Value> {
+    let inner = it.get_slot("helper inner");
+    let outer = inner == Value::Null;
+    let iterated = if outer {
+        it.get_slot("iterated")
+    } else {
+        inner
+    };
+    let result = match iterated.kind() {
+        ValueKind::Iterator(iterator, next) => next.call(agent, iterator.clone(), vec![]),
+        _ => unreachable!(),
+    };
+    match result {
+        Ok(result) if outer => await_value(agent, it, result, on_next, on_next_rejected),
+        Ok(result) => await_value(agent, it, result, on_inner_next, on_inner_rejected),
+        Err(e) if outer => {
+            it.set_slot("helper done", Value::from(true));
+            settle(agent, it, Err(e))
+        }
+        Err(e) => close(agent, it, Err(e)),
+    }
+}
+
+// closes the wrapped iterator, awaiting its `return`, and then settles with
+// `result`. if `result` is an error any error from `return` is ignored.
+fn close(agent: &Agent, it: &Value, result: Result<Value, Value>) -> Result<(), Value> {
+    it.set_slot("helper done", Value::from(true));
+    let failed = result.is_err();
+    let value = match result {
+        Ok(v) => v,
+        Err(e) => e,
+    };
+    it.set_slot("helper result", value.clone());
+    it.set_slot("helper failed", Value::from(failed));
+    let closed = match it.get_slot("iterated").kind() {
+        ValueKind::Iterator(iterator, _) => iterator
+            .get(agent, ObjectKey::new(agent, "return"))
+            .and_then(|f| {
+                if f == Value::Null {
+                    Ok(Value::Null)
+                } else {
+                    f.call(agent, iterator.clone(), vec![])
+                }
+            }),
+        _ => unreachable!(),
+    };
+    match closed {
+        Ok(closed) => await_value(agent, it, closed, on_closed, on_close_rejected),
+        Err(_) if failed => settle(agent, it, Err(value)),
+        Err(e) => settle(agent, it, Err(e)),
+    }
+}
+
+fn on_closed(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    let result = it.get_slot("helper result");
+    if it.get_slot("helper failed") == Value::from(true) {
+        settle(agent, &it, Err(result))?;
+    } else {
+        settle(agent, &it, Ok(result))?;
+    }
+    Ok(Value::Null)
+}
+
+fn on_close_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    if it.get_slot("helper failed") == Value::from(true) {
+        settle(agent, &it, Err(it.get_slot("helper result")))?;
+    } else {
+        settle(agent, &it, Err(args.get(0).cloned().unwrap_or(Value::Null)))?;
+    }
+    Ok(Value::Null)
+}
+
+// the wrapped iterator is exhausted.
+fn finish(agent: &Agent, it: &Value) -> Result<(), Value> {
+    it.set_slot("helper done", Value::from(true));
+    let kind = kind(it);
+    let result = match kind.as_str() {
+        _ if is_lazy(&kind) => Value::new_iter_result(agent, Value::Null, true),
+        "forEach" | "find" => Ok(Value::Null),
+        "some" => Ok(Value::from(false)),
+        "every" => Ok(Value::from(true)),
+        "reduce" => {
+            if it.get_slot("helper empty") == Value::from(true) {
+                Err(Value::new_error(
+                    agent,
+                    "reduce of empty iterator with no initial value",
+                ))
+            } else {
+                Ok(it.get_slot("helper value"))
+            }
+        }
+        "toArray" => match it.get_slot("helper value").kind() {
+            ValueKind::List(values) => Ok(Value::new_array_from_vec(
+                agent,
+                values.borrow().iter().cloned().collect(),
+            )),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    settle(agent, it, result)
+}
+
+fn on_next(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    let result = args.get(0).cloned().unwrap_or(Value::Null);
+    let step = if result.type_of() != "object" {
+        Err(Value::new_error(agent, "iterator result is not an object"))
+    } else {
+        result
+            .get(agent, ObjectKey::new(agent, "done"))
+            .and_then(|done| {
+                if done.to_bool() {
+                    Ok(None)
+                } else {
+                    Ok(Some(result.get(agent, ObjectKey::new(agent, "value"))?))
+                }
+            })
+    };
+    match step {
+        Ok(Some(value)) => step_value(agent, &it, value)?,
+        Ok(None) => finish(agent, &it)?,
+        Err(e) => {
+            it.set_slot("helper done", Value::from(true));
+            settle(agent, &it, Err(e))?;
+        }
+    }
+    Ok(Value::Null)
+}
+
+fn on_next_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    it.set_slot("helper done", Value::from(true));
+    settle(agent, &it, Err(args.get(0).cloned().unwrap_or(Value::Null)))?;
+    Ok(Value::Null)
+}
+
+fn on_inner_next(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    let result = args.get(0).cloned().unwrap_or(Value::Null);
+    let step = if result.type_of() != "object" {
+        Err(Value::new_error(agent, "iterator result is not an object"))
+    } else {
+        result
+            .get(agent, ObjectKey::new(agent, "done"))
+            .and_then(|done| {
+                if done.to_bool() {
+                    Ok(None)
+                } else {
+                    Ok(Some(result.get(agent, ObjectKey::new(agent, "value"))?))
+                }
+            })
+    };
+    match step {
+        Ok(Some(value)) => settle_value(agent, &it, value)?,
+        Ok(None) => {
+            it.set_slot("helper inner", Value::Null);
+            pull(agent, &it)?;
+        }
+        Err(e) => close(agent, &it, Err(e))?,
+    }
+    Ok(Value::Null)
+}
+
+fn on_inner_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    close(agent, &it, Err(args.get(0).cloned().unwrap_or(Value::Null)))?;
+    Ok(Value::Null)
+}
+
+// handles a value from the wrapped iterator.
+fn step_value(agent: &Agent, it: &Value, value: Value) -> Result<(), Value> {
+    match kind(it).as_str() {
+        "take" => return settle_value(agent, it, value),
+        "drop" => {
+            let remaining = count(it);
+            if remaining > 0.0 {
+                it.set_slot("helper count", Value::from(remaining - 1.0));
+                return pull(agent, it);
+            }
+            return settle_value(agent, it, value);
+        }
+        "toArray" => {
+            if let ValueKind::List(values) = it.get_slot("helper value").kind() {
+                values.borrow_mut().push_back(value);
+            }
+            return pull(agent, it);
+        }
+        "reduce" if it.get_slot("helper empty") == Value::from(true) => {
+            it.set_slot("helper empty", Value::from(false));
+            it.set_slot("helper value", value);
+            it.set_slot("helper count", Value::from(count(it) + 1.0));
+            return pull(agent, it);
+        }
+        _ => {}
+    }
+
+    let counter = count(it);
+    it.set_slot("helper count", Value::from(counter + 1.0));
+    let args = if kind(it) == "reduce" {
+        vec![it.get_slot("helper value"), value, Value::from(counter)]
+    } else {
+        it.set_slot("helper value", value.clone());
+        vec![value, Value::from(counter)]
+    };
+    match it.get_slot("helper fn").call(agent, Value::Null, args) {
+        Ok(result) => await_value(agent, it, result, on_callback, on_callback_rejected),
+        Err(e) => close(agent, it, Err(e)),
+    }
+}
+
+// handles the awaited result of a callback.
+fn on_callback(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    let result = args.get(0).cloned().unwrap_or(Value::Null);
+    match kind(&it).as_str() {
+        "map" => settle_value(agent, &it, result)?,
+        "filter" => {
+            if result.to_bool() {
+                settle_value(agent, &it, it.get_slot("helper value"))?;
+            } else {
+                pull(agent, &it)?;
+            }
+        }
+        "flatMap" => {
+            let inner = result
+                .get(agent, ObjectKey::well_known_symbol("asyncIterator"))
+                .and_then(|f| {
+                    if f == Value::Null {
+                        result.to_iterator(agent)
+                    } else {
+                        result.to_async_iterator(agent)
+                    }
+                });
+            match inner {
+                Ok(inner) => {
+                    it.set_slot("helper inner", inner);
+                    pull(agent, &it)?;
+                }
+                Err(e) => close(agent, &it, Err(e))?,
+            }
+        }
+        "forEach" => pull(agent, &it)?,
+        "some" if result.to_bool() => close(agent, &it, Ok(Value::from(true)))?,
+        "every" if !result.to_bool() => close(agent, &it, Ok(Value::from(false)))?,
+        "find" if result.to_bool() => close(agent, &it, Ok(it.get_slot("helper value")))?,
+        "some" | "every" | "find" => pull(agent, &it)?,
+        "reduce" => {
+            it.set_slot("helper value", result);
+            pull(agent, &it)?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(Value::Null)
+}
+
+fn on_callback_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = this_helper(ctx);
+    close(agent, &it, Err(args.get(0).cloned().unwrap_or(Value::Null)))?;
+    Ok(Value::Null)
+}
+
+fn new_helper(agent: &Agent, iterated: Value, kind: &str, f: Value, count: f64) -> Value {
+    let prototype = if is_lazy(kind) {
+        agent.intrinsics.async_iterator_helper_prototype.clone()
+    } else {
+        agent.intrinsics.object_prototype.clone()
+    };
+    let it = Value::new_custom_object(prototype);
+    it.set_slot("helper kind", Value::from(kind));
+    it.set_slot("helper fn", f);
+    it.set_slot("helper count", Value::from(count));
+    it.set_slot("helper value", Value::Null);
+    it.set_slot("helper empty", Value::from(false));
+    it.set_slot("helper inner", Value::Null);
+    it.set_slot("helper queue", Value::new_list());
+    it.set_slot("helper busy", Value::from(false));
+    it.set_slot("helper done", Value::from(false));
+    it.set_slot("helper returning", Value::from(false));
+    it.set_slot("helper result", Value::Null);
+    it.set_slot("helper failed", Value::from(false));
+    it.set_slot("iterated", iterated);
+    it
+}
+
+/// Create a lazy helper of `kind` over the async iterator `iterated`.
+pub fn create_async_helper(
+    agent: &Agent,
+    iterated: Value,
+    kind: &str,
+    f: Value,
+    count: f64,
+) -> Value {
+    new_helper(agent, iterated, kind, f, count)
+}
+
+/// Consume `iterated` with the helper `kind`, returning a promise for the
+/// result. `initial` is the initial value for reduce.
+pub fn consume_async(
+    agent: &Agent,
+    iterated: Value,
+    kind: &str,
+    f: Value,
+    initial: Option<Value>,
+) -> Result<Value, Value> {
+    let it = new_helper(agent, iterated, kind, f, 0.0);
+    match initial {
+        Some(initial) => it.set_slot("helper value", initial),
+        None if kind == "reduce" => it.set_slot("helper empty", Value::from(true)),
+        None if kind == "toArray" => it.set_slot("helper value", Value::new_list()),
+        None => {}
+    }
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    if let ValueKind::List(queue) = it.get_slot("helper queue").kind() {
+        queue.borrow_mut().push_back(promise.clone());
+    }
+    start(agent, &it)?;
+    Ok(promise)
+}
+
+fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() != "object" || !this.has_slot("helper queue") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    if let ValueKind::List(queue) = this.get_slot("helper queue").kind() {
+        queue.borrow_mut().push_back(promise.clone());
+    }
+    if this.get_slot("helper busy") == Value::from(false) {
+        start(agent, &this)?;
+    }
+    Ok(promise)
+}
+
+fn r#return(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() != "object" || !this.has_slot("helper queue") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    if let ValueKind::List(queue) = this.get_slot("helper queue").kind() {
+        queue.borrow_mut().push_back(promise.clone());
+    }
+    this.set_slot("helper returning", Value::from(true));
+    if this.get_slot("helper busy") == Value::from(false) {
+        start(agent, &this)?;
+    }
+    Ok(promise)
+}
+
+pub fn create_async_iterator_helper_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.async_iterator_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "return"),
+            Value::new_named_builtin_function(agent, r#return, "return", 0),
+        )
+        .unwrap();
+
+    proto
+}
@@ -1,9 +1,121 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, Value};
+use crate::intrinsics::async_iterator_helper_prototype::{consume_async, create_async_helper};
+use crate::intrinsics::async_split_iterator_prototype::create_split_iterator;
+use crate::intrinsics::iterator_prototype::{count_arg, function_arg};
+use crate::value::{ObjectKey, Value, ValueKind};
 
 fn iterator(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    ctx.scope.borrow().get_this(agent)
+    ctx.this(agent)
+}
+
+fn lines(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let iterated = ctx.this(agent)?.to_async_iterator(agent)?;
+    // by default invalid utf-8 is replaced, `{ fatal: true }` rejects instead.
+    let fatal = match args.get(0).map(Value::kind) {
+        Some(_) if args[0].type_of() == "object" => args[0]
+            .get(agent, ObjectKey::new(agent, "fatal"))?
+            .to_bool(),
+        None | Some(ValueKind::Null) => false,
+        _ => return Err(Value::new_error(agent, "options must be an object")),
+    };
+    Ok(create_split_iterator(agent, iterated, None, fatal))
+}
+
+fn chunks(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let iterated = ctx.this(agent)?.to_async_iterator(agent)?;
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if n >= 1.0 && n.fract() == 0.0 => Ok(create_split_iterator(
+            agent,
+            iterated,
+            Some(n as usize),
+            false,
+        )),
+        _ => Err(Value::new_error(agent, "size must be a positive integer")),
+    }
+}
+
+fn iterated(agent: &Agent, ctx: &Context) -> Result<Value, Value> {
+    ctx.this(agent)?.to_async_iterator(agent)
+}
+
+fn map(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mapper = function_arg(agent, &args, "mapper")?;
+    let iterated = iterated(agent, ctx)?;
+    Ok(create_async_helper(agent, iterated, "map", mapper, 0.0))
+}
+
+fn filter(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let predicate = function_arg(agent, &args, "predicate")?;
+    let iterated = iterated(agent, ctx)?;
+    Ok(create_async_helper(
+        agent, iterated, "filter", predicate, 0.0,
+    ))
+}
+
+fn take(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let limit = count_arg(agent, &args, "limit")?;
+    let iterated = iterated(agent, ctx)?;
+    Ok(create_async_helper(
+        agent,
+        iterated,
+        "take",
+        Value::Null,
+        limit,
+    ))
+}
+
+fn drop(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let count = count_arg(agent, &args, "count")?;
+    let iterated = iterated(agent, ctx)?;
+    Ok(create_async_helper(
+        agent,
+        iterated,
+        "drop",
+        Value::Null,
+        count,
+    ))
+}
+
+fn flat_map(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mapper = function_arg(agent, &args, "mapper")?;
+    let iterated = iterated(agent, ctx)?;
+    Ok(create_async_helper(agent, iterated, "flatMap", mapper, 0.0))
+}
+
+fn reduce(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let reducer = function_arg(agent, &args, "reducer")?;
+    let iterated = iterated(agent, ctx)?;
+    consume_async(agent, iterated, "reduce", reducer, args.get(1).cloned())
+}
+
+fn for_each(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = function_arg(agent, &args, "callback")?;
+    let iterated = iterated(agent, ctx)?;
+    consume_async(agent, iterated, "forEach", f, None)
+}
+
+fn some(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let predicate = function_arg(agent, &args, "predicate")?;
+    let iterated = iterated(agent, ctx)?;
+    consume_async(agent, iterated, "some", predicate, None)
+}
+
+fn every(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let predicate = function_arg(agent, &args, "predicate")?;
+    let iterated = iterated(agent, ctx)?;
+    consume_async(agent, iterated, "every", predicate, None)
+}
+
+fn find(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let predicate = function_arg(agent, &args, "predicate")?;
+    let iterated = iterated(agent, ctx)?;
+    consume_async(agent, iterated, "find", predicate, None)
+}
+
+fn to_array(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let iterated = iterated(agent, ctx)?;
+    consume_async(agent, iterated, "toArray", Value::Null, None)
 }
 
 pub fn create_async_iterator_prototype(agent: &Agent) -> Value {
@@ -17,5 +129,45 @@ pub fn create_async_iterator_prototype(agent: &Agent) -> Value {
         )
         .unwrap();
 
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "lines"),
+            Value::new_named_builtin_function(agent, lines, "lines", 1),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "chunks"),
+            Value::new_named_builtin_function(agent, chunks, "chunks", 1),
+        )
+        .unwrap();
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("map", map, 1);
+    method!("filter", filter, 1);
+    method!("take", take, 1);
+    method!("drop", drop, 1);
+    method!("flatMap", flat_map, 1);
+    method!("reduce", reduce, 1);
+    method!("forEach", for_each, 1);
+    method!("some", some, 1);
+    method!("every", every, 1);
+    method!("find", find, 1);
+    method!("toArray", to_array, 0);
+
     proto
 }
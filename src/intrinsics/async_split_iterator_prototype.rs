@@ -0,0 +1,245 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::promise::{new_promise_capability, promise_resolve_i};
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
+
+// An async iterator created by `lines()` or `chunks(n)` which buffers the
+// strings or buffers produced by the iterator it wraps and splits them up
+// again. Calls to `next` are answered in order, at most one pull from the
+// wrapped iterator is in flight at a time.
+
+fn with_bytes<T, F: FnOnce(&mut Vec<u8>) -> T>(it: &Value, f: F) -> T {
+    if let ValueKind::Object(o) = it.get_slot("split buffer").kind() {
+        if let ObjectKind::Buffer(b) = &o.kind {
+            return f(&mut b.borrow_mut());
+        }
+    }
+    unreachable!();
+}
+
+fn decode(agent: &Agent, it: &Value, bytes: Vec<u8>) -> Result<Value, Value> {
+    if it.get_slot("split fatal") == Value::from(true) {
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(Value::from(s)),
+            Err(_) => Err(Value::new_error(agent, "invalid utf-8")),
+        }
+    } else {
+        Ok(Value::from(String::from_utf8_lossy(&bytes).to_string()))
+    }
+}
+
+fn take_item(agent: &Agent, it: &Value) -> Result<Option<Value>, Value> {
+    let done = it.get_slot("split done") == Value::from(true);
+    match it.get_slot("split size").kind() {
+        // lines
+        ValueKind::Null => {
+            let line = with_bytes(it, |b| match b.iter().position(|c| *c == b'\n') {
+                Some(i) => {
+                    let mut line = b.drain(..=i).collect::<Vec<u8>>();
+                    line.pop();
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    Some(line)
+                }
+                None if done && !b.is_empty() => Some(b.drain(..).collect()),
+                None => None,
+            });
+            match line {
+                Some(line) => {
+                    let line = decode(agent, it, line)?;
+                    Ok(Some(Value::new_iter_result(agent, line, false)?))
+                }
+                None if done => Ok(Some(Value::new_iter_result(agent, Value::Null, true)?)),
+                None => Ok(None),
+            }
+        }
+        // chunks
+        ValueKind::Number(n) => {
+            let size = n as usize;
+            let chunk = with_bytes(it, |b| {
+                if b.len() >= size {
+                    Some(b.drain(..size).collect::<Vec<u8>>())
+                } else if done && !b.is_empty() {
+                    Some(b.drain(..).collect())
+                } else {
+                    None
+                }
+            });
+            match chunk {
+                Some(chunk) => {
+                    let chunk = Value::new_buffer_from_vec(agent, chunk);
+                    Ok(Some(Value::new_iter_result(agent, chunk, false)?))
+                }
+                None if done => Ok(Some(Value::new_iter_result(agent, Value::Null, true)?)),
+                None => Ok(None),
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn settle_front(agent: &Agent, it: &Value, result: Result<Value, Value>) -> Result<(), Value> {
+    if let ValueKind::List(queue) = it.get_slot("split queue").kind() {
+        let promise = queue.borrow_mut().pop_front().unwrap();
+        match result {
+            Ok(v) => promise
+                .get_slot("resolve")
+                .call(agent, Value::Null, vec![v])?,
+            Err(e) => promise
+                .get_slot("reject")
+                .call(agent, Value::Null, vec![e])?,
+        };
+        Ok(())
+    } else {
+        unreachable!();
+    }
+}
+
+fn pump(agent: &Agent, it: &Value) -> Result<(), Value> {
+    loop {
+        let pending = match it.get_slot("split queue").kind() {
+            ValueKind::List(queue) => !queue.borrow().is_empty(),
+            _ => unreachable!(),
+        };
+        if !pending {
+            return Ok(());
+        }
+        match take_item(agent, it) {
+            Ok(Some(result)) => settle_front(agent, it, Ok(result))?,
+            Err(e) => settle_front(agent, it, Err(e))?,
+            Ok(None) => {
+                if it.get_slot("split pulling") == Value::from(true) {
+                    return Ok(());
+                }
+                let pulled = match it.get_slot("split iterated").kind() {
+                    ValueKind::Iterator(iterator, next) => {
+                        next.call(agent, iterator.clone(), vec![])
+                    }
+                    _ => unreachable!(),
+                };
+                let promise = match pulled
+                    .and_then(|p| promise_resolve_i(agent, agent.intrinsics.promise.clone(), p))
+                {
+                    Ok(p) => p,
+                    Err(e) => {
+                        settle_front(agent, it, Err(e))?;
+                        continue;
+                    }
+                };
+                it.set_slot("split pulling", Value::from(true));
+
+                let on_fulfilled = Value::new_builtin_function(agent, on_fulfilled);
+                on_fulfilled.set_slot("split iterator", it.clone());
+                let on_rejected = Value::new_builtin_function(agent, on_rejected);
+                on_rejected.set_slot("split iterator", it.clone());
+                promise.get(agent, ObjectKey::new(agent, "then"))?.call(
+                    agent,
+                    promise,
+                    vec![on_fulfilled, on_rejected],
+                )?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn append(agent: &Agent, it: &Value, chunk: Value) -> Result<(), Value> {
+    match chunk.kind() {
+        ValueKind::String(s) => {
+            with_bytes(it, |b| b.extend_from_slice(s.as_bytes()));
+            Ok(())
+        }
+        ValueKind::Object(o) => match &o.kind {
+            ObjectKind::Buffer(chunk) => {
+                with_bytes(it, |b| b.extend_from_slice(&chunk.borrow()));
+                Ok(())
+            }
+            _ => Err(Value::new_error(agent, "chunk must be a string or buffer")),
+        },
+        _ => Err(Value::new_error(agent, "chunk must be a string or buffer")),
+    }
+}
+
+fn on_fulfilled(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = ctx.callee().get_slot("split iterator");
+    it.set_slot("split pulling", Value::from(false));
+    let result = args.get(0).cloned().unwrap_or(Value::Null);
+    let appended = if result.get(agent, ObjectKey::new(agent, "done"))? == Value::from(true) {
+        it.set_slot("split done", Value::from(true));
+        Ok(())
+    } else {
+        let value = result.get(agent, ObjectKey::new(agent, "value"))?;
+        append(agent, &it, value)
+    };
+    if let Err(e) = appended {
+        settle_front(agent, &it, Err(e))?;
+    }
+    pump(agent, &it)?;
+    Ok(Value::Null)
+}
+
+fn on_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = ctx.callee().get_slot("split iterator");
+    it.set_slot("split pulling", Value::from(false));
+    let e = args.get(0).unwrap_or(&Value::Null).clone();
+    settle_front(agent, &it, Err(e))?;
+    pump(agent, &it)?;
+    Ok(Value::Null)
+}
+
+fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("split queue") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    if let ValueKind::List(queue) = this.get_slot("split queue").kind() {
+        queue.borrow_mut().push_back(promise.clone());
+    }
+    pump(agent, &this)?;
+    Ok(promise)
+}
+
+/// Create a split iterator over `iterated`. `size` is the chunk size, or
+/// `None` to split on lines.
+pub fn create_split_iterator(
+    agent: &Agent,
+    iterated: Value,
+    size: Option<usize>,
+    fatal: bool,
+) -> Value {
+    let it = Value::new_custom_object(agent.intrinsics.async_split_iterator_prototype.clone());
+    it.set_slot("split iterated", iterated);
+    it.set_slot("split queue", Value::new_list());
+    it.set_slot(
+        "split buffer",
+        Value::new_buffer_from_vec(agent, Vec::new()),
+    );
+    it.set_slot(
+        "split size",
+        match size {
+            Some(n) => Value::from(n as f64),
+            None => Value::Null,
+        },
+    );
+    it.set_slot("split fatal", Value::from(fatal));
+    it.set_slot("split done", Value::from(false));
+    it.set_slot("split pulling", Value::from(false));
+    it
+}
+
+pub fn create_async_split_iterator_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.async_iterator_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+
+    proto
+}
@@ -0,0 +1,127 @@
+//! Integers of any size, written `42n`. Arithmetic only combines a bigint
+//! with another bigint, mixing one with a number throws rather than losing
+//! precision either way; `BigInt(n)` converts a number explicitly.
+
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
+use num::{BigInt, FromPrimitive, Signed, ToPrimitive, Zero};
+use std::rc::Rc;
+
+pub fn mixed_error(agent: &Agent) -> Value {
+    Value::new_error(agent, "cannot mix bigint and other types")
+}
+
+// the bitwise operators and shifts only work on numbers.
+pub fn unsupported(agent: &Agent, _a: &BigInt, _b: &BigInt) -> Result<Value, Value> {
+    Err(Value::new_error(agent, "operator does not support bigints"))
+}
+
+fn value(n: BigInt) -> Result<Value, Value> {
+    Ok(Value::BigInt(Rc::new(n)))
+}
+
+pub fn add(_agent: &Agent, a: &BigInt, b: &BigInt) -> Result<Value, Value> {
+    value(a + b)
+}
+
+pub fn sub(_agent: &Agent, a: &BigInt, b: &BigInt) -> Result<Value, Value> {
+    value(a - b)
+}
+
+pub fn mul(_agent: &Agent, a: &BigInt, b: &BigInt) -> Result<Value, Value> {
+    value(a * b)
+}
+
+// division truncates towards zero, and the remainder takes the sign of the
+// dividend.
+pub fn div(agent: &Agent, a: &BigInt, b: &BigInt) -> Result<Value, Value> {
+    if b.is_zero() {
+        return Err(Value::new_error(agent, "division by zero"));
+    }
+    value(a / b)
+}
+
+pub fn rem(agent: &Agent, a: &BigInt, b: &BigInt) -> Result<Value, Value> {
+    if b.is_zero() {
+        return Err(Value::new_error(agent, "division by zero"));
+    }
+    value(a % b)
+}
+
+pub fn pow(agent: &Agent, a: &BigInt, b: &BigInt) -> Result<Value, Value> {
+    if b.is_negative() {
+        return Err(Value::new_error(agent, "exponent must not be negative"));
+    }
+    match b.to_usize() {
+        Some(e) => value(num::pow(a.clone(), e)),
+        None => Err(Value::new_error(agent, "exponent is too large")),
+    }
+}
+
+fn bigint(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let null = Value::Null;
+    let arg = args.get(0).unwrap_or(&null);
+    match arg.kind() {
+        ValueKind::BigInt(n) => Ok(Value::BigInt(n.clone())),
+        ValueKind::Number(n) if n.fract() == 0.0 => match BigInt::from_f64(n) {
+            Some(n) => value(n),
+            None => Err(Value::new_error(agent, "cannot convert number to bigint")),
+        },
+        ValueKind::Number(..) => Err(Value::new_error(
+            agent,
+            "cannot convert a non-integer to a bigint",
+        )),
+        ValueKind::Boolean(b) => value(BigInt::from(b as u8)),
+        ValueKind::String(s) => match BigInt::parse_bytes(s.trim().as_bytes(), 10) {
+            Some(n) => value(n),
+            None => Err(Value::new_error(agent, "cannot convert string to bigint")),
+        },
+        _ => Err(Value::new_error(
+            agent,
+            &format!("cannot convert {} to bigint", arg.type_of()),
+        )),
+    }
+}
+
+fn to_string(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let n = match ctx.this(agent)?.kind() {
+        ValueKind::BigInt(n) => n.clone(),
+        ValueKind::Object(o) => match &o.kind {
+            ObjectKind::BigInt(n) => n.clone(),
+            _ => return Err(Value::new_error(agent, "invalid receiver")),
+        },
+        _ => return Err(Value::new_error(agent, "invalid receiver")),
+    };
+    let radix = match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(r)) if (2.0..=36.0).contains(&r) && r.fract() == 0.0 => r as u32,
+        None | Some(ValueKind::Null) => 10,
+        _ => return Err(Value::new_error(agent, "radix must be between 2 and 36")),
+    };
+    Ok(Value::from(n.to_str_radix(radix)))
+}
+
+pub fn create_bigint_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    let f = Value::new_named_builtin_function(agent, to_string, "toString", 0);
+    proto
+        .set(agent, ObjectKey::well_known_symbol("toString"), f.clone())
+        .unwrap();
+    proto
+        .set(agent, ObjectKey::new(agent, "toString"), f)
+        .unwrap();
+
+    proto
+}
+
+pub fn create_bigint(agent: &Agent) -> Value {
+    let f = Value::new_named_builtin_function(agent, bigint, "BigInt", 1);
+    f.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.bigint_prototype.clone(),
+    )
+    .unwrap();
+    f
+}
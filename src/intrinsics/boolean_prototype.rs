@@ -1,10 +1,10 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, ObjectKind, Value};
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
 
 fn to_string(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    match ctx.scope.borrow().get_this(agent)? {
-        Value::Object(o) => match o.kind {
+    match ctx.this(agent)?.kind() {
+        ValueKind::Object(o) => match o.kind {
             ObjectKind::Boolean(b) => Ok(Value::from(b.to_string())),
             _ => Err(Value::new_error(agent, "invalid receiver")),
         },
@@ -19,7 +19,7 @@ pub fn create_boolean_prototype(agent: &Agent) -> Value {
         .set(
             agent,
             ObjectKey::well_known_symbol("toString"),
-            Value::new_builtin_function(agent, to_string),
+            Value::new_named_builtin_function(agent, to_string, "toString", 0),
         )
         .unwrap();
 
@@ -0,0 +1,106 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::bytes_prototype::{decode_base64, decode_hex, to_bytes, zeroed_bytes};
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
+
+fn bytes(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => Ok(Value::new_buffer_from_vec(agent, Vec::new())),
+        Some(ValueKind::String(s)) => Ok(Value::new_buffer_from_vec(agent, s.as_bytes().to_vec())),
+        Some(ValueKind::Object(o)) => match &o.kind {
+            ObjectKind::Buffer(b) => Ok(Value::new_buffer_from_vec(agent, b.borrow().clone())),
+            ObjectKind::Array(values) => {
+                let mut bytes = Vec::with_capacity(values.borrow().len());
+                for v in values.borrow().iter() {
+                    match v.kind() {
+                        ValueKind::Number(n) if (0.0..=255.0).contains(&n) && n.fract() == 0.0 => {
+                            bytes.push(n as u8)
+                        }
+                        _ => return Err(Value::new_error(agent, "byte values must be 0-255")),
+                    }
+                }
+                Ok(Value::new_buffer_from_vec(agent, bytes))
+            }
+            _ => Err(Value::new_error(agent, "invalid bytes source")),
+        },
+        _ => Err(Value::new_error(agent, "invalid bytes source")),
+    }
+}
+
+fn string_arg<'a>(agent: &Agent, args: &'a [Value]) -> Result<&'a str, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => Ok(s),
+        _ => Err(Value::new_error(agent, "argument must be a string")),
+    }
+}
+
+fn from_string(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let s = string_arg(agent, &args)?;
+    Ok(Value::new_buffer_from_vec(agent, s.as_bytes().to_vec()))
+}
+
+fn from_hex(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match decode_hex(string_arg(agent, &args)?) {
+        Some(b) => Ok(Value::new_buffer_from_vec(agent, b)),
+        None => Err(Value::new_error(agent, "invalid hex string")),
+    }
+}
+
+fn from_base64(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match decode_base64(string_arg(agent, &args)?) {
+        Some(b) => Ok(Value::new_buffer_from_vec(agent, b)),
+        None => Err(Value::new_error(agent, "invalid base64 string")),
+    }
+}
+
+fn alloc(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => {
+            let bytes = zeroed_bytes(agent, n as usize)?;
+            Ok(Value::new_buffer_from_vec(agent, bytes))
+        }
+        _ => Err(Value::new_error(
+            agent,
+            "size must be a non-negative integer",
+        )),
+    }
+}
+
+fn is_bytes(_agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    Ok(Value::from(args.get(0).and_then(to_bytes).is_some()))
+}
+
+pub fn create_bytes(agent: &Agent) -> Value {
+    let b = Value::new_named_builtin_function(agent, bytes, "Bytes", 1);
+
+    b.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.bytes_prototype.clone(),
+    )
+    .expect("failed to set prototype on bytes constructor");
+    agent
+        .intrinsics
+        .bytes_prototype
+        .set(agent, ObjectKey::new(agent, "constructor"), b.clone())
+        .expect("failed to set constructor on bytes prototype");
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            b.set(
+                agent,
+                ObjectKey::new(agent, $name),
+                Value::new_named_builtin_function(agent, $fn, $name, $length),
+            )
+            .expect(concat!("failed to set ", $name, " on bytes constructor"));
+        };
+    }
+
+    method!("fromString", from_string, 1);
+    method!("fromHex", from_hex, 1);
+    method!("fromBase64", from_base64, 1);
+    method!("alloc", alloc, 1);
+    method!("isBytes", is_bytes, 1);
+
+    b
+}
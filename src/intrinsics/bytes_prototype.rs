@@ -0,0 +1,219 @@
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, ObjectKind, ValueKind};
+use crate::{Agent, Value};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        s += &format!("{:02x}", b);
+    }
+    s
+}
+
+pub fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(s.len() / 2);
+    let chars = s.as_bytes();
+    for pair in chars.chunks(2) {
+        let hi = (pair[0] as char).to_digit(16)?;
+        let lo = (pair[1] as char).to_digit(16)?;
+        bytes.push((hi * 16 + lo) as u8);
+    }
+    Some(bytes)
+}
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (u32::from(chunk[0]) << 16)
+            | (u32::from(*chunk.get(1).unwrap_or(&0)) << 8)
+            | u32::from(*chunk.get(2).unwrap_or(&0));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(BASE64[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+pub fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let v = BASE64.iter().position(|b| *b == c)? as u32;
+        n = (n << 6) | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Copy the contents of a Bytes value, or None if `value` is not one.
+pub fn to_bytes(value: &Value) -> Option<Vec<u8>> {
+    if let ValueKind::Object(o) = value.kind() {
+        if let ObjectKind::Buffer(b) = &o.kind {
+            return Some(b.borrow().clone());
+        }
+    }
+    None
+}
+
+fn this_bytes(agent: &Agent, ctx: &Context) -> Result<Vec<u8>, Value> {
+    match to_bytes(&ctx.this(agent)?) {
+        Some(b) => Ok(b),
+        None => Err(Value::new_error(agent, "invalid receiver")),
+    }
+}
+
+// Resolve a relative index the way slice() does, negative counts from the end.
+pub fn relative_index(
+    agent: &Agent,
+    value: Option<&Value>,
+    len: usize,
+    default: usize,
+) -> Result<usize, Value> {
+    match value.map(Value::kind) {
+        None | Some(ValueKind::Null) => Ok(default),
+        Some(ValueKind::Number(n)) if n.fract() == 0.0 => {
+            if n < 0.0 {
+                Ok((len as f64 + n).max(0.0) as usize)
+            } else {
+                Ok(n.min(len as f64) as usize)
+            }
+        }
+        _ => Err(Value::new_error(agent, "index must be an integer")),
+    }
+}
+
+/// The most bytes a script can ask a buffer to be created with.
+pub const MAX_BYTE_LENGTH: usize = 1 << 30;
+
+/// `length` zeroed bytes, or a RangeError if there are more than
+/// `MAX_BYTE_LENGTH` or they can't be allocated, rather than the allocation
+/// failing and aborting the process.
+pub fn zeroed_bytes(agent: &Agent, length: usize) -> Result<Vec<u8>, Value> {
+    let mut bytes = Vec::new();
+    let message = if length > MAX_BYTE_LENGTH {
+        format!("size must be at most {}", MAX_BYTE_LENGTH)
+    } else if bytes.try_reserve_exact(length).is_err() {
+        format!("cannot allocate {} bytes", length)
+    } else {
+        bytes.resize(length, 0);
+        return Ok(bytes);
+    };
+    let e = Value::new_error(agent, &message);
+    e.set(agent, ObjectKey::NAME, Value::from("RangeError"))?;
+    Err(e)
+}
+
+fn slice(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let bytes = this_bytes(agent, ctx)?;
+    let start = relative_index(agent, args.get(0), bytes.len(), 0)?;
+    let end = relative_index(agent, args.get(1), bytes.len(), bytes.len())?;
+    let slice = if start < end {
+        bytes[start..end].to_vec()
+    } else {
+        Vec::new()
+    };
+    Ok(Value::new_buffer_from_vec(agent, slice))
+}
+
+fn to_string(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let bytes = this_bytes(agent, ctx)?;
+    let encoding = match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => s.as_str(),
+        None | Some(ValueKind::Null) => "utf-8",
+        _ => return Err(Value::new_error(agent, "encoding must be a string")),
+    };
+    match encoding {
+        "utf-8" | "utf8" => Ok(Value::from(String::from_utf8_lossy(&bytes).to_string())),
+        "hex" => Ok(Value::from(encode_hex(&bytes))),
+        "base64" => Ok(Value::from(encode_base64(&bytes))),
+        _ => Err(Value::new_error(agent, "unknown encoding")),
+    }
+}
+
+fn index_of(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let bytes = this_bytes(agent, ctx)?;
+    let needle = match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if (0.0..=255.0).contains(&n) && n.fract() == 0.0 => {
+            vec![n as u8]
+        }
+        Some(ValueKind::String(s)) => s.as_bytes().to_vec(),
+        _ => match args.get(0).and_then(to_bytes) {
+            Some(b) => b,
+            None => return Err(Value::new_error(agent, "invalid search value")),
+        },
+    };
+    let from = relative_index(agent, args.get(1), bytes.len(), 0)?;
+    if needle.is_empty() {
+        return Ok(Value::from(from as f64));
+    }
+    if bytes.len() >= needle.len() {
+        for i in from..=(bytes.len() - needle.len()) {
+            if bytes[i..i + needle.len()] == needle[..] {
+                return Ok(Value::from(i as f64));
+            }
+        }
+    }
+    Ok(Value::from(-1.0))
+}
+
+fn concat(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mut bytes = this_bytes(agent, ctx)?;
+    for arg in &args {
+        match arg.kind() {
+            ValueKind::String(s) => bytes.extend_from_slice(s.as_bytes()),
+            _ => match to_bytes(arg) {
+                Some(b) => bytes.extend(b),
+                None => return Err(Value::new_error(agent, "value must be a string or bytes")),
+            },
+        }
+    }
+    Ok(Value::new_buffer_from_vec(agent, bytes))
+}
+
+fn equals(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let bytes = this_bytes(agent, ctx)?;
+    match args.get(0).and_then(to_bytes) {
+        Some(other) => Ok(Value::from(bytes == other)),
+        None => Ok(Value::from(false)),
+    }
+}
+
+pub fn create_bytes_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.array_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("slice", slice, 2);
+    method!("toString", to_string, 1);
+    method!("indexOf", index_of, 2);
+    method!("concat", concat, 0);
+    method!("equals", equals, 1);
+
+    proto
+}
@@ -0,0 +1,115 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, Value, ValueKind};
+use lazy_static::lazy_static;
+use std::time::Instant;
+
+lazy_static! {
+    static ref START: Instant = Instant::now();
+}
+
+fn format_args(agent: &Agent, args: Vec<Value>) -> String {
+    let mut strings = Vec::with_capacity(args.len());
+    for v in args {
+        strings.push(match v.kind() {
+            ValueKind::String(s) => s.clone(),
+            _ => Value::inspect(agent, &v),
+        });
+    }
+    format!("{}\n", strings.join(" "))
+}
+
+fn log(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    agent.output(&format_args(agent, args));
+    Ok(Value::Null)
+}
+
+fn error(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    agent.output_error(&format_args(agent, args));
+    Ok(Value::Null)
+}
+
+fn dir(agent: &Agent, args: Vec<Value>, _: &Context) -> Result<Value, Value> {
+    let value = args.get(0).cloned().unwrap_or(Value::Null);
+    agent.output(&format!("{}\n", Value::inspect(agent, &value)));
+    Ok(Value::Null)
+}
+
+fn elapsed_ms() -> f64 {
+    let d = START.elapsed();
+    d.as_secs() as f64 * 1000.0 + f64::from(d.subsec_nanos()) / 1_000_000.0
+}
+
+fn label(agent: &Agent, args: &[Value]) -> Result<String, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => Ok(s.clone()),
+        None | Some(ValueKind::Null) => Ok("default".to_string()),
+        _ => Err(Value::new_error(agent, "label must be a string")),
+    }
+}
+
+fn time(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("console timers") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    let label = label(agent, &args)?;
+    this.get_slot("console timers").set(
+        agent,
+        ObjectKey::new(agent, &label),
+        Value::from(elapsed_ms()),
+    )?;
+    Ok(Value::Null)
+}
+
+fn time_end(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("console timers") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    let label = label(agent, &args)?;
+    let timers = this.get_slot("console timers");
+    match timers
+        .get(agent, ObjectKey::new(agent, label.as_str()))?
+        .kind()
+    {
+        ValueKind::Number(start) => {
+            timers.set(agent, ObjectKey::new(agent, label.as_str()), Value::Null)?;
+            agent.output(&format!("{}: {}ms\n", label, elapsed_ms() - start));
+        }
+        _ => agent.output_error(&format!("Timer '{}' does not exist\n", label)),
+    }
+    Ok(Value::Null)
+}
+
+pub fn create_console(agent: &Agent) -> Value {
+    let console = Value::new_custom_object(agent.intrinsics.object_prototype.clone());
+    console.set_slot(
+        "console timers",
+        Value::new_object(agent.intrinsics.object_prototype.clone()),
+    );
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            console
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("log", log, 0);
+    method!("info", log, 0);
+    method!("error", error, 0);
+    method!("warn", error, 0);
+    method!("dir", dir, 1);
+    method!("time", time, 0);
+    method!("timeEnd", time_end, 0);
+
+    console
+}
@@ -0,0 +1,290 @@
+//! A double ended queue for scripts, stored as the same `Value::List` the
+//! runtime uses for its own queues. Pushing and popping at either end is
+//! O(1) (amortized for pushes), unlike inserting at the front of an array
+//! which moves every element.
+
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::iterator_prototype::step;
+use crate::value::{ObjectKey, Value, ValueKind};
+use gc::{GcCell, Trace};
+use std::collections::VecDeque;
+
+fn this_deque(agent: &Agent, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() == "object" && this.has_slot("deque items") {
+        Ok(this)
+    } else {
+        Err(Value::new_error(agent, "invalid receiver"))
+    }
+}
+
+// every push and pop bumps the version, which lets iterators notice that
+// the deque changed underneath them.
+fn modified(deque: &Value) {
+    if let ValueKind::Number(n) = deque.get_slot("deque version").kind() {
+        deque.set_slot("deque version", Value::from(n + 1.0));
+    }
+}
+
+// `borrow_mut` roots and unroots every item in the list, which would make
+// each push and pop as slow as the deque is long. Nothing here allocates, so
+// the items can stay unrooted while they are moved in and out instead.
+fn push_items(list: &GcCell<VecDeque<Value>>, args: Vec<Value>, front: bool) -> usize {
+    unsafe {
+        list.with_unrooted_mut(|items, rooted| {
+            for arg in args {
+                if !rooted {
+                    arg.unroot();
+                }
+                if front {
+                    items.push_front(arg);
+                } else {
+                    items.push_back(arg);
+                }
+            }
+            items.len()
+        })
+    }
+}
+
+fn pop_item(list: &GcCell<VecDeque<Value>>, front: bool) -> Option<Value> {
+    unsafe {
+        list.with_unrooted_mut(|items, rooted| {
+            let item = if front {
+                items.pop_front()
+            } else {
+                items.pop_back()
+            };
+            if let Some(item) = &item {
+                if !rooted {
+                    item.root();
+                }
+            }
+            item
+        })
+    }
+}
+
+fn new_deque(agent: &Agent, items: Option<&Value>) -> Result<Value, Value> {
+    let list = match items {
+        Some(items) if *items != Value::Null => {
+            let iterator = items.to_iterator(agent)?;
+            let mut list = VecDeque::new();
+            while let Some(item) = step(agent, &iterator)? {
+                list.push_back(item);
+            }
+            Value::new_list_from_iter(list)
+        }
+        _ => Value::new_list(),
+    };
+    let deque = Value::new_custom_object(agent.intrinsics.deque_prototype.clone());
+    deque.set_slot("deque items", list);
+    deque.set_slot("deque version", Value::from(0));
+    Ok(deque)
+}
+
+fn deque(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    new_deque(agent, args.get(0))
+}
+
+fn from(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match args.get(0) {
+        Some(items) if *items != Value::Null => new_deque(agent, Some(items)),
+        _ => Err(Value::new_error(agent, "argument must be iterable")),
+    }
+}
+
+macro_rules! push {
+    ($name:ident, $front:expr) => {
+        fn $name(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+            let deque = this_deque(agent, ctx)?;
+            let length = if let ValueKind::List(list) = deque.get_slot("deque items").kind() {
+                push_items(&list, args, $front)
+            } else {
+                unreachable!();
+            };
+            modified(&deque);
+            Ok(Value::from(length as f64))
+        }
+    };
+}
+
+macro_rules! pop {
+    ($name:ident, $front:expr) => {
+        fn $name(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+            let deque = this_deque(agent, ctx)?;
+            let value = if let ValueKind::List(list) = deque.get_slot("deque items").kind() {
+                pop_item(&list, $front)
+            } else {
+                unreachable!();
+            };
+            match value {
+                Some(value) => {
+                    modified(&deque);
+                    Ok(value)
+                }
+                None => Ok(Value::Null),
+            }
+        }
+    };
+}
+
+macro_rules! peek {
+    ($name:ident, $method:ident) => {
+        fn $name(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+            let deque = this_deque(agent, ctx)?;
+            if let ValueKind::List(list) = deque.get_slot("deque items").kind() {
+                Ok(list.borrow().$method().cloned().unwrap_or(Value::Null))
+            } else {
+                unreachable!();
+            }
+        }
+    };
+}
+
+push!(push_back, false);
+push!(push_front, true);
+pop!(pop_back, false);
+pop!(pop_front, true);
+peek!(peek_back, back);
+peek!(peek_front, front);
+
+fn size(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let deque = this_deque(agent, ctx)?;
+    if let ValueKind::List(list) = deque.get_slot("deque items").kind() {
+        Ok(Value::from(list.borrow().len() as f64))
+    } else {
+        unreachable!();
+    }
+}
+
+fn to_array(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let deque = this_deque(agent, ctx)?;
+    if let ValueKind::List(list) = deque.get_slot("deque items").kind() {
+        let items = list.borrow().iter().cloned().collect();
+        Ok(Value::new_array_from_vec(agent, items))
+    } else {
+        unreachable!();
+    }
+}
+
+fn iterator(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let deque = this_deque(agent, ctx)?;
+    let it = Value::new_custom_object(agent.intrinsics.deque_iterator_prototype.clone());
+    it.set_slot("deque iterator version", deque.get_slot("deque version"));
+    it.set_slot("deque iterator next index", Value::from(0));
+    it.set_slot("iterated object", deque);
+    Ok(it)
+}
+
+// iterating from front to back, pushing or popping before the iterator is
+// done makes the next step throw rather than skip or repeat items.
+fn next(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = ctx.this(agent)?;
+    if it.type_of() != "object" || !it.has_slot("deque iterator next index") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    let deque = it.get_slot("iterated object");
+    if deque == Value::Null {
+        return Value::new_iter_result(agent, Value::Null, true);
+    }
+    if deque.get_slot("deque version") != it.get_slot("deque iterator version") {
+        it.set_slot("iterated object", Value::Null);
+        return Err(Value::new_error(
+            agent,
+            "deque was modified during iteration",
+        ));
+    }
+    let index = if let ValueKind::Number(n) = it.get_slot("deque iterator next index").kind() {
+        n as usize
+    } else {
+        unreachable!();
+    };
+    let value = if let ValueKind::List(list) = deque.get_slot("deque items").kind() {
+        list.borrow().get(index).cloned()
+    } else {
+        unreachable!();
+    };
+    match value {
+        Some(value) => {
+            it.set_slot("deque iterator next index", Value::from((index + 1) as f64));
+            Value::new_iter_result(agent, value, false)
+        }
+        None => {
+            it.set_slot("iterated object", Value::Null);
+            Value::new_iter_result(agent, Value::Null, true)
+        }
+    }
+}
+
+pub fn create_deque_iterator_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.iterator_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+
+    proto
+}
+
+pub fn create_deque_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("pushBack", push_back, 1);
+    method!("pushFront", push_front, 1);
+    method!("popBack", pop_back, 0);
+    method!("popFront", pop_front, 0);
+    method!("peekBack", peek_back, 0);
+    method!("peekFront", peek_front, 0);
+    method!("size", size, 0);
+    method!("toArray", to_array, 0);
+
+    proto
+        .set(
+            agent,
+            ObjectKey::well_known_symbol("iterator"),
+            Value::new_builtin_function(agent, iterator),
+        )
+        .unwrap();
+
+    proto
+}
+
+pub fn create_deque(agent: &Agent) -> Value {
+    let c = Value::new_named_builtin_function(agent, deque, "Deque", 0);
+    c.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.deque_prototype.clone(),
+    )
+    .unwrap();
+    c.set(
+        agent,
+        ObjectKey::new(agent, "from"),
+        Value::new_named_builtin_function(agent, from, "from", 1),
+    )
+    .unwrap();
+    agent
+        .intrinsics
+        .deque_prototype
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
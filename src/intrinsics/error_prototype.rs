@@ -1,17 +1,17 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, Value};
+use crate::value::{ObjectKey, Value, ValueKind};
 
 fn to_string(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
 
-    let name = match this.get(agent, ObjectKey::from("name"))? {
-        Value::String(s) => s,
+    let name = match this.get(agent, ObjectKey::NAME)?.kind() {
+        ValueKind::String(s) => s.clone(),
         _ => return Err(Value::new_error(agent, "Invalid error object")),
     };
-    let message = match this.get(agent, ObjectKey::from("message"))? {
-        Value::String(s) => format!(": {}", s),
-        Value::Null => "".to_string(),
+    let message = match this.get(agent, ObjectKey::new(agent, "message"))?.kind() {
+        ValueKind::String(s) => format!(": {}", s),
+        ValueKind::Null => "".to_string(),
         _ => return Err(Value::new_error(agent, "Invalid error object")),
     };
 
@@ -25,12 +25,12 @@ pub fn create_error_prototype(agent: &Agent) -> Value {
         .set(
             agent,
             ObjectKey::well_known_symbol("toString"),
-            Value::new_builtin_function(agent, to_string),
+            Value::new_named_builtin_function(agent, to_string, "toString", 0),
         )
         .unwrap();
 
     proto
-        .set(agent, ObjectKey::from("name"), Value::from("Error"))
+        .set(agent, ObjectKey::NAME, Value::from("Error"))
         .unwrap();
 
     proto
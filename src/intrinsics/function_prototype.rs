@@ -1,11 +1,52 @@
 use crate::interpreter::Context;
-use crate::value::ObjectKey;
+use crate::value::{ObjectKey, ValueKind};
 use crate::{Agent, Value};
 
+fn bound_function(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = ctx.callee();
+    let target = f.get_slot("bound target");
+    let mut all = match f.get_slot("bound arguments").kind() {
+        ValueKind::Tuple(bound) => bound.clone(),
+        _ => unreachable!(),
+    };
+    all.extend(args);
+    target.call(agent, f.get_slot("bound this"), all)
+}
+
+fn bind(agent: &Agent, mut args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let target = ctx.this(agent)?;
+    if target.type_of() != "function" {
+        return Err(Value::new_error(agent, "this must be a function"));
+    }
+    let this = if args.is_empty() {
+        Value::Null
+    } else {
+        args.remove(0)
+    };
+    let name = match target.get(agent, ObjectKey::NAME)?.kind() {
+        ValueKind::String(s) => s.clone(),
+        _ => String::new(),
+    };
+    let length = match target.get(agent, ObjectKey::LENGTH)?.kind() {
+        ValueKind::Number(n) if n > args.len() as f64 => n as usize - args.len(),
+        _ => 0,
+    };
+    let f = Value::new_named_builtin_function(
+        agent,
+        bound_function,
+        &format!("bound {}", name),
+        length,
+    );
+    f.set_slot("bound target", target);
+    f.set_slot("bound this", this);
+    f.set_slot("bound arguments", Value::Tuple(args));
+    Ok(f)
+}
+
 // TODO: figure out how to make this a tail call
 fn call(agent: &Agent, mut args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
     let this = args.remove(0);
-    ctx.scope.borrow().get_this(agent)?.call(agent, this, args)
+    ctx.this(agent)?.call(agent, this, args)
 }
 
 pub fn create_function_prototype(agent: &mut Agent) {
@@ -15,8 +56,16 @@ pub fn create_function_prototype(agent: &mut Agent) {
     proto
         .set(
             agent,
-            ObjectKey::from("call"),
-            Value::new_builtin_function(agent, call),
+            ObjectKey::new(agent, "call"),
+            Value::new_named_builtin_function(agent, call, "call", 1),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "bind"),
+            Value::new_named_builtin_function(agent, bind, "bind", 1),
         )
         .unwrap();
 }
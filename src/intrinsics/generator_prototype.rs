@@ -1,16 +1,25 @@
 use crate::agent::Agent;
-use crate::interpreter::Context;
-use crate::value::{ObjectKey, Value};
+use crate::interpreter::{Context, Interpreter};
+use crate::value::{ObjectKey, Value, ValueKind};
 
-fn next(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
-    if let Value::WrappedContext(context, _) = this.get_slot("generator context") {
-        let mut args = args;
+// resumes the generator with the completion set up by `f`. `finished` is
+// the result when the generator has already completed.
+fn resume<F>(
+    agent: &Agent,
+    ctx: &Context,
+    f: F,
+    finished: Result<Value, Value>,
+) -> Result<Value, Value>
+where
+    F: FnOnce(&mut Interpreter),
+{
+    let this = ctx.this(agent)?;
+    if let ValueKind::WrappedContext(context, _) = this.get_slot("generator context").kind() {
         if context.borrow_mut().interpreter.is_none() {
-            Value::new_iter_result(agent, Value::Null, true)
+            finished
         } else {
             let mut interpreter = context.borrow_mut().interpreter.take().unwrap();
-            interpreter.accumulator = args.pop().unwrap_or(Value::Null);
+            f(&mut interpreter);
             match interpreter.run(agent) {
                 Ok(r) => match r {
                     Ok(v) => Value::new_iter_result(agent, v, true),
@@ -29,31 +38,33 @@ fn next(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value>
     }
 }
 
+fn next(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mut args = args;
+    let value = args.pop().unwrap_or(Value::Null);
+    let finished = Value::new_iter_result(agent, Value::Null, true);
+    resume(agent, ctx, |i| i.accumulator = value, finished)
+}
+
+fn r#return(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mut args = args;
+    let value = args.pop().unwrap_or(Value::Null);
+    let finished = Value::new_iter_result(agent, value.clone(), true);
+    resume(
+        agent,
+        ctx,
+        |i| {
+            i.exception = Some(value);
+            i.returning = true;
+        },
+        finished,
+    )
+}
+
 fn throw(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
-    if let Value::WrappedContext(context, _) = this.get_slot("generator context") {
-        let mut args = args;
-        if context.borrow_mut().interpreter.is_none() {
-            Value::new_iter_result(agent, Value::Null, true)
-        } else {
-            let mut interpreter = context.borrow_mut().interpreter.take().unwrap();
-            interpreter.exception = Some(args.pop().unwrap_or(Value::Null));
-            match interpreter.run(agent) {
-                Ok(r) => match r {
-                    Ok(v) => Value::new_iter_result(agent, v, true),
-                    Err(e) => Err(e),
-                },
-                Err(c) => {
-                    context.borrow_mut().interpreter = Some(interpreter);
-                    let mut c = c;
-                    let value = std::mem::replace(&mut c.0, Value::Null);
-                    Value::new_iter_result(agent, value, false)
-                }
-            }
-        }
-    } else {
-        unreachable!();
-    }
+    let mut args = args;
+    let error = args.pop().unwrap_or(Value::Null);
+    let finished = Err(error.clone());
+    resume(agent, ctx, |i| i.exception = Some(error), finished)
 }
 
 pub fn create_generator_prototype(agent: &Agent) -> Value {
@@ -62,16 +73,24 @@ pub fn create_generator_prototype(agent: &Agent) -> Value {
     proto
         .set(
             agent,
-            ObjectKey::from("next"),
-            Value::new_builtin_function(agent, next),
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 1),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "return"),
+            Value::new_named_builtin_function(agent, r#return, "return", 1),
         )
         .unwrap();
 
     proto
         .set(
             agent,
-            ObjectKey::from("throw"),
-            Value::new_builtin_function(agent, throw),
+            ObjectKey::new(agent, "throw"),
+            Value::new_named_builtin_function(agent, throw, "throw", 1),
         )
         .unwrap();
 
@@ -0,0 +1,257 @@
+use crate::agent::Agent;
+use crate::builtins::{process, timers};
+use crate::interpreter::Context;
+use crate::intrinsics::typed_array::TypedArrayKind;
+use crate::intrinsics::{
+    create_abort_controller, create_array_buffer, create_bigint, create_console, create_data_view,
+    create_deque, create_object, create_performance, create_proxy, create_reflect,
+    create_text_decoder, create_text_encoder, create_typed_array, create_url,
+    create_url_search_params,
+};
+use crate::value::{data_clone_error, CloneData, ObjectKey, ObjectKind, Value, ValueKind};
+
+// values other than numbers are not NaN, and only bigints among them are
+// finite.
+fn is_nan(_agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) => Ok(Value::from(n.is_nan())),
+        _ => Ok(Value::from(false)),
+    }
+}
+
+fn is_finite(_agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) => Ok(Value::from(n.is_finite())),
+        Some(ValueKind::BigInt(..)) => Ok(Value::from(true)),
+        _ => Ok(Value::from(false)),
+    }
+}
+
+fn parse_int(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (s,) = check_args!(agent, args, "parseInt", [string]);
+    let s = s.trim();
+    let mut radix = match args.get(1).map(Value::kind) {
+        Some(ValueKind::Number(n)) if (2.0..=36.0).contains(&n) && n.fract() == 0.0 => {
+            Some(n as u32)
+        }
+        None | Some(ValueKind::Null) => None,
+        _ => return Err(Value::new_error(agent, "radix must be between 2 and 36")),
+    };
+
+    let (negative, s) = if s.starts_with('-') {
+        (true, &s[1..])
+    } else if s.starts_with('+') {
+        (false, &s[1..])
+    } else {
+        (false, s)
+    };
+
+    let s = match radix {
+        None | Some(16) if s.starts_with("0x") || s.starts_with("0X") => {
+            radix = Some(16);
+            &s[2..]
+        }
+        _ => s,
+    };
+    let radix = radix.unwrap_or(10);
+
+    let mut result = None;
+    for c in s.chars() {
+        match c.to_digit(radix) {
+            Some(d) => result = Some(result.unwrap_or(0f64) * f64::from(radix) + f64::from(d)),
+            None => break,
+        }
+    }
+
+    match result {
+        Some(n) if negative => Ok(Value::from(-n)),
+        Some(n) => Ok(Value::from(n)),
+        None => Ok(Value::from(std::f64::NAN)),
+    }
+}
+
+fn parse_float(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (s,) = check_args!(agent, args, "parseFloat", [string]);
+    let s = s.trim();
+    let bytes = s.as_bytes();
+
+    let mut end = 0;
+    if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+        end += 1;
+    }
+    if s[end..].starts_with("Infinity") {
+        let n = if bytes[0] == b'-' {
+            std::f64::NEG_INFINITY
+        } else {
+            std::f64::INFINITY
+        };
+        return Ok(Value::from(n));
+    }
+
+    let digits_start = end;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
+    }
+    if end < bytes.len() && bytes[end] == b'.' {
+        end += 1;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+    }
+    if end == digits_start || &s[digits_start..end] == "." {
+        return Ok(Value::from(std::f64::NAN));
+    }
+    // only consume an exponent if it actually has digits
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exp_end = end + 1;
+        if exp_end < bytes.len() && (bytes[exp_end] == b'+' || bytes[exp_end] == b'-') {
+            exp_end += 1;
+        }
+        if exp_end < bytes.len() && bytes[exp_end].is_ascii_digit() {
+            while exp_end < bytes.len() && bytes[exp_end].is_ascii_digit() {
+                exp_end += 1;
+            }
+            end = exp_end;
+        }
+    }
+
+    match s[..end].parse::<f64>() {
+        Ok(n) => Ok(Value::from(n)),
+        Err(_) => Ok(Value::from(std::f64::NAN)),
+    }
+}
+
+fn call_microtask_job(agent: &Agent, args: Vec<Value>) -> Result<(), Value> {
+    args[0].call(agent, Value::Null, Vec::new())?;
+    Ok(())
+}
+
+fn queue_microtask(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (f,) = check_args!(agent, args, "queueMicrotask", [function]);
+    agent.enqueue_job(call_microtask_job, vec![f]);
+    Ok(Value::Null)
+}
+
+// the same as the `typeof` operator, except that an undeclared identifier
+// passed to it is still an error.
+fn r#type(_agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    Ok(Value::from(args.get(0).unwrap_or(&Value::Null).type_of()))
+}
+
+// a deep copy within this agent. `transfer` lists ArrayBuffers whose bytes
+// move to the copy instead of being copied.
+fn structured_clone(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let null = Value::Null;
+    let value = args.get(0).unwrap_or(&null);
+    let transfer = match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => Vec::new(),
+        Some(_) if args[1].type_of() == "object" => {
+            match args[1]
+                .get(agent, ObjectKey::new(agent, "transfer"))?
+                .kind()
+            {
+                ValueKind::Null => Vec::new(),
+                ValueKind::Object(o) => match &o.kind {
+                    ObjectKind::Array(values) => values.borrow().clone(),
+                    _ => return Err(Value::new_error(agent, "transfer must be an array")),
+                },
+                _ => return Err(Value::new_error(agent, "transfer must be an array")),
+            }
+        }
+        _ => return Err(Value::new_error(agent, "options must be an object")),
+    };
+    match CloneData::with_transfer(value, &transfer) {
+        Ok(data) => Ok(data.to_value(agent)),
+        Err(path) => Err(data_clone_error(agent, &path)),
+    }
+}
+
+pub fn create_global_object(agent: &Agent) -> Value {
+    let global = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:expr, $length:expr) => {
+            global
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("isNaN", is_nan, 1);
+    method!("isFinite", is_finite, 1);
+    method!("parseInt", parse_int, 2);
+    method!("parseFloat", parse_float, 1);
+    if agent.capabilities().timers() {
+        method!("setTimeout", timers::set_timeout, 2);
+        method!("setInterval", timers::set_interval, 2);
+        method!("clearTimeout", timers::clear_timer, 1);
+        method!("clearInterval", timers::clear_timer, 1);
+    }
+    method!("queueMicrotask", queue_microtask, 1);
+    method!("type", r#type, 1);
+    method!("structuredClone", structured_clone, 1);
+
+    macro_rules! value {
+        ($name:expr, $value:expr) => {
+            global
+                .set(agent, ObjectKey::new(agent, $name), $value)
+                .unwrap();
+        };
+    }
+
+    value!("Symbol", agent.intrinsics.symbol.clone());
+    value!("BigInt", create_bigint(agent));
+    value!("Bytes", agent.intrinsics.bytes.clone());
+    value!("Object", create_object(agent));
+    value!("Proxy", create_proxy(agent));
+    value!("ArrayBuffer", create_array_buffer(agent));
+    value!("DataView", create_data_view(agent));
+    for kind in TypedArrayKind::ALL.iter() {
+        if *kind == TypedArrayKind::Uint8 {
+            value!(kind.name(), agent.intrinsics.uint8_array.clone());
+        } else {
+            value!(kind.name(), create_typed_array(agent, *kind));
+        }
+    }
+    value!("TextEncoder", create_text_encoder(agent));
+    value!("TextDecoder", create_text_decoder(agent));
+    value!("AbortController", create_abort_controller(agent));
+    value!("URL", create_url(agent));
+    value!("URLSearchParams", create_url_search_params(agent));
+    value!("Deque", create_deque(agent));
+
+    define_agent_globals(agent, &global);
+    global
+}
+
+/// A global object for an agent which shares the intrinsics `template` was
+/// created with. The functions and constructors on it are frozen, so they are
+/// shared too, but the objects which scripts can change or which hold the
+/// state of an agent are created again.
+pub fn copy_global_object(agent: &Agent, template: &Value) -> Value {
+    let global = Value::new_object_like(agent.intrinsics.object_prototype.clone(), template);
+    define_agent_globals(agent, &global);
+    global
+}
+
+fn define_agent_globals(agent: &Agent, global: &Value) {
+    macro_rules! value {
+        ($name:expr, $value:expr) => {
+            global
+                .set(agent, ObjectKey::new(agent, $name), $value)
+                .unwrap();
+        };
+    }
+
+    value!("Reflect", create_reflect(agent));
+    value!("console", create_console(agent));
+    value!("performance", create_performance(agent));
+    if agent.capabilities().process() {
+        value!("process", process::create_global(agent));
+    }
+    value!("globalThis", global.clone());
+}
@@ -0,0 +1,47 @@
+use crate::interpreter::Context;
+use crate::intrinsics::bytes_prototype::to_bytes;
+use crate::intrinsics::promise::promise_resolve_i;
+use crate::value::ObjectKey;
+use crate::{Agent, Value};
+
+fn body(agent: &Agent, ctx: &Context) -> Result<Vec<u8>, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("http response body") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    Ok(to_bytes(&this.get_slot("http response body")).unwrap())
+}
+
+fn text(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let body = body(agent, ctx)?;
+    let text = Value::from(String::from_utf8_lossy(&body).to_string());
+    promise_resolve_i(agent, agent.intrinsics.promise.clone(), text)
+}
+
+fn bytes(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let body = body(agent, ctx)?;
+    let bytes = Value::new_buffer_from_vec(agent, body);
+    promise_resolve_i(agent, agent.intrinsics.promise.clone(), bytes)
+}
+
+pub fn create_http_response_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "text"),
+            Value::new_named_builtin_function(agent, text, "text", 0),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "bytes"),
+            Value::new_named_builtin_function(agent, bytes, "bytes", 0),
+        )
+        .unwrap();
+
+    proto
+}
@@ -0,0 +1,151 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::iterator_prototype::{call_closing, close, step};
+use crate::value::{ObjectKey, Value, ValueKind};
+
+// The lazy iterators returned by map, filter, take, drop and flatMap. Each
+// call to `next` pulls only as many values from the wrapped iterator as it
+// needs to produce one value.
+
+fn count(it: &Value) -> f64 {
+    match it.get_slot("helper count").kind() {
+        ValueKind::Number(n) => n,
+        _ => unreachable!(),
+    }
+}
+
+fn produce(agent: &Agent, it: &Value, iterated: &Value) -> Result<Option<Value>, Value> {
+    let f = it.get_slot("helper fn");
+    match it.get_slot("helper kind").kind() {
+        ValueKind::String(kind) if kind == "map" => match step(agent, iterated)? {
+            Some(value) => {
+                let counter = count(it);
+                it.set_slot("helper count", Value::from(counter + 1.0));
+                let args = vec![value, Value::from(counter)];
+                Ok(Some(call_closing(agent, iterated, &f, args)?))
+            }
+            None => Ok(None),
+        },
+        ValueKind::String(kind) if kind == "filter" => {
+            while let Some(value) = step(agent, iterated)? {
+                let counter = count(it);
+                it.set_slot("helper count", Value::from(counter + 1.0));
+                let args = vec![value.clone(), Value::from(counter)];
+                if call_closing(agent, iterated, &f, args)?.to_bool() {
+                    return Ok(Some(value));
+                }
+            }
+            Ok(None)
+        }
+        ValueKind::String(kind) if kind == "take" => {
+            let remaining = count(it);
+            if remaining == 0.0 {
+                close(agent, iterated)?;
+                return Ok(None);
+            }
+            it.set_slot("helper count", Value::from(remaining - 1.0));
+            step(agent, iterated)
+        }
+        ValueKind::String(kind) if kind == "drop" => {
+            let mut remaining = count(it);
+            while remaining > 0.0 {
+                remaining -= 1.0;
+                it.set_slot("helper count", Value::from(remaining));
+                if step(agent, iterated)?.is_none() {
+                    return Ok(None);
+                }
+            }
+            step(agent, iterated)
+        }
+        ValueKind::String(kind) if kind == "flatMap" => loop {
+            let inner = it.get_slot("helper inner");
+            if inner != Value::Null {
+                match step(agent, &inner) {
+                    Ok(Some(value)) => return Ok(Some(value)),
+                    Ok(None) => it.set_slot("helper inner", Value::Null),
+                    Err(e) => {
+                        let _ = close(agent, iterated);
+                        return Err(e);
+                    }
+                }
+            }
+            match step(agent, iterated)? {
+                Some(value) => {
+                    let counter = count(it);
+                    it.set_slot("helper count", Value::from(counter + 1.0));
+                    let args = vec![value, Value::from(counter)];
+                    let mapped = call_closing(agent, iterated, &f, args)?;
+                    match mapped.to_iterator(agent) {
+                        Ok(inner) => it.set_slot("helper inner", inner),
+                        Err(e) => {
+                            let _ = close(agent, iterated);
+                            return Err(e);
+                        }
+                    }
+                }
+                None => return Ok(None),
+            }
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn next(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = ctx.this(agent)?;
+    if it.type_of() != "object" || !it.has_slot("helper kind") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    if it.get_slot("helper done") == Value::from(true) {
+        return Value::new_iter_result(agent, Value::Null, true);
+    }
+    let iterated = it.get_slot("iterated");
+    match produce(agent, &it, &iterated) {
+        Ok(Some(value)) => Value::new_iter_result(agent, value, false),
+        Ok(None) => {
+            it.set_slot("helper done", Value::from(true));
+            Value::new_iter_result(agent, Value::Null, true)
+        }
+        Err(e) => {
+            it.set_slot("helper done", Value::from(true));
+            Err(e)
+        }
+    }
+}
+
+fn r#return(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = ctx.this(agent)?;
+    if it.type_of() != "object" || !it.has_slot("helper kind") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    if it.get_slot("helper done") != Value::from(true) {
+        it.set_slot("helper done", Value::from(true));
+        let inner = it.get_slot("helper inner");
+        if inner != Value::Null {
+            close(agent, &inner)?;
+        }
+        close(agent, &it.get_slot("iterated"))?;
+    }
+    Value::new_iter_result(agent, Value::Null, true)
+}
+
+pub fn create_iterator_helper_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.iterator_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "return"),
+            Value::new_named_builtin_function(agent, r#return, "return", 0),
+        )
+        .unwrap();
+
+    proto
+}
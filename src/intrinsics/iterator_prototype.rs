@@ -1,23 +1,191 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, Value};
+use crate::value::{ObjectKey, Value, ValueKind};
 
-fn iterator(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    ctx.scope.borrow().get_this(agent)
+// calls `next` on an iterator created by `to_iterator`, returning None once
+// it is done.
+pub fn step(agent: &Agent, iterated: &Value) -> Result<Option<Value>, Value> {
+    if let ValueKind::Iterator(iterator, next) = iterated.kind() {
+        let result = next.call(agent, iterator.clone(), vec![])?;
+        if result.get(agent, ObjectKey::new(agent, "done"))?.to_bool() {
+            Ok(None)
+        } else {
+            Ok(Some(result.get(agent, ObjectKey::new(agent, "value"))?))
+        }
+    } else {
+        unreachable!();
+    }
+}
+
+// calls `return` on an iterator created by `to_iterator`, if it has one.
+pub fn close(agent: &Agent, iterated: &Value) -> Result<(), Value> {
+    if let ValueKind::Iterator(iterator, _) = iterated.kind() {
+        let f = iterator.get(agent, ObjectKey::new(agent, "return"))?;
+        if f != Value::Null {
+            f.call(agent, iterator.clone(), vec![])?;
+        }
+        Ok(())
+    } else {
+        unreachable!();
+    }
+}
+
+// calls a callback passed to a helper, closing the iterator if it throws.
+pub fn call_closing(
+    agent: &Agent,
+    iterated: &Value,
+    f: &Value,
+    args: Vec<Value>,
+) -> Result<Value, Value> {
+    f.call(agent, Value::Null, args).map_err(|e| {
+        let _ = close(agent, iterated);
+        e
+    })
 }
 
-fn map(agent: &Agent, mut args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let iterated = ctx.scope.borrow().get_this(agent)?.to_iterator(agent)?;
-    let mapper = args.pop().unwrap_or(Value::Null);
-    if mapper.type_of() != "function" {
-        return Err(Value::new_error(agent, "mapper is not a function"));
+pub fn function_arg(agent: &Agent, args: &[Value], name: &str) -> Result<Value, Value> {
+    match args.get(0) {
+        Some(f) if f.type_of() == "function" => Ok(f.clone()),
+        _ => Err(Value::new_error(
+            agent,
+            &format!("{} is not a function", name),
+        )),
     }
-    let iterator = Value::new_custom_object(agent.intrinsics.iterator_map_prototype.clone());
-    iterator.set_slot("mapper", mapper);
+}
+
+pub fn count_arg(agent: &Agent, args: &[Value], name: &str) -> Result<f64, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if n >= 0.0 => Ok(n.floor()),
+        _ => Err(Value::new_error(
+            agent,
+            &format!("{} must be a non-negative number", name),
+        )),
+    }
+}
+
+fn iterator(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    ctx.this(agent)
+}
+
+fn helper(agent: &Agent, ctx: &Context, kind: &str, f: Value, count: f64) -> Result<Value, Value> {
+    let iterated = ctx.this(agent)?.to_iterator(agent)?;
+    let iterator = Value::new_custom_object(agent.intrinsics.iterator_helper_prototype.clone());
+    iterator.set_slot("helper kind", Value::from(kind));
+    iterator.set_slot("helper fn", f);
+    iterator.set_slot("helper count", Value::from(count));
+    iterator.set_slot("helper inner", Value::Null);
+    iterator.set_slot("helper done", Value::from(false));
     iterator.set_slot("iterated", iterated);
     Ok(iterator)
 }
 
+fn map(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mapper = function_arg(agent, &args, "mapper")?;
+    helper(agent, ctx, "map", mapper, 0.0)
+}
+
+fn filter(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let predicate = function_arg(agent, &args, "predicate")?;
+    helper(agent, ctx, "filter", predicate, 0.0)
+}
+
+fn take(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let limit = count_arg(agent, &args, "limit")?;
+    helper(agent, ctx, "take", Value::Null, limit)
+}
+
+fn drop(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let count = count_arg(agent, &args, "count")?;
+    helper(agent, ctx, "drop", Value::Null, count)
+}
+
+fn flat_map(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let mapper = function_arg(agent, &args, "mapper")?;
+    helper(agent, ctx, "flatMap", mapper, 0.0)
+}
+
+fn reduce(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let reducer = function_arg(agent, &args, "reducer")?;
+    let iterated = ctx.this(agent)?.to_iterator(agent)?;
+    let (mut accumulator, mut counter) = match args.get(1) {
+        Some(initial) => (initial.clone(), 0),
+        None => match step(agent, &iterated)? {
+            Some(first) => (first, 1),
+            None => {
+                return Err(Value::new_error(
+                    agent,
+                    "reduce of empty iterator with no initial value",
+                ))
+            }
+        },
+    };
+    while let Some(value) = step(agent, &iterated)? {
+        let args = vec![accumulator, value, Value::from(counter as f64)];
+        accumulator = call_closing(agent, &iterated, &reducer, args)?;
+        counter += 1;
+    }
+    Ok(accumulator)
+}
+
+fn for_each(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = function_arg(agent, &args, "callback")?;
+    let iterated = ctx.this(agent)?.to_iterator(agent)?;
+    let mut counter = 0;
+    while let Some(value) = step(agent, &iterated)? {
+        call_closing(
+            agent,
+            &iterated,
+            &f,
+            vec![value, Value::from(counter as f64)],
+        )?;
+        counter += 1;
+    }
+    Ok(Value::Null)
+}
+
+// shared by some, every and find. stops at the first value for which the
+// predicate returns `stop_on`, closing the iterator.
+fn search(
+    agent: &Agent,
+    args: &[Value],
+    ctx: &Context,
+    stop_on: bool,
+) -> Result<Option<Value>, Value> {
+    let predicate = function_arg(agent, args, "predicate")?;
+    let iterated = ctx.this(agent)?.to_iterator(agent)?;
+    let mut counter = 0;
+    while let Some(value) = step(agent, &iterated)? {
+        let args = vec![value.clone(), Value::from(counter as f64)];
+        if call_closing(agent, &iterated, &predicate, args)?.to_bool() == stop_on {
+            close(agent, &iterated)?;
+            return Ok(Some(value));
+        }
+        counter += 1;
+    }
+    Ok(None)
+}
+
+fn some(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    Ok(Value::from(search(agent, &args, ctx, true)?.is_some()))
+}
+
+fn every(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    Ok(Value::from(search(agent, &args, ctx, false)?.is_none()))
+}
+
+fn find(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    Ok(search(agent, &args, ctx, true)?.unwrap_or(Value::Null))
+}
+
+fn to_array(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let iterated = ctx.this(agent)?.to_iterator(agent)?;
+    let mut values = Vec::new();
+    while let Some(value) = step(agent, &iterated)? {
+        values.push(value);
+    }
+    Ok(Value::new_array_from_vec(agent, values))
+}
+
 pub fn create_iterator_prototype(agent: &Agent) -> Value {
     let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
 
@@ -29,13 +197,29 @@ pub fn create_iterator_prototype(agent: &Agent) -> Value {
         )
         .unwrap();
 
-    proto
-        .set(
-            agent,
-            ObjectKey::from("map"),
-            Value::new_builtin_function(agent, map),
-        )
-        .unwrap();
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("map", map, 1);
+    method!("filter", filter, 1);
+    method!("take", take, 1);
+    method!("drop", drop, 1);
+    method!("flatMap", flat_map, 1);
+    method!("reduce", reduce, 1);
+    method!("forEach", for_each, 1);
+    method!("some", some, 1);
+    method!("every", every, 1);
+    method!("find", find, 1);
+    method!("toArray", to_array, 0);
 
     proto
 }
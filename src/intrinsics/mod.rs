@@ -1,42 +1,91 @@
+pub mod abort_controller;
+pub mod array_buffer;
 mod array_iterator_prototype;
 mod array_prototype;
+mod async_iterator_helper_prototype;
 mod async_iterator_prototype;
+mod async_split_iterator_prototype;
+pub mod bigint;
 mod boolean_prototype;
+mod bytes;
+pub mod bytes_prototype;
+mod console;
+mod deque;
 mod error_prototype;
 mod function_prototype;
 mod generator_prototype;
-mod iterator_map_prototype;
+mod global_object;
+mod http_response_prototype;
+mod iterator_helper_prototype;
 mod iterator_prototype;
 mod net_client_prototype;
 mod net_server_prototype;
 mod number_prototype;
+mod object;
 mod object_prototype;
 pub mod perform_await;
+mod performance;
 pub mod promise;
 mod promise_prototype;
+pub mod proxy;
+pub mod range;
+mod reflect;
 mod regex_prototype;
 mod string_prototype;
 mod symbol;
 mod symbol_prototype;
+pub mod text_encoding;
+mod time_prototype;
+pub mod typed_array;
+pub mod url;
+mod worker_messages_prototype;
+mod worker_prototype;
 
 pub use perform_await::perform_await;
 
+pub use abort_controller::{create_abort_controller, create_abort_signal_prototype};
+pub use array_buffer::{
+    create_array_buffer, create_array_buffer_prototype, create_data_view,
+    create_data_view_prototype,
+};
 pub use array_iterator_prototype::create_array_iterator_prototype;
 pub use array_prototype::create_array_prototype;
+pub use async_iterator_helper_prototype::create_async_iterator_helper_prototype;
 pub use async_iterator_prototype::create_async_iterator_prototype;
+pub use async_split_iterator_prototype::create_async_split_iterator_prototype;
+pub use bigint::{create_bigint, create_bigint_prototype};
 pub use boolean_prototype::create_boolean_prototype;
+pub use bytes::create_bytes;
+pub use bytes_prototype::create_bytes_prototype;
+pub use console::create_console;
+pub use deque::{create_deque, create_deque_iterator_prototype, create_deque_prototype};
 pub use error_prototype::create_error_prototype;
 pub use function_prototype::create_function_prototype;
 pub use generator_prototype::create_generator_prototype;
-pub use iterator_map_prototype::create_iterator_map_prototype;
+pub use global_object::{copy_global_object, create_global_object};
+pub use http_response_prototype::create_http_response_prototype;
+pub use iterator_helper_prototype::create_iterator_helper_prototype;
 pub use iterator_prototype::create_iterator_prototype;
 pub use net_client_prototype::create_net_client_prototype;
 pub use net_server_prototype::create_net_server_prototype;
 pub use number_prototype::create_number_prototype;
-pub use object_prototype::create_object_prototype;
+pub use object::create_object;
+pub use object_prototype::{create_object_prototype, create_object_prototype_methods};
+pub use performance::create_performance;
 pub use promise::create_promise;
 pub use promise_prototype::create_promise_prototype;
+pub use proxy::create_proxy;
+pub use range::{create_range_iterator_prototype, create_range_prototype};
+pub use reflect::create_reflect;
 pub use regex_prototype::create_regex_prototype;
 pub use string_prototype::create_string_prototype;
 pub use symbol::create_symbol;
 pub use symbol_prototype::create_symbol_prototype;
+pub use text_encoding::{create_text_decoder, create_text_encoder};
+pub use time_prototype::create_time_prototype;
+pub use typed_array::{create_typed_array, create_typed_array_prototype};
+pub use url::{
+    create_url, create_url_prototype, create_url_search_params, create_url_search_params_prototype,
+};
+pub use worker_messages_prototype::create_worker_messages_prototype;
+pub use worker_prototype::create_worker_prototype;
@@ -1,25 +1,32 @@
 use crate::agent::MioMapType;
+use crate::args::type_error;
 use crate::interpreter::Context;
 use crate::intrinsics::promise::new_promise_capability;
-use crate::value::{ObjectKey, ObjectKind};
+use crate::value::{ObjectKey, ObjectKind, ValueKind};
 use crate::IntoValue;
 use crate::{Agent, Value};
 use num::ToPrimitive;
 use std::io::prelude::*;
 
 fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
     if !this.has_slot("net client queue") {
-        return Err(Value::new_error(agent, "invalid receiver"));
+        return Err(type_error(
+            agent,
+            "client.next",
+            "receiver",
+            "a net client",
+            Some(&this),
+        ));
     }
 
-    if let Value::List(buffer) = this.get_slot("net client buffer") {
+    if let ValueKind::List(buffer) = this.get_slot("net client buffer").kind() {
         if let Some(promise) = buffer.borrow_mut().pop_front() {
             return Ok(promise);
         }
     }
 
-    if let Value::List(queue) = this.get_slot("net client queue") {
+    if let ValueKind::List(queue) = this.get_slot("net client queue").kind() {
         let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
         queue.borrow_mut().push_back(promise.clone());
         Ok(promise)
@@ -29,19 +36,25 @@ fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
 }
 
 fn write(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
     if !this.has_slot("net client token") {
-        return Err(Value::new_error(agent, "invalid receiver"));
+        return Err(type_error(
+            agent,
+            "client.write",
+            "receiver",
+            "a net client",
+            Some(&this),
+        ));
     }
-    if let Value::Number(t) = this.get_slot("net client token") {
+    if let ValueKind::Number(t) = this.get_slot("net client token").kind() {
         let token = mio::Token(t.to_usize().unwrap());
         let map = agent.mio_map.borrow_mut();
         if let MioMapType::Net(crate::builtins::net::Net::Client(s, ..)) =
             map.get(&token).expect("socket missing in mio_map")
         {
             let mut s = s;
-            match args.get(0) {
-                Some(Value::String(str)) => {
+            match args.get(0).map(Value::kind) {
+                Some(ValueKind::String(str)) => {
                     match s.write_all(str.as_bytes()) {
                         Ok(_) => Ok(Value::Null),
                         Err(e) => Err(e.into_value(agent)),
@@ -49,7 +62,7 @@ fn write(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value>
                     // s.write_all(str.as_bytes())?;
                     // Ok(Value::Null)
                 }
-                Some(Value::Object(o)) => {
+                Some(ValueKind::Object(o)) => {
                     if let ObjectKind::Buffer(b) = &o.kind {
                         match s.write_all(&b.borrow()) {
                             Ok(_) => Ok(Value::Null),
@@ -58,10 +71,22 @@ fn write(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value>
                     // s.write_all(&b.borrow())?;
                     // Ok(Value::Null)
                     } else {
-                        Err(Value::new_error(agent, "data must be a string or buffer"))
+                        Err(type_error(
+                            agent,
+                            "client.write",
+                            "argument 1",
+                            "a string or buffer",
+                            args.get(0),
+                        ))
                     }
                 }
-                _ => Err(Value::new_error(agent, "data must be a string or buffer")),
+                _ => Err(type_error(
+                    agent,
+                    "client.write",
+                    "argument 1",
+                    "a string or buffer",
+                    args.get(0),
+                )),
             }
         } else {
             unreachable!();
@@ -72,12 +97,18 @@ fn write(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value>
 }
 
 fn close(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
     if !this.has_slot("net client token") {
-        return Err(Value::new_error(agent, "invalid receiver"));
+        return Err(type_error(
+            agent,
+            "client.close",
+            "receiver",
+            "a net client",
+            Some(&this),
+        ));
     }
 
-    if let Value::Number(t) = this.get_slot("net client token") {
+    if let ValueKind::Number(t) = this.get_slot("net client token").kind() {
         let token = mio::Token(t.to_usize().unwrap());
         agent.mio_map.borrow_mut().remove(&token);
         Ok(Value::Null)
@@ -92,24 +123,24 @@ pub fn create_net_client_prototype(agent: &Agent) -> Value {
     proto
         .set(
             agent,
-            ObjectKey::from("next"),
-            Value::new_builtin_function(agent, next),
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
         )
         .unwrap();
 
     proto
         .set(
             agent,
-            ObjectKey::from("write"),
-            Value::new_builtin_function(agent, write),
+            ObjectKey::new(agent, "write"),
+            Value::new_named_builtin_function(agent, write, "write", 1),
         )
         .unwrap();
 
     proto
         .set(
             agent,
-            ObjectKey::from("close"),
-            Value::new_builtin_function(agent, close),
+            ObjectKey::new(agent, "close"),
+            Value::new_named_builtin_function(agent, close, "close", 0),
         )
         .unwrap();
 
@@ -1,22 +1,29 @@
+use crate::args::type_error;
 use crate::interpreter::Context;
 use crate::intrinsics::promise::new_promise_capability;
-use crate::value::ObjectKey;
+use crate::value::{ObjectKey, ValueKind};
 use crate::{Agent, Value};
 use num::ToPrimitive;
 
 fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
     if !this.has_slot("net server queue") {
-        return Err(Value::new_error(agent, "invalid receiver"));
+        return Err(type_error(
+            agent,
+            "server.next",
+            "receiver",
+            "a net server",
+            Some(&this),
+        ));
     }
 
-    if let Value::List(buffer) = this.get_slot("net server buffer") {
+    if let ValueKind::List(buffer) = this.get_slot("net server buffer").kind() {
         if let Some(promise) = buffer.borrow_mut().pop_front() {
             return Ok(promise);
         }
     }
 
-    if let Value::List(queue) = this.get_slot("net server queue") {
+    if let ValueKind::List(queue) = this.get_slot("net server queue").kind() {
         let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
         queue.borrow_mut().push_back(promise.clone());
         Ok(promise)
@@ -26,12 +33,18 @@ fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
 }
 
 fn close(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
     if !this.has_slot("net server token") {
-        return Err(Value::new_error(agent, "invalid receiver"));
+        return Err(type_error(
+            agent,
+            "server.close",
+            "receiver",
+            "a net server",
+            Some(&this),
+        ));
     }
 
-    if let Value::Number(t) = this.get_slot("net server token") {
+    if let ValueKind::Number(t) = this.get_slot("net server token").kind() {
         let token = mio::Token(t.to_usize().unwrap());
         agent.mio_map.borrow_mut().remove(&token);
         Ok(Value::Null)
@@ -40,22 +53,46 @@ fn close(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
     }
 }
 
+// the `{ host, port }` the server is bound to, which has the port the OS
+// picked if it was asked to listen on port 0.
+fn address(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("net server address") {
+        return Err(type_error(
+            agent,
+            "server.address",
+            "receiver",
+            "a net server",
+            Some(&this),
+        ));
+    }
+    Ok(this.get_slot("net server address"))
+}
+
 pub fn create_net_server_prototype(agent: &Agent) -> Value {
     let proto = Value::new_object(agent.intrinsics.async_iterator_prototype.clone());
 
     proto
         .set(
             agent,
-            ObjectKey::from("next"),
-            Value::new_builtin_function(agent, next),
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "close"),
+            Value::new_named_builtin_function(agent, close, "close", 0),
         )
         .unwrap();
 
     proto
         .set(
             agent,
-            ObjectKey::from("close"),
-            Value::new_builtin_function(agent, close),
+            ObjectKey::new(agent, "address"),
+            Value::new_named_builtin_function(agent, address, "address", 0),
         )
         .unwrap();
 
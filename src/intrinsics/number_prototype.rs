@@ -1,13 +1,13 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
 use crate::num_util;
-use crate::value::{ObjectKey, ObjectKind, Value};
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
 
 fn to_string(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
 
-    match this {
-        Value::Object(o) => match o.kind {
+    match this.kind() {
+        ValueKind::Object(o) => match o.kind {
             ObjectKind::Number(n) => Ok(Value::from(num_util::to_string(n))),
             _ => Err(Value::new_error(agent, "invalid receiver")),
         },
@@ -22,14 +22,14 @@ pub fn create_number_prototype(agent: &Agent) -> Value {
         .set(
             agent,
             ObjectKey::well_known_symbol("toString"),
-            Value::new_builtin_function(agent, to_string),
+            Value::new_named_builtin_function(agent, to_string, "toString", 0),
         )
         .unwrap();
 
     macro_rules! FN_1 {
         ( $n:ident ) => {
             fn $n(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-                if let Value::Object(o) = ctx.scope.borrow().get_this(agent)? {
+                if let ValueKind::Object(o) = ctx.this(agent)?.kind() {
                     if let ObjectKind::Number(n) = o.kind {
                         Ok(Value::from(n.$n()))
                     } else {
@@ -42,8 +42,8 @@ pub fn create_number_prototype(agent: &Agent) -> Value {
             proto
                 .set(
                     agent,
-                    ObjectKey::from(stringify!($n)),
-                    Value::new_builtin_function(agent, $n),
+                    ObjectKey::new(agent, stringify!($n)),
+                    Value::new_named_builtin_function(agent, $n, stringify!($n), 0),
                 )
                 .unwrap();
         };
@@ -68,7 +68,7 @@ pub fn create_number_prototype(agent: &Agent) -> Value {
     macro_rules! CHECK {
         ( $n:ident, $sln:expr ) => {
             fn $n(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-                if let Value::Object(o) = ctx.scope.borrow().get_this(agent)? {
+                if let ValueKind::Object(o) = ctx.this(agent)?.kind() {
                     if let ObjectKind::Number(n) = o.kind {
                         Ok(Value::from(n.$n()))
                     } else {
@@ -81,8 +81,8 @@ pub fn create_number_prototype(agent: &Agent) -> Value {
             proto
                 .set(
                     agent,
-                    ObjectKey::from($sln),
-                    Value::new_builtin_function(agent, $n),
+                    ObjectKey::new(agent, $sln),
+                    Value::new_named_builtin_function(agent, $n, $sln, 0),
                 )
                 .unwrap();
         };
@@ -0,0 +1,121 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::iterator_prototype::{close, step};
+use crate::value::{ObjectKey, Value};
+
+fn object(agent: &Agent, _args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    Ok(Value::new_object(agent.intrinsics.object_prototype.clone()))
+}
+
+// the names of the own properties of an object, symbol keys are left out.
+fn keys(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (target,) = check_args!(agent, args, "Object.keys", [object]);
+    let keys = target
+        .keys(agent)?
+        .iter()
+        .filter(|key| match key {
+            ObjectKey::Symbol(..) => false,
+            _ => true,
+        })
+        .map(|key| key.to_value())
+        .collect();
+    Ok(Value::new_array_from_vec(agent, keys))
+}
+
+// the symbol keys of the own properties of an object.
+fn symbols(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (target,) = check_args!(agent, args, "Object.symbols", [object]);
+    let symbols = target
+        .keys(agent)?
+        .iter()
+        .filter(|key| match key {
+            ObjectKey::Symbol(..) => true,
+            _ => false,
+        })
+        .map(|key| key.to_value())
+        .collect();
+    Ok(Value::new_array_from_vec(agent, symbols))
+}
+
+// `[key, value]` pairs of the own properties of an object, symbol keys are
+// left out.
+fn entries(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (target,) = check_args!(agent, args, "Object.entries", [object]);
+    let mut entries = Vec::new();
+    for key in target.keys(agent)? {
+        if let ObjectKey::Symbol(..) = key {
+            continue;
+        }
+        let value = target.get(agent, key.clone())?;
+        entries.push(Value::new_array_from_vec(
+            agent,
+            vec![key.to_value(), value],
+        ));
+    }
+    Ok(Value::new_array_from_vec(agent, entries))
+}
+
+// the inverse of `entries`, from any iterable of `[key, value]` pairs.
+fn from_entries(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (iterable,) = check_args!(agent, args, "Object.fromEntries", [any]);
+    let iterated = iterable.to_iterator(agent)?;
+    let o = Value::new_object(agent.intrinsics.object_prototype.clone());
+    while let Some(entry) = step(agent, &iterated)? {
+        let define = || {
+            if !entry.is_object() {
+                return Err(Value::new_error(agent, "entry must be an object"));
+            }
+            let key = entry
+                .get(agent, ObjectKey::Number(0))?
+                .to_object_key(agent)?;
+            let value = entry.get(agent, ObjectKey::Number(1))?;
+            o.set(agent, key, value)
+        };
+        if let Err(e) = define() {
+            let _ = close(agent, &iterated);
+            return Err(e);
+        }
+    }
+    Ok(o)
+}
+
+// whether `key` is an own property of the object, ignoring its prototypes.
+fn has_own(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (target, key) = check_args!(agent, args, "Object.hasOwn", [object, any]);
+    let key = key.to_object_key(agent)?;
+    Ok(Value::from(target.has_own_property(agent, key)?))
+}
+
+pub fn create_object(agent: &Agent) -> Value {
+    let o = Value::new_named_builtin_function(agent, object, "Object", 0);
+    o.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.object_prototype.clone(),
+    )
+    .unwrap();
+    agent
+        .intrinsics
+        .object_prototype
+        .set(agent, ObjectKey::new(agent, "constructor"), o.clone())
+        .unwrap();
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            o.set(
+                agent,
+                ObjectKey::new(agent, $name),
+                Value::new_named_builtin_function(agent, $fn, $name, $length),
+            )
+            .unwrap();
+        };
+    }
+
+    method!("keys", keys, 1);
+    method!("symbols", symbols, 1);
+    method!("entries", entries, 1);
+    method!("fromEntries", from_entries, 1);
+    method!("hasOwn", has_own, 2);
+
+    o
+}
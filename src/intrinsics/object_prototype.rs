@@ -1,5 +1,50 @@
-use crate::value::Value;
+use crate::interpreter::Context;
+use crate::value::ObjectKey;
+use crate::{Agent, Value};
 
 pub fn create_object_prototype() -> Value {
     Value::new_object(Value::Null)
 }
+
+fn has_own_property(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (key,) = check_args!(agent, args, "Object.prototype.hasOwnProperty", [any]);
+    let key = key.to_object_key(agent)?;
+    Ok(Value::from(ctx.this(agent)?.has_own_property(agent, key)?))
+}
+
+fn is_prototype_of(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (value,) = check_args!(agent, args, "Object.prototype.isPrototypeOf", [any]);
+    let this = ctx.this(agent)?;
+    if !value.is_object() {
+        return Ok(Value::from(false));
+    }
+    let mut prototype = value.get_prototype_of(agent)?;
+    while prototype.is_object() {
+        if prototype == this {
+            return Ok(Value::from(true));
+        }
+        prototype = prototype.get_prototype_of(agent)?;
+    }
+    Ok(Value::from(false))
+}
+
+// the methods need the function prototype, which is created after the object
+// prototype it inherits from.
+pub fn create_object_prototype_methods(agent: &Agent) {
+    let proto = &agent.intrinsics.object_prototype;
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("hasOwnProperty", has_own_property, 1);
+    method!("isPrototypeOf", is_prototype_of, 1);
+}
@@ -1,48 +1,42 @@
-use crate::interpreter::Context;
+use crate::interpreter::{Context, Interpreter};
 use crate::intrinsics::promise::promise_resolve_i;
-use crate::value::ObjectKey;
+use crate::value::{ObjectKey, ValueKind};
 use crate::{Agent, Value};
+use gc::{Gc, GcCell};
 
 pub fn on_fulfilled(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let f = ctx.function.as_ref().unwrap();
-    if let Value::WrappedContext(context, promise) = f.get_slot("async context") {
+    let f = ctx.callee();
+    if let ValueKind::WrappedContext(context, promise) = f.get_slot("async context").kind() {
         let mut args = args;
         let mut interpreter = context.borrow_mut().interpreter.take().unwrap();
         interpreter.accumulator = args.remove(0);
-        match interpreter.run(agent) {
-            Ok(r) => match r {
-                Ok(v) => {
-                    promise
-                        .unwrap()
-                        .get_slot("resolve")
-                        .call(agent, Value::Null, vec![v])?;
-                }
-                Err(e) => {
-                    promise
-                        .unwrap()
-                        .get_slot("reject")
-                        .call(agent, Value::Null, vec![e])?;
-                }
-            },
-            Err(c) => {
-                context.borrow_mut().interpreter = Some(interpreter);
-                let mut c = c;
-                let value = std::mem::replace(&mut c.0, Value::Null);
-                perform_await(agent, Value::WrappedContext(context, promise), value)?;
-            }
-        }
-        Ok(Value::Null)
+        resume(agent, &f, context.clone(), promise.clone(), interpreter)
     } else {
         unreachable!();
     }
 }
 
 pub fn on_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let f = ctx.function.as_ref().unwrap();
-    if let Value::WrappedContext(context, promise) = f.get_slot("async context") {
+    let f = ctx.callee();
+    if let ValueKind::WrappedContext(context, promise) = f.get_slot("async context").kind() {
         let mut args = args;
         let mut interpreter = context.borrow_mut().interpreter.take().unwrap();
         interpreter.exception = Some(args.remove(0));
+        resume(agent, &f, context.clone(), promise.clone(), interpreter)
+    } else {
+        unreachable!();
+    }
+}
+
+// runs the async function until it awaits again or settles its promise.
+fn resume(
+    agent: &Agent,
+    f: &Value,
+    context: Gc<GcCell<Context>>,
+    promise: Option<Box<Value>>,
+    mut interpreter: Interpreter,
+) -> Result<Value, Value> {
+    agent.with_async_stack(&f.get_slot("async stack"), true, || {
         match interpreter.run(agent) {
             Ok(r) => match r {
                 Ok(v) => {
@@ -62,24 +56,35 @@ pub fn on_rejected(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Val
                 context.borrow_mut().interpreter = Some(interpreter);
                 let mut c = c;
                 let value = std::mem::replace(&mut c.0, Value::Null);
-                perform_await(agent, Value::WrappedContext(context, promise), value)?;
+                let reject = promise.as_ref().unwrap().get_slot("reject");
+                if let Err(e) = perform_await(agent, Value::WrappedContext(context, promise), value)
+                {
+                    reject.call(agent, Value::Null, vec![e])?;
+                }
             }
         }
         Ok(Value::Null)
-    } else {
-        unreachable!();
-    }
+    })
 }
 
+/// Suspend the async function `ctx` until `value` settles. Any value may be
+/// awaited: promises and other thenables are adopted, and everything else
+/// resolves to itself. Either way the function resumes from a later job,
+/// never synchronously.
 pub fn perform_await(agent: &Agent, ctx: Value, value: Value) -> Result<(), Value> {
     let promise = promise_resolve_i(agent, agent.intrinsics.promise.clone(), value)?;
 
+    // the function suspending is the innermost frame, it is entered again
+    // when it resumes.
+    let stack = agent.capture_async_stack();
     let on_fulfilled = Value::new_builtin_function(agent, on_fulfilled);
     on_fulfilled.set_slot("async context", ctx.clone());
+    on_fulfilled.set_slot("async stack", stack.clone());
     let on_rejected = Value::new_builtin_function(agent, on_rejected);
     on_rejected.set_slot("async context", ctx);
+    on_rejected.set_slot("async stack", stack);
 
-    promise.get(agent, ObjectKey::from("then"))?.call(
+    promise.get(agent, ObjectKey::new(agent, "then"))?.call(
         agent,
         promise,
         vec![on_fulfilled, on_rejected],
@@ -0,0 +1,177 @@
+use crate::agent::Agent;
+use crate::args::type_error;
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, Value, ValueKind};
+
+fn this_performance(agent: &Agent, ctx: &Context, name: &str) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.has_slot("performance entries") {
+        Ok(this)
+    } else {
+        Err(type_error(
+            agent,
+            name,
+            "receiver",
+            "the performance object",
+            Some(&this),
+        ))
+    }
+}
+
+fn now(agent: &Agent, _args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    Ok(Value::from(agent.now()))
+}
+
+fn add_entry(
+    agent: &Agent,
+    performance: &Value,
+    name: String,
+    entry_type: &str,
+    start: f64,
+    duration: f64,
+) -> Result<Value, Value> {
+    let entry = Value::new_object(agent.intrinsics.object_prototype.clone());
+    entry.set(agent, ObjectKey::NAME, Value::from(name))?;
+    entry.set(
+        agent,
+        ObjectKey::new(agent, "entryType"),
+        Value::from(entry_type),
+    )?;
+    entry.set(
+        agent,
+        ObjectKey::new(agent, "startTime"),
+        Value::from(start),
+    )?;
+    entry.set(
+        agent,
+        ObjectKey::new(agent, "duration"),
+        Value::from(duration),
+    )?;
+    if let ValueKind::List(entries) = performance.get_slot("performance entries").kind() {
+        entries.borrow_mut().push_back(entry.clone());
+    }
+    Ok(entry)
+}
+
+// the start time of the most recent mark with this name.
+fn mark_time(agent: &Agent, performance: &Value, name: &str) -> Result<f64, Value> {
+    if let ValueKind::List(entries) = performance.get_slot("performance entries").kind() {
+        for entry in entries.borrow().iter().rev() {
+            if entry.get(agent, ObjectKey::new(agent, "entryType"))? == Value::from("mark")
+                && entry.get(agent, ObjectKey::NAME)? == Value::from(name)
+            {
+                if let ValueKind::Number(n) =
+                    entry.get(agent, ObjectKey::new(agent, "startTime"))?.kind()
+                {
+                    return Ok(n);
+                }
+            }
+        }
+    }
+    Err(Value::new_error(
+        agent,
+        &format!("performance.measure: no mark named '{}'", name),
+    ))
+}
+
+fn mark(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let performance = this_performance(agent, ctx, "performance.mark")?;
+    let (name,) = check_args!(agent, args, "performance.mark", [string]);
+    add_entry(agent, &performance, name, "mark", agent.now(), 0.0)
+}
+
+// measures between two marks. Without a start mark the measure starts at the
+// time origin, and without an end mark it ends now.
+fn measure(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let performance = this_performance(agent, ctx, "performance.measure")?;
+    let (name, start_mark, end_mark) = check_args!(
+        agent,
+        args,
+        "performance.measure",
+        [string, optional, optional]
+    );
+    let start = match start_mark.kind() {
+        ValueKind::Null => 0.0,
+        ValueKind::String(mark) => mark_time(agent, &performance, mark)?,
+        _ => {
+            return Err(type_error(
+                agent,
+                "performance.measure",
+                "argument 2",
+                "a string",
+                Some(&start_mark),
+            ))
+        }
+    };
+    let end = match end_mark.kind() {
+        ValueKind::Null => agent.now(),
+        ValueKind::String(mark) => mark_time(agent, &performance, mark)?,
+        _ => {
+            return Err(type_error(
+                agent,
+                "performance.measure",
+                "argument 3",
+                "a string",
+                Some(&end_mark),
+            ))
+        }
+    };
+    add_entry(agent, &performance, name, "measure", start, end - start)
+}
+
+fn get_entries_by_name(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let performance = this_performance(agent, ctx, "performance.getEntriesByName")?;
+    let (name, entry_type) = check_args!(
+        agent,
+        args,
+        "performance.getEntriesByName",
+        [string, optional]
+    );
+    let mut found = Vec::new();
+    if let ValueKind::List(entries) = performance.get_slot("performance entries").kind() {
+        for entry in entries.borrow().iter() {
+            if entry.get(agent, ObjectKey::NAME)? != Value::from(name.as_str()) {
+                continue;
+            }
+            if entry_type != Value::Null
+                && entry.get(agent, ObjectKey::new(agent, "entryType"))? != entry_type
+            {
+                continue;
+            }
+            found.push(entry.clone());
+        }
+    }
+    Ok(Value::new_array_from_vec(agent, found))
+}
+
+pub fn create_performance(agent: &Agent) -> Value {
+    let performance = Value::new_custom_object(agent.intrinsics.object_prototype.clone());
+    performance.set_slot("performance entries", Value::new_list());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            performance
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("now", now, 0);
+    method!("mark", mark, 1);
+    method!("measure", measure, 1);
+    method!("getEntriesByName", get_entries_by_name, 1);
+
+    performance
+        .set(
+            agent,
+            ObjectKey::new(agent, "timeOrigin"),
+            Value::from(agent.time_origin()),
+        )
+        .unwrap();
+
+    performance
+}
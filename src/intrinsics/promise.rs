@@ -1,22 +1,22 @@
 use crate::agent::Agent;
+use crate::args::type_error;
+use crate::builtins::timers;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, Value};
+use crate::value::{ObjectKey, Value, ValueKind};
+use mio::Token;
+use num::ToPrimitive;
+use std::time::Duration;
 
 fn trigger_promise_reactions(
     agent: &Agent,
     reactions: Value,
     argument: Value,
 ) -> Result<Value, Value> {
-    if let Value::List(list) = &reactions {
+    if let ValueKind::List(list) = reactions.kind() {
         loop {
             let item = list.borrow_mut().pop_front();
             match item {
-                Some(reaction) => {
-                    agent.enqueue_job(
-                        promise_reaction_job,
-                        vec![reaction.clone(), argument.clone()],
-                    );
-                }
+                Some(reaction) => enqueue_reaction(agent, reaction, argument.clone()),
                 None => break,
             }
         }
@@ -26,7 +26,31 @@ fn trigger_promise_reactions(
     Ok(Value::Null)
 }
 
-pub fn promise_reaction_job(agent: &Agent, args: Vec<Value>) -> Result<(), Value> {
+/// Queue a job running `reaction` with `argument`. The job carries the
+/// stack which queued it, for async stack traces.
+pub fn enqueue_reaction(agent: &Agent, reaction: Value, argument: Value) {
+    let stack = agent.capture_async_stack();
+    agent.enqueue_job(promise_reaction_job, vec![reaction, argument, stack]);
+}
+
+fn promise_reaction_job(agent: &Agent, args: Vec<Value>) -> Result<(), Value> {
+    let stack = args[2].clone();
+    agent.with_async_stack(&stack, false, || traced_reaction(agent, args))
+}
+
+fn traced_reaction(agent: &Agent, args: Vec<Value>) -> Result<(), Value> {
+    if !agent.capture_native_frames() {
+        return run_promise_reaction(agent, args);
+    }
+    // errors rejecting the derived promise are attributed to the job rather
+    // than whichever builtin happened to queue it.
+    agent.push_native_frame("promise reaction job".to_string());
+    let r = run_promise_reaction(agent, args);
+    agent.pop_native_frame();
+    r
+}
+
+fn run_promise_reaction(agent: &Agent, args: Vec<Value>) -> Result<(), Value> {
     let reaction = args[0].clone();
     let argument = args[1].clone();
 
@@ -74,8 +98,8 @@ fn reject_promise(agent: &Agent, promise: Value, reason: Value) -> Result<Value,
     promise.set_slot("promise state", Value::from("rejected"));
     promise.set_slot("fulfill reactions", Value::Null);
     promise.set_slot("reject reactions", Value::Null);
-    if promise.has_slot("promise handled") {
-        agent.uncaught_exception(reason.clone());
+    if !promise.has_slot("promise handled") {
+        agent.track_rejection(promise.clone());
     }
     trigger_promise_reactions(agent, reactions, reason)
 }
@@ -105,7 +129,7 @@ fn promise_resolve_function(
     args: Vec<Value>,
     ctx: &Context,
 ) -> Result<Value, Value> {
-    let f = ctx.function.clone().unwrap();
+    let f = ctx.callee();
 
     let already_resolved = f.get_slot("already resolved");
     if already_resolved.get_slot("resolved") == Value::from(true) {
@@ -115,31 +139,46 @@ fn promise_resolve_function(
     }
 
     let promise = f.get_slot("promise");
-    let resolution = args.get(0).unwrap_or(&Value::Null).clone();
+    let (resolution,) = check_args!(agent, args, "resolve", [optional]);
     if promise == resolution {
         reject_promise(
             agent,
             promise,
             Value::new_error(agent, "cannot resolve a promise with itself"),
         )
-    } else if resolution.has_slot("promise state") {
-        let ResolvingFunctions { resolve, reject } = create_resolving_functions(agent, &promise);
-        let then_call_result = resolution.get(agent, ObjectKey::from("then"))?.call(
-            agent,
-            resolution,
-            vec![resolve, reject.clone()],
-        );
-        match then_call_result {
-            Ok(v) => Ok(v),
-            Err(e) => reject.call(agent, Value::Null, vec![e]),
+    } else if resolution.is_object() {
+        let then = match resolution.get(agent, ObjectKey::new(agent, "then")) {
+            Ok(then) => then,
+            Err(e) => return reject_promise(agent, promise, e),
+        };
+        if then.type_of() == "function" {
+            // `then` is called from a job so that user code never runs
+            // inside the resolve function.
+            agent.enqueue_job(
+                promise_resolve_thenable_job,
+                vec![promise, resolution, then],
+            );
+            Ok(Value::Null)
+        } else {
+            fulfill_promise(agent, promise, resolution)
         }
     } else {
         fulfill_promise(agent, promise, resolution)
     }
 }
 
+// adopts the state of a thenable, which may or may not be a real promise.
+fn promise_resolve_thenable_job(agent: &Agent, args: Vec<Value>) -> Result<(), Value> {
+    let (promise, thenable, then) = (&args[0], &args[1], &args[2]);
+    let ResolvingFunctions { resolve, reject } = create_resolving_functions(agent, promise);
+    if let Err(e) = then.call(agent, thenable.clone(), vec![resolve, reject.clone()]) {
+        reject.call(agent, Value::Null, vec![e])?;
+    }
+    Ok(())
+}
+
 fn promise_reject_function(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let f = ctx.function.clone().unwrap();
+    let f = ctx.callee();
 
     let already_resolved = f.get_slot("already resolved");
     if already_resolved.get_slot("resolved") == Value::from(true) {
@@ -149,16 +188,12 @@ fn promise_reject_function(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Re
     }
 
     let promise = f.get_slot("promise");
-    let resolution = args.get(0).unwrap_or(&Value::Null).clone();
-    reject_promise(agent, promise, resolution)
+    let (reason,) = check_args!(agent, args, "reject", [optional]);
+    reject_promise(agent, promise, reason)
 }
 
 fn promise(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
-    let executor = args[0].clone();
-
-    if executor.type_of() != "function" {
-        return Err(Value::new_error(agent, "executor must be a function"));
-    }
+    let (executor,) = check_args!(agent, args, "Promise", [function]);
 
     let promise = Value::new_custom_object(agent.intrinsics.promise_prototype.clone());
     promise.set_slot("promise state", Value::from("pending"));
@@ -181,10 +216,9 @@ fn get_capabilities_executor(
     args: Vec<Value>,
     ctx: &Context,
 ) -> Result<Value, Value> {
-    let f = ctx.function.clone().unwrap();
+    let f = ctx.callee();
 
-    let resolve = args.get(0).unwrap_or(&Value::Null).clone();
-    let reject = args.get(1).unwrap_or(&Value::Null).clone();
+    let (resolve, reject) = check_args!(agent, args, "executor", [optional, optional]);
 
     if f.get_slot("resolve") != Value::Null || f.get_slot("reject") != Value::Null {
         return Err(Value::new_error(agent, "type error"));
@@ -210,7 +244,7 @@ pub fn new_promise_capability(agent: &Agent, constructor: Value) -> Result<Value
 
 pub fn promise_resolve_i(agent: &Agent, c: Value, x: Value) -> Result<Value, Value> {
     if x.has_slot("promise state") {
-        let x_constructor = x.get(agent, ObjectKey::from("constructor"))?;
+        let x_constructor = x.get(agent, ObjectKey::new(agent, "constructor"))?;
         if x_constructor == c {
             return Ok(x.clone());
         }
@@ -223,52 +257,329 @@ pub fn promise_resolve_i(agent: &Agent, c: Value, x: Value) -> Result<Value, Val
 }
 
 fn promise_resolve(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let c = ctx.scope.borrow().get_this(agent)?;
-    if c.type_of() != "object" && c.type_of() != "function" {
-        return Err(Value::new_error(agent, "this must be an object"));
+    let c = ctx.this(agent)?;
+    if !c.is_object() {
+        return Err(type_error(
+            agent,
+            "Promise.resolve",
+            "receiver",
+            "an object",
+            Some(&c),
+        ));
     }
-    let x = args.get(0).unwrap_or(&Value::Null).clone();
+    let (x,) = check_args!(agent, args, "Promise.resolve", [optional]);
     promise_resolve_i(agent, c, x)
 }
 
 fn promise_reject(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let x = args.get(0).unwrap_or(&Value::Null);
-    let c = ctx.scope.borrow().get_this(agent)?;
-    if c.type_of() != "object" && c.type_of() != "function" {
-        return Err(Value::new_error(agent, "this must be an object"));
+    let c = ctx.this(agent)?;
+    if !c.is_object() {
+        return Err(type_error(
+            agent,
+            "Promise.reject",
+            "receiver",
+            "an object",
+            Some(&c),
+        ));
     }
+    let (x,) = check_args!(agent, args, "Promise.reject", [optional]);
     let capability = new_promise_capability(agent, c)?;
     capability
         .get_slot("reject")
-        .call(agent, Value::Null, vec![x.clone()])?;
+        .call(agent, Value::Null, vec![x])?;
+    Ok(capability)
+}
+
+// Settle a new promise of constructor `c` with the result of calling `f`,
+// rejecting if the call throws.
+fn promise_from_call(agent: &Agent, c: Value, f: &Value) -> Result<Value, Value> {
+    match f.call(agent, Value::Null, Vec::new()) {
+        Ok(value) => promise_resolve_i(agent, c, value),
+        Err(e) => {
+            let capability = new_promise_capability(agent, c)?;
+            capability
+                .get_slot("reject")
+                .call(agent, Value::Null, vec![e])?;
+            Ok(capability)
+        }
+    }
+}
+
+fn check_timers(agent: &Agent, name: &str) -> Result<(), Value> {
+    if agent.capabilities().timers() {
+        Ok(())
+    } else {
+        Err(Value::new_error(
+            agent,
+            &format!("{}: timers are not allowed", name),
+        ))
+    }
+}
+
+fn milliseconds(agent: &Agent, name: &str, position: &str, value: &Value) -> Result<f64, Value> {
+    match value.kind() {
+        ValueKind::Number(n) if n >= 0.0 && n.is_finite() => Ok(n),
+        _ => Err(type_error(
+            agent,
+            name,
+            position,
+            "a non-negative number",
+            Some(value),
+        )),
+    }
+}
+
+fn timeout_expired(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = ctx.callee();
+    let ms = match f.get_slot("timeout duration").kind() {
+        ValueKind::Number(n) => n,
+        _ => 0.0,
+    };
+    let e = Value::new_error(agent, &format!("timed out after {}ms", ms));
+    e.set(agent, ObjectKey::NAME, Value::from("TimeoutError"))?;
+    f.get_slot("timeout promise")
+        .get_slot("reject")
+        .call(agent, Value::Null, vec![e])
+}
+
+// the timer is dropped once the inner promise settles, so it does not keep
+// the event loop alive.
+fn timeout_settled(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = ctx.callee();
+    if let ValueKind::Number(n) = f.get_slot("timeout timer").kind() {
+        timers::clear(agent, Token(n as usize));
+    }
+    let value = args.get(0).cloned().unwrap_or(Value::Null);
+    let settle = if f.get_slot("timeout fulfilled") == Value::from(true) {
+        "resolve"
+    } else {
+        "reject"
+    };
+    f.get_slot("timeout promise")
+        .get_slot(settle)
+        .call(agent, Value::Null, vec![value])
+}
+
+/// `Promise.timeout(promiseOrFn, ms)` settles like the promise, or the result
+/// of calling the function, unless `ms` milliseconds pass first, in which
+/// case it rejects with a TimeoutError.
+fn promise_timeout(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let c = ctx.this(agent)?;
+    if !c.is_object() {
+        return Err(type_error(
+            agent,
+            "Promise.timeout",
+            "receiver",
+            "an object",
+            Some(&c),
+        ));
+    }
+    let (inner, ms) = check_args!(agent, args, "Promise.timeout", [optional, optional]);
+    let ms = milliseconds(agent, "Promise.timeout", "argument 2", &ms)?;
+    check_timers(agent, "Promise.timeout")?;
+
+    let inner = if inner.type_of() == "function" {
+        promise_from_call(agent, c.clone(), &inner)?
+    } else {
+        promise_resolve_i(agent, c.clone(), inner)?
+    };
+    let capability = new_promise_capability(agent, c)?;
+
+    let expired = Value::new_builtin_function(agent, timeout_expired);
+    expired.set_slot("timeout promise", capability.clone());
+    expired.set_slot("timeout duration", Value::from(ms));
+    let token = timers::set(agent, expired, Duration::from_millis(ms.to_u64().unwrap()));
+
+    let reactions = [true, false].iter().map(|fulfilled| {
+        let f = Value::new_builtin_function(agent, timeout_settled);
+        f.set_slot("timeout promise", capability.clone());
+        f.set_slot("timeout timer", Value::from(token.0 as f64));
+        f.set_slot("timeout fulfilled", Value::from(*fulfilled));
+        f
+    });
+    inner.get(agent, ObjectKey::new(agent, "then"))?.call(
+        agent,
+        inner.clone(),
+        reactions.collect(),
+    )?;
+
+    Ok(capability)
+}
+
+// One attempt of `Promise.retry`, called straight away for the first and by
+// a timer for the rest.
+fn retry_attempt(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let attempt = ctx.callee();
+    let count = match attempt.get_slot("retry count").kind() {
+        ValueKind::Number(n) => n + 1.0,
+        _ => unreachable!(),
+    };
+    attempt.set_slot("retry count", Value::from(count));
+
+    let capability = attempt.get_slot("retry promise");
+    let promise = promise_from_call(
+        agent,
+        agent.intrinsics.promise.clone(),
+        &attempt.get_slot("retry function"),
+    )?;
+    let on_rejected = Value::new_builtin_function(agent, retry_failed);
+    on_rejected.set_slot("retry attempt", attempt);
+    promise.get(agent, ObjectKey::new(agent, "then"))?.call(
+        agent,
+        promise.clone(),
+        vec![capability.get_slot("resolve"), on_rejected],
+    )?;
+    Ok(Value::Null)
+}
+
+fn retry_failed(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let attempt = ctx.callee().get_slot("retry attempt");
+    let reason = args.get(0).cloned().unwrap_or(Value::Null);
+    let count = match attempt.get_slot("retry count").kind() {
+        ValueKind::Number(n) => n,
+        _ => unreachable!(),
+    };
+    if Value::from(count) == attempt.get_slot("retry attempts") {
+        let e = if reason.is_object() {
+            reason
+        } else {
+            let e = Value::new_error(agent, &format!("failed after {} attempts", count));
+            e.set(agent, ObjectKey::new(agent, "cause"), reason)?;
+            e
+        };
+        e.set(agent, ObjectKey::new(agent, "attempts"), Value::from(count))?;
+        return attempt.get_slot("retry promise").get_slot("reject").call(
+            agent,
+            Value::Null,
+            vec![e],
+        );
+    }
+
+    let delay = match attempt.get_slot("retry delay").kind() {
+        ValueKind::Number(n) => n,
+        _ => 0.0,
+    };
+    if let ValueKind::Number(backoff) = attempt.get_slot("retry backoff").kind() {
+        attempt.set_slot("retry delay", Value::from(delay * backoff));
+    }
+    timers::set(
+        agent,
+        attempt,
+        Duration::from_millis(delay.to_u64().unwrap_or(std::u64::MAX)),
+    );
+    Ok(Value::Null)
+}
+
+fn retry_option(
+    agent: &Agent,
+    options: &Value,
+    name: &str,
+    default: f64,
+    valid: fn(f64) -> bool,
+    expected: &str,
+) -> Result<f64, Value> {
+    if !options.is_object() {
+        return Ok(default);
+    }
+    let value = options.get(agent, ObjectKey::new(agent, name))?;
+    match value.kind() {
+        ValueKind::Null => Ok(default),
+        ValueKind::Number(n) if valid(n) => Ok(n),
+        _ => Err(type_error(
+            agent,
+            "Promise.retry",
+            &format!("option '{}'", name),
+            expected,
+            Some(&value),
+        )),
+    }
+}
+
+/// `Promise.retry(fn, { attempts, delay, backoff }?)` calls `fn` until the
+/// promise it returns fulfills, at most `attempts` times (3 by default). It
+/// waits `delay` milliseconds after the first failure, multiplied by
+/// `backoff` after each one after that. If every attempt fails it rejects
+/// with the last error, with an `attempts` property.
+fn promise_retry(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (f, options) = check_args!(agent, args, "Promise.retry", [function, optional]);
+    let attempts = retry_option(
+        agent,
+        &options,
+        "attempts",
+        3.0,
+        |n| n >= 1.0 && n.fract() == 0.0,
+        "a positive integer",
+    )?;
+    let delay = retry_option(
+        agent,
+        &options,
+        "delay",
+        0.0,
+        |n| n >= 0.0 && n.is_finite(),
+        "a non-negative number",
+    )?;
+    let backoff = retry_option(
+        agent,
+        &options,
+        "backoff",
+        1.0,
+        |n| n >= 1.0 && n.is_finite(),
+        "a number of at least 1",
+    )?;
+    if attempts > 1.0 {
+        check_timers(agent, "Promise.retry")?;
+    }
+
+    let capability = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+    let attempt = Value::new_builtin_function(agent, retry_attempt);
+    attempt.set_slot("retry promise", capability.clone());
+    attempt.set_slot("retry function", f);
+    attempt.set_slot("retry attempts", Value::from(attempts));
+    attempt.set_slot("retry delay", Value::from(delay));
+    attempt.set_slot("retry backoff", Value::from(backoff));
+    attempt.set_slot("retry count", Value::from(0));
+    attempt.call(agent, Value::Null, Vec::new())?;
+
     Ok(capability)
 }
 
 pub fn create_promise(agent: &Agent) -> Value {
-    let p = Value::new_builtin_function(agent, promise);
+    let p = Value::new_named_builtin_function(agent, promise, "Promise", 1);
 
     p.set(
         agent,
-        ObjectKey::from("prototype"),
+        ObjectKey::new(agent, "prototype"),
         agent.intrinsics.promise_prototype.clone(),
     )
     .unwrap();
     p.set(
         agent,
-        ObjectKey::from("resolve"),
-        Value::new_builtin_function(agent, promise_resolve),
+        ObjectKey::new(agent, "resolve"),
+        Value::new_named_builtin_function(agent, promise_resolve, "resolve", 1),
+    )
+    .unwrap();
+    p.set(
+        agent,
+        ObjectKey::new(agent, "reject"),
+        Value::new_named_builtin_function(agent, promise_reject, "reject", 1),
+    )
+    .unwrap();
+    p.set(
+        agent,
+        ObjectKey::new(agent, "timeout"),
+        Value::new_named_builtin_function(agent, promise_timeout, "timeout", 2),
     )
     .unwrap();
     p.set(
         agent,
-        ObjectKey::from("reject"),
-        Value::new_builtin_function(agent, promise_reject),
+        ObjectKey::new(agent, "retry"),
+        Value::new_named_builtin_function(agent, promise_retry, "retry", 1),
     )
     .unwrap();
     agent
         .intrinsics
         .promise_prototype
-        .set(agent, ObjectKey::from("constructor"), p.clone())
+        .set(agent, ObjectKey::new(agent, "constructor"), p.clone())
         .unwrap();
 
     p
@@ -1,15 +1,25 @@
 use crate::agent::Agent;
+use crate::args::type_error;
 use crate::interpreter::Context;
-use crate::intrinsics::promise::{new_promise_capability, promise_reaction_job, promise_resolve_i};
-use crate::value::{ObjectKey, Value};
+use crate::intrinsics::promise::{enqueue_reaction, new_promise_capability, promise_resolve_i};
+use crate::value::{ObjectKey, Value, ValueKind};
 
 fn promise_proto_then(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let mut on_fulfilled = args.get(0).unwrap_or(&Value::Null).clone();
-    let mut on_rejected = args.get(1).unwrap_or(&Value::Null).clone();
+    let (mut on_fulfilled, mut on_rejected) =
+        check_args!(agent, args, "Promise.prototype.then", [optional, optional]);
 
-    let this = ctx.scope.borrow().get_this(agent)?;
+    let this = ctx.this(agent)?;
+    if !this.has_slot("promise state") {
+        return Err(type_error(
+            agent,
+            "Promise.prototype.then",
+            "receiver",
+            "a promise",
+            Some(&this),
+        ));
+    }
 
-    let constructor = this.get(agent, ObjectKey::from("constructor"))?;
+    let constructor = this.get(agent, ObjectKey::new(agent, "constructor"))?;
 
     let promise = new_promise_capability(agent, constructor)?;
 
@@ -21,7 +31,7 @@ fn promise_proto_then(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<
     }
 
     let fulfill_reaction = Value::new_custom_object(Value::Null);
-    fulfill_reaction.set_slot("kind", Value::from("resolve"));
+    fulfill_reaction.set_slot("kind", Value::from("fulfill"));
     fulfill_reaction.set_slot("promise", promise.clone());
     fulfill_reaction.set_slot("handler", on_fulfilled);
 
@@ -30,29 +40,31 @@ fn promise_proto_then(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<
     reject_reaction.set_slot("promise", promise.clone());
     reject_reaction.set_slot("handler", on_rejected);
 
+    // a rejection is only reported if nothing ever handles it
+    this.set_slot("promise handled", Value::from(true));
+
     let state = this.get_slot("promise state");
-    if let Value::String(s) = &state {
+    if let ValueKind::String(s) = state.kind() {
         match s.as_str() {
             "pending" => {
-                if let Value::List(reactions) = &this.get_slot("fulfill reactions") {
+                if let ValueKind::List(reactions) = this.get_slot("fulfill reactions").kind() {
                     reactions.borrow_mut().push_back(fulfill_reaction);
                 } else {
                     unreachable!();
                 }
-                if let Value::List(reactions) = &this.get_slot("reject reactions") {
+                if let ValueKind::List(reactions) = this.get_slot("reject reactions").kind() {
                     reactions.borrow_mut().push_back(reject_reaction);
-                    this.set_slot("promise handled", Value::from(true));
                 } else {
                     unreachable!();
                 }
             }
             "fulfilled" => {
                 let value = this.get_slot("result");
-                agent.enqueue_job(promise_reaction_job, vec![fulfill_reaction, value]);
+                enqueue_reaction(agent, fulfill_reaction, value);
             }
             "rejected" => {
                 let reason = this.get_slot("result");
-                agent.enqueue_job(promise_reaction_job, vec![reject_reaction, reason]);
+                enqueue_reaction(agent, reject_reaction, reason);
             }
             _ => unreachable!(),
         }
@@ -64,65 +76,71 @@ fn promise_proto_then(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<
 }
 
 fn promise_proto_catch(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let on_rejected = args.get(0).unwrap_or(&Value::Null).clone();
-    let this = ctx.scope.borrow().get_this(agent)?;
-    let then = this.get(agent, ObjectKey::from("then"))?;
+    let (on_rejected,) = check_args!(agent, args, "Promise.prototype.catch", [optional]);
+    let this = ctx.this(agent)?;
+    let then = this.get(agent, ObjectKey::new(agent, "then"))?;
     then.call(agent, this.clone(), vec![Value::Null, on_rejected])
 }
 
 fn value_thunk(_a: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let f = ctx.function.clone().unwrap();
+    let f = ctx.callee();
     Ok(f.get_slot("value"))
 }
 
 fn value_thrower(_a: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let f = ctx.function.clone().unwrap();
+    let f = ctx.callee();
     Err(f.get_slot("value"))
 }
 
 fn then_finally_function(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let f = ctx.function.clone().unwrap();
+    let f = ctx.callee();
     let on_finally = f.get_slot("on finally");
     let result = on_finally.call(agent, Value::Null, vec![])?;
     let c = f.get_slot("constructor");
     let promise = promise_resolve_i(agent, c, result)?;
-    let value = args.get(0).unwrap_or(&Value::Null).clone();
+    let (value,) = check_args!(agent, args, "thenFinally", [optional]);
     let value_thunk = Value::new_builtin_function(agent, value_thunk);
     value_thunk.set_slot("value", value);
     promise
-        .get(agent, ObjectKey::from("then"))?
+        .get(agent, ObjectKey::new(agent, "then"))?
         .call(agent, promise, vec![value_thunk])
 }
 
 fn catch_finally_function(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let f = ctx.function.clone().unwrap();
+    let f = ctx.callee();
     let on_finally = f.get_slot("on finally");
     let result = on_finally.call(agent, Value::Null, vec![])?;
     let c = f.get_slot("constructor");
     let promise = promise_resolve_i(agent, c, result)?;
-    let value = args.get(0).unwrap_or(&Value::Null).clone();
+    let (value,) = check_args!(agent, args, "catchFinally", [optional]);
     let thrower = Value::new_builtin_function(agent, value_thrower);
     thrower.set_slot("value", value);
     promise
-        .get(agent, ObjectKey::from("then"))?
+        .get(agent, ObjectKey::new(agent, "then"))?
         .call(agent, promise, vec![thrower])
 }
 
 fn promise_proto_finally(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let promise = ctx.scope.borrow().get_this(agent)?;
-    if promise.type_of() != "object" && promise.type_of() != "function" {
-        return Err(Value::new_error(agent, "invalid this"));
+    let promise = ctx.this(agent)?;
+    if !promise.is_object() {
+        return Err(type_error(
+            agent,
+            "Promise.prototype.finally",
+            "receiver",
+            "an object",
+            Some(&promise),
+        ));
     }
 
-    let c = promise.get(agent, ObjectKey::from("constructor"))?;
-    if c.type_of() != "object" && c.type_of() != "function" {
+    let c = promise.get(agent, ObjectKey::new(agent, "constructor"))?;
+    if !c.is_object() {
         return Err(Value::new_error(
             agent,
             "this does not derive a valid constructor",
         ));
     }
 
-    let on_finally = args.get(0).unwrap_or(&Value::Null).clone();
+    let (on_finally,) = check_args!(agent, args, "Promise.prototype.finally", [optional]);
 
     let (then_finally, catch_finally) = if on_finally.type_of() == "function" {
         let then_finally = Value::new_builtin_function(agent, then_finally_function);
@@ -136,7 +154,7 @@ fn promise_proto_finally(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Resu
         (on_finally.clone(), on_finally)
     };
 
-    promise.get(agent, ObjectKey::from("then"))?.call(
+    promise.get(agent, ObjectKey::new(agent, "then"))?.call(
         agent,
         promise,
         vec![then_finally, catch_finally],
@@ -148,20 +166,20 @@ pub fn create_promise_prototype(agent: &Agent) -> Value {
 
     p.set(
         agent,
-        ObjectKey::from("then"),
-        Value::new_builtin_function(agent, promise_proto_then),
+        ObjectKey::new(agent, "then"),
+        Value::new_named_builtin_function(agent, promise_proto_then, "then", 2),
     )
     .expect("unable to set then on promise prototype");
     p.set(
         agent,
-        ObjectKey::from("catch"),
-        Value::new_builtin_function(agent, promise_proto_catch),
+        ObjectKey::new(agent, "catch"),
+        Value::new_named_builtin_function(agent, promise_proto_catch, "catch", 1),
     )
     .expect("unable to set catch on promise prototype");
     p.set(
         agent,
-        ObjectKey::from("finally"),
-        Value::new_builtin_function(agent, promise_proto_finally),
+        ObjectKey::new(agent, "finally"),
+        Value::new_named_builtin_function(agent, promise_proto_finally, "finally", 1),
     )
     .expect("unable to set finally on promise prototype");
 
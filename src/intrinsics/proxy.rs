@@ -0,0 +1,96 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, Value};
+
+// looks up the trap `name` on `handler`, None means forward to the target.
+fn trap(agent: &Agent, handler: &Value, name: &str) -> Result<Option<Value>, Value> {
+    let f = handler.get(agent, ObjectKey::new(agent, name))?;
+    match f.type_of() {
+        "null" => Ok(None),
+        "function" => Ok(Some(f)),
+        _ => Err(Value::new_error(
+            agent,
+            &format!("proxy trap '{}' is not a function", name),
+        )),
+    }
+}
+
+pub fn get(
+    agent: &Agent,
+    target: &Value,
+    handler: &Value,
+    key: ObjectKey,
+    receiver: Value,
+) -> Result<Value, Value> {
+    match trap(agent, handler, "get")? {
+        Some(f) => f.call(
+            agent,
+            handler.clone(),
+            vec![target.clone(), key.to_value(), receiver],
+        ),
+        None => target.get(agent, key),
+    }
+}
+
+pub fn set(
+    agent: &Agent,
+    target: &Value,
+    handler: &Value,
+    key: ObjectKey,
+    value: Value,
+    receiver: Value,
+) -> Result<Value, Value> {
+    match trap(agent, handler, "set")? {
+        Some(f) => {
+            let args = vec![target.clone(), key.to_value(), value.clone(), receiver];
+            if f.call(agent, handler.clone(), args)?.to_bool() {
+                Ok(value)
+            } else {
+                Err(Value::new_error(
+                    agent,
+                    &format!("proxy set trap returned false for '{}'", key),
+                ))
+            }
+        }
+        None => target.set(agent, key, value),
+    }
+}
+
+pub fn has(agent: &Agent, target: &Value, handler: &Value, key: ObjectKey) -> Result<bool, Value> {
+    match trap(agent, handler, "has")? {
+        Some(f) => Ok(f
+            .call(agent, handler.clone(), vec![target.clone(), key.to_value()])?
+            .to_bool()),
+        None => target.has(agent, key),
+    }
+}
+
+pub fn delete_property(
+    agent: &Agent,
+    target: &Value,
+    handler: &Value,
+    key: ObjectKey,
+) -> Result<bool, Value> {
+    match trap(agent, handler, "deleteProperty")? {
+        Some(f) => Ok(f
+            .call(agent, handler.clone(), vec![target.clone(), key.to_value()])?
+            .to_bool()),
+        None => target.delete(agent, key),
+    }
+}
+
+fn proxy(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match (args.get(0), args.get(1)) {
+        (Some(target), Some(handler)) if target.is_object() && handler.is_object() => {
+            Ok(Value::new_proxy(target.clone(), handler.clone()))
+        }
+        _ => Err(Value::new_error(
+            agent,
+            "target and handler must be objects",
+        )),
+    }
+}
+
+pub fn create_proxy(agent: &Agent) -> Value {
+    Value::new_named_builtin_function(agent, proxy, "Proxy", 2)
+}
@@ -0,0 +1,140 @@
+//! Ranges, `start..end` and `start..=end`, iterate the integers from `start`
+//! up to `end`, which `..` excludes and `..=` includes. Both bounds must be
+//! integers, anything else throws. A range whose start is past its end is
+//! empty rather than counting down, `rev()` iterates one backwards.
+
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, Value, ValueKind};
+
+fn integer(value: &Value) -> Option<f64> {
+    match value.kind() {
+        ValueKind::Number(n) if n.fract() == 0.0 => Some(n),
+        _ => None,
+    }
+}
+
+pub fn create_range(
+    agent: &Agent,
+    start: &Value,
+    end: &Value,
+    inclusive: bool,
+) -> Result<Value, Value> {
+    let (start, end) = match (integer(start), integer(end)) {
+        (Some(start), Some(end)) => (start, if inclusive { end + 1.0 } else { end }),
+        _ => return Err(Value::new_error(agent, "range bounds must be integers")),
+    };
+    // an empty range ends where it starts
+    let end = end.max(start);
+    let range = Value::new_custom_object(agent.intrinsics.range_prototype.clone());
+    range.set_slot("range start", Value::from(start));
+    range.set_slot("range end", Value::from(end));
+    range.set(agent, ObjectKey::LENGTH, Value::from(end - start))?;
+    Ok(range)
+}
+
+fn this_bounds(agent: &Agent, ctx: &Context) -> Result<(f64, f64), Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() == "object" && this.has_slot("range start") {
+        match (
+            this.get_slot("range start").kind(),
+            this.get_slot("range end").kind(),
+        ) {
+            (ValueKind::Number(start), ValueKind::Number(end)) => Ok((start, end)),
+            _ => unreachable!(),
+        }
+    } else {
+        Err(Value::new_error(agent, "invalid receiver"))
+    }
+}
+
+fn range_iterator(agent: &Agent, next: f64, end: f64, step: f64) -> Value {
+    let it = Value::new_custom_object(agent.intrinsics.range_iterator_prototype.clone());
+    it.set_slot("range iterator next", Value::from(next));
+    it.set_slot("range iterator end", Value::from(end));
+    it.set_slot("range iterator step", Value::from(step));
+    it
+}
+
+fn iterator(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (start, end) = this_bounds(agent, ctx)?;
+    Ok(range_iterator(agent, start, end, 1.0))
+}
+
+fn rev(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (start, end) = this_bounds(agent, ctx)?;
+    Ok(range_iterator(agent, end - 1.0, start - 1.0, -1.0))
+}
+
+fn contains(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (start, end) = this_bounds(agent, ctx)?;
+    Ok(Value::from(match args.get(0).and_then(integer) {
+        Some(n) => n >= start && n < end,
+        None => false,
+    }))
+}
+
+fn next(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let it = ctx.this(agent)?;
+    if it.type_of() != "object" || !it.has_slot("range iterator next") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    match (
+        it.get_slot("range iterator next").kind(),
+        it.get_slot("range iterator end").kind(),
+        it.get_slot("range iterator step").kind(),
+    ) {
+        (ValueKind::Number(next), ValueKind::Number(end), ValueKind::Number(step)) => {
+            if next == end {
+                return Value::new_iter_result(agent, Value::Null, true);
+            }
+            it.set_slot("range iterator next", Value::from(next + step));
+            Value::new_iter_result(agent, Value::from(next), false)
+        }
+        _ => unreachable!(),
+    }
+}
+
+pub fn create_range_iterator_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.iterator_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+
+    proto
+}
+
+// ranges inherit the iterator helpers, which iterate them from the start.
+pub fn create_range_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.iterator_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("contains", contains, 1);
+    method!("rev", rev, 0);
+
+    proto
+        .set(
+            agent,
+            ObjectKey::well_known_symbol("iterator"),
+            Value::new_builtin_function(agent, iterator),
+        )
+        .unwrap();
+
+    proto
+}
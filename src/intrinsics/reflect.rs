@@ -0,0 +1,184 @@
+use crate::agent::Agent;
+use crate::args::{object, type_error};
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, Value, ValueKind};
+
+fn key_arg(agent: &Agent, args: &[Value]) -> Result<ObjectKey, Value> {
+    args.get(1).unwrap_or(&Value::Null).to_object_key(agent)
+}
+
+// turns an array-like argument into a list of arguments.
+fn list_arg(agent: &Agent, args: &[Value], name: &str, index: usize) -> Result<Vec<Value>, Value> {
+    let list = match args.get(index) {
+        Some(list) if list.is_object() || list.type_of() == "tuple" => list,
+        list => {
+            return Err(type_error(
+                agent,
+                name,
+                &format!("argument {}", index + 1),
+                "an array-like object",
+                list,
+            ))
+        }
+    };
+    let length = match list.get(agent, ObjectKey::LENGTH)?.kind() {
+        ValueKind::Number(n) if n > 0.0 => n as usize,
+        _ => 0,
+    };
+    let mut values = Vec::with_capacity(length);
+    for i in 0..length {
+        values.push(list.get(agent, ObjectKey::from(i))?);
+    }
+    Ok(values)
+}
+
+fn apply(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (target, this) = check_args!(agent, args, "Reflect.apply", [function, optional]);
+    target.call(agent, this, list_arg(agent, &args, "Reflect.apply", 2)?)
+}
+
+fn construct(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (target,) = check_args!(agent, args, "Reflect.construct", [function]);
+    let new_target = match args.get(2) {
+        Some(new_target) if new_target.type_of() == "function" => new_target.clone(),
+        Some(new_target) => {
+            return Err(type_error(
+                agent,
+                "Reflect.construct",
+                "argument 3",
+                "a function",
+                Some(new_target),
+            ))
+        }
+        None => target.clone(),
+    };
+    target.construct(
+        agent,
+        list_arg(agent, &args, "Reflect.construct", 1)?,
+        new_target,
+    )
+}
+
+fn get(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let target = object(agent, &args, "Reflect.get", 0)?;
+    target.get(agent, key_arg(agent, &args)?)
+}
+
+fn set(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let target = object(agent, &args, "Reflect.set", 0)?;
+    let key = key_arg(agent, &args)?;
+    if target.is_frozen(agent)?
+        || (!target.is_extensible(agent)? && !target.has_own_property(agent, key.clone())?)
+    {
+        return Ok(Value::from(false));
+    }
+    let value = args.get(2).cloned().unwrap_or(Value::Null);
+    target.set(agent, key, value)?;
+    Ok(Value::from(true))
+}
+
+fn has(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let target = object(agent, &args, "Reflect.has", 0)?;
+    Ok(Value::from(target.has(agent, key_arg(agent, &args)?)?))
+}
+
+fn own_keys(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let target = object(agent, &args, "Reflect.ownKeys", 0)?;
+    let keys = target
+        .keys(agent)?
+        .iter()
+        .map(|key| key.to_value())
+        .collect::<Vec<Value>>();
+    Ok(Value::new_array_from_vec(agent, keys))
+}
+
+fn delete_property(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let target = object(agent, &args, "Reflect.deleteProperty", 0)?;
+    Ok(Value::from(target.delete(agent, key_arg(agent, &args)?)?))
+}
+
+// only data properties exist, so the descriptor may only carry a `value`.
+fn define_property(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let target = object(agent, &args, "Reflect.defineProperty", 0)?;
+    let key = key_arg(agent, &args)?;
+    let descriptor = match args.get(2) {
+        Some(d) if d.type_of() == "object" => d.clone(),
+        descriptor => {
+            return Err(type_error(
+                agent,
+                "Reflect.defineProperty",
+                "argument 3",
+                "an object",
+                descriptor,
+            ))
+        }
+    };
+    if descriptor.has(agent, ObjectKey::new(agent, "get"))?
+        || descriptor.has(agent, ObjectKey::new(agent, "set"))?
+    {
+        return Err(Value::new_error(
+            agent,
+            "accessor properties are not supported",
+        ));
+    }
+    if target.is_frozen(agent)?
+        || (!target.is_extensible(agent)? && !target.has_own_property(agent, key.clone())?)
+    {
+        return Ok(Value::from(false));
+    }
+    let value = descriptor.get(agent, ObjectKey::new(agent, "value"))?;
+    target.set(agent, key, value)?;
+    Ok(Value::from(true))
+}
+
+fn get_prototype_of(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    object(agent, &args, "Reflect.getPrototypeOf", 0)?.get_prototype_of(agent)
+}
+
+fn set_prototype_of(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let target = object(agent, &args, "Reflect.setPrototypeOf", 0)?;
+    let prototype = args.get(1).cloned().unwrap_or(Value::Null);
+    Ok(Value::from(target.set_prototype_of(agent, prototype)?))
+}
+
+fn is_extensible(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    Ok(Value::from(
+        object(agent, &args, "Reflect.isExtensible", 0)?.is_extensible(agent)?,
+    ))
+}
+
+fn prevent_extensions(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    object(agent, &args, "Reflect.preventExtensions", 0)?.prevent_extensions(agent)?;
+    Ok(Value::from(true))
+}
+
+pub fn create_reflect(agent: &Agent) -> Value {
+    let reflect = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            reflect
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("apply", apply, 3);
+    method!("construct", construct, 2);
+    method!("get", get, 2);
+    method!("set", set, 3);
+    method!("has", has, 2);
+    method!("ownKeys", own_keys, 1);
+    method!("deleteProperty", delete_property, 2);
+    method!("defineProperty", define_property, 3);
+    method!("getPrototypeOf", get_prototype_of, 1);
+    method!("setPrototypeOf", set_prototype_of, 2);
+    method!("isExtensible", is_extensible, 1);
+    method!("preventExtensions", prevent_extensions, 1);
+
+    reflect
+}
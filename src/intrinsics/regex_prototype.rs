@@ -1,14 +1,14 @@
 use crate::agent::Agent;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, ObjectKind, Value};
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
 
 fn match_(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
-    match this {
-        Value::Object(o) => {
+    let this = ctx.this(agent)?;
+    match this.kind() {
+        ValueKind::Object(o) => {
             if let ObjectKind::Regex(re) = &o.kind {
                 let mut args = args;
-                if let Some(Value::String(s)) = args.pop() {
+                if let Some(ValueKind::String(s)) = args.pop().as_ref().map(Value::kind) {
                     if let Some(captures) = re.captures(s.as_str()) {
                         let o = Value::new_array(agent);
                         let mut i = 0;
@@ -17,7 +17,7 @@ fn match_(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value
                                 Some(s) => {
                                     o.set(
                                         agent,
-                                        ObjectKey::from(s),
+                                        ObjectKey::new(agent, s),
                                         Value::from(captures.name(s).unwrap().as_str()),
                                     )?;
                                 }
@@ -47,13 +47,13 @@ fn match_(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value
 }
 
 fn test(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    let this = ctx.scope.borrow().get_this(agent)?;
-    match this {
-        Value::Object(o) => {
+    let this = ctx.this(agent)?;
+    match this.kind() {
+        ValueKind::Object(o) => {
             if let ObjectKind::Regex(re) = &o.kind {
                 let mut args = args;
-                match args.pop().unwrap_or(Value::Null) {
-                    Value::String(s) => Ok(Value::from(re.is_match(s.as_str()))),
+                match args.pop().unwrap_or(Value::Null).kind() {
+                    ValueKind::String(s) => Ok(Value::from(re.is_match(s.as_str()))),
                     _ => Err(Value::new_error(agent, "input must be a string")),
                 }
             } else {
@@ -70,16 +70,16 @@ pub fn create_regex_prototype(agent: &Agent) -> Value {
     proto
         .set(
             agent,
-            ObjectKey::from("match"),
-            Value::new_builtin_function(agent, match_),
+            ObjectKey::new(agent, "match"),
+            Value::new_named_builtin_function(agent, match_, "match", 1),
         )
         .unwrap();
 
     proto
         .set(
             agent,
-            ObjectKey::from("test"),
-            Value::new_builtin_function(agent, test),
+            ObjectKey::new(agent, "test"),
+            Value::new_named_builtin_function(agent, test, "test", 1),
         )
         .unwrap();
 
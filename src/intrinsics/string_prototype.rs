@@ -1,47 +1,304 @@
+//! Strings are sequences of Unicode scalar values. `length`, indexing,
+//! iteration, `slice` and `indexOf` all count scalar values, never UTF-8
+//! bytes or UTF-16 units, so none of them can split a character. A character
+//! followed by combining marks is still several scalar values.
+
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, ObjectKind};
+use crate::value::{ObjectKey, ObjectKind, ValueKind};
 use crate::{Agent, Value};
 use unic::normal::StrNormalForm;
 
+// the scalar values of the receiver, which is a string or a String object.
+fn this_chars(agent: &Agent, ctx: &Context) -> Result<Vec<char>, Value> {
+    match ctx.this(agent)?.kind() {
+        ValueKind::String(s) => Ok(s.chars().collect()),
+        ValueKind::Object(o) => match &o.kind {
+            ObjectKind::String(s) => Ok(s.clone()),
+            _ => Err(Value::new_error(agent, "invalid receiver")),
+        },
+        _ => Err(Value::new_error(agent, "invalid receiver")),
+    }
+}
+
+// an index argument, counting back from `len` when negative and clamped to
+// the string.
+fn relative_index(arg: Option<&Value>, len: usize, default: usize) -> usize {
+    match arg.map(Value::kind) {
+        Some(ValueKind::Number(n)) if n.is_nan() => 0,
+        Some(ValueKind::Number(n)) => {
+            let n = n.trunc();
+            if n < 0.0 {
+                (len as f64 + n).max(0.0) as usize
+            } else {
+                n.min(len as f64) as usize
+            }
+        }
+        _ => default,
+    }
+}
+
 fn normalize(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
-    if let Value::Object(o) = ctx.scope.borrow().get_this(agent)? {
-        if let ObjectKind::String(s) = &o.kind {
-            match args.get(0).unwrap_or(&Value::Null) {
-                Value::String(form) => Ok(Value::from(match form.as_str() {
-                    "NFC" => s.iter().cloned().nfc().collect::<String>(),
-                    "NFD" => s.iter().cloned().nfd().collect::<String>(),
-                    "NFKC" => s.iter().cloned().nfkc().collect::<String>(),
-                    "NFKD" => s.iter().cloned().nfkd().collect::<String>(),
-                    _ => {
+    let s = this_chars(agent, ctx)?;
+    match args.get(0).unwrap_or(&Value::Null).kind() {
+        ValueKind::String(form) => Ok(Value::from(match form.as_str() {
+            "NFC" => s.iter().cloned().nfc().collect::<String>(),
+            "NFD" => s.iter().cloned().nfd().collect::<String>(),
+            "NFKC" => s.iter().cloned().nfkc().collect::<String>(),
+            "NFKD" => s.iter().cloned().nfkd().collect::<String>(),
+            _ => {
+                return Err(Value::new_error(
+                    agent,
+                    "The normalization form should be one of NFC, NFD, NFKC, NFKD.",
+                ));
+            }
+        })),
+        ValueKind::Null => Ok(Value::from(s.iter().cloned().nfc().collect::<String>())),
+        _ => Err(Value::new_error(
+            agent,
+            "The normalization form should be one of NFC, NFD, NFKC, NFKD.",
+        )),
+    }
+}
+
+// iterates the characters of the string as one character strings.
+fn chars(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    this_chars(agent, ctx)?;
+    let it = Value::new_custom_object(agent.intrinsics.array_iterator_prototype.clone());
+    it.set_slot("array iterator next index", Value::from(0));
+    it.set_slot("iterated object", ctx.this(agent)?.to_object(agent)?);
+    Ok(it)
+}
+
+fn code_points(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let s = this_chars(agent, ctx)?;
+    let points = s
+        .into_iter()
+        .map(|c| Value::from(f64::from(u32::from(c))))
+        .collect();
+    let it = Value::new_custom_object(agent.intrinsics.array_iterator_prototype.clone());
+    it.set_slot("array iterator next index", Value::from(0));
+    it.set_slot("iterated object", Value::new_array_from_vec(agent, points));
+    Ok(it)
+}
+
+fn slice(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let s = this_chars(agent, ctx)?;
+    let start = relative_index(args.get(0), s.len(), 0);
+    let end = relative_index(args.get(1), s.len(), s.len());
+    if start >= end {
+        return Ok(Value::from(""));
+    }
+    Ok(Value::from(s[start..end].iter().collect::<String>()))
+}
+
+fn index_of(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let s = this_chars(agent, ctx)?;
+    let search = match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(search)) => search.chars().collect::<Vec<char>>(),
+        _ => return Err(Value::new_error(agent, "search must be a string")),
+    };
+    let from = relative_index(args.get(1), s.len(), 0);
+    if search.is_empty() {
+        return Ok(Value::from(from as f64));
+    }
+    let found = (from..s.len())
+        .find(|i| s[*i..].starts_with(&search))
+        .map_or(-1.0, |i| i as f64);
+    Ok(Value::from(found))
+}
+
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    width: usize,
+    precision: Option<usize>,
+}
+
+// `[[fill]align][width][.precision]`, where align is one of `<`, `>` or `^`.
+fn parse_spec(spec: &[char]) -> Option<Spec> {
+    let align = |c: Option<&char>| match c {
+        Some('<') => Some(Align::Left),
+        Some('>') => Some(Align::Right),
+        Some('^') => Some(Align::Center),
+        _ => None,
+    };
+    let (fill, align, mut i) = match (align(spec.get(1)), align(spec.get(0))) {
+        (Some(a), _) => (spec[0], Some(a), 2),
+        (None, Some(a)) => (' ', Some(a), 1),
+        (None, None) => (' ', None, 0),
+    };
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while *i < spec.len() && spec[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        spec[start..*i]
+            .iter()
+            .collect::<String>()
+            .parse::<usize>()
+            .ok()
+    };
+    let width = digits(&mut i).unwrap_or(0);
+    let precision = if spec.get(i) == Some(&'.') {
+        i += 1;
+        Some(digits(&mut i)?)
+    } else {
+        None
+    };
+    if i != spec.len() {
+        return None;
+    }
+    Some(Spec {
+        fill,
+        align,
+        width,
+        precision,
+    })
+}
+
+// precision is fixed decimals for numbers and a maximum length for anything
+// else. numbers align right by default, everything else left.
+fn format_value(agent: &Agent, value: &Value, spec: &Spec) -> Result<String, Value> {
+    let (s, align) = match value.kind() {
+        ValueKind::Number(n) => {
+            let s = match spec.precision {
+                Some(p) if n.is_finite() => format!("{:.*}", p, n),
+                _ => crate::num_util::to_string(n),
+            };
+            (s, Align::Right)
+        }
+        v => {
+            // null has no object to convert it with
+            let s = match v {
+                ValueKind::Null => "null".to_string(),
+                _ => match value.to_string(agent)?.kind() {
+                    ValueKind::String(s) => s.to_string(),
+                    _ => unreachable!(),
+                },
+            };
+            let s = match spec.precision {
+                Some(p) => s.chars().take(p).collect(),
+                None => s,
+            };
+            (s, Align::Left)
+        }
+    };
+    let pad = spec.width.saturating_sub(s.chars().count());
+    let (left, right) = match spec.align.as_ref().unwrap_or(&align) {
+        Align::Left => (0, pad),
+        Align::Right => (pad, 0),
+        Align::Center => (pad / 2, pad - pad / 2),
+    };
+    let fill = |n: usize| std::iter::repeat(spec.fill).take(n).collect::<String>();
+    Ok(format!("{}{}{}", fill(left), s, fill(right)))
+}
+
+// `{0}` is a positional argument and `{key}` a property of the last
+// argument, either followed by `:spec`. `{{` and `}}` are literal braces.
+fn format(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let s = this_chars(agent, ctx)?;
+    let mut out = String::new();
+    let mut i = 0;
+    while i < s.len() {
+        match (s[i], s.get(i + 1)) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                out.push(s[i]);
+                i += 2;
+            }
+            ('}', _) => {
+                return Err(Value::new_error(agent, "unmatched '}' in format string"));
+            }
+            ('{', _) => {
+                let end = match s[i + 1..].iter().position(|c| *c == '{' || *c == '}') {
+                    Some(n) if s[i + 1 + n] == '}' => i + 1 + n,
+                    _ => return Err(Value::new_error(agent, "unmatched '{' in format string")),
+                };
+                let placeholder = s[i..=end].iter().collect::<String>();
+                let inner = &s[i + 1..end];
+                let (field, spec) = match inner.iter().position(|c| *c == ':') {
+                    Some(n) => (&inner[..n], &inner[n + 1..]),
+                    None => (inner, &[][..]),
+                };
+                let spec = match parse_spec(spec) {
+                    Some(spec) => spec,
+                    None => {
+                        return Err(Value::new_error(
+                            agent,
+                            &format!("invalid format spec in {}", placeholder),
+                        ));
+                    }
+                };
+                let field = field.iter().collect::<String>();
+                let value = if !field.is_empty() && field.chars().all(|c| c.is_ascii_digit()) {
+                    field
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| args.get(n))
+                        .cloned()
+                } else {
+                    match args.last() {
+                        Some(named) if !field.is_empty() && named.type_of() == "object" => {
+                            let key = ObjectKey::new(agent, field.as_str());
+                            if named.has(agent, key.clone())? {
+                                Some(named.get(agent, key)?)
+                            } else {
+                                None
+                            }
+                        }
+                        _ => None,
+                    }
+                };
+                match value {
+                    Some(value) => out.push_str(&format_value(agent, &value, &spec)?),
+                    None => {
                         return Err(Value::new_error(
                             agent,
-                            "The normalization form should be one of NFC, NFD, NFKC, NFKD.",
+                            &format!("unknown placeholder {}", placeholder),
                         ));
                     }
-                })),
-                Value::Null => Ok(Value::from(s.iter().cloned().nfc().collect::<String>())),
-                _ => {
-                    return Err(Value::new_error(
-                        agent,
-                        "The normalization form should be one of NFC, NFD, NFKC, NFKD.",
-                    ));
                 }
+                i = end + 1;
+            }
+            (c, _) => {
+                out.push(c);
+                i += 1;
             }
-        } else {
-            Err(Value::new_error(agent, "invalid receiver"))
         }
-    } else {
-        Err(Value::new_error(agent, "invalid receiver"))
     }
+    Ok(Value::from(out))
 }
 
 pub fn create_string_prototype(agent: &Agent) -> Value {
     let p = Value::new_object(agent.intrinsics.object_prototype.clone());
 
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            p.set(
+                agent,
+                ObjectKey::new(agent, $name),
+                Value::new_named_builtin_function(agent, $fn, $name, $length),
+            )
+            .unwrap();
+        };
+    }
+
+    method!("normalize", normalize, 1);
+    method!("chars", chars, 0);
+    method!("codePoints", code_points, 0);
+    method!("slice", slice, 2);
+    method!("indexOf", index_of, 2);
+    method!("format", format, 0);
+
     p.set(
         agent,
-        ObjectKey::from("normalize"),
-        Value::new_builtin_function(agent, normalize),
+        ObjectKey::well_known_symbol("iterator"),
+        Value::new_builtin_function(agent, chars),
     )
     .unwrap();
 
@@ -1,10 +1,11 @@
 use crate::agent::Agent;
+use crate::args::type_error;
 use crate::interpreter::Context;
-use crate::value::{ObjectKey, Value};
+use crate::value::{ObjectKey, ObjectKind, Symbol, Value, ValueKind};
 
 fn symbol(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
-    let desc = match args.get(0) {
-        Some(Value::String(s)) => Ok(Some(s.clone())),
+    let desc = match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => Ok(Some(s.clone())),
         Some(_) => Err(Value::new_error(agent, "invalid description")),
         None => Ok(None),
     }?;
@@ -12,35 +13,122 @@ fn symbol(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Valu
 }
 
 fn private(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
-    let desc = match args.get(0) {
-        Some(Value::String(s)) => Ok(Some(s.clone())),
+    let desc = match args.get(0).map(Value::kind) {
+        Some(ValueKind::String(s)) => Ok(Some(s.clone())),
         Some(_) => Err(Value::new_error(agent, "invalid description")),
         None => Ok(None),
     }?;
     Ok(Value::new_private_symbol(desc))
 }
 
+// registered symbols are identified by their key alone, so `Symbol.for('a')`
+// is the same symbol as the literal `:a`, in every agent.
+fn symbol_for(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let (key,) = check_args!(agent, args, "Symbol.for", [string]);
+    Ok(Value::new_well_known_symbol(&key))
+}
+
+fn key_for(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Symbol(Symbol::Registered(key))) => Ok(Value::from(key.as_str())),
+        Some(ValueKind::Symbol(..)) => Ok(Value::Null),
+        _ => Err(type_error(
+            agent,
+            "Symbol.keyFor",
+            "argument 1",
+            "a symbol",
+            args.get(0),
+        )),
+    }
+}
+
+fn this_symbol(agent: &Agent, ctx: &Context, name: &str) -> Result<Symbol, Value> {
+    let this = ctx.this(agent)?;
+    if let ValueKind::Object(o) = this.kind() {
+        if let ObjectKind::Symbol(s) = &o.kind {
+            return Ok(s.clone());
+        }
+    }
+    Err(type_error(agent, name, "receiver", "a symbol", Some(&this)))
+}
+
+fn to_string(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let s = this_symbol(agent, ctx, "Symbol.prototype.toString")?;
+    Ok(Value::from(s.to_string()))
+}
+
+fn description(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    match &this_symbol(agent, ctx, "Symbol.prototype.description")? {
+        Symbol::Registered(key) => Ok(Value::from(key.clone())),
+        Symbol::Unregistered {
+            description: Some(description),
+            ..
+        } => Ok(Value::from(description.clone())),
+        Symbol::Unregistered { .. } => Ok(Value::Null),
+    }
+}
+
 pub fn create_symbol(agent: &Agent) -> Value {
-    let s = Value::new_builtin_function(agent, symbol);
+    let s = Value::new_named_builtin_function(agent, symbol, "Symbol", 0);
 
     s.set(
         agent,
-        ObjectKey::from("prototype"),
+        ObjectKey::new(agent, "prototype"),
         agent.intrinsics.symbol_prototype.clone(),
     )
     .expect("failed to set prototype on symbol constructor");
     agent
         .intrinsics
         .symbol_prototype
-        .set(agent, ObjectKey::from("constructor"), s.clone())
+        .set(agent, ObjectKey::new(agent, "constructor"), s.clone())
         .expect("failed to set constructor on symbol prototype");
 
     s.set(
         agent,
-        ObjectKey::from("private"),
-        Value::new_builtin_function(agent, private),
+        ObjectKey::new(agent, "private"),
+        Value::new_named_builtin_function(agent, private, "private", 1),
     )
     .expect("failed to set private on symbol constructor");
+    s.set(
+        agent,
+        ObjectKey::new(agent, "for"),
+        Value::new_named_builtin_function(agent, symbol_for, "for", 1),
+    )
+    .expect("failed to set for on symbol constructor");
+    s.set(
+        agent,
+        ObjectKey::new(agent, "keyFor"),
+        Value::new_named_builtin_function(agent, key_for, "keyFor", 1),
+    )
+    .expect("failed to set keyFor on symbol constructor");
+    s.set(
+        agent,
+        ObjectKey::new(agent, "iterator"),
+        Value::new_well_known_symbol("iterator"),
+    )
+    .expect("failed to set iterator on symbol constructor");
+    s.set(
+        agent,
+        ObjectKey::new(agent, "asyncIterator"),
+        Value::new_well_known_symbol("asyncIterator"),
+    )
+    .expect("failed to set asyncIterator on symbol constructor");
+
+    let proto = &agent.intrinsics.symbol_prototype;
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "toString"),
+            Value::new_named_builtin_function(agent, to_string, "toString", 0),
+        )
+        .expect("failed to set toString on symbol prototype");
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "description"),
+            Value::new_named_builtin_function(agent, description, "description", 0),
+        )
+        .expect("failed to set description on symbol prototype");
 
     s
 }
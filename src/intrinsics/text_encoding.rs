@@ -0,0 +1,321 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::array_buffer::to_array_buffer;
+use crate::intrinsics::bytes_prototype::to_bytes;
+use crate::intrinsics::typed_array::{set_element, view_length, TypedArrayKind};
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
+
+// the bytes of anything which holds bytes: an ArrayBuffer, a typed array or
+// DataView over one, or Bytes.
+pub fn buffer_source_bytes(agent: &Agent, value: &Value) -> Result<Vec<u8>, Value> {
+    if let Some(bytes) = to_bytes(value) {
+        return Ok(bytes);
+    }
+    if let Some(data) = to_array_buffer(value) {
+        return Ok(data.borrow().clone());
+    }
+    if let ValueKind::Object(o) = value.kind() {
+        if let ObjectKind::TypedArray {
+            kind,
+            buffer,
+            offset,
+            length,
+        } = &o.kind
+        {
+            let length = view_length(*kind, buffer, *offset, *length) * kind.size();
+            let data = to_array_buffer(buffer).unwrap().borrow();
+            return Ok(data[*offset..*offset + length].to_vec());
+        }
+    }
+    if value.type_of() == "object" && value.has_slot("data view buffer") {
+        if let (ValueKind::Number(offset), ValueKind::Number(length)) = (
+            value.get_slot("data view offset").kind(),
+            value.get_slot("data view length").kind(),
+        ) {
+            let (offset, length) = (offset as usize, length as usize);
+            let buffer = value.get_slot("data view buffer");
+            let data = to_array_buffer(&buffer).unwrap().borrow();
+            if offset + length <= data.len() {
+                return Ok(data[offset..offset + length].to_vec());
+            }
+            return Ok(Vec::new());
+        }
+    }
+    Err(Value::new_error(
+        agent,
+        "input must be an ArrayBuffer, a view of one, or Bytes",
+    ))
+}
+
+pub fn new_uint8_array(agent: &Agent, bytes: Vec<u8>) -> Result<Value, Value> {
+    let prototype = agent
+        .intrinsics
+        .uint8_array
+        .get(agent, ObjectKey::new(agent, "prototype"))?;
+    let length = bytes.len();
+    let buffer = Value::new_array_buffer(agent, bytes);
+    Ok(Value::new_typed_array(
+        prototype,
+        TypedArrayKind::Uint8,
+        buffer,
+        0,
+        length,
+    ))
+}
+
+fn string_arg(agent: &Agent, value: Option<&Value>) -> Result<String, Value> {
+    match value.map(Value::kind) {
+        None | Some(ValueKind::Null) => Ok(String::new()),
+        Some(ValueKind::String(s)) => Ok(s.clone()),
+        Some(_) => Err(Value::new_error(agent, "input must be a string")),
+    }
+}
+
+fn text_encoder(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let prototype = ctx
+        .callee()
+        .get(agent, ObjectKey::new(agent, "prototype"))?;
+    let encoder = Value::new_custom_object(prototype);
+    encoder.set_slot("text encoder", Value::from(true));
+    encoder.set(
+        agent,
+        ObjectKey::new(agent, "encoding"),
+        Value::from("utf-8"),
+    )?;
+    Ok(encoder)
+}
+
+fn this_text_encoder(agent: &Agent, ctx: &Context) -> Result<(), Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() == "object" && this.has_slot("text encoder") {
+        Ok(())
+    } else {
+        Err(Value::new_error(agent, "invalid receiver"))
+    }
+}
+
+fn encode(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    this_text_encoder(agent, ctx)?;
+    let s = string_arg(agent, args.get(0))?;
+    new_uint8_array(agent, s.into_bytes())
+}
+
+// writes as many whole characters as fit, never splitting one across the end
+// of the destination.
+fn encode_into(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    this_text_encoder(agent, ctx)?;
+    let s = string_arg(agent, args.get(0))?;
+    let (buffer, offset, length) = match args.get(1).map(Value::kind) {
+        Some(ValueKind::Object(o)) => match &o.kind {
+            ObjectKind::TypedArray {
+                kind: TypedArrayKind::Uint8,
+                buffer,
+                offset,
+                length,
+            } => (
+                buffer.clone(),
+                *offset,
+                view_length(TypedArrayKind::Uint8, buffer, *offset, *length),
+            ),
+            _ => return Err(Value::new_error(agent, "destination must be a Uint8Array")),
+        },
+        _ => return Err(Value::new_error(agent, "destination must be a Uint8Array")),
+    };
+
+    let mut read = 0;
+    let mut written = 0;
+    let mut units = [0; 4];
+    for c in s.chars() {
+        let encoded = c.encode_utf8(&mut units).as_bytes();
+        if written + encoded.len() > length {
+            break;
+        }
+        for byte in encoded {
+            set_element(
+                TypedArrayKind::Uint8,
+                &buffer,
+                offset,
+                written,
+                f64::from(*byte),
+            );
+            written += 1;
+        }
+        read += 1;
+    }
+
+    let result = Value::new_object(agent.intrinsics.object_prototype.clone());
+    result.set(
+        agent,
+        ObjectKey::new(agent, "read"),
+        Value::from(read as f64),
+    )?;
+    result.set(
+        agent,
+        ObjectKey::new(agent, "written"),
+        Value::from(written as f64),
+    )?;
+    Ok(result)
+}
+
+pub fn create_text_encoder(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("encode", encode, 0);
+    method!("encodeInto", encode_into, 2);
+
+    let c = Value::new_named_builtin_function(agent, text_encoder, "TextEncoder", 0);
+    c.set(agent, ObjectKey::new(agent, "prototype"), proto.clone())
+        .unwrap();
+    proto
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
+
+fn option(agent: &Agent, options: Option<&Value>, name: &str) -> Result<bool, Value> {
+    match options.filter(|o| **o != Value::Null) {
+        None => Ok(false),
+        Some(options) if options.is_object() => {
+            Ok(options.get(agent, ObjectKey::new(agent, name))?.to_bool())
+        }
+        Some(_) => Err(Value::new_error(agent, "options must be an object")),
+    }
+}
+
+fn text_decoder(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => {}
+        Some(ValueKind::String(label)) => match label.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => {}
+            _ => {
+                return Err(Value::new_error(
+                    agent,
+                    &format!("unsupported encoding '{}'", label),
+                ))
+            }
+        },
+        Some(_) => return Err(Value::new_error(agent, "encoding must be a string")),
+    }
+    let fatal = option(agent, args.get(1), "fatal")?;
+    let ignore_bom = option(agent, args.get(1), "ignoreBOM")?;
+
+    let prototype = ctx
+        .callee()
+        .get(agent, ObjectKey::new(agent, "prototype"))?;
+    let decoder = Value::new_custom_object(prototype);
+    decoder.set_slot("text decoder fatal", Value::from(fatal));
+    decoder.set_slot("text decoder ignore bom", Value::from(ignore_bom));
+    decoder.set_slot(
+        "text decoder pending",
+        Value::new_array_buffer(agent, Vec::new()),
+    );
+    decoder.set_slot("text decoder bom seen", Value::from(false));
+    decoder.set(
+        agent,
+        ObjectKey::new(agent, "encoding"),
+        Value::from("utf-8"),
+    )?;
+    decoder.set(agent, ObjectKey::new(agent, "fatal"), Value::from(fatal))?;
+    decoder.set(
+        agent,
+        ObjectKey::new(agent, "ignoreBOM"),
+        Value::from(ignore_bom),
+    )?;
+    Ok(decoder)
+}
+
+// the length of an unfinished character at the end of `bytes`, which a
+// streaming decode holds back until the rest of it arrives.
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let b = bytes[bytes.len() - back];
+        if b & 0xC0 == 0x80 {
+            continue;
+        }
+        let needed = match b {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return 0,
+        };
+        return if needed > back { back } else { 0 };
+    }
+    0
+}
+
+fn decode(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() != "object" || !this.has_slot("text decoder pending") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    let stream = option(agent, args.get(1), "stream")?;
+
+    let pending = this.get_slot("text decoder pending");
+    let mut bytes = to_array_buffer(&pending).unwrap().borrow().clone();
+    match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => {}
+        Some(_) => bytes.extend(buffer_source_bytes(agent, &args[0])?),
+    }
+
+    let held = if stream { incomplete_tail(&bytes) } else { 0 };
+    let rest = bytes.split_off(bytes.len() - held);
+    this.set_slot("text decoder pending", Value::new_array_buffer(agent, rest));
+
+    let mut start = 0;
+    if this.get_slot("text decoder bom seen") == Value::from(false) && !bytes.is_empty() {
+        if this.get_slot("text decoder ignore bom") == Value::from(false)
+            && bytes.starts_with(&[0xEF, 0xBB, 0xBF])
+        {
+            start = 3;
+        }
+        this.set_slot("text decoder bom seen", Value::from(true));
+    }
+    if !stream {
+        // the next call starts a new stream
+        this.set_slot("text decoder bom seen", Value::from(false));
+    }
+
+    let bytes = &bytes[start..];
+    if this.get_slot("text decoder fatal") == Value::from(true) {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(Value::from(s)),
+            Err(_) => Err(Value::new_error(
+                agent,
+                "the encoded data was not valid utf-8",
+            )),
+        }
+    } else {
+        Ok(Value::from(String::from_utf8_lossy(bytes).into_owned()))
+    }
+}
+
+pub fn create_text_decoder(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "decode"),
+            Value::new_named_builtin_function(agent, decode, "decode", 0),
+        )
+        .unwrap();
+
+    let c = Value::new_named_builtin_function(agent, text_decoder, "TextDecoder", 0);
+    c.set(agent, ObjectKey::new(agent, "prototype"), proto.clone())
+        .unwrap();
+    proto
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
@@ -0,0 +1,72 @@
+use crate::builtins::time::{create_time, duration_ms, format_iso, time_value};
+use crate::interpreter::Context;
+use crate::value::{ObjectKey, ValueKind};
+use crate::{Agent, Value};
+
+fn this_time(agent: &Agent, ctx: &Context) -> Result<(i64, i64), Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("time value") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+    time_value(agent, &this)
+}
+
+fn to_iso_string(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (ms, offset) = this_time(agent, ctx)?;
+    Ok(Value::from(format_iso(ms, offset)))
+}
+
+fn get_time(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (ms, _) = this_time(agent, ctx)?;
+    Ok(Value::from(ms as f64))
+}
+
+fn add(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (ms, offset) = this_time(agent, ctx)?;
+    let duration = duration_ms(agent, args.get(0).unwrap_or(&Value::Null))?;
+    create_time(agent, ms + duration, offset)
+}
+
+fn diff(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (ms, _) = this_time(agent, ctx)?;
+    let (other, _) = time_value(agent, args.get(0).unwrap_or(&Value::Null))?;
+    Ok(Value::from((ms - other) as f64))
+}
+
+fn with_offset(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (ms, _) = this_time(agent, ctx)?;
+    match args.get(0).map(Value::kind) {
+        Some(ValueKind::Number(n)) if n.fract() == 0.0 && n.abs() < 24.0 * 60.0 => {
+            create_time(agent, ms, n as i64)
+        }
+        _ => Err(Value::new_error(
+            agent,
+            "offset must be a whole number of minutes less than a day",
+        )),
+    }
+}
+
+pub fn create_time_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("toISOString", to_iso_string, 0);
+    method!("toString", to_iso_string, 0);
+    method!("getTime", get_time, 0);
+    method!("add", add, 1);
+    method!("diff", diff, 1);
+    method!("withOffset", with_offset, 1);
+
+    proto
+}
@@ -0,0 +1,441 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::array_buffer::to_array_buffer;
+use crate::intrinsics::bytes_prototype::{relative_index, zeroed_bytes};
+use crate::intrinsics::iterator_prototype::step;
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
+use gc::GcCell;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypedArrayKind {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl TypedArrayKind {
+    pub const ALL: [TypedArrayKind; 8] = [
+        TypedArrayKind::Int8,
+        TypedArrayKind::Uint8,
+        TypedArrayKind::Int16,
+        TypedArrayKind::Uint16,
+        TypedArrayKind::Int32,
+        TypedArrayKind::Uint32,
+        TypedArrayKind::Float32,
+        TypedArrayKind::Float64,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TypedArrayKind::Int8 => "Int8Array",
+            TypedArrayKind::Uint8 => "Uint8Array",
+            TypedArrayKind::Int16 => "Int16Array",
+            TypedArrayKind::Uint16 => "Uint16Array",
+            TypedArrayKind::Int32 => "Int32Array",
+            TypedArrayKind::Uint32 => "Uint32Array",
+            TypedArrayKind::Float32 => "Float32Array",
+            TypedArrayKind::Float64 => "Float64Array",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TypedArrayKind> {
+        TypedArrayKind::ALL
+            .iter()
+            .find(|kind| kind.name() == name)
+            .cloned()
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            TypedArrayKind::Int8 | TypedArrayKind::Uint8 => 1,
+            TypedArrayKind::Int16 | TypedArrayKind::Uint16 => 2,
+            TypedArrayKind::Int32 | TypedArrayKind::Uint32 | TypedArrayKind::Float32 => 4,
+            TypedArrayKind::Float64 => 8,
+        }
+    }
+
+    // elements are stored little-endian. `bytes` is exactly `size()` long.
+    pub fn decode(self, bytes: &[u8]) -> f64 {
+        let mut b = [0; 8];
+        b[..bytes.len()].copy_from_slice(bytes);
+        match self {
+            TypedArrayKind::Int8 => f64::from(b[0] as i8),
+            TypedArrayKind::Uint8 => f64::from(b[0]),
+            TypedArrayKind::Int16 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+            TypedArrayKind::Uint16 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+            TypedArrayKind::Int32 => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            TypedArrayKind::Uint32 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            TypedArrayKind::Float32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            TypedArrayKind::Float64 => f64::from_le_bytes(b),
+        }
+    }
+
+    // integer kinds wrap around like the bitwise operators, with NaN and the
+    // infinities becoming 0.
+    pub fn encode(self, n: f64, bytes: &mut [u8]) {
+        let int = if n.is_finite() {
+            (n.trunc() % 4_294_967_296.0) as i64
+        } else {
+            0
+        };
+        match self {
+            TypedArrayKind::Int8 | TypedArrayKind::Uint8 => bytes[0] = int as u8,
+            TypedArrayKind::Int16 | TypedArrayKind::Uint16 => {
+                bytes.copy_from_slice(&(int as u16).to_le_bytes())
+            }
+            TypedArrayKind::Int32 | TypedArrayKind::Uint32 => {
+                bytes.copy_from_slice(&(int as u32).to_le_bytes())
+            }
+            TypedArrayKind::Float32 => bytes.copy_from_slice(&(n as f32).to_le_bytes()),
+            TypedArrayKind::Float64 => bytes.copy_from_slice(&n.to_le_bytes()),
+        }
+    }
+}
+
+// the bytes of the ArrayBuffer backing a typed array.
+fn data(buffer: &Value) -> &GcCell<Vec<u8>> {
+    to_array_buffer(buffer).unwrap()
+}
+
+/// The number of elements a view can reach, which is 0 once its buffer has
+/// been detached.
+pub fn view_length(kind: TypedArrayKind, buffer: &Value, offset: usize, length: usize) -> usize {
+    if offset + length * kind.size() <= data(buffer).borrow().len() {
+        length
+    } else {
+        0
+    }
+}
+
+pub fn get_element(
+    kind: TypedArrayKind,
+    buffer: &Value,
+    offset: usize,
+    index: usize,
+) -> Option<f64> {
+    let start = offset + index * kind.size();
+    data(buffer)
+        .borrow()
+        .get(start..start + kind.size())
+        .map(|bytes| kind.decode(bytes))
+}
+
+pub fn set_element(
+    kind: TypedArrayKind,
+    buffer: &Value,
+    offset: usize,
+    index: usize,
+    n: f64,
+) -> bool {
+    let start = offset + index * kind.size();
+    match data(buffer)
+        .borrow_mut()
+        .get_mut(start..start + kind.size())
+    {
+        Some(bytes) => {
+            kind.encode(n, bytes);
+            true
+        }
+        None => false,
+    }
+}
+
+struct TypedArray {
+    kind: TypedArrayKind,
+    buffer: Value,
+    offset: usize,
+    length: usize,
+}
+
+fn to_typed_array(value: &Value) -> Option<TypedArray> {
+    if let ValueKind::Object(o) = value.kind() {
+        if let ObjectKind::TypedArray {
+            kind,
+            buffer,
+            offset,
+            length,
+        } = &o.kind
+        {
+            return Some(TypedArray {
+                kind: *kind,
+                buffer: buffer.clone(),
+                offset: *offset,
+                length: view_length(*kind, buffer, *offset, *length),
+            });
+        }
+    }
+    None
+}
+
+fn this_typed_array(agent: &Agent, ctx: &Context) -> Result<(Value, TypedArray), Value> {
+    let this = ctx.this(agent)?;
+    match to_typed_array(&this) {
+        Some(ta) => Ok((this, ta)),
+        None => Err(Value::new_error(agent, "invalid receiver")),
+    }
+}
+
+impl TypedArray {
+    fn values(&self) -> Vec<f64> {
+        (0..self.length)
+            .map(|i| get_element(self.kind, &self.buffer, self.offset, i).unwrap_or(0.0))
+            .collect()
+    }
+}
+
+// reads the numbers out of another typed array, or any iterable of numbers.
+fn collect_numbers(agent: &Agent, source: &Value) -> Result<Vec<f64>, Value> {
+    if let Some(ta) = to_typed_array(source) {
+        return Ok(ta.values());
+    }
+    let iterated = source.to_iterator(agent)?;
+    let mut numbers = Vec::new();
+    while let Some(value) = step(agent, &iterated)? {
+        match value.kind() {
+            ValueKind::Number(n) => numbers.push(n),
+            _ => {
+                return Err(Value::new_error(
+                    agent,
+                    "typed array values must be numbers",
+                ))
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+// a typed array of `length` zeroes, in a buffer of its own.
+fn create_zeroed(
+    agent: &Agent,
+    prototype: Value,
+    kind: TypedArrayKind,
+    length: usize,
+) -> Result<Value, Value> {
+    let data = zeroed_bytes(agent, length.saturating_mul(kind.size()))?;
+    let buffer = Value::new_array_buffer(agent, data);
+    Ok(Value::new_typed_array(prototype, kind, buffer, 0, length))
+}
+
+fn create(
+    agent: &Agent,
+    prototype: Value,
+    kind: TypedArrayKind,
+    numbers: &[f64],
+) -> Result<Value, Value> {
+    let typed_array = create_zeroed(agent, prototype, kind, numbers.len())?;
+    let buffer = to_typed_array(&typed_array).unwrap().buffer;
+    for (i, n) in numbers.iter().enumerate() {
+        set_element(kind, &buffer, 0, i, *n);
+    }
+    Ok(typed_array)
+}
+
+fn typed_array(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = ctx.callee();
+    let kind = match f.get_slot("typed array kind").kind() {
+        ValueKind::String(name) => TypedArrayKind::from_name(name).unwrap(),
+        _ => unreachable!(),
+    };
+    let prototype = f.get(agent, ObjectKey::new(agent, "prototype"))?;
+    match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => create_zeroed(agent, prototype, kind, 0),
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => {
+            create_zeroed(agent, prototype, kind, n as usize)
+        }
+        Some(ValueKind::Number(..)) => Err(Value::new_error(
+            agent,
+            "length must be a non-negative integer",
+        )),
+        Some(_) if to_array_buffer(&args[0]).is_some() => {
+            view(agent, &args, prototype, kind, args[0].clone())
+        }
+        Some(_) => {
+            let numbers = collect_numbers(agent, &args[0])?;
+            create(agent, prototype, kind, &numbers)
+        }
+    }
+}
+
+// `new Uint8Array(buffer, byteOffset?, length?)` views an existing buffer
+// without copying it.
+fn view(
+    agent: &Agent,
+    args: &[Value],
+    prototype: Value,
+    kind: TypedArrayKind,
+    buffer: Value,
+) -> Result<Value, Value> {
+    let byte_length = data(&buffer).borrow().len();
+    let offset = match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => 0,
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "byteOffset must be a non-negative integer",
+            ))
+        }
+    };
+    if offset % kind.size() != 0 {
+        return Err(Value::new_error(
+            agent,
+            &format!(
+                "byteOffset of {} must be a multiple of {}",
+                kind.name(),
+                kind.size()
+            ),
+        ));
+    }
+    if offset > byte_length {
+        return Err(Value::new_error(agent, "byteOffset is out of bounds"));
+    }
+    let length = match args.get(2).map(Value::kind) {
+        None | Some(ValueKind::Null) => {
+            if (byte_length - offset) % kind.size() != 0 {
+                return Err(Value::new_error(
+                    agent,
+                    &format!(
+                        "byteLength of {} must be a multiple of {}",
+                        kind.name(),
+                        kind.size()
+                    ),
+                ));
+            }
+            (byte_length - offset) / kind.size()
+        }
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "length must be a non-negative integer",
+            ))
+        }
+    };
+    if offset + length * kind.size() > byte_length {
+        return Err(Value::new_error(agent, "length is out of bounds"));
+    }
+    Ok(Value::new_typed_array(
+        prototype, kind, buffer, offset, length,
+    ))
+}
+
+fn set(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (_, ta) = this_typed_array(agent, ctx)?;
+    let numbers = match args.get(0) {
+        Some(source) if source.is_object() || source.type_of() == "tuple" => {
+            collect_numbers(agent, source)?
+        }
+        _ => return Err(Value::new_error(agent, "source must be an object")),
+    };
+    let offset = match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => 0,
+        Some(ValueKind::Number(n)) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+        _ => {
+            return Err(Value::new_error(
+                agent,
+                "offset must be a non-negative integer",
+            ))
+        }
+    };
+    if offset + numbers.len() > ta.length {
+        return Err(Value::new_error(agent, "offset is out of bounds"));
+    }
+    for (i, n) in numbers.iter().enumerate() {
+        set_element(ta.kind, &ta.buffer, ta.offset, offset + i, *n);
+    }
+    Ok(Value::Null)
+}
+
+fn subarray(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (this, ta) = this_typed_array(agent, ctx)?;
+    let begin = relative_index(agent, args.get(0), ta.length, 0)?;
+    let end = relative_index(agent, args.get(1), ta.length, ta.length)?.max(begin);
+    Ok(Value::new_typed_array(
+        this.get_prototype_of(agent)?,
+        ta.kind,
+        ta.buffer.clone(),
+        ta.offset + begin * ta.kind.size(),
+        end - begin,
+    ))
+}
+
+fn slice(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (this, ta) = this_typed_array(agent, ctx)?;
+    let begin = relative_index(agent, args.get(0), ta.length, 0)?;
+    let end = relative_index(agent, args.get(1), ta.length, ta.length)?.max(begin);
+    let numbers = &ta.values()[begin..end];
+    create(agent, this.get_prototype_of(agent)?, ta.kind, numbers)
+}
+
+fn iterator(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (this, _) = this_typed_array(agent, ctx)?;
+    let it = Value::new_custom_object(agent.intrinsics.array_iterator_prototype.clone());
+    it.set_slot("array iterator next index", Value::from(0));
+    it.set_slot("iterated object", this);
+    Ok(it)
+}
+
+pub fn create_typed_array_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("set", set, 1);
+    method!("subarray", subarray, 0);
+    method!("slice", slice, 0);
+
+    proto
+        .set(
+            agent,
+            ObjectKey::well_known_symbol("iterator"),
+            Value::new_builtin_function(agent, iterator),
+        )
+        .unwrap();
+
+    proto
+}
+
+/// Create the constructor for one kind of typed array, along with the
+/// prototype its instances share.
+pub fn create_typed_array(agent: &Agent, kind: TypedArrayKind) -> Value {
+    let c = Value::new_named_builtin_function(agent, typed_array, kind.name(), 1);
+    c.set_slot("typed array kind", Value::from(kind.name()));
+
+    let proto = Value::new_object(agent.intrinsics.typed_array_prototype.clone());
+    let bytes_per_element = Value::from(kind.size() as f64);
+    c.set(agent, ObjectKey::new(agent, "prototype"), proto.clone())
+        .unwrap();
+    c.set(
+        agent,
+        ObjectKey::new(agent, "BYTES_PER_ELEMENT"),
+        bytes_per_element.clone(),
+    )
+    .unwrap();
+    proto
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "BYTES_PER_ELEMENT"),
+            bytes_per_element,
+        )
+        .unwrap();
+
+    c
+}
@@ -0,0 +1,433 @@
+use crate::agent::Agent;
+use crate::interpreter::Context;
+use crate::intrinsics::iterator_prototype::step;
+use crate::value::{ObjectKey, ObjectKind, Value, ValueKind};
+use ::url::{form_urlencoded, quirks, Url};
+
+/// Read a component of a URL, or None if `name` is not one. These behave like
+/// the accessors on `URL.prototype` in browsers.
+pub fn get_component(url: &Url, name: &str) -> Option<Value> {
+    let s = match name {
+        "href" => quirks::href(url),
+        "origin" => return Some(Value::from(quirks::origin(url))),
+        "protocol" => quirks::protocol(url),
+        "username" => quirks::username(url),
+        "password" => quirks::password(url),
+        "host" => quirks::host(url),
+        "hostname" => quirks::hostname(url),
+        "port" => quirks::port(url),
+        "pathname" => quirks::pathname(url),
+        "search" => quirks::search(url),
+        "hash" => quirks::hash(url),
+        _ => return None,
+    };
+    Some(Value::from(s))
+}
+
+/// Update a component of a URL, or return None if `name` is not one. Like in
+/// browsers, values which can't be applied are ignored, except for `href`.
+pub fn set_component(
+    agent: &Agent,
+    url: &mut Url,
+    name: &str,
+    value: &Value,
+) -> Option<Result<(), Value>> {
+    let s = match value.kind() {
+        ValueKind::String(s) => s.as_str(),
+        _ => {
+            return match name {
+                "href" | "origin" | "protocol" | "username" | "password" | "host" | "hostname"
+                | "port" | "pathname" | "search" | "hash" => Some(Err(Value::new_error(
+                    agent,
+                    &format!("{} must be a string", name),
+                ))),
+                _ => None,
+            }
+        }
+    };
+    match name {
+        "href" => {
+            if quirks::set_href(url, s).is_err() {
+                return Some(Err(Value::new_error(agent, "invalid URL")));
+            }
+        }
+        "origin" => {}
+        "protocol" => {
+            let _ = quirks::set_protocol(url, s);
+        }
+        "username" => {
+            let _ = quirks::set_username(url, s);
+        }
+        "password" => {
+            let _ = quirks::set_password(url, s);
+        }
+        "host" => {
+            let _ = quirks::set_host(url, s);
+        }
+        "hostname" => {
+            let _ = quirks::set_hostname(url, s);
+        }
+        "port" => {
+            let _ = quirks::set_port(url, s);
+        }
+        "pathname" => quirks::set_pathname(url, s),
+        "search" => quirks::set_search(url, s),
+        "hash" => quirks::set_hash(url, s),
+        _ => return None,
+    }
+    Some(Ok(()))
+}
+
+fn string_arg(agent: &Agent, value: Option<&Value>, name: &str) -> Result<String, Value> {
+    match value.map(Value::kind) {
+        Some(ValueKind::String(s)) => Ok(s.clone()),
+        _ => Err(Value::new_error(
+            agent,
+            &format!("{} must be a string", name),
+        )),
+    }
+}
+
+// any value is accepted as a parameter name or value, like in browsers.
+fn to_string(agent: &Agent, value: Option<&Value>) -> Result<String, Value> {
+    match value
+        .cloned()
+        .unwrap_or(Value::Null)
+        .to_string(agent)?
+        .kind()
+    {
+        ValueKind::String(s) => Ok(s.clone()),
+        _ => unreachable!(),
+    }
+}
+
+fn url(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let input = string_arg(agent, args.get(0), "input")?;
+    let parsed = match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => Url::parse(&input),
+        base => {
+            let base = match base {
+                Some(ValueKind::Object(o)) => match &o.kind {
+                    ObjectKind::Url { url, .. } => url.borrow().clone(),
+                    _ => return Err(Value::new_error(agent, "base must be a string or URL")),
+                },
+                _ => Url::parse(&string_arg(agent, args.get(1), "base")?)
+                    .map_err(|e| Value::new_error(agent, &format!("invalid base URL: {}", e)))?,
+            };
+            base.join(&input)
+        }
+    };
+    match parsed {
+        Ok(url) => Ok(Value::new_url(agent, url)),
+        Err(e) => Err(Value::new_error(agent, &format!("invalid URL: {}", e))),
+    }
+}
+
+fn href(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if let ValueKind::Object(o) = this.kind() {
+        if let ObjectKind::Url { url, .. } = &o.kind {
+            return Ok(Value::from(url.borrow().as_str()));
+        }
+    }
+    Err(Value::new_error(agent, "invalid receiver"))
+}
+
+pub fn create_url_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "toJSON"),
+            Value::new_named_builtin_function(agent, href, "toJSON", 0),
+        )
+        .unwrap();
+    proto
+        .set(
+            agent,
+            ObjectKey::well_known_symbol("toString"),
+            Value::new_named_builtin_function(agent, href, "toString", 0),
+        )
+        .unwrap();
+    proto
+}
+
+pub fn create_url(agent: &Agent) -> Value {
+    let c = Value::new_named_builtin_function(agent, url, "URL", 1);
+    c.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.url_prototype.clone(),
+    )
+    .unwrap();
+    agent
+        .intrinsics
+        .url_prototype
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
+
+/// A new URLSearchParams. If `url` is a URL object the parameters are its
+/// query, and stay in sync with it.
+pub fn new_search_params(agent: &Agent, url: Value) -> Value {
+    let params = Value::new_custom_object(agent.intrinsics.url_search_params_prototype.clone());
+    params.set_slot("url search params url", url);
+    params.set_slot("url search params query", Value::from(""));
+    params
+}
+
+fn this_search_params(agent: &Agent, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if this.type_of() == "object" && this.has_slot("url search params url") {
+        Ok(this)
+    } else {
+        Err(Value::new_error(agent, "invalid receiver"))
+    }
+}
+
+fn read_pairs(params: &Value) -> Vec<(String, String)> {
+    let parse = |query: &str| {
+        form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect::<Vec<(String, String)>>()
+    };
+    if let ValueKind::Object(o) = params.get_slot("url search params url").kind() {
+        if let ObjectKind::Url { url, .. } = &o.kind {
+            return parse(url.borrow().query().unwrap_or(""));
+        }
+    }
+    match params.get_slot("url search params query").kind() {
+        ValueKind::String(query) => parse(query),
+        _ => unreachable!(),
+    }
+}
+
+fn write_pairs(params: &Value, pairs: &[(String, String)]) {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish();
+    if let ValueKind::Object(o) = params.get_slot("url search params url").kind() {
+        if let ObjectKind::Url { url, .. } = &o.kind {
+            let mut url = url.borrow_mut();
+            if query.is_empty() {
+                url.set_query(None);
+            } else {
+                url.set_query(Some(&query));
+            }
+            return;
+        }
+    }
+    params.set_slot("url search params query", Value::from(query));
+}
+
+// `new URLSearchParams(init?)` accepts a query string, an iterable of
+// (name, value) pairs, or an object whose properties are the parameters.
+fn url_search_params(agent: &Agent, args: Vec<Value>, _ctx: &Context) -> Result<Value, Value> {
+    let params = new_search_params(agent, Value::Null);
+    match args.get(0).map(Value::kind) {
+        None | Some(ValueKind::Null) => {}
+        Some(ValueKind::String(s)) => {
+            params.set_slot(
+                "url search params query",
+                Value::from(s.trim_start_matches('?')),
+            );
+        }
+        Some(_) if args[0].type_of() == "tuple" || args[0].is_object() => {
+            let init = &args[0];
+            let mut pairs = Vec::new();
+            if init.has(agent, ObjectKey::well_known_symbol("iterator"))? {
+                let iterator = init.to_iterator(agent)?;
+                while let Some(pair) = step(agent, &iterator)? {
+                    let length = pair.get(agent, ObjectKey::LENGTH)?;
+                    if length != Value::from(2) {
+                        return Err(Value::new_error(
+                            agent,
+                            "each pair must have a name and a value",
+                        ));
+                    }
+                    pairs.push((
+                        to_string(agent, Some(&pair.get(agent, ObjectKey::Number(0))?))?,
+                        to_string(agent, Some(&pair.get(agent, ObjectKey::Number(1))?))?,
+                    ));
+                }
+            } else {
+                for key in init.keys(agent)? {
+                    let value = init.get(agent, key.clone())?;
+                    pairs.push((key.to_string(), to_string(agent, Some(&value))?));
+                }
+            }
+            write_pairs(&params, &pairs);
+        }
+        Some(_) => return Err(Value::new_error(agent, "invalid URLSearchParams init")),
+    }
+    Ok(params)
+}
+
+fn append(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let mut pairs = read_pairs(&params);
+    pairs.push((
+        to_string(agent, args.get(0))?,
+        to_string(agent, args.get(1))?,
+    ));
+    write_pairs(&params, &pairs);
+    Ok(Value::Null)
+}
+
+// `value` narrows which pairs match, when it is given.
+fn matcher(agent: &Agent, args: &[Value]) -> Result<impl Fn(&(String, String)) -> bool, Value> {
+    let name = to_string(agent, args.get(0))?;
+    let value = match args.get(1).map(Value::kind) {
+        None | Some(ValueKind::Null) => None,
+        _ => Some(to_string(agent, args.get(1))?),
+    };
+    Ok(move |(n, v): &(String, String)| {
+        *n == name && value.as_ref().map_or(true, |value| v == value)
+    })
+}
+
+fn delete(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let matches = matcher(agent, &args)?;
+    let mut pairs = read_pairs(&params);
+    pairs.retain(|pair| !matches(pair));
+    write_pairs(&params, &pairs);
+    Ok(Value::Null)
+}
+
+fn get(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let name = to_string(agent, args.get(0))?;
+    Ok(read_pairs(&params)
+        .into_iter()
+        .find(|(n, _)| *n == name)
+        .map_or(Value::Null, |(_, v)| Value::from(v)))
+}
+
+fn get_all(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let name = to_string(agent, args.get(0))?;
+    let values = read_pairs(&params)
+        .into_iter()
+        .filter(|(n, _)| *n == name)
+        .map(|(_, v)| Value::from(v))
+        .collect();
+    Ok(Value::new_array_from_vec(agent, values))
+}
+
+fn has(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let matches = matcher(agent, &args)?;
+    Ok(Value::from(read_pairs(&params).iter().any(matches)))
+}
+
+// replaces the first pair with `name` and removes the rest, or appends one
+// if there are none.
+fn set(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let name = to_string(agent, args.get(0))?;
+    let value = to_string(agent, args.get(1))?;
+    let mut pairs = read_pairs(&params);
+    match pairs.iter().position(|(n, _)| *n == name) {
+        Some(first) => {
+            pairs[first].1 = value;
+            let mut i = 0;
+            pairs.retain(|(n, _)| {
+                i += 1;
+                i - 1 <= first || *n != name
+            });
+        }
+        None => pairs.push((name, value)),
+    }
+    write_pairs(&params, &pairs);
+    Ok(Value::Null)
+}
+
+// a stable sort by name, so pairs with the same name keep their order.
+fn sort(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let mut pairs = read_pairs(&params);
+    pairs.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+    write_pairs(&params, &pairs);
+    Ok(Value::Null)
+}
+
+fn to_query_string(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(read_pairs(&params))
+        .finish();
+    Ok(Value::from(query))
+}
+
+// iterates over a snapshot of the pairs at the time iteration started.
+fn entries(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let params = this_search_params(agent, ctx)?;
+    let pairs = read_pairs(&params)
+        .into_iter()
+        .map(|(n, v)| Value::new_array_from_vec(agent, vec![Value::from(n), Value::from(v)]))
+        .collect();
+    let it = Value::new_custom_object(agent.intrinsics.array_iterator_prototype.clone());
+    it.set_slot("array iterator next index", Value::from(0));
+    it.set_slot("iterated object", Value::new_array_from_vec(agent, pairs));
+    Ok(it)
+}
+
+pub fn create_url_search_params_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    macro_rules! method {
+        ($name:expr, $fn:ident, $length:expr) => {
+            proto
+                .set(
+                    agent,
+                    ObjectKey::new(agent, $name),
+                    Value::new_named_builtin_function(agent, $fn, $name, $length),
+                )
+                .unwrap();
+        };
+    }
+
+    method!("append", append, 2);
+    method!("delete", delete, 1);
+    method!("get", get, 1);
+    method!("getAll", get_all, 1);
+    method!("has", has, 1);
+    method!("set", set, 2);
+    method!("sort", sort, 0);
+    method!("entries", entries, 0);
+
+    proto
+        .set(
+            agent,
+            ObjectKey::well_known_symbol("toString"),
+            Value::new_named_builtin_function(agent, to_query_string, "toString", 0),
+        )
+        .unwrap();
+    proto
+        .set(
+            agent,
+            ObjectKey::well_known_symbol("iterator"),
+            Value::new_builtin_function(agent, entries),
+        )
+        .unwrap();
+
+    proto
+}
+
+pub fn create_url_search_params(agent: &Agent) -> Value {
+    let c = Value::new_named_builtin_function(agent, url_search_params, "URLSearchParams", 0);
+    c.set(
+        agent,
+        ObjectKey::new(agent, "prototype"),
+        agent.intrinsics.url_search_params_prototype.clone(),
+    )
+    .unwrap();
+    agent
+        .intrinsics
+        .url_search_params_prototype
+        .set(agent, ObjectKey::new(agent, "constructor"), c.clone())
+        .unwrap();
+    c
+}
@@ -0,0 +1,48 @@
+use crate::interpreter::Context;
+use crate::intrinsics::promise::new_promise_capability;
+use crate::value::{ObjectKey, ValueKind};
+use crate::{Agent, Value};
+
+fn next(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("worker queue") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    if let ValueKind::List(buffer) = this.get_slot("worker buffer").kind() {
+        if let Some(promise) = buffer.borrow_mut().pop_front() {
+            return Ok(promise);
+        }
+    }
+
+    if this.get_slot("worker done") == Value::from(true) {
+        let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+        let result = Value::new_iter_result(agent, Value::Null, true)?;
+        promise
+            .get_slot("resolve")
+            .call(agent, Value::Null, vec![result])?;
+        return Ok(promise);
+    }
+
+    if let ValueKind::List(queue) = this.get_slot("worker queue").kind() {
+        let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+        queue.borrow_mut().push_back(promise.clone());
+        Ok(promise)
+    } else {
+        unreachable!();
+    }
+}
+
+pub fn create_worker_messages_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.async_iterator_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "next"),
+            Value::new_named_builtin_function(agent, next, "next", 0),
+        )
+        .unwrap();
+
+    proto
+}
@@ -0,0 +1,46 @@
+use crate::builtins::worker;
+use crate::interpreter::Context;
+use crate::value::ObjectKey;
+use crate::{Agent, Value};
+
+fn post_message(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("worker token") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    worker::post_message(agent, &this, args.get(0).unwrap_or(&Value::Null))?;
+    Ok(Value::Null)
+}
+
+fn terminate(agent: &Agent, _: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let this = ctx.this(agent)?;
+    if !this.has_slot("worker token") {
+        return Err(Value::new_error(agent, "invalid receiver"));
+    }
+
+    worker::terminate(agent, &this);
+    Ok(Value::Null)
+}
+
+pub fn create_worker_prototype(agent: &Agent) -> Value {
+    let proto = Value::new_object(agent.intrinsics.object_prototype.clone());
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "postMessage"),
+            Value::new_named_builtin_function(agent, post_message, "postMessage", 1),
+        )
+        .unwrap();
+
+    proto
+        .set(
+            agent,
+            ObjectKey::new(agent, "terminate"),
+            Value::new_named_builtin_function(agent, terminate, "terminate", 0),
+        )
+        .unwrap();
+
+    proto
+}
@@ -1,5 +1,7 @@
 #![allow(clippy::cyclomatic_complexity)]
 #![allow(clippy::option_option)]
+// `Value::Null` and the like are constants which hold a `Cell`
+#![allow(clippy::borrow_interior_mutable_const)]
 
 #[macro_use]
 extern crate gc_derive;
@@ -46,36 +48,51 @@ macro_rules! custom_trace {
     }
 }
 
+#[macro_use]
+mod args;
 mod agent;
+mod ast_json;
 mod builtins;
+mod interner;
 mod interpreter;
 mod intrinsics;
 mod linked_list;
 mod module;
 mod num_util;
+mod optimizer;
 mod parser;
+mod printer;
+mod property_map;
 mod runtime;
 mod serde;
 mod sort;
+mod tracer;
 mod value;
+mod visitor;
 
 pub trait IntoValue: Sized {
     fn into_value(&self, _: &agent::Agent) -> value::Value;
 }
 
-pub use agent::Agent;
+pub use agent::{Agent, AgentCapabilities, AgentOptions, Realm};
 pub use interpreter::{Context, Interpreter, Scope};
-pub use parser::Parser;
-pub use value::Value;
+pub use parser::{
+    tokenize, Error as ParseError, FunctionKind, Node, Operator, Parser, SourcePosition,
+    SourceSpan, Token,
+};
+pub use printer::print_node;
+pub use tracer::{ChromeTracer, FlatProfiler, Span, Tracer};
+pub use value::{BuiltinFunction, Value};
+pub use visitor::{walk, walk_mut, VisitResult, Visitor, VisitorMut};
 
 pub fn disassemble(code: &str) {
     let mut agent = Agent::new();
 
     let ast = match Parser::parse(code) {
         Ok(ast) => ast,
-        Err(e) => panic!(format!("{:?}", e)),
+        Err(e) => panic!(format!("{}", e)),
     };
-    let _idx = agent.assembler.assemble(&ast);
+    let _idx = agent.assembler.assemble("disassembly", &ast);
 
     interpreter::disassemble(&agent.assembler, 0, std::usize::MAX);
 }
@@ -1,8 +1,15 @@
-use crate::interpreter::{Context, Interpreter, Scope};
+use crate::interpreter::{Context, Interpreter, Scope, SuspendValue};
+use crate::intrinsics::perform_await;
+use crate::intrinsics::promise::new_promise_capability;
 use crate::parser::{Node, Parser};
+use crate::value::{ObjectKey, ValueKind};
 use crate::{Agent, IntoValue, Value};
 use gc::{Gc, GcCell};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+/// Parses the source of an asset, like a JSON file, into the value it
+/// exports as its default export.
+pub type AssetLoader = Box<dyn Fn(&Agent, &str) -> Result<Value, Value>>;
 
 #[derive(Debug, PartialEq, Clone)]
 enum ModuleStatus {
@@ -13,11 +20,61 @@ enum ModuleStatus {
     Evaluated,
 }
 
+// an import which hasn't been linked yet
+#[derive(Debug)]
+struct Import {
+    specifier: String,
+    names: Vec<String>,
+    default: bool,
+    // the asset type after `as`
+    kind: Option<String>,
+}
+
+fn import_meta_resolve(agent: &Agent, args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let (specifier,) = check_args!(agent, args, "import.meta.resolve", [string]);
+    let referrer = match ctx.callee().get_slot("import meta url").kind() {
+        ValueKind::String(s) => s.clone(),
+        _ => unreachable!(),
+    };
+    match agent.resolve(&specifier, &referrer) {
+        Ok(filename) => Ok(Value::from(filename)),
+        Err(e) => Err(Value::new_error(
+            agent,
+            &format!("cannot resolve '{}' from '{}': {}", specifier, referrer, e),
+        )),
+    }
+}
+
+// `url` is the filename of the module, `resolve(specifier)` the filename an
+// import of `specifier` from it would load, and `main` whether it is the
+// module the agent was started with.
+fn create_import_meta(agent: &Agent, filename: &str) -> Value {
+    let meta = Value::new_object(agent.intrinsics.object_prototype.clone());
+    let resolve = Value::new_named_builtin_function(agent, import_meta_resolve, "resolve", 1);
+    resolve.set_slot("import meta url", Value::from(filename));
+    meta.set(agent, ObjectKey::new(agent, "url"), Value::from(filename))
+        .unwrap();
+    meta.set(agent, ObjectKey::new(agent, "resolve"), resolve)
+        .unwrap();
+    meta.set(agent, ObjectKey::new(agent, "main"), Value::from(false))
+        .unwrap();
+    meta
+}
+
 #[derive(Debug, Finalize)]
 pub struct Module {
     pub filename: String,
+    /// The asset type of a module which isn't slither source, like "json".
+    pub kind: Option<String>,
     imports: HashSet<String>,
+    unlinked: Vec<Import>,
     pub context: Gc<GcCell<Context>>,
+    /// The `import.meta` object of the module, null for assets.
+    pub import_meta: Value,
+    /// A promise for the completion of a module which awaits at its top
+    /// level, or imports one which does. Null for a module which evaluated
+    /// without waiting, or hasn't been evaluated.
+    pub evaluation: Value,
     status: ModuleStatus,
     dfs_index: u32,
     dfs_ancestor_index: u32,
@@ -27,53 +84,62 @@ pub struct Module {
 unsafe impl gc::Trace for Module {
     custom_trace!(this, {
         mark(&this.context);
+        mark(&this.import_meta);
+        mark(&this.evaluation);
     });
 }
 
 impl Module {
     pub fn new(filename: &str, source: &str, agent: &mut Agent) -> Result<Module, Value> {
-        let ast = match Parser::parse(&source) {
+        let ast = match Parser::parse_module(&source) {
             Ok(v) => v,
             Err(e) => return Err(e.into_value(agent)),
         };
 
         let mut module = Module {
             filename: filename.to_string(),
+            kind: None,
             context: Context::new(Scope::new(Some(agent.root_scope.clone()))),
+            import_meta: create_import_meta(agent, filename),
+            evaluation: Value::Null,
             imports: HashSet::new(),
+            unlinked: Vec::new(),
             status: ModuleStatus::Uninstantiated,
             dfs_index: 0,
             dfs_ancestor_index: 0,
-            bytecode_position: agent.assembler.assemble(&ast),
+            bytecode_position: agent.assembler.assemble(filename, &ast),
         };
 
+        {
+            // `import.meta` is found by name, and can't be shadowed as it
+            // isn't an identifier
+            let ctx = module.context.borrow();
+            let mut scope = ctx.scope.borrow_mut();
+            scope.create(agent, "import.meta", false)?;
+            scope.initialize("import.meta", module.import_meta.clone());
+        }
+
         if let Node::Block(_scope, stmts) = ast {
             for stmt in stmts {
                 match stmt {
-                    Node::ImportDefaultDeclaration(specifier, name) => {
-                        let mr = agent.load(&specifier, filename)?;
-                        module
-                            .context
-                            .borrow()
-                            .scope
-                            .borrow_mut()
-                            .create_import(&name, mr);
-                        module.imports.insert(specifier);
+                    Node::ImportDefaultDeclaration(specifier, name, kind) => {
+                        module.unlinked.push(Import {
+                            specifier,
+                            names: vec![name],
+                            default: true,
+                            kind,
+                        });
                     }
                     Node::ImportNamedDeclaration(specifier, names) => {
-                        let mr = agent.load(&specifier, filename)?;
-                        for name in names {
-                            module
-                                .context
-                                .borrow()
-                                .scope
-                                .borrow_mut()
-                                .create_import(&name, mr.clone());
-                        }
-                        module.imports.insert(specifier);
+                        module.unlinked.push(Import {
+                            specifier,
+                            names,
+                            default: false,
+                            kind: None,
+                        });
                     }
                     Node::ImportStandardDeclaration(specifier, names) => {
-                        match agent.builtins.get(&specifier) {
+                        match agent.builtin(&specifier) {
                             Some(s) => {
                                 for name in names {
                                     match s.get(&name) {
@@ -103,6 +169,103 @@ impl Module {
         Ok(module)
     }
 
+    /// Create a module of the asset type `kind`, which exports `value` and
+    /// has nothing to evaluate.
+    pub fn new_asset(
+        filename: &str,
+        kind: &str,
+        value: Value,
+        agent: &Agent,
+    ) -> Result<Module, Value> {
+        let context = Context::new(Scope::new(Some(agent.root_scope.clone())));
+        {
+            let ctx = context.borrow();
+            let mut scope = ctx.scope.borrow_mut();
+            scope.create(agent, "default", false)?;
+            scope.initialize("default", value);
+        }
+        Ok(Module {
+            filename: filename.to_string(),
+            kind: Some(kind.to_string()),
+            context,
+            import_meta: Value::Null,
+            evaluation: Value::Null,
+            imports: HashSet::new(),
+            unlinked: Vec::new(),
+            status: ModuleStatus::Evaluated,
+            dfs_index: 0,
+            dfs_ancestor_index: 0,
+            bytecode_position: 0,
+        })
+    }
+
+    /// Mark the module as the one the agent was started with, which scripts
+    /// see as `import.meta.main`.
+    pub fn set_main(&self, agent: &Agent) -> Result<(), Value> {
+        if self.import_meta.is_object() {
+            self.import_meta
+                .set(agent, ObjectKey::new(agent, "main"), Value::from(true))?;
+        }
+        Ok(())
+    }
+
+    /// Loads the modules `module` imports from and binds the names it
+    /// imports. A module is cached before it is linked, so two modules can
+    /// import from each other: each gets the other as it is, and a binding
+    /// which hasn't been initialized yet can't be used until it is.
+    pub fn link(agent: &mut Agent, module: &Gc<GcCell<Module>>) -> Result<(), Value> {
+        let (filename, unlinked) = {
+            let mut module = module.borrow_mut();
+            let unlinked = std::mem::replace(&mut module.unlinked, Vec::new());
+            (module.filename.clone(), unlinked)
+        };
+        for import in unlinked {
+            if !agent.capabilities().fs() {
+                return Err(Value::new_error(
+                    agent,
+                    &format!(
+                        "cannot import '{}', this agent may not read files",
+                        import.specifier
+                    ),
+                ));
+            }
+            let kind = import.kind.as_ref().map(String::as_str);
+            let mr = agent.load_as(&import.specifier, &filename, kind)?;
+            let mut module = module.borrow_mut();
+            if mr.borrow().kind.is_some() {
+                // assets are never evaluated, so their value can be copied
+                if !import.default {
+                    return Err(Value::new_error(
+                        agent,
+                        &format!("'{}' only has a default export", import.specifier),
+                    ));
+                }
+                let value = mr
+                    .borrow()
+                    .context
+                    .borrow()
+                    .scope
+                    .borrow()
+                    .get_own("default");
+                let ctx = module.context.borrow();
+                let mut scope = ctx.scope.borrow_mut();
+                scope.create(agent, &import.names[0], false)?;
+                scope.initialize(&import.names[0], value.unwrap());
+                continue;
+            }
+            for name in import.names {
+                module
+                    .context
+                    .borrow()
+                    .scope
+                    .borrow_mut()
+                    .create_import(&name, mr.clone());
+            }
+            module.imports.insert(import.specifier);
+        }
+        Ok(())
+    }
+
     pub fn instantiate(agent: &mut Agent, module: Gc<GcCell<Module>>) -> Result<(), Value> {
         inner_module_instantiation(agent, module, &mut Vec::new(), 0)?;
         Ok(())
@@ -114,6 +277,108 @@ impl Module {
     }
 }
 
+/// Run the body of a module, which starts at `position` in `context`. A body
+/// which awaits at its top level suspends like an async function, and is
+/// resumed by the event loop: it is returned as suspended with a promise for
+/// the value it completes with.
+pub(crate) fn run_body(
+    agent: &Agent,
+    position: usize,
+    context: Gc<GcCell<Context>>,
+) -> Result<Result<Value, Value>, SuspendValue> {
+    let mut interpreter = Interpreter::new(position, context.clone());
+    match interpreter.run(agent) {
+        Ok(r) => Ok(r),
+        Err(suspended) => {
+            let value = suspended.0.clone();
+            let promise = match new_promise_capability(agent, agent.intrinsics.promise.clone()) {
+                Ok(p) => p,
+                Err(e) => return Ok(Err(e)),
+            };
+            context.borrow_mut().interpreter = Some(interpreter);
+            let ctx = Value::WrappedContext(context, Some(Box::new(promise.clone())));
+            if let Err(e) = perform_await(agent, ctx, value) {
+                return Ok(Err(e));
+            }
+            Err(SuspendValue(promise))
+        }
+    }
+}
+
+// waits for each promise in "module pending" in turn, then runs the body of
+// the module and settles "module promise" with its completion.
+fn evaluate_when_ready(agent: &Agent, _args: Vec<Value>, ctx: &Context) -> Result<Value, Value> {
+    let f = ctx.callee();
+    let capability = f.get_slot("module promise");
+    let next = match f.get_slot("module pending").kind() {
+        ValueKind::List(list) => list.borrow_mut().pop_front(),
+        _ => unreachable!(),
+    };
+    if let Some(next) = next {
+        let reject = capability.get_slot("reject");
+        next.get(agent, ObjectKey::new(agent, "then"))?
+            .call(agent, next, vec![f, reject])?;
+        return Ok(Value::Null);
+    }
+    let context = match f.get_slot("module context").kind() {
+        ValueKind::WrappedContext(context, _) => context.clone(),
+        _ => unreachable!(),
+    };
+    let position = match f.get_slot("module position").kind() {
+        ValueKind::Number(n) => n as usize,
+        _ => unreachable!(),
+    };
+    let (settle, value) = match run_body(agent, position, context) {
+        Ok(Ok(v)) => ("resolve", v),
+        Ok(Err(e)) => ("reject", e),
+        // adopted by the promise for the module
+        Err(suspended) => ("resolve", suspended.0.clone()),
+    };
+    capability
+        .get_slot(settle)
+        .call(agent, Value::Null, vec![value])?;
+    Ok(Value::Null)
+}
+
+// whether `evaluation` is a promise a dependent module should wait for
+fn is_pending(evaluation: &Value) -> bool {
+    evaluation.has_slot("promise state")
+        && evaluation.get_slot("promise state") != Value::from("fulfilled")
+}
+
+/// The loaders an agent starts with, for JSON and plain text.
+pub(crate) fn default_loaders() -> HashMap<String, AssetLoader> {
+    let mut loaders: HashMap<String, AssetLoader> = HashMap::new();
+    loaders.insert("json".to_string(), Box::new(load_json));
+    loaders.insert("text".to_string(), Box::new(load_text));
+    loaders.insert("txt".to_string(), Box::new(load_text));
+    loaders
+}
+
+fn load_json(agent: &Agent, source: &str) -> Result<Value, Value> {
+    match serde_json::from_str::<serde_json::Value>(source) {
+        Ok(v) => Ok(Value::from_rust(agent, &v)),
+        Err(e) => {
+            let error = Value::new_error(agent, &format!("Invalid JSON: {}", e));
+            error.set(
+                agent,
+                ObjectKey::new(agent, "line"),
+                Value::from(e.line() as f64),
+            )?;
+            error.set(
+                agent,
+                ObjectKey::new(agent, "column"),
+                Value::from(e.column() as f64),
+            )?;
+            Err(error)
+        }
+    }
+}
+
+fn load_text(_agent: &Agent, source: &str) -> Result<Value, Value> {
+    Ok(Value::from(source))
+}
+
 fn inner_module_instantiation(
     agent: &mut Agent,
     module: Gc<GcCell<Module>>,
@@ -134,8 +399,14 @@ fn inner_module_instantiation(
             }
             index += 1;
             stack.push(module.clone());
-            for import in &module.borrow().imports {
-                let m = agent.load(import.as_str(), module.borrow().filename.as_str())?;
+            // cloned, as a cycle leads back to this module, which is then
+            // borrowed mutably
+            let (imports, filename) = {
+                let module = module.borrow();
+                (module.imports.clone(), module.filename.clone())
+            };
+            for import in &imports {
+                let m = agent.load(import.as_str(), filename.as_str())?;
                 index = inner_module_instantiation(agent, m.clone(), stack, index)?;
                 if m.borrow().status == ModuleStatus::Instantiating {
                     let mut module = module.borrow_mut();
@@ -176,21 +447,51 @@ fn inner_module_evaluation(
             }
             index += 1;
             stack.push(module.clone());
-            for import in &module.borrow().imports {
-                let m = agent.load(import.as_str(), module.borrow().filename.as_str())?;
+            // the imports which are still awaiting at their top level
+            let mut pending = Vec::new();
+            // cloned, as a cycle leads back to this module, which is then
+            // borrowed mutably
+            let (imports, filename) = {
+                let module = module.borrow();
+                (module.imports.clone(), module.filename.clone())
+            };
+            for import in &imports {
+                let m = agent.load(import.as_str(), filename.as_str())?;
                 index = inner_module_evaluation(agent, m.clone(), stack, index)?;
                 if m.borrow().status == ModuleStatus::Evaluating {
                     let mut module = module.borrow_mut();
                     module.dfs_ancestor_index =
                         std::cmp::min(module.dfs_ancestor_index, m.borrow().dfs_ancestor_index);
                 }
+                let evaluation = m.borrow().evaluation.clone();
+                if is_pending(&evaluation) {
+                    pending.push(evaluation);
+                }
             }
-            {
+            let (position, context) = {
                 let module = module.borrow();
-                let mut interpreter =
-                    Interpreter::new(module.bytecode_position, module.context.clone());
-                interpreter.run(agent).unwrap()?;
-            }
+                (module.bytecode_position, module.context.clone())
+            };
+            let evaluation = if pending.is_empty() {
+                match run_body(agent, position, context) {
+                    Ok(r) => {
+                        r?;
+                        Value::Null
+                    }
+                    Err(suspended) => suspended.0.clone(),
+                }
+            } else {
+                // the body runs once everything it imports has evaluated
+                let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+                let f = Value::new_builtin_function(agent, evaluate_when_ready);
+                f.set_slot("module promise", promise.clone());
+                f.set_slot("module pending", Value::new_list_from_iter(pending));
+                f.set_slot("module context", Value::WrappedContext(context, None));
+                f.set_slot("module position", Value::from(position as f64));
+                f.call(agent, Value::Null, Vec::new())?;
+                promise
+            };
+            module.borrow_mut().evaluation = evaluation;
             if module.borrow().dfs_ancestor_index == module.borrow().dfs_index {
                 loop {
                     let m = stack.pop().unwrap();
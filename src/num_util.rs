@@ -6,6 +6,21 @@ pub fn f64_shr(a: f64, b: f64) -> f64 {
     (a as i64 >> b as i64) as f64
 }
 
+// `a >>> b`, which shifts the bits of `a` as an unsigned 32 bit integer, so
+// zeros fill in from the left. `x >>> 0` converts `x` to an unsigned integer.
+pub fn f64_ushr(a: f64, b: f64) -> f64 {
+    f64::from(to_uint32(a) >> (to_uint32(b) & 31))
+}
+
+// ToUint32: the integral part of `n` modulo 2^32, or 0 if it is not finite.
+fn to_uint32(n: f64) -> u32 {
+    if n.is_finite() {
+        n.trunc().rem_euclid(4_294_967_296.0) as u32
+    } else {
+        0
+    }
+}
+
 pub fn f64_bor(a: f64, b: f64) -> f64 {
     (a as i64 | b as i64) as f64
 }
@@ -22,18 +37,62 @@ pub fn f64_bnot(a: f64) -> f64 {
     !(a as i64) as f64
 }
 
+/// Converts a number to a string the way every conversion to a string does:
+/// the shortest digits which round trip, without a decimal point for
+/// integral values, and in exponent notation below 1e-6 or from 1e21.
+/// Negative zero prints as `0`.
 pub fn to_string(n: f64) -> String {
     if n.is_nan() {
-        return "NAN".to_string();
+        return "NaN".to_string();
     }
     if n.is_infinite() {
-        return if n > 0f64 { "INFINITY" } else { "-INFINITY" }.to_string();
+        return if n > 0f64 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if n == 0f64 {
+        return "0".to_string();
     }
+
+    // ryu finds the shortest digits, which are then laid out again as
+    // `0.digits * 10^point`.
     let mut buffer = ryu::Buffer::new();
-    let s = buffer.format(n);
-    if s.ends_with(".0") {
-        s[0..(s.len() - 2)].to_string()
+    let s = buffer.format(n.abs());
+    let (mantissa, exponent) = match s.find('e') {
+        Some(i) => (&s[..i], s[i + 1..].parse::<i32>().unwrap()),
+        None => (s, 0),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+        None => (mantissa, ""),
+    };
+    let all = format!("{}{}", integer, fraction);
+    let leading = all.len() - all.trim_start_matches('0').len();
+    let digits = all.trim_start_matches('0').trim_end_matches('0');
+    let point = integer.len() as i32 + exponent - leading as i32;
+    let k = digits.len() as i32;
+
+    let mut out = String::new();
+    if n < 0f64 {
+        out.push('-');
+    }
+    if k <= point && point <= 21 {
+        out.push_str(digits);
+        out.push_str(&"0".repeat((point - k) as usize));
+    } else if 0 < point && point <= 21 {
+        out.push_str(&digits[..point as usize]);
+        out.push('.');
+        out.push_str(&digits[point as usize..]);
+    } else if -6 < point && point <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat(-point as usize));
+        out.push_str(digits);
     } else {
-        s.to_string()
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let e = point - 1;
+        out.push_str(&format!("e{}{}", if e < 0 { '-' } else { '+' }, e.abs()));
     }
+    out
 }
@@ -0,0 +1,107 @@
+use crate::parser::Node;
+use crate::visitor::{walk_mut, VisitResult, VisitorMut};
+
+// whether control never reaches the statement after `node`.
+fn terminates(node: &Node) -> bool {
+    match node {
+        Node::ReturnStatement(..)
+        | Node::ThrowStatement(..)
+        | Node::BreakStatement(..)
+        | Node::ContinueStatement(..) => true,
+        Node::Block(_, stmts) => stmts.last().map_or(false, terminates),
+        Node::IfStatement(_, consequent, Some(alternative)) => {
+            terminates(consequent) && terminates(alternative)
+        }
+        _ => false,
+    }
+}
+
+// imports and exports are linked before the module runs, wherever they are.
+fn is_module_item(node: &Node) -> bool {
+    match node {
+        Node::ImportDeclaration(..)
+        | Node::ImportNamedDeclaration(..)
+        | Node::ImportDefaultDeclaration(..)
+        | Node::ImportStandardDeclaration(..)
+        | Node::ExportDeclaration(..) => true,
+        _ => false,
+    }
+}
+
+struct DeadCode;
+
+impl VisitorMut for DeadCode {
+    fn visit_node(&mut self, node: &mut Node) -> VisitResult {
+        if let Node::Block(scope, stmts) = node {
+            if let Some(index) = stmts.iter().position(terminates) {
+                let dead = stmts.split_off(index + 1);
+                // the positions of the statements kept stay with them
+                if scope.positions.len() > index {
+                    let positions = scope.positions.split_off(index + 1);
+                    scope.positions.extend(
+                        dead.iter()
+                            .zip(positions)
+                            .filter(|(stmt, _)| is_module_item(stmt))
+                            .map(|(_, position)| position),
+                    );
+                }
+                stmts.extend(dead.into_iter().filter(is_module_item));
+            }
+        }
+        VisitResult::Continue
+    }
+}
+
+/// Remove the statements of every block in `block` which follow a `return`,
+/// `throw`, `break` or `continue`, including one in both branches of an
+/// `if`. The bindings those statements declare stay in the block's scope,
+/// so a closure naming one still finds it uninitialized rather than finding
+/// a binding further out.
+pub fn eliminate_dead_code(block: &mut Node) {
+    walk_mut(&mut DeadCode, block);
+}
+
+#[test]
+fn test_eliminate_dead_code() {
+    let parse = |source| crate::Parser::parse(source).unwrap();
+
+    assert_eq!(
+        parse("function f() { return 1; g(); h(); }"),
+        parse("function f() { return 1; }")
+    );
+    assert_eq!(
+        parse("while x { if y { break; } else { continue; } z(); }"),
+        parse("while x { if y { break; } else { continue; } }")
+    );
+    assert_eq!(
+        parse("function f() { if x { return 1; } g(); }"),
+        parse("function f() { if x { return 1; } g(); }")
+    );
+
+    // the binding of `a` is still declared
+    match parse("function f() { throw 1; let a = 2; }") {
+        Node::Block(_, stmts) => match &stmts[0] {
+            Node::FunctionDeclaration(_, _, _, body) => match &**body {
+                Node::Block(scope, stmts) => {
+                    assert_eq!(stmts.len(), 1);
+                    assert!(scope.bindings.contains_key("a"));
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+
+    // a folded `if` keeps none of the declarations of the branch it drops
+    match parse("if true { let a = 1; } else { let b = 2; }") {
+        Node::Block(_, stmts) => match &stmts[0] {
+            Node::Block(scope, _) => {
+                assert!(scope.bindings.contains_key("a"));
+                assert!(!scope.bindings.contains_key("b"));
+            }
+            n => panic!("{:?}", n),
+        },
+        _ => unreachable!(),
+    }
+}
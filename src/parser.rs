@@ -1,10 +1,13 @@
-use crate::num_util::{f64_band, f64_bnot, f64_bor, f64_bxor, f64_shl, f64_shr};
+use crate::num_util::{f64_band, f64_bnot, f64_bor, f64_bxor, f64_shl, f64_shr, f64_ushr};
+use crate::optimizer::eliminate_dead_code;
 use crate::{Agent, IntoValue, Value};
 use indexmap::IndexMap;
-use std::collections::VecDeque;
+use num::{BigInt, Zero};
+use std::collections::{HashMap, VecDeque};
 use std::iter::Peekable;
 use std::ops::{Div, Mul, Rem, Sub};
 use std::str::Chars;
+use unic::ucd::ident::{is_xid_continue, is_xid_start};
 
 include!(concat!(env!("OUT_DIR"), "/unicode_name_map_gen.rs"));
 
@@ -24,6 +27,8 @@ pub enum Operator {
     ModAssign,
     LeftShift,
     RightShift,
+    UnsignedRightShift,
+    UnsignedRightShiftAssign,
     GreaterThan,
     GreaterThanOrEqual,
     Not,
@@ -40,16 +45,20 @@ pub enum Operator {
     NotEqual,
     Typeof,
     Void,
+    Delete,
     Has,
+    Range,
+    RangeInclusive,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-enum Token {
+pub enum Token {
     Null,
     True,
     False,
 
     NumberLiteral(f64),
+    BigIntLiteral(BigInt),
     StringLiteral(String),
 
     Identifier(String),
@@ -69,6 +78,7 @@ enum Token {
     BackQuote,
     Ellipsis,
     Arrow,
+    Pipeline,
 
     This,
     Function,
@@ -84,6 +94,7 @@ enum Token {
     Try,
     Catch,
     Finally,
+    Defer,
     If,
     Else,
     While,
@@ -101,35 +112,76 @@ enum Token {
 
     Operator(Operator),
 
+    // only made by `tokenize`, the parser reads these itself
+    TemplateString(String),
+    RegexLiteral(String),
+
     EOF,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-#[repr(u8)]
-pub enum FunctionKind {
-    Normal = 0b0001,
-    Async = 0b0010,
-    Generator = 0b0100,
-    Arrow = 0b1000,
+// a set of flags rather than an enum, since arrows combine with the other
+// kinds and masking may leave no flags at all
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FunctionKind(u8);
+
+#[allow(non_upper_case_globals)]
+impl FunctionKind {
+    pub const Normal: FunctionKind = FunctionKind(0b0001);
+    pub const Async: FunctionKind = FunctionKind(0b0010);
+    pub const Generator: FunctionKind = FunctionKind(0b0100);
+    pub const Arrow: FunctionKind = FunctionKind(0b1000);
+
+    pub(crate) const NAMES: &'static [(&'static str, FunctionKind)] = &[
+        ("normal", FunctionKind::Normal),
+        ("async", FunctionKind::Async),
+        ("generator", FunctionKind::Generator),
+        ("arrow", FunctionKind::Arrow),
+    ];
+
+    /// Accepts only the kinds the parser makes: exactly one of normal, async
+    /// or generator, which may also be an arrow.
+    pub fn from_bits(bits: u8) -> Option<FunctionKind> {
+        match bits & !FunctionKind::Arrow.0 {
+            0b0001 | 0b0010 | 0b0100 => Some(FunctionKind(bits)),
+            _ => None,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        FunctionKind::NAMES
+            .iter()
+            .filter(move |(_, flag)| self.0 & flag.0 != 0)
+            .map(|(name, _)| *name)
+    }
 }
 
-impl From<u8> for FunctionKind {
-    fn from(n: u8) -> Self {
-        unsafe { std::mem::transmute::<u8, Self>(n) }
+impl std::fmt::Display for FunctionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, name) in self.names().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
     }
 }
 
 impl std::ops::BitAnd for FunctionKind {
     type Output = Self;
     fn bitand(self, rhs: Self) -> Self {
-        (self as u8 & rhs as u8).into()
+        FunctionKind(self.0 & rhs.0)
     }
 }
 
 impl std::ops::BitOr for FunctionKind {
     type Output = Self;
     fn bitor(self, rhs: Self) -> Self {
-        (self as u8 | rhs as u8).into()
+        FunctionKind(self.0 | rhs.0)
     }
 }
 
@@ -139,10 +191,21 @@ pub enum ScopeKind {
     Block,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Scope {
     pub kind: ScopeKind,
     pub bindings: IndexMap<String, bool>,
+    /// Where each statement of the block starts and ends, for reporting
+    /// where an error is thrown. Blocks which weren't parsed from source may
+    /// have none.
+    pub positions: Vec<SourceSpan>,
+}
+
+// trees are the same wherever their statements were in the source
+impl PartialEq for Scope {
+    fn eq(&self, other: &Scope) -> bool {
+        self.kind == other.kind && self.bindings == other.bindings
+    }
 }
 
 impl Scope {
@@ -157,16 +220,18 @@ impl Scope {
                 ParseScope::GeneratorFunction => ScopeKind::Block,
             },
             bindings: IndexMap::new(),
+            positions: Vec::new(),
         }
     }
 
-    fn declare(&mut self, name: &str, mutable: bool) -> bool {
-        if self.bindings.contains_key(name) {
-            false
-        } else {
-            self.bindings.insert_full(name.to_string(), mutable);
-            true
-        }
+    fn with_positions(mut self, positions: Vec<SourceSpan>) -> Scope {
+        self.positions = positions;
+        self
+    }
+
+    fn declare(&mut self, name: &str, mutable: bool) {
+        debug_assert!(!self.bindings.contains_key(name));
+        self.bindings.insert(name.to_string(), mutable);
     }
 }
 
@@ -176,6 +241,7 @@ pub enum Node {
     TrueLiteral,
     FalseLiteral,
     NumberLiteral(f64),
+    BigIntLiteral(BigInt),
     StringLiteral(String),
     SymbolLiteral(String),
     RegexLiteral(String),
@@ -202,12 +268,15 @@ pub enum Node {
     YieldExpression(Option<Box<Node>>),
     AwaitExpression(Box<Node>),
     ThisExpression,
+    // `import.meta`, the metadata of the module it is in
+    ImportMeta,
     NewExpression(Box<Node>),
 
     MatchExpression(Box<Node>, Vec<Node>),
     MatchArm(Box<Node>, Box<Node>),
     ObjectPattern(IndexMap<String, Node>, bool),
     ArrayPattern(Vec<Node>, bool),
+    TuplePattern(Vec<Node>),
 
     MemberExpression(Box<Node>, String),
     ComputedMemberExpression(Box<Node>, Box<Node>),
@@ -225,8 +294,9 @@ pub enum Node {
 
     ReturnStatement(Option<Box<Node>>),
     ThrowStatement(Box<Node>),
-    BreakStatement,
-    ContinueStatement,
+    BreakStatement(Option<String>),
+    ContinueStatement(Option<String>),
+    LabeledStatement(String, Box<Node>),
     TryStatement(
         Box<Node>,
         Option<String>,
@@ -236,11 +306,19 @@ pub enum Node {
 
     ImportDeclaration(String),
     ImportNamedDeclaration(String, Vec<String>),
-    ImportDefaultDeclaration(String, String),
+    // the specifier, the binding, and the asset type after `as`
+    ImportDefaultDeclaration(String, String, Option<String>),
     ImportStandardDeclaration(String, Vec<String>),
     ExportDeclaration(Box<Node>),
 
     Initializer(Box<Node>, Box<Node>),
+    RestElement(String),
+    SpreadElement(Box<Node>),
+    // `...expr` in an object literal
+    ObjectSpread(Box<Node>),
+
+    // a statement which couldn't be parsed, from `Parser::parse_with_recovery`
+    ErrorNode,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -255,36 +333,175 @@ enum ParseScope {
     GeneratorFunction = 0b0010_1000,
 }
 
+/// A syntax error, with where it was found when that is known.
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Error {
-    NormalEOF,
-    UnexpectedEOF,
-    UnexpectedToken,
-    DuplicateBinding,
-    InvalidAssignmentTarget,
+    NormalEOF(Option<SourcePosition>),
+    UnexpectedEOF(Option<SourcePosition>),
+    UnexpectedToken(Option<SourcePosition>),
+    // the position of the binding which is declared again
+    DuplicateBinding(SourcePosition),
+    InvalidAssignmentTarget(Option<SourcePosition>),
+    DuplicateLabel(Option<SourcePosition>),
+    UndefinedLabel(Option<SourcePosition>),
+    InvalidDeleteTarget(Option<SourcePosition>),
+    // the right of `|>` isn't a call or a name
+    InvalidPipelineTarget(Option<SourcePosition>),
+}
+
+impl Error {
+    pub fn position(&self) -> Option<SourcePosition> {
+        match *self {
+            Error::NormalEOF(p)
+            | Error::UnexpectedEOF(p)
+            | Error::UnexpectedToken(p)
+            | Error::InvalidAssignmentTarget(p)
+            | Error::DuplicateLabel(p)
+            | Error::UndefinedLabel(p)
+            | Error::InvalidDeleteTarget(p)
+            | Error::InvalidPipelineTarget(p) => p,
+            Error::DuplicateBinding(p) => Some(p),
+        }
+    }
+
+    // errors are mostly created without a position, and get the position of
+    // wherever they are first caught which knows one.
+    fn or_at(self, position: SourcePosition) -> Error {
+        let p = Some(position);
+        match self {
+            Error::NormalEOF(None) => Error::NormalEOF(p),
+            Error::UnexpectedEOF(None) => Error::UnexpectedEOF(p),
+            Error::UnexpectedToken(None) => Error::UnexpectedToken(p),
+            Error::InvalidAssignmentTarget(None) => Error::InvalidAssignmentTarget(p),
+            Error::DuplicateLabel(None) => Error::DuplicateLabel(p),
+            Error::UndefinedLabel(None) => Error::UndefinedLabel(p),
+            Error::InvalidDeleteTarget(None) => Error::InvalidDeleteTarget(p),
+            Error::InvalidPipelineTarget(None) => Error::InvalidPipelineTarget(p),
+            e => e,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Error::NormalEOF(..) => "NormalEOF",
+            Error::UnexpectedEOF(..) => "UnexpectedEOF",
+            Error::UnexpectedToken(..) => "UnexpectedToken",
+            Error::DuplicateBinding(original) => {
+                return write!(
+                    f,
+                    "DuplicateBinding: already declared at {}:{}",
+                    original.line, original.column
+                );
+            }
+            Error::InvalidAssignmentTarget(..) => "InvalidAssignmentTarget",
+            Error::DuplicateLabel(..) => "DuplicateLabel",
+            Error::UndefinedLabel(..) => "UndefinedLabel",
+            Error::InvalidDeleteTarget(..) => "InvalidDeleteTarget",
+            Error::InvalidPipelineTarget(..) => "InvalidPipelineTarget",
+        };
+        match self.position() {
+            Some(p) => write!(f, "{} at {}:{}", name, p.line, p.column),
+            None => write!(f, "{}", name),
+        }
+    }
 }
 
 impl IntoValue for Error {
     fn into_value(&self, agent: &Agent) -> Value {
-        Value::new_error(agent, &format!("{:?}", self))
+        Value::new_error(agent, &self.to_string())
     }
 }
 
+/// A position in the source, `line` and `column` start at 1 and `index`
+/// counts chars from the start of the source.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SourcePosition {
+    pub index: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Where a token was read from, `end` is just past its last char.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SourceSpan {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
 struct Lexer<'a> {
     chars: Peekable<Chars<'a>>,
-    peeked: Option<Result<Token, Error>>,
+    peeked: Option<(Result<Token, Error>, SourcePosition, SourcePosition)>,
+    index: usize,
+    line: usize,
+    column: usize,
+    // where the token last returned by `next` starts and ends
+    token_start: SourcePosition,
+    token_end: SourcePosition,
 }
 
 impl<'a> Lexer<'a> {
     fn new(code: &'a str) -> Lexer<'a> {
+        let start = SourcePosition {
+            index: 0,
+            line: 1,
+            column: 1,
+        };
         Lexer {
             chars: code.chars().peekable(),
             peeked: None,
+            index: 0,
+            line: 1,
+            column: 1,
+            token_start: start,
+            token_end: start,
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    pub fn position(&self) -> SourcePosition {
+        SourcePosition {
+            index: self.index,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    // the start of the token last returned by `next`
+    pub fn token_position(&self) -> SourcePosition {
+        self.token_start
+    }
+
+    // the end of the token last returned by `next`, or of the template or
+    // regex text read after it
+    fn token_end(&self) -> SourcePosition {
+        self.token_end
+    }
+
+    // the start of the token `next` will return
+    fn peek_position(&mut self) -> SourcePosition {
+        let _ = self.peek();
+        match self.peeked {
+            Some((_, start, _)) => start,
+            None => self.position(),
         }
     }
 
     fn inner_next(&mut self) -> Result<Token, Error> {
-        Ok(match self.chars.next() {
+        self.token_start = self.position();
+        Ok(match self.next_char() {
             Some(c) => match c {
                 ' ' | '\t' | '\r' | '\n' => self.next()?,
                 '0' => {
@@ -295,166 +512,63 @@ impl<'a> Lexer<'a> {
                         _ => None,
                     };
                     if let Some(radix) = radix {
-                        self.chars.next();
+                        self.next_char();
                         let mut str = String::new();
                         while let Some(c) = self.chars.peek() {
                             match c {
                                 '_' => {
-                                    self.chars.next().unwrap();
-                                    if self.chars.peek() == Some(&'_') {
-                                        return Err(Error::UnexpectedToken);
-                                    }
+                                    self.next_char().unwrap();
+                                    self.separator(!str.is_empty(), radix)?;
                                 }
-                                '0' | '1' => str.push(self.chars.next().unwrap()),
-                                '2'...'7' if radix > 7 => str.push(self.chars.next().unwrap()),
-                                '8' | '9' if radix > 15 => str.push(self.chars.next().unwrap()),
+                                '0' | '1' => str.push(self.next_char().unwrap()),
+                                '2'...'7' if radix > 7 => str.push(self.next_char().unwrap()),
+                                '8' | '9' if radix > 15 => str.push(self.next_char().unwrap()),
                                 'a'...'f' | 'A'...'F' if radix > 15 => {
-                                    str.push(self.chars.next().unwrap())
+                                    str.push(self.next_char().unwrap())
                                 }
                                 _ => break,
                             }
                         }
-                        match u64::from_str_radix(&str, radix) {
-                            Ok(n) => Token::NumberLiteral(n as f64),
-                            Err(_) => return Err(Error::UnexpectedToken),
-                        }
-                    } else {
-                        Token::NumberLiteral(0.0)
-                    }
-                }
-                '1'...'9' => {
-                    let mut str = c.to_string();
-                    let mut exp_str = String::new();
-                    let mut one_dot = false;
-                    let mut in_exp = false;
-                    while let Some(c) = self.chars.peek() {
-                        match c {
-                            '_' => {
-                                self.chars.next().unwrap();
-                                if self.chars.peek() == Some(&'_') {
-                                    return Err(Error::UnexpectedToken);
-                                }
-                                continue;
-                            }
-                            '0'...'9' => {
-                                if in_exp {
-                                    exp_str.push(self.chars.next().unwrap());
-                                } else {
-                                    str.push(self.chars.next().unwrap());
-                                }
-                            }
-                            'e' if !in_exp => {
-                                self.chars.next().unwrap();
-                                in_exp = true;
-                            }
-                            '.' if !in_exp => {
-                                if !one_dot {
-                                    one_dot = true;
-                                    str.push(self.chars.next().unwrap());
-                                    if self.chars.peek() == Some(&'_') {
-                                        return Err(Error::UnexpectedToken);
-                                    }
-                                } else {
-                                    break;
-                                }
+                        if self.chars.peek() == Some(&'n') {
+                            self.next_char();
+                            match BigInt::parse_bytes(str.as_bytes(), radix) {
+                                Some(n) => Token::BigIntLiteral(n),
+                                None => return Err(Error::UnexpectedToken(None)),
                             }
-                            _ => break,
-                        }
-                    }
-                    match str.parse::<f64>() {
-                        Ok(n) => {
-                            if in_exp {
-                                match exp_str.parse::<u32>() {
-                                    Ok(e) => Token::NumberLiteral(n * (10u64.pow(e) as f64)),
-                                    Err(_) => return Err(Error::UnexpectedToken),
-                                }
-                            } else {
-                                Token::NumberLiteral(n)
+                        } else {
+                            match u64::from_str_radix(&str, radix) {
+                                Ok(n) => Token::NumberLiteral(n as f64),
+                                Err(_) => return Err(Error::UnexpectedToken(None)),
                             }
                         }
-                        Err(_) => return Err(Error::UnexpectedToken),
+                    } else {
+                        self.decimal(c)?
                     }
                 }
+                '1'...'9' => self.decimal(c)?,
                 '"' | '\'' => {
                     let mut str = String::new();
                     while let Some(char) = self.chars.peek() {
                         if *char == c {
-                            self.chars.next();
+                            self.next_char();
                             break;
                         }
-                        let c = self.chars.next().unwrap();
+                        let c = self.next_char().unwrap();
                         match c {
-                            '\\' => match self.chars.next().unwrap() {
-                                'n' => str.push('\n'),
-                                't' => str.push('\t'),
-                                '"' => str.push('"'),
-                                '\'' => str.push('\''),
-                                '\\' => str.push('\\'),
-                                'u' => {
-                                    if Some('{') != self.chars.next() {
-                                        return Err(Error::UnexpectedToken);
-                                    }
-                                    let mut n = String::new();
-                                    macro_rules! digit {
-                                        () => {
-                                            let next = self.chars.next();
-                                            match next {
-                                                Some('0'...'9') | Some('a'...'f')
-                                                | Some('A'...'F') => {
-                                                    n.push(next.unwrap());
-                                                }
-                                                _ => return Err(Error::UnexpectedToken),
-                                            }
-                                        };
-                                    }
-                                    digit!();
-                                    digit!();
-                                    digit!();
-                                    digit!();
-                                    match u32::from_str_radix(n.as_str(), 16) {
-                                        Ok(n) => match std::char::from_u32(n) {
-                                            Some(c) => str.push(c),
-                                            None => return Err(Error::UnexpectedToken),
-                                        },
-                                        Err(_) => return Err(Error::UnexpectedToken),
-                                    }
-                                    if Some('}') != self.chars.next() {
-                                        return Err(Error::UnexpectedToken);
-                                    }
-                                }
-                                'U' => {
-                                    if Some('{') != self.chars.next() {
-                                        return Err(Error::UnexpectedToken);
-                                    }
-                                    let mut name = String::new();
-                                    loop {
-                                        match self.chars.next() {
-                                            Some('}') => break,
-                                            None => return Err(Error::UnexpectedEOF),
-                                            Some(c) => name.push(c),
-                                        }
-                                    }
-                                    match UNICODE_NAME_MAP.get(name.as_str()) {
-                                        Some(c) => str.push(*c),
-                                        None => return Err(Error::UnexpectedToken),
-                                    };
-                                }
-                                _ => return Err(Error::UnexpectedToken),
-                            },
-                            '\r' | '\n' => return Err(Error::UnexpectedToken),
+                            '\\' => str.push(self.escape_sequence()?),
+                            '\r' | '\n' => return Err(Error::UnexpectedToken(None)),
                             c => str.push(c),
                         }
                     }
                     Token::StringLiteral(str)
                 }
-                'a'...'z' | 'A'...'Z' | '_' => {
+                c if is_identifier_start(c) => {
                     let mut ident = c.to_string();
                     while let Some(c) = self.chars.peek() {
-                        match c {
-                            'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => {
-                                ident.push(self.chars.next().unwrap())
-                            }
-                            _ => break,
+                        if is_identifier_part(*c) {
+                            ident.push(self.next_char().unwrap());
+                        } else {
+                            break;
                         }
                     }
                     // UPDATE parse_identifier WHEN YOU ADD TO THIS LIST!
@@ -473,6 +587,7 @@ impl<'a> Lexer<'a> {
                         "try" => Token::Try,
                         "catch" => Token::Catch,
                         "finally" => Token::Finally,
+                        "defer" => Token::Defer,
                         "break" => Token::Break,
                         "continue" => Token::Continue,
                         "if" => Token::If,
@@ -492,6 +607,7 @@ impl<'a> Lexer<'a> {
                         "match" => Token::Match,
                         "typeof" => Token::Operator(Operator::Typeof),
                         "void" => Token::Operator(Operator::Void),
+                        "delete" => Token::Operator(Operator::Delete),
                         "has" => Token::Operator(Operator::Has),
                         _ => Token::Identifier(ident),
                     }
@@ -507,12 +623,17 @@ impl<'a> Lexer<'a> {
                 '?' => Token::Question,
                 '.' => match self.chars.peek() {
                     Some('.') => {
-                        self.chars.next();
-                        if let Some('.') = self.chars.peek() {
-                            self.chars.next();
-                            Token::Ellipsis
-                        } else {
-                            return Err(Error::UnexpectedToken);
+                        self.next_char();
+                        match self.chars.peek() {
+                            Some('.') => {
+                                self.next_char();
+                                Token::Ellipsis
+                            }
+                            Some('=') => {
+                                self.next_char();
+                                Token::Operator(Operator::RangeInclusive)
+                            }
+                            _ => Token::Operator(Operator::Range),
                         }
                     }
                     _ => Token::Dot,
@@ -521,24 +642,24 @@ impl<'a> Lexer<'a> {
                 '`' => Token::BackQuote,
                 '+' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::AddAssign)
                     }
                     _ => Token::Operator(Operator::Add),
                 },
                 '-' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::SubAssign)
                     }
                     _ => Token::Operator(Operator::Sub),
                 },
                 '*' => match self.chars.peek() {
                     Some('*') => {
-                        self.chars.next();
+                        self.next_char();
                         match self.chars.peek() {
                             Some('=') => {
-                                self.chars.next();
+                                self.next_char();
                                 Token::Operator(Operator::PowAssign)
                             }
                             _ => Token::Operator(Operator::Pow),
@@ -546,7 +667,7 @@ impl<'a> Lexer<'a> {
                     }
                     _ => match self.chars.peek() {
                         Some('=') => {
-                            self.chars.next();
+                            self.next_char();
                             Token::Operator(Operator::MulAssign)
                         }
                         _ => Token::Operator(Operator::Mul),
@@ -554,16 +675,16 @@ impl<'a> Lexer<'a> {
                 },
                 '/' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::DivAssign)
                     }
                     Some('*') => {
                         loop {
                             if self.chars.peek() == None {
-                                return Err(Error::UnexpectedEOF);
+                                return Err(Error::UnexpectedEOF(None));
                             }
-                            if let Some('*') = self.chars.next() {
-                                if let Some('/') = self.chars.next() {
+                            if let Some('*') = self.next_char() {
+                                if let Some('/') = self.next_char() {
                                     break;
                                 }
                             }
@@ -573,9 +694,9 @@ impl<'a> Lexer<'a> {
                     Some('/') => {
                         loop {
                             if self.chars.peek() == None {
-                                return Err(Error::UnexpectedEOF);
+                                return Err(Error::UnexpectedEOF(None));
                             }
-                            if let Some('\n') = self.chars.next() {
+                            if let Some('\n') = self.next_char() {
                                 break;
                             }
                         }
@@ -585,106 +706,338 @@ impl<'a> Lexer<'a> {
                 },
                 '%' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::ModAssign)
                     }
                     _ => Token::Operator(Operator::Mod),
                 },
                 '<' => match self.chars.peek() {
                     Some('<') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::LeftShift)
                     }
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::LessThanOrEqual)
                     }
                     _ => Token::Operator(Operator::LessThan),
                 },
                 '!' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::NotEqual)
                     }
                     _ => Token::Operator(Operator::Not),
                 },
                 '>' => match self.chars.peek() {
                     Some('>') => {
-                        self.chars.next();
-                        Token::Operator(Operator::RightShift)
+                        self.next_char();
+                        if self.chars.peek() == Some(&'>') {
+                            self.next_char();
+                            if self.chars.peek() == Some(&'=') {
+                                self.next_char();
+                                Token::Operator(Operator::UnsignedRightShiftAssign)
+                            } else {
+                                Token::Operator(Operator::UnsignedRightShift)
+                            }
+                        } else {
+                            Token::Operator(Operator::RightShift)
+                        }
                     }
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::GreaterThanOrEqual)
                     }
                     _ => Token::Operator(Operator::GreaterThan),
                 },
                 '&' => match self.chars.peek() {
                     Some('&') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::LogicalAND)
                     }
                     _ => Token::Operator(Operator::BitwiseAND),
                 },
                 '|' => match self.chars.peek() {
                     Some('|') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::LogicalOR)
                     }
+                    Some('>') => {
+                        self.next_char();
+                        Token::Pipeline
+                    }
                     _ => Token::Operator(Operator::BitwiseOR),
                 },
                 '^' => Token::Operator(Operator::BitwiseXOR),
                 '~' => Token::Operator(Operator::BitwiseNOT),
                 '=' => match self.chars.peek() {
                     Some('=') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Operator(Operator::Equal)
                     }
                     Some('>') => {
-                        self.chars.next();
+                        self.next_char();
                         Token::Arrow
                     }
                     _ => Token::Operator(Operator::Assign),
                 },
                 '@' => Token::At,
-                _ => return Err(Error::UnexpectedToken),
+                _ => return Err(Error::UnexpectedToken(None)),
             },
             None => Token::EOF,
         })
     }
 
+    // a numeric separator, which was just consumed, must be between two
+    // digits, so not doubled, trailing, or next to a `.`, `e` or prefix.
+    fn separator(&mut self, after_digit: bool, radix: u32) -> Result<(), Error> {
+        match self.chars.peek() {
+            Some(c) if after_digit && c.is_digit(radix) => Ok(()),
+            _ => Err(Error::UnexpectedToken(None)),
+        }
+    }
+
+    fn decimal(&mut self, first: char) -> Result<Token, Error> {
+        let mut str = first.to_string();
+        let mut exp_str = String::new();
+        let mut one_dot = false;
+        let mut in_exp = false;
+        let mut after_digit = true;
+        while let Some(c) = self.chars.peek() {
+            match c {
+                '_' => {
+                    self.next_char().unwrap();
+                    self.separator(after_digit, 10)?;
+                }
+                '0'...'9' => {
+                    if in_exp {
+                        exp_str.push(self.next_char().unwrap());
+                    } else {
+                        str.push(self.next_char().unwrap());
+                    }
+                    after_digit = true;
+                }
+                'e' if !in_exp => {
+                    self.next_char().unwrap();
+                    in_exp = true;
+                    after_digit = false;
+                    if let Some('+') | Some('-') = self.chars.peek() {
+                        exp_str.push(self.next_char().unwrap());
+                    }
+                }
+                '.' if !in_exp => {
+                    // `1..2` is a range, not `1.` followed by `.2`
+                    let mut ahead = self.chars.clone();
+                    ahead.next();
+                    if !one_dot && ahead.peek() != Some(&'.') {
+                        one_dot = true;
+                        str.push(self.next_char().unwrap());
+                        after_digit = false;
+                    } else {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+        // `n` makes an integer literal a bigint
+        if !one_dot && !in_exp && self.chars.peek() == Some(&'n') {
+            self.next_char();
+            return match BigInt::parse_bytes(str.as_bytes(), 10) {
+                Some(n) => Ok(Token::BigIntLiteral(n)),
+                None => Err(Error::UnexpectedToken(None)),
+            };
+        }
+        if in_exp {
+            // the exponent needs at least one digit after its sign
+            match exp_str.parse::<i32>() {
+                Ok(_) => str = format!("{}e{}", str, exp_str),
+                Err(_) => return Err(Error::UnexpectedToken(None)),
+            }
+        }
+        match str.parse::<f64>() {
+            Ok(n) => Ok(Token::NumberLiteral(n)),
+            Err(_) => Err(Error::UnexpectedToken(None)),
+        }
+    }
+
     fn next(&mut self) -> Result<Token, Error> {
         match self.peeked.take() {
-            Some(v) => v,
-            None => self.inner_next(),
+            Some((v, start, end)) => {
+                self.token_start = start;
+                self.token_end = end;
+                v
+            }
+            None => {
+                let token = self.inner_next();
+                self.token_end = self.position();
+                // a token which can't be lexed is reported where it starts
+                let start = self.token_start;
+                token.map_err(|e| e.or_at(start))
+            }
         }
     }
 
     pub fn peek(&mut self) -> Result<&Token, Error> {
         if self.peeked.is_none() {
-            self.peeked = Some(self.next());
+            let previous = (self.token_start, self.token_end);
+            let token = self.next();
+            self.peeked = Some((token, self.token_start, self.token_end));
+            self.token_start = previous.0;
+            self.token_end = previous.1;
         }
         match self.peeked {
-            Some(Ok(ref value)) => Ok(value),
-            Some(Err(e)) => Err(e),
+            Some((Ok(ref value), ..)) => Ok(value),
+            Some((Err(e), ..)) => Err(e),
             _ => unreachable!(),
         }
     }
 
+    // whether a line break separates the next token from the one last
+    // returned by `next`, which lets a statement end without a semicolon.
+    fn maybe_semicolon(&mut self) -> bool {
+        if self.peek().is_err() {
+            return false;
+        }
+        match self.peeked {
+            Some((_, start, _)) => start.line > self.token_end.line,
+            None => unreachable!(),
+        }
+    }
+
+    // lexes one token past the peeked one without consuming either
+    fn peek_second(&mut self) -> Result<Token, Error> {
+        self.peek()?;
+        let peeked = self.peeked.take();
+        let chars = self.chars.clone();
+        let position = self.position();
+        let token_start = self.token_start;
+        let token_end = self.token_end;
+        let second = self.next();
+        self.chars = chars;
+        self.index = position.index;
+        self.line = position.line;
+        self.column = position.column;
+        self.token_start = token_start;
+        self.token_end = token_end;
+        self.peeked = peeked;
+        second
+    }
+
     pub fn peek_immutable(&self) -> Result<&Token, Error> {
         match self.peeked {
-            Some(Ok(ref value)) => Ok(value),
-            Some(Err(e)) => Err(e),
+            Some((Ok(ref value), ..)) => Ok(value),
+            Some((Err(e), ..)) => Err(e),
             _ => panic!(),
         }
     }
 
+    // the character an escape sequence in a string or template literal stands
+    // for, after its `\\`.
+    fn escape_sequence(&mut self) -> Result<char, Error> {
+        match self.next_char() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('"') => Ok('"'),
+            Some('\'') => Ok('\''),
+            Some('\\') => Ok('\\'),
+            Some('u') => {
+                if Some('{') != self.next_char() {
+                    return Err(Error::UnexpectedToken(None));
+                }
+                // one to six hex digits, up to 10FFFF
+                let mut n = String::new();
+                loop {
+                    match self.next_char() {
+                        Some('}') if !n.is_empty() => break,
+                        Some(c) if c.is_ascii_hexdigit() && n.len() < 6 => n.push(c),
+                        _ => return Err(Error::UnexpectedToken(None)),
+                    }
+                }
+                u32::from_str_radix(n.as_str(), 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or(Error::UnexpectedToken(None))
+            }
+            Some('U') => {
+                if Some('{') != self.next_char() {
+                    return Err(Error::UnexpectedToken(None));
+                }
+                let mut name = String::new();
+                loop {
+                    match self.next_char() {
+                        Some('}') => break,
+                        None => return Err(Error::UnexpectedEOF(None)),
+                        Some(c) => name.push(c),
+                    }
+                }
+                match UNICODE_NAME_MAP.get(name.as_str()) {
+                    Some(c) => Ok(*c),
+                    None => Err(Error::UnexpectedToken(None)),
+                }
+            }
+            Some(_) => Err(Error::UnexpectedToken(None)),
+            None => Err(Error::UnexpectedEOF(None)),
+        }
+    }
+
+    // the text of a template literal up to the next substitution or the end
+    // of the literal, with its escapes replaced, and whether a substitution
+    // follows.
+    fn template_quasi(&mut self) -> Result<(String, bool), Error> {
+        let mut current = String::new();
+        loop {
+            match self.next_char() {
+                Some('$') => {
+                    if self.chars.peek() == Some(&'{') {
+                        self.next_char();
+                        self.token_end = self.position();
+                        return Ok((current, true));
+                    }
+                    current.push('$');
+                }
+                Some('`') => {
+                    self.token_end = self.position();
+                    return Ok((current, false));
+                }
+                Some('\\') => match self.chars.peek() {
+                    Some('`') | Some('$') => current.push(self.next_char().unwrap()),
+                    _ => current.push(self.escape_sequence()?),
+                },
+                Some(c) => current.push(c),
+                None => return Err(Error::UnexpectedEOF(None)),
+            }
+        }
+    }
+
+    // the pattern of a regex literal, after its opening `/`
+    fn regex_body(&mut self) -> Result<String, Error> {
+        let mut pattern = String::new();
+        loop {
+            match self.next_char() {
+                Some('/') => {
+                    self.token_end = self.position();
+                    return Ok(pattern);
+                }
+                Some('\\') => {
+                    pattern.push('\\');
+                    match self.next_char() {
+                        Some(c) => pattern.push(c),
+                        None => return Err(Error::UnexpectedEOF(None)),
+                    }
+                }
+                Some(c) => pattern.push(c),
+                None => return Err(Error::UnexpectedEOF(None)),
+            }
+        }
+    }
+
     fn skip_hashbang(&mut self) {
         if self.chars.peek() == Some(&'#') {
-            self.chars.next();
+            self.next_char();
             if self.chars.peek() == Some(&'!') {
                 loop {
-                    match self.chars.next() {
+                    match self.next_char() {
                         Some('\n') | None => break,
                         _ => {}
                     }
@@ -694,6 +1047,106 @@ impl<'a> Lexer<'a> {
     }
 }
 
+// identifiers are made of unicode word characters, so accented latin, greek,
+// CJK and so on work. `$` is allowed anywhere in them as in JavaScript, it is
+// only special before `{` inside a template literal.
+pub(crate) fn is_identifier_start(c: char) -> bool {
+    c == '_' || c == '$' || is_xid_start(c)
+}
+
+pub(crate) fn is_identifier_part(c: char) -> bool {
+    c == '$' || is_xid_continue(c)
+}
+
+// whether a token can end a value, after which `/` is a division
+fn ends_value(token: &Token) -> bool {
+    match token {
+        Token::Identifier(..)
+        | Token::NumberLiteral(..)
+        | Token::BigIntLiteral(..)
+        | Token::StringLiteral(..)
+        | Token::RegexLiteral(..)
+        | Token::Null
+        | Token::True
+        | Token::False
+        | Token::This
+        | Token::RightParen
+        | Token::RightBracket
+        | Token::RightBrace => true,
+        _ => false,
+    }
+}
+
+/// Split source into tokens, each with the span it was read from, without
+/// parsing it. Comments and whitespace are skipped, and the last token is
+/// `Token::EOF`.
+///
+/// A template literal is a `BackQuote` followed by a `TemplateString` for
+/// each run of text, whose span includes the `${` or backquote ending it, and
+/// the tokens of each substitution up to its closing `RightBrace`. A `/`
+/// where a value could start begins a `RegexLiteral`.
+pub fn tokenize(source: &str) -> Result<Vec<(Token, SourceSpan)>, Error> {
+    let mut lexer = Lexer::new(source);
+    lexer.skip_hashbang();
+    let mut tokens = Vec::new();
+    let mut after_value = false;
+    let mut depth = 0usize;
+    // the brace depth at which each open substitution started
+    let mut substitutions = Vec::new();
+    loop {
+        let token = lexer.next()?;
+        let mut span = SourceSpan {
+            start: lexer.token_start,
+            end: lexer.token_end,
+        };
+        let quasi = match token {
+            Token::EOF if !substitutions.is_empty() => {
+                return Err(Error::UnexpectedEOF(Some(span.start)));
+            }
+            Token::EOF => {
+                tokens.push((token, span));
+                return Ok(tokens);
+            }
+            Token::Operator(Operator::Div) if !after_value => {
+                let pattern = lexer.regex_body()?;
+                span.end = lexer.position();
+                tokens.push((Token::RegexLiteral(pattern), span));
+                after_value = true;
+                continue;
+            }
+            Token::BackQuote => true,
+            Token::RightBrace if substitutions.last() == Some(&depth) => {
+                substitutions.pop();
+                true
+            }
+            Token::LeftBrace => {
+                depth += 1;
+                false
+            }
+            Token::RightBrace => {
+                depth = depth.saturating_sub(1);
+                false
+            }
+            _ => false,
+        };
+        after_value = ends_value(&token);
+        tokens.push((token, span));
+        if quasi {
+            let start = lexer.position();
+            let (text, substitution) = lexer.template_quasi()?;
+            let span = SourceSpan {
+                start,
+                end: lexer.position(),
+            };
+            tokens.push((Token::TemplateString(text), span));
+            if substitution {
+                substitutions.push(depth);
+            }
+            after_value = !substitution;
+        }
+    }
+}
+
 fn constant_fold(op: Operator, left: &Node, right: &Node) -> Option<Node> {
     macro_rules! num_binop_num {
         ($fn:expr) => {
@@ -757,6 +1210,7 @@ fn constant_fold(op: Operator, left: &Node, right: &Node) -> Option<Node> {
         },
         Operator::LeftShift => num_binop_num!(f64_shl),
         Operator::RightShift => num_binop_num!(f64_shr),
+        Operator::UnsignedRightShift => num_binop_num!(f64_ushr),
         Operator::GreaterThan => num_binop_bool!(f64::gt),
         Operator::LessThan => num_binop_bool!(f64::lt),
         Operator::GreaterThanOrEqual => num_binop_bool!(f64::ge),
@@ -766,9 +1220,27 @@ fn constant_fold(op: Operator, left: &Node, right: &Node) -> Option<Node> {
             Some(false) => Some(Node::TrueLiteral),
             None => None,
         },
-        // Operator::Equal => {
-        //   every combination of literal needs to be tested here
-        // }
+        // literals of different types are never equal, but only literals of
+        // the same type are folded.
+        Operator::Equal => {
+            let equal = match (left, right) {
+                (Node::NullLiteral, Node::NullLiteral) => true,
+                (Node::TrueLiteral, Node::TrueLiteral)
+                | (Node::FalseLiteral, Node::FalseLiteral) => true,
+                (Node::TrueLiteral, Node::FalseLiteral)
+                | (Node::FalseLiteral, Node::TrueLiteral) => false,
+                (Node::NumberLiteral(l), Node::NumberLiteral(r)) => l == r,
+                (Node::BigIntLiteral(l), Node::BigIntLiteral(r)) => l == r,
+                (Node::StringLiteral(l), Node::StringLiteral(r)) => l == r,
+                (Node::SymbolLiteral(l), Node::SymbolLiteral(r)) => l == r,
+                _ => return None,
+            };
+            Some(if equal {
+                Node::TrueLiteral
+            } else {
+                Node::FalseLiteral
+            })
+        }
         Operator::NotEqual => match constant_fold(Operator::Equal, left, right) {
             Some(Node::TrueLiteral) => Some(Node::FalseLiteral),
             Some(Node::FalseLiteral) => Some(Node::TrueLiteral),
@@ -781,9 +1253,11 @@ fn constant_fold(op: Operator, left: &Node, right: &Node) -> Option<Node> {
                 Some(Node::StringLiteral("boolean".to_string()))
             }
             Node::NumberLiteral(..) => Some(Node::StringLiteral("number".to_string())),
+            Node::BigIntLiteral(..) => Some(Node::StringLiteral("bigint".to_string())),
             Node::StringLiteral(..) => Some(Node::StringLiteral("string".to_string())),
             Node::SymbolLiteral(..) => Some(Node::StringLiteral("symbol".to_string())),
             Node::TupleLiteral(..) => Some(Node::StringLiteral("tuple".to_string())),
+            Node::RegexLiteral(..) => Some(Node::StringLiteral("regex".to_string())),
             Node::ObjectLiteral(..) | Node::ArrayLiteral(..) => {
                 Some(Node::StringLiteral("object".to_string()))
             }
@@ -793,6 +1267,16 @@ fn constant_fold(op: Operator, left: &Node, right: &Node) -> Option<Node> {
     }
 }
 
+// the truthiness of a literal which can be dropped without losing any side
+// effects, unlike an array or object literal whose elements are evaluated.
+fn constant_primitive_truthy(node: &Node) -> Option<bool> {
+    match node {
+        Node::ParenthesizedExpression(e) => constant_primitive_truthy(e),
+        Node::ArrayLiteral(..) | Node::TupleLiteral(..) | Node::ObjectLiteral(..) => None,
+        _ => constant_truthy(node),
+    }
+}
+
 fn constant_truthy(node: &Node) -> Option<bool> {
     match node {
         Node::ParenthesizedExpression(e) => constant_truthy(e),
@@ -801,12 +1285,45 @@ fn constant_truthy(node: &Node) -> Option<bool> {
         Node::FalseLiteral => Some(false),
         Node::StringLiteral(s) => Some(!s.is_empty()),
         Node::NumberLiteral(n) => Some(*n != 0.0),
+        Node::BigIntLiteral(n) => Some(!n.is_zero()),
         Node::SymbolLiteral(..) => Some(true),
         Node::ArrayLiteral(..) | Node::TupleLiteral(..) | Node::ObjectLiteral(..) => Some(true),
         _ => None,
     }
 }
 
+// spread elements are parsed as part of any expression list, but only call
+// arguments, array literals (and arrow function parameters, as a rest element)
+// may contain one.
+fn no_spread_element(list: &[Node]) -> Result<(), Error> {
+    if list.iter().any(|n| match n {
+        Node::SpreadElement(..) => true,
+        _ => false,
+    }) {
+        Err(Error::UnexpectedToken(None))
+    } else {
+        Ok(())
+    }
+}
+
+// the directive which makes the rest of a script or function body strict
+fn is_use_strict(node: &Node) -> bool {
+    match node {
+        Node::ExpressionStatement(e) => match &**e {
+            Node::StringLiteral(s) => s == "use strict",
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_strict_body(body: &Node) -> bool {
+    match body {
+        Node::Block(_, statements) => statements.first().map_or(false, is_use_strict),
+        _ => false,
+    }
+}
+
 macro_rules! binop_production {
     ( $name:ident, $lower:ident, [ $( $op:path ),* ] ) => {
         fn $name(&mut self) -> Result<Node, Error> {
@@ -828,20 +1345,79 @@ macro_rules! binop_production {
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     scope: Vec<Scope>,
+    // where the names in each scope were declared, including parameters
+    declared: Vec<HashMap<String, SourcePosition>>,
     scope_bits: u8,
+    labels: Vec<String>,
+    // the errors recovered from so far, when recovering
+    errors: Option<Vec<Error>>,
+    // inside a script or function which begins with `'use strict'`, where a
+    // repeated parameter is an error when parsing rather than when the
+    // function is called. the rest of what strict mode forbids already
+    // throws everywhere: assigning to an undeclared name, adding to an
+    // object which can't be extended, and deleting a name.
+    strict: bool,
+    // parsing the body of a module, where `await` may be used outside of any
+    // function.
+    module: bool,
 }
 
 impl<'a> Parser<'a> {
-    pub fn parse(code: &'a str) -> Result<Node, Error> {
-        let mut parser = Parser {
+    fn new(code: &'a str) -> Parser<'a> {
+        Parser {
             lexer: Lexer::new(code),
             scope_bits: 0,
             scope: Vec::new(),
-        };
+            declared: Vec::new(),
+            labels: Vec::new(),
+            errors: None,
+            strict: false,
+            module: false,
+        }
+    }
+
+    pub fn parse(code: &'a str) -> Result<Node, Error> {
+        Parser::new(code).parse_script()
+    }
+
+    /// Parse `code` as the body of a module, which unlike a script may
+    /// `await` at its top level.
+    pub fn parse_module(code: &'a str) -> Result<Node, Error> {
+        let mut parser = Parser::new(code);
+        parser.module = true;
+        parser.parse_script()
+    }
+
+    /// Parse as much of `code` as possible, for tools which want every syntax
+    /// error in a file and not just the first. A statement which can't be
+    /// parsed becomes a `Node::ErrorNode`, and parsing starts again after
+    /// the next `;` or line break, or at the `}` which ends its block.
+    pub fn parse_with_recovery(code: &'a str) -> (Node, Vec<Error>) {
+        let mut parser = Parser::new(code);
+        parser.errors = Some(Vec::new());
+        let result = parser.parse_script();
+        let mut errors = parser.errors.take().unwrap();
+        match result {
+            Ok(node) => (node, errors),
+            Err(e) => {
+                errors.push(e);
+                (
+                    Node::Block(Scope::new(ParseScope::TopLevel), Vec::new()),
+                    errors,
+                )
+            }
+        }
+    }
 
-        parser.lexer.skip_hashbang();
+    fn parse_script(&mut self) -> Result<Node, Error> {
+        self.lexer.skip_hashbang();
 
-        if let Node::Block(scope, mut stmts) = parser.parse_block(ParseScope::TopLevel)? {
+        let block = self.parse_block(ParseScope::TopLevel);
+        // anything not reported with a position is reported at the last token
+        let position = self.lexer.token_position();
+        let mut block = block.map_err(|e| e.or_at(position))?;
+        eliminate_dead_code(&mut block);
+        if let Node::Block(scope, mut stmts) = block {
             if let Some(Node::ExpressionStatement(..)) = stmts.last() {
                 // if the last item is an expression statement, replace it with the expression
                 // so that the value will be left on the stack to inspect in tests
@@ -863,13 +1439,26 @@ impl<'a> Parser<'a> {
         (self.scope_bits & scope as u8) == scope as u8
     }
 
-    fn declare(&mut self, name: &str, mutable: bool) -> Result<(), Error> {
-        let scope = self.scope.last_mut().unwrap();
-        if scope.declare(name, mutable) {
-            Ok(())
-        } else {
-            Err(Error::DuplicateBinding)
+    // inside an async function, or at the top level of a module
+    fn can_await(&self) -> bool {
+        self.scope(ParseScope::AsyncFunction) || (self.module && !self.scope(ParseScope::Function))
+    }
+
+    // declares `name` in the innermost scope. names from outer scopes may be
+    // shadowed, but a parameter can't be redeclared in the function body.
+    fn declare(
+        &mut self,
+        name: &str,
+        mutable: bool,
+        position: SourcePosition,
+    ) -> Result<(), Error> {
+        let declared = self.declared.last_mut().unwrap();
+        if let Some(original) = declared.get(name) {
+            return Err(Error::DuplicateBinding(*original));
         }
+        declared.insert(name.to_string(), position);
+        self.scope.last_mut().unwrap().declare(name, mutable);
+        Ok(())
     }
 
     fn peek(&mut self, token: Token) -> bool {
@@ -889,11 +1478,41 @@ impl<'a> Parser<'a> {
     fn expect(&mut self, token: Token) -> Result<Token, Error> {
         match self.lexer.next()? {
             ref t if t == &token => Ok(token),
-            _ => Err(Error::UnexpectedToken),
+            _ => Err(self.unexpected_token()),
+        }
+    }
+
+    // the token last read, which is not what should be there
+    fn unexpected_token(&self) -> Error {
+        Error::UnexpectedToken(Some(self.lexer.token_position()))
+    }
+
+    // a statement may also be ended by a line break, by the `}` closing its
+    // block, or at the top level by the end of the input. statements which
+    // continue on the next line, like `a\n(b)`, are parsed as one statement
+    // before this is reached.
+    fn expect_semicolon(&mut self) -> Result<(), Error> {
+        if self.eat(Token::Semicolon)
+            || self.peek(Token::RightBrace)
+            || (self.scope.len() == 1 && self.peek(Token::EOF))
+            || self.lexer.maybe_semicolon()
+        {
+            Ok(())
+        } else {
+            Err(Error::UnexpectedToken(None))
         }
     }
 
     fn build_binary(&self, op: Operator, left: Node, right: Node) -> Node {
+        // `&&` and `||` evaluate to one of their operands, so a literal left
+        // hand side decides which one.
+        match (op, constant_primitive_truthy(&left)) {
+            (Operator::LogicalAND, Some(true)) | (Operator::LogicalOR, Some(false)) => {
+                return right
+            }
+            (Operator::LogicalAND, Some(false)) | (Operator::LogicalOR, Some(true)) => return left,
+            _ => {}
+        }
         if let Some(node) = constant_fold(op, &left, &right) {
             node
         } else {
@@ -910,9 +1529,19 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_statement(&mut self) -> Result<Node, Error> {
-        self.lexer.peek()?;
+        let identifier = if let Token::Identifier(..) = self.lexer.peek()? {
+            true
+        } else {
+            false
+        };
+        if identifier && self.lexer.peek_second()? == Token::Colon {
+            return self.parse_labeled_statement();
+        }
+        // `import.meta` starts an expression, not an import declaration
+        let import_meta =
+            self.lexer.peek()? == &Token::Import && self.lexer.peek_second()? == Token::Dot;
         match self.lexer.peek_immutable()? {
-            Token::EOF => Err(Error::NormalEOF),
+            Token::EOF => Err(Error::NormalEOF(None)),
             Token::LeftBrace => self.parse_block(ParseScope::Block),
             Token::Let | Token::Const => self.parse_lexical_declaration(),
             Token::Function => {
@@ -935,50 +1564,231 @@ impl<'a> Parser<'a> {
             Token::For => self.parse_for(),
             Token::Continue if self.scope(ParseScope::Loop) => {
                 self.lexer.next()?;
-                self.expect(Token::Semicolon)?;
-                Ok(Node::ContinueStatement)
+                let label = self.parse_label_reference()?;
+                Ok(Node::ContinueStatement(label))
             }
             Token::Break if self.scope(ParseScope::Loop) => {
                 self.lexer.next()?;
-                self.expect(Token::Semicolon)?;
-                Ok(Node::BreakStatement)
+                let label = self.parse_label_reference()?;
+                Ok(Node::BreakStatement(label))
             }
             Token::Return if self.scope(ParseScope::Function) => self.parse_return(),
             Token::Throw => self.parse_throw(),
             Token::Try => self.parse_try(),
             Token::At => self.parse_decorators(),
-            Token::Import if self.scope(ParseScope::TopLevel) => self.parse_import(),
+            Token::Import if self.scope(ParseScope::TopLevel) && !import_meta => {
+                self.parse_import()
+            }
             Token::Export if self.scope(ParseScope::TopLevel) => self.parse_export(),
             _ => {
                 let r = self.parse_expression()?;
-                self.expect(Token::Semicolon)?;
+                self.expect_semicolon()?;
                 Ok(Node::ExpressionStatement(Box::new(r)))
             }
         }
     }
 
+    fn parse_labeled_statement(&mut self) -> Result<Node, Error> {
+        let label = self.parse_identifier(false)?;
+        self.expect(Token::Colon)?;
+        if self.labels.contains(&label) {
+            return Err(Error::DuplicateLabel(None));
+        }
+        self.labels.push(label);
+        let body = match self.lexer.peek()? {
+            Token::While => self.parse_while(),
+            Token::For => self.parse_for(),
+            _ => Err(Error::UnexpectedToken(None)),
+        };
+        let label = self.labels.pop().unwrap();
+        match body? {
+            // `while false {}` is folded away
+            Node::NullLiteral => Ok(Node::NullLiteral),
+            body => Ok(Node::LabeledStatement(label, Box::new(body))),
+        }
+    }
+
+    // a label on the next line is a separate statement
+    fn parse_label_reference(&mut self) -> Result<Option<String>, Error> {
+        if self.eat(Token::Semicolon)
+            || self.peek(Token::RightBrace)
+            || self.lexer.maybe_semicolon()
+        {
+            return Ok(None);
+        }
+        let label = self.parse_identifier(false)?;
+        self.expect_semicolon()?;
+        if self.labels.contains(&label) {
+            Ok(Some(label))
+        } else {
+            Err(Error::UndefinedLabel(None))
+        }
+    }
+
     fn parse_block(&mut self, scope: ParseScope) -> Result<Node, Error> {
+        self.parse_block_with(scope, HashMap::new())
+    }
+
+    // `declared` holds the parameters when parsing the body of a function
+    fn parse_block_with(
+        &mut self,
+        scope: ParseScope,
+        declared: HashMap<String, SourcePosition>,
+    ) -> Result<Node, Error> {
         if scope != ParseScope::TopLevel {
             self.expect(Token::LeftBrace)?;
         }
         let saved = self.scope_bits;
+        let strict = self.strict;
         self.scope_bits |= scope as u8;
         self.scope.push(Scope::new(scope));
+        self.declared.push(declared);
+        // labels can't be targeted from inside a nested function
+        let labels = match scope {
+            ParseScope::Function | ParseScope::AsyncFunction | ParseScope::GeneratorFunction => {
+                std::mem::replace(&mut self.labels, Vec::new())
+            }
+            _ => Vec::new(),
+        };
         let mut statements = Vec::new();
+        let mut positions = Vec::new();
+        let mut deferred = Vec::new();
         while !self.eat(Token::RightBrace) {
-            match self.parse_statement() {
-                Ok(s) => statements.push(s),
-                Err(Error::NormalEOF) if scope == ParseScope::TopLevel => break,
+            let position = self.lexer.peek_position();
+            let state = (
+                self.scope_bits,
+                self.scope.len(),
+                self.declared.len(),
+                self.labels.len(),
+                self.lexer.position(),
+            );
+            let statement = if self.eat(Token::Defer) {
+                self.parse_expression()
+                    .and_then(|e| self.expect_semicolon().map(|_| e))
+                    .map(|e| {
+                        let span = SourceSpan {
+                            start: position,
+                            end: self.lexer.token_end(),
+                        };
+                        deferred.push((statements.len(), e, span))
+                    })
+            } else {
+                self.parse_statement().map(|s| {
+                    statements.push(s);
+                    positions.push(SourceSpan {
+                        start: position,
+                        end: self.lexer.token_end(),
+                    });
+                })
+            };
+            match statement {
+                Ok(()) => {
+                    if statements.len() == 1
+                        && deferred.is_empty()
+                        && scope != ParseScope::Block
+                        && scope != ParseScope::Loop
+                        && is_use_strict(&statements[0])
+                    {
+                        self.strict = true;
+                    }
+                }
+                Err(Error::NormalEOF(_)) if scope == ParseScope::TopLevel => break,
+                Err(e) if self.errors.is_some() => {
+                    // put back whatever the statement had started
+                    self.scope_bits = state.0;
+                    self.scope.truncate(state.1);
+                    self.declared.truncate(state.2);
+                    self.labels.truncate(state.3);
+                    let errors = self.errors.as_mut().unwrap();
+                    if let Error::NormalEOF(_) = e {
+                        // an unclosed block ends with the input, which is only
+                        // reported once however many blocks are open
+                        match errors.last() {
+                            Some(Error::UnexpectedEOF(..)) => {}
+                            _ => errors.push(Error::UnexpectedEOF(Some(self.lexer.position()))),
+                        }
+                        break;
+                    }
+                    errors.push(e.or_at(self.lexer.token_position()));
+                    self.synchronize(scope == ParseScope::TopLevel, state.4);
+                    statements.push(Node::ErrorNode);
+                    positions.push(SourceSpan {
+                        start: position,
+                        end: self.lexer.token_end(),
+                    });
+                }
                 Err(e) => {
                     self.scope_bits = saved;
+                    self.strict = strict;
                     self.scope.pop();
+                    self.declared.pop();
+                    self.labels.extend(labels);
                     return Err(e);
                 }
             }
         }
+        self.labels.extend(labels);
         let scope = self.scope.pop().unwrap();
+        self.declared.pop();
         self.scope_bits = saved;
-        Ok(Node::Block(scope, statements))
+        self.strict = strict;
+        // `defer expr;` runs `expr` when the block is left, however it is
+        // left, by putting the rest of the block in a try with `expr` as the
+        // finally clause. the last defer is the innermost, so cleanups run
+        // in reverse, and an error thrown by a cleanup replaces whatever was
+        // leaving the block, as with any finally clause.
+        for (index, expr, span) in deferred.into_iter().rev() {
+            let rest = statements.split_off(index);
+            let rest_positions = positions.split_off(index);
+            let end = rest_positions.last().map_or(span.end, |s| s.end);
+            statements.push(Node::TryStatement(
+                Box::new(Node::Block(
+                    Scope::new(ParseScope::Block).with_positions(rest_positions),
+                    rest,
+                )),
+                None,
+                None,
+                Some(Box::new(Node::Block(
+                    Scope::new(ParseScope::Block).with_positions(vec![span]),
+                    vec![Node::ExpressionStatement(Box::new(expr))],
+                ))),
+            ));
+            positions.push(SourceSpan {
+                start: span.start,
+                end,
+            });
+        }
+        Ok(Node::Block(scope.with_positions(positions), statements))
+    }
+
+    // skip the rest of a statement which couldn't be parsed, up to and
+    // including a `;`, or up to a line break or a `}`, which ends the block
+    // unless it is at the top level. something is always skipped, so that
+    // recovering can't get stuck.
+    fn synchronize(&mut self, top_level: bool, start: SourcePosition) {
+        loop {
+            let progress = self.lexer.position().index > start.index;
+            let (eof, semicolon, brace) = match self.lexer.peek() {
+                Ok(t) => (
+                    *t == Token::EOF,
+                    *t == Token::Semicolon,
+                    *t == Token::RightBrace,
+                ),
+                Err(_) => (false, false, false),
+            };
+            if eof {
+                return;
+            }
+            if semicolon {
+                self.lexer.next().unwrap();
+                return;
+            }
+            if progress && ((brace && !top_level) || self.lexer.maybe_semicolon()) {
+                return;
+            }
+            // a token which can't be lexed is skipped like any other
+            let _ = self.lexer.next();
+        }
     }
 
     fn parse_lexical_declaration(&mut self) -> Result<Node, Error> {
@@ -987,13 +1797,14 @@ impl<'a> Parser<'a> {
         } else if self.eat(Token::Const) {
             false
         } else {
-            return Err(Error::UnexpectedToken);
+            return Err(Error::UnexpectedToken(Some(self.lexer.position())));
         };
         let name = self.parse_identifier(false)?;
-        self.declare(name.as_str(), mutable)?;
+        let position = self.lexer.token_position();
+        self.declare(name.as_str(), mutable, position)?;
         self.expect(Token::Operator(Operator::Assign))?;
         let init = self.parse_expression()?;
-        self.expect(Token::Semicolon)?;
+        self.expect_semicolon()?;
         Ok(Node::LexicalInitialization(name, Box::new(init)))
     }
 
@@ -1007,19 +1818,28 @@ impl<'a> Parser<'a> {
         } else {
             Some(self.parse_identifier(false)?)
         };
+        let position = self.lexer.token_position();
         self.expect(Token::LeftParen)?;
-        let args = self.parse_parameters(Token::RightParen)?;
-        let body = self.parse_block(match kind {
-            FunctionKind::Normal => ParseScope::Function,
-            FunctionKind::Async => ParseScope::AsyncFunction,
-            FunctionKind::Generator => ParseScope::GeneratorFunction,
-            _ => unreachable!(),
-        })?;
+        let (args, declared, repeated) = self.parse_parameters(Token::RightParen)?;
+        let body = self.parse_block_with(
+            match kind {
+                FunctionKind::Normal => ParseScope::Function,
+                FunctionKind::Async => ParseScope::AsyncFunction,
+                FunctionKind::Generator => ParseScope::GeneratorFunction,
+                _ => unreachable!(),
+            },
+            declared,
+        )?;
+        if let Some(original) = repeated {
+            if self.strict || is_strict_body(&body) {
+                return Err(Error::DuplicateBinding(original));
+            }
+        }
         Ok(if expression {
             Node::FunctionExpression(kind, name, args, Box::new(body))
         } else {
             let name = name.unwrap();
-            self.declare(name.as_str(), false)?;
+            self.declare(name.as_str(), false, position)?;
             Node::FunctionDeclaration(kind, name, args, Box::new(body))
         })
     }
@@ -1068,7 +1888,7 @@ impl<'a> Parser<'a> {
 
     fn parse_for(&mut self) -> Result<Node, Error> {
         self.expect(Token::For)?;
-        let r#async = if self.scope(ParseScope::AsyncFunction) {
+        let r#async = if self.can_await() {
             self.eat(Token::Await)
         } else {
             false
@@ -1087,13 +1907,18 @@ impl<'a> Parser<'a> {
 
     fn parse_return(&mut self) -> Result<Node, Error> {
         self.expect(Token::Return)?;
-        if self.eat(Token::Semicolon) {
+        // `return` followed by a line break or the end of its block returns
+        // nothing
+        if self.eat(Token::Semicolon)
+            || self.peek(Token::RightBrace)
+            || self.lexer.maybe_semicolon()
+        {
             Ok(Node::ReturnStatement(None))
         } else if self.scope(ParseScope::GeneratorFunction) {
-            Err(Error::UnexpectedToken)
+            Err(Error::UnexpectedToken(None))
         } else {
             let expr = self.parse_expression()?;
-            self.expect(Token::Semicolon)?;
+            self.expect_semicolon()?;
             Ok(Node::ReturnStatement(Some(Box::new(
                 if let Node::CallExpression(callee, arguments) = expr {
                     Node::TailCallExpression(callee, arguments)
@@ -1106,8 +1931,12 @@ impl<'a> Parser<'a> {
 
     fn parse_throw(&mut self) -> Result<Node, Error> {
         self.expect(Token::Throw)?;
+        // rather than throwing nothing
+        if self.lexer.maybe_semicolon() {
+            return Err(Error::UnexpectedToken(None));
+        }
         let expr = self.parse_expression()?;
-        self.expect(Token::Semicolon)?;
+        self.expect_semicolon()?;
         Ok(Node::ThrowStatement(Box::new(expr)))
     }
 
@@ -1157,7 +1986,7 @@ impl<'a> Parser<'a> {
         } else if self.eat(Token::Function) {
             FunctionKind::Normal
         } else {
-            return Err(Error::UnexpectedToken);
+            return Err(Error::UnexpectedToken(None));
         };
         if let Node::FunctionDeclaration(kind, name, args, body) =
             self.parse_function(false, kind)?
@@ -1180,7 +2009,7 @@ impl<'a> Parser<'a> {
             Token::StringLiteral(s) => {
                 let specifier = s.to_string();
                 self.lexer.next()?;
-                self.expect(Token::Semicolon)?;
+                self.expect_semicolon()?;
                 Ok(Node::ImportDeclaration(specifier))
             }
 
@@ -1193,20 +2022,21 @@ impl<'a> Parser<'a> {
                 match self.lexer.next()? {
                     Token::StringLiteral(c) => {
                         let specifier = c;
-                        self.expect(Token::Semicolon)?;
+                        self.expect_semicolon()?;
                         Ok(Node::ImportNamedDeclaration(specifier, bindings))
                     }
                     Token::Identifier(ref s) if s == "standard" => {
                         self.expect(Token::Colon)?;
                         let namespace = self.parse_identifier(true)?;
-                        self.expect(Token::Semicolon)?;
+                        self.expect_semicolon()?;
                         Ok(Node::ImportStandardDeclaration(namespace, bindings))
                     }
-                    _ => Err(Error::UnexpectedToken),
+                    _ => Err(Error::UnexpectedToken(None)),
                 }
             }
 
             // import x from "specifier";
+            // import x from "specifier" as json;
             Token::Identifier(..) => {
                 let binding = self.parse_identifier(false)?;
                 self.expect(Token::From)?;
@@ -1214,11 +2044,17 @@ impl<'a> Parser<'a> {
                     Token::StringLiteral(s) => s,
                     _ => unreachable!(),
                 };
-                self.expect(Token::Semicolon)?;
-                Ok(Node::ImportDefaultDeclaration(specifier, binding))
+                let kind = if self.peek(Token::Identifier("as".to_string())) {
+                    self.lexer.next()?;
+                    Some(self.parse_identifier(true)?)
+                } else {
+                    None
+                };
+                self.expect_semicolon()?;
+                Ok(Node::ImportDefaultDeclaration(specifier, binding, kind))
             }
 
-            _ => Err(Error::UnexpectedToken),
+            _ => Err(Error::UnexpectedToken(None)),
         }
     }
 
@@ -1230,7 +2066,7 @@ impl<'a> Parser<'a> {
                 self.lexer.next()?;
                 self.parse_function(false, FunctionKind::Normal)
             }
-            _ => Err(Error::UnexpectedToken),
+            _ => Err(Error::UnexpectedToken(None)),
         }?;
         Ok(Node::ExportDeclaration(Box::new(decl)))
     }
@@ -1241,6 +2077,9 @@ impl<'a> Parser<'a> {
 
     fn parse_assignment_expression(&mut self) -> Result<Node, Error> {
         if self.eat(Token::Yield) && self.scope(ParseScope::GeneratorFunction) {
+            if self.lexer.maybe_semicolon() {
+                return Ok(Node::YieldExpression(None));
+            }
             match self.lexer.peek()? {
                 Token::Semicolon
                 | Token::RightBrace
@@ -1256,7 +2095,7 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        let mut lhs = self.parse_conditional_expression()?;
+        let mut lhs = self.parse_pipeline_expression()?;
 
         macro_rules! op_assign {
             ($op:expr) => {{
@@ -1276,6 +2115,9 @@ impl<'a> Parser<'a> {
             Token::Operator(Operator::PowAssign) => op_assign!(Operator::PowAssign),
             Token::Operator(Operator::DivAssign) => op_assign!(Operator::DivAssign),
             Token::Operator(Operator::ModAssign) => op_assign!(Operator::ModAssign),
+            Token::Operator(Operator::UnsignedRightShiftAssign) => {
+                op_assign!(Operator::UnsignedRightShiftAssign)
+            }
             _ => {}
         }
 
@@ -1287,10 +2129,29 @@ impl<'a> Parser<'a> {
             Node::Identifier(..) => Ok(()),
             Node::MemberExpression(..) => Ok(()),
             Node::ComputedMemberExpression(..) => Ok(()),
-            _ => Err(Error::InvalidAssignmentTarget),
+            // at the assignment operator
+            _ => Err(Error::InvalidAssignmentTarget(Some(
+                self.lexer.token_position(),
+            ))),
         }
     }
 
+    // `value |> f` is `f(value)`, and `value |> f(a)` is `f(value, a)`.
+    fn parse_pipeline_expression(&mut self) -> Result<Node, Error> {
+        let mut lhs = self.parse_conditional_expression()?;
+        while self.eat(Token::Pipeline) {
+            lhs = match self.parse_conditional_expression()? {
+                Node::CallExpression(callee, mut arguments) => {
+                    arguments.insert(0, lhs);
+                    Node::CallExpression(callee, arguments)
+                }
+                callee @ Node::Identifier(..) => Node::CallExpression(Box::new(callee), vec![lhs]),
+                _ => return Err(Error::InvalidPipelineTarget(None)),
+            };
+        }
+        Ok(lhs)
+    }
+
     fn parse_conditional_expression(&mut self) -> Result<Node, Error> {
         let lhs = self.parse_logical_or_expression()?;
         if self.eat(Token::Question) {
@@ -1350,7 +2211,7 @@ impl<'a> Parser<'a> {
 
     binop_production!(
         parse_relational_expression,
-        parse_shift_expression,
+        parse_range_expression,
         [
             Operator::LessThan,
             Operator::GreaterThan,
@@ -1360,10 +2221,28 @@ impl<'a> Parser<'a> {
         ]
     );
 
+    // ranges don't chain, `a..b..c` is an error.
+    fn parse_range_expression(&mut self) -> Result<Node, Error> {
+        let lhs = self.parse_shift_expression()?;
+        match self.lexer.peek()? {
+            Token::Operator(op) if *op == Operator::Range || *op == Operator::RangeInclusive => {
+                let op = *op;
+                self.lexer.next()?;
+                let rhs = self.parse_shift_expression()?;
+                Ok(self.build_binary(op, lhs, rhs))
+            }
+            _ => Ok(lhs),
+        }
+    }
+
     binop_production!(
         parse_shift_expression,
         parse_additive_expression,
-        [Operator::LeftShift, Operator::RightShift]
+        [
+            Operator::LeftShift,
+            Operator::RightShift,
+            Operator::UnsignedRightShift
+        ]
     );
 
     binop_production!(
@@ -1417,7 +2296,17 @@ impl<'a> Parser<'a> {
                 let expr = self.parse_unary_expression()?;
                 Ok(self.build_unary(Operator::Void, expr))
             }
-            Token::Await if self.scope(ParseScope::AsyncFunction) => {
+            Token::Operator(Operator::Delete) => {
+                self.lexer.next()?;
+                match self.parse_unary_expression()? {
+                    expr @ Node::MemberExpression(..)
+                    | expr @ Node::ComputedMemberExpression(..) => {
+                        Ok(Node::UnaryExpression(Operator::Delete, Box::new(expr)))
+                    }
+                    _ => Err(Error::InvalidDeleteTarget(None)),
+                }
+            }
+            Token::Await if self.can_await() => {
                 self.lexer.next()?;
                 let expr = self.parse_unary_expression()?;
                 Ok(Node::AwaitExpression(Box::new(expr)))
@@ -1427,7 +2316,12 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_left_hand_side_expression(&mut self) -> Result<Node, Error> {
-        let mut base = self.parse_primary_expression()?;
+        let base = self.parse_primary_expression()?;
+        self.parse_member_expression(base, true)
+    }
+
+    // the property accesses, and the calls if `calls` is set, after `base`
+    fn parse_member_expression(&mut self, mut base: Node, calls: bool) -> Result<Node, Error> {
         loop {
             if self.eat(Token::Dot) {
                 let property = self.parse_identifier(true)?;
@@ -1436,7 +2330,7 @@ impl<'a> Parser<'a> {
                 let property = self.parse_expression()?;
                 self.expect(Token::RightBracket)?;
                 base = Node::ComputedMemberExpression(Box::new(base), Box::new(property));
-            } else if self.eat(Token::LeftParen) {
+            } else if calls && self.eat(Token::LeftParen) {
                 let (list, ..) = self.parse_expression_list(Token::RightParen)?;
                 base = Node::CallExpression(Box::new(base), list);
             } else {
@@ -1448,29 +2342,30 @@ impl<'a> Parser<'a> {
     fn parse_identifier(&mut self, allow_keyword: bool) -> Result<String, Error> {
         match self.lexer.next()? {
             Token::Identifier(name) => Ok(name),
-            Token::Throw if allow_keyword => Ok("throw".to_string()),
-            Token::Catch if allow_keyword => Ok("catch".to_string()),
+            // every keyword the lexer makes, in its order. contextual words
+            // like `of`, `get` or `static` are lexed as identifiers anyway
             Token::True if allow_keyword => Ok("true".to_string()),
             Token::False if allow_keyword => Ok("false".to_string()),
             Token::Null if allow_keyword => Ok("null".to_string()),
             Token::This if allow_keyword => Ok("this".to_string()),
             Token::Class if allow_keyword => Ok("class".to_string()),
             Token::Extends if allow_keyword => Ok("extends".to_string()),
-            Token::Finally if allow_keyword => Ok("finally".to_string()),
             Token::Function if allow_keyword => Ok("function".to_string()),
             Token::Let if allow_keyword => Ok("let".to_string()),
             Token::Const if allow_keyword => Ok("const".to_string()),
             Token::Throw if allow_keyword => Ok("throw".to_string()),
             Token::Return if allow_keyword => Ok("return".to_string()),
-            Token::While if allow_keyword => Ok("while".to_string()),
-            Token::For if allow_keyword => Ok("for".to_string()),
-            Token::In if allow_keyword => Ok("in".to_string()),
-            Token::Break if allow_keyword => Ok("break".to_string()),
-            Token::Continue if allow_keyword => Ok("continue".to_string()),
             Token::Try if allow_keyword => Ok("try".to_string()),
             Token::Catch if allow_keyword => Ok("catch".to_string()),
+            Token::Finally if allow_keyword => Ok("finally".to_string()),
+            Token::Defer if allow_keyword => Ok("defer".to_string()),
+            Token::Break if allow_keyword => Ok("break".to_string()),
+            Token::Continue if allow_keyword => Ok("continue".to_string()),
             Token::If if allow_keyword => Ok("if".to_string()),
             Token::Else if allow_keyword => Ok("else".to_string()),
+            Token::While if allow_keyword => Ok("while".to_string()),
+            Token::For if allow_keyword => Ok("for".to_string()),
+            Token::In if allow_keyword => Ok("in".to_string()),
             Token::New if allow_keyword => Ok("new".to_string()),
             Token::Import if allow_keyword => Ok("import".to_string()),
             Token::Export if allow_keyword => Ok("export".to_string()),
@@ -1483,8 +2378,9 @@ impl<'a> Parser<'a> {
             Token::Match if allow_keyword => Ok("match".to_string()),
             Token::Operator(Operator::Typeof) if allow_keyword => Ok("typeof".to_string()),
             Token::Operator(Operator::Void) if allow_keyword => Ok("void".to_string()),
+            Token::Operator(Operator::Delete) if allow_keyword => Ok("delete".to_string()),
             Token::Operator(Operator::Has) if allow_keyword => Ok("has".to_string()),
-            _ => Err(Error::UnexpectedToken),
+            _ => Err(self.unexpected_token()),
         }
     }
 
@@ -1496,31 +2392,36 @@ impl<'a> Parser<'a> {
             Token::False => Ok(Node::FalseLiteral),
             Token::StringLiteral(s) => Ok(Node::StringLiteral(s)),
             Token::NumberLiteral(n) => Ok(Node::NumberLiteral(n)),
+            Token::BigIntLiteral(n) => Ok(Node::BigIntLiteral(n)),
             Token::Colon => {
                 let name = self.parse_identifier(false)?;
                 Ok(Node::SymbolLiteral(name))
             }
-            Token::Operator(Operator::Div) => {
-                let mut pattern = String::new();
-                loop {
-                    match self.lexer.chars.next() {
-                        Some('/') => break,
-                        Some('\\') => {
-                            pattern.push('\\');
-                            pattern.push(self.lexer.chars.next().unwrap());
-                        }
-                        Some(c) => {
-                            pattern.push(c);
-                        }
-                        None => return Err(Error::UnexpectedEOF),
-                    }
+            Token::Operator(Operator::Div) => Ok(Node::RegexLiteral(self.lexer.regex_body()?)),
+            Token::This => Ok(Node::ThisExpression),
+            // import.meta is only found in the top level of a module, not in
+            // the functions it declares
+            Token::Import if !self.scope(ParseScope::Function) => {
+                self.expect(Token::Dot)?;
+                match self.lexer.next()? {
+                    Token::Identifier(ref name) if name == "meta" => Ok(Node::ImportMeta),
+                    _ => Err(Error::UnexpectedToken(None)),
                 }
-                Ok(Node::RegexLiteral(pattern))
             }
-            Token::This => Ok(Node::ThisExpression),
+            // the arguments end the expression being constructed, so
+            // `new A().b()` calls `b` on the new object
             Token::New => {
-                let expr = self.parse_left_hand_side_expression()?;
-                Ok(Node::NewExpression(Box::new(expr)))
+                let callee = self.parse_primary_expression()?;
+                let callee = self.parse_member_expression(callee, false)?;
+                if self.eat(Token::LeftParen) {
+                    let (list, ..) = self.parse_expression_list(Token::RightParen)?;
+                    Ok(Node::NewExpression(Box::new(Node::CallExpression(
+                        Box::new(callee),
+                        list,
+                    ))))
+                } else {
+                    Ok(Node::NewExpression(Box::new(callee)))
+                }
             }
             Token::Identifier(i) => Ok(Node::Identifier(i)),
             Token::LeftBracket => {
@@ -1539,6 +2440,10 @@ impl<'a> Parser<'a> {
                             break;
                         }
                     }
+                    if self.eat(Token::Ellipsis) {
+                        fields.push(Node::ObjectSpread(Box::new(self.parse_expression()?)));
+                        continue;
+                    }
                     let name = if self.eat(Token::LeftBracket) {
                         let name = self.parse_expression()?;
                         self.expect(Token::RightBracket)?;
@@ -1553,7 +2458,7 @@ impl<'a> Parser<'a> {
                     } else if let Node::StringLiteral(n) = &name {
                         Node::Identifier(n.to_string())
                     } else {
-                        return Err(Error::UnexpectedToken);
+                        return Err(Error::UnexpectedToken(None));
                     };
                     fields.push(Node::Initializer(Box::new(name), Box::new(init)));
                 }
@@ -1564,9 +2469,10 @@ impl<'a> Parser<'a> {
                 if self.eat(Token::Arrow) {
                     // ( ... ) =>
                     self.parse_arrow_function(FunctionKind::Normal, list)
-                } else if list.is_empty() {
+                } else if list.is_empty() || no_spread_element(&list).is_err() {
                     // ( )
-                    Err(Error::UnexpectedToken)
+                    // ( ...rest )
+                    Err(Error::UnexpectedToken(None))
                 } else if list.len() == 1 && !trailing {
                     // ( expr )
                     Ok(Node::ParenthesizedExpression(Box::new(list.pop().unwrap())))
@@ -1578,7 +2484,7 @@ impl<'a> Parser<'a> {
             }
             Token::Async => {
                 self.expect(Token::LeftParen)?;
-                let list = self.parse_parameters(Token::RightParen)?;
+                let (list, ..) = self.parse_parameters(Token::RightParen)?;
                 self.expect(Token::Arrow)?;
                 self.parse_arrow_function(FunctionKind::Async, list)
             }
@@ -1586,87 +2492,15 @@ impl<'a> Parser<'a> {
             Token::BackQuote => {
                 let mut quasis = Vec::new();
                 let mut expressions = Vec::new();
-                let mut current = String::new();
                 loop {
-                    match self.lexer.chars.next() {
-                        Some('$') => {
-                            if self.lexer.chars.peek() == Some(&'{') {
-                                quasis.push(current);
-                                current = String::new();
-                                self.lexer.chars.next();
-                                let expr = self.parse_expression()?;
-                                expressions.push(expr);
-                                self.expect(Token::RightBrace)?;
-                            } else {
-                                current.push('$');
-                            }
-                        }
-                        Some('`') => break,
-                        Some(c) => {
-                            if c == '\\' {
-                                match self.lexer.chars.next() {
-                                    Some('n') => current.push('\n'),
-                                    Some('t') => current.push('\t'),
-                                    Some('\\') => current.push('\\'),
-                                    Some('u') => {
-                                        if Some('{') != self.lexer.chars.next() {
-                                            return Err(Error::UnexpectedToken);
-                                        }
-                                        let mut n = String::new();
-                                        macro_rules! digit {
-                                            () => {
-                                                let next = self.lexer.chars.next();
-                                                match next {
-                                                    Some('0'...'9') | Some('a'...'f')
-                                                    | Some('A'...'F') => {
-                                                        n.push(next.unwrap());
-                                                    }
-                                                    _ => return Err(Error::UnexpectedToken),
-                                                }
-                                            };
-                                        }
-                                        digit!();
-                                        digit!();
-                                        digit!();
-                                        digit!();
-                                        match u32::from_str_radix(n.as_str(), 16) {
-                                            Ok(n) => match std::char::from_u32(n) {
-                                                Some(c) => current.push(c),
-                                                None => return Err(Error::UnexpectedToken),
-                                            },
-                                            Err(_) => return Err(Error::UnexpectedToken),
-                                        }
-                                        if Some('}') != self.lexer.chars.next() {
-                                            return Err(Error::UnexpectedToken);
-                                        }
-                                    }
-                                    Some('U') => {
-                                        if Some('{') != self.lexer.chars.next() {
-                                            return Err(Error::UnexpectedToken);
-                                        }
-                                        let mut name = String::new();
-                                        loop {
-                                            match self.lexer.chars.next() {
-                                                Some('}') => break,
-                                                None => return Err(Error::UnexpectedEOF),
-                                                Some(c) => name.push(c),
-                                            }
-                                        }
-                                        match UNICODE_NAME_MAP.get(name.as_str()) {
-                                            Some(c) => current.push(*c),
-                                            None => return Err(Error::UnexpectedToken),
-                                        };
-                                    }
-                                    None | _ => return Err(Error::UnexpectedEOF),
-                                }
-                            } else {
-                                current.push(c);
-                            }
-                        }
-                        None => return Err(Error::UnexpectedEOF),
+                    let (quasi, substitution) = self.lexer.template_quasi()?;
+                    quasis.push(quasi);
+                    if !substitution {
+                        break;
                     }
+                    expressions.push(self.parse_expression()?);
+                    self.expect(Token::RightBrace)?;
                 }
-                quasis.push(current);
                 Ok(Node::TemplateLiteral(quasis, expressions))
             }
             Token::Match => {
@@ -1683,7 +2517,7 @@ impl<'a> Parser<'a> {
                             break;
                         }
                     }
-                    let pattern = self.parse_pattern()?;
+                    let pattern = self.parse_pattern(&mut HashMap::new())?;
                     self.expect(Token::Arrow)?;
                     let consequent = if self.peek(Token::LeftBrace) {
                         self.parse_block(ParseScope::Block)?
@@ -1694,33 +2528,53 @@ impl<'a> Parser<'a> {
                 }
                 Ok(Node::MatchExpression(Box::new(expr), arms))
             }
-            _ => Err(Error::UnexpectedToken),
+            _ => Err(Error::UnexpectedToken(None)),
         }
     }
 
-    fn parse_pattern(&mut self) -> Result<Node, Error> {
-        match self.lexer.peek()? {
+    // the pattern of a match arm. `bindings` holds the names it binds so far,
+    // a name can only be bound once per arm.
+    fn parse_pattern(
+        &mut self,
+        bindings: &mut HashMap<String, SourcePosition>,
+    ) -> Result<Node, Error> {
+        match self.lexer.next()? {
             // 1
+            // -1
             // "hi"
+            // null
+            Token::NumberLiteral(n) => Ok(Node::NumberLiteral(n)),
+            Token::BigIntLiteral(n) => Ok(Node::BigIntLiteral(n)),
+            Token::Operator(Operator::Sub) => match self.lexer.next()? {
+                Token::NumberLiteral(n) => Ok(Node::NumberLiteral(-n)),
+                Token::BigIntLiteral(n) => Ok(Node::BigIntLiteral(-n)),
+                _ => Err(Error::UnexpectedToken(None)),
+            },
+            Token::StringLiteral(s) => Ok(Node::StringLiteral(s)),
+            Token::Null => Ok(Node::NullLiteral),
+            Token::True => Ok(Node::TrueLiteral),
+            Token::False => Ok(Node::FalseLiteral),
             // a
-            Token::NumberLiteral(..) | Token::StringLiteral(..) | Token::Identifier(..) => {
-                self.parse_expression()
+            // _
+            Token::Identifier(name) => {
+                if name != "_" {
+                    let position = self.lexer.token_position();
+                    if let Some(original) = bindings.get(&name) {
+                        return Err(Error::DuplicateBinding(*original));
+                    }
+                    bindings.insert(name.clone(), position);
+                }
+                Ok(Node::Identifier(name))
             }
             // { a }
             // { a: b }
             // { a: { c } }
             // { a: b, ... }
             Token::LeftBrace => {
-                self.lexer.next()?;
                 let mut patterns = IndexMap::new();
                 let mut first = true;
                 let mut wildcard = false;
                 while !self.eat(Token::RightBrace) {
-                    if !first && self.eat(Token::Ellipsis) {
-                        wildcard = true;
-                        self.expect(Token::RightBrace)?;
-                        break;
-                    }
                     if first {
                         first = false;
                     } else {
@@ -1729,11 +2583,21 @@ impl<'a> Parser<'a> {
                             break;
                         }
                     }
+                    if self.eat(Token::Ellipsis) {
+                        wildcard = true;
+                        self.expect(Token::RightBrace)?;
+                        break;
+                    }
                     let name = self.parse_identifier(false)?;
                     if self.eat(Token::Colon) {
-                        let pattern = self.parse_pattern()?;
+                        let pattern = self.parse_pattern(bindings)?;
                         patterns.insert(name, pattern);
                     } else {
+                        let position = self.lexer.token_position();
+                        if let Some(original) = bindings.get(&name) {
+                            return Err(Error::DuplicateBinding(*original));
+                        }
+                        bindings.insert(name.clone(), position);
                         patterns.insert(name.to_string(), Node::Identifier(name));
                     }
                 }
@@ -1743,16 +2607,10 @@ impl<'a> Parser<'a> {
             // [{ b }]
             // [a, ...]
             Token::LeftBracket => {
-                self.lexer.next()?;
                 let mut patterns = Vec::new();
                 let mut first = true;
                 let mut wildcard = false;
                 while !self.eat(Token::RightBracket) {
-                    if !first && self.eat(Token::Ellipsis) {
-                        wildcard = true;
-                        self.expect(Token::RightBracket)?;
-                        break;
-                    }
                     if first {
                         first = false;
                     } else {
@@ -1761,12 +2619,39 @@ impl<'a> Parser<'a> {
                             break;
                         }
                     }
-                    let pattern = self.parse_pattern()?;
+                    if self.eat(Token::Ellipsis) {
+                        wildcard = true;
+                        self.expect(Token::RightBracket)?;
+                        break;
+                    }
+                    let pattern = self.parse_pattern(bindings)?;
                     patterns.push(pattern);
                 }
                 Ok(Node::ArrayPattern(patterns, wildcard))
             }
-            _ => Err(Error::UnexpectedToken),
+            // (a, b)
+            // (a,)
+            // (a)
+            Token::LeftParen => {
+                let mut patterns = Vec::new();
+                let mut trailing = false;
+                while !self.eat(Token::RightParen) {
+                    patterns.push(self.parse_pattern(bindings)?);
+                    trailing = self.eat(Token::Comma);
+                    if !trailing {
+                        self.expect(Token::RightParen)?;
+                        break;
+                    }
+                }
+                if patterns.len() == 1 && !trailing {
+                    Ok(patterns.pop().unwrap())
+                } else if patterns.is_empty() {
+                    Err(Error::UnexpectedToken(None))
+                } else {
+                    Ok(Node::TuplePattern(patterns))
+                }
+            }
+            _ => Err(Error::UnexpectedToken(None)),
         }
     }
 
@@ -1776,7 +2661,8 @@ impl<'a> Parser<'a> {
         }
         let name = self.parse_identifier(false)?;
         if !expression {
-            self.declare(&name, false)?;
+            let position = self.lexer.token_position();
+            self.declare(&name, false, position)?;
         }
         let extends = if self.eat(Token::Extends) {
             Some(Box::new(self.parse_left_hand_side_expression()?))
@@ -1786,12 +2672,15 @@ impl<'a> Parser<'a> {
         self.expect(Token::LeftBrace)?;
         let mut fields = Vec::new();
         while !self.eat(Token::RightBrace) {
-            let name = self.parse_identifier(false)?;
+            let name = if self.eat(Token::LeftBracket) {
+                let name = self.parse_expression()?;
+                self.expect(Token::RightBracket)?;
+                name
+            } else {
+                Node::StringLiteral(self.parse_identifier(false)?)
+            };
             let f = self.parse_function(true, FunctionKind::Normal)?;
-            fields.push(Node::Initializer(
-                Box::new(Node::StringLiteral(name)),
-                Box::new(f),
-            ));
+            fields.push(Node::Initializer(Box::new(name), Box::new(f)));
         }
         if expression {
             Ok(Node::ClassExpression(name, extends, fields))
@@ -1805,9 +2694,19 @@ impl<'a> Parser<'a> {
         kind: FunctionKind,
         mut args: Vec<Node>,
     ) -> Result<Node, Error> {
-        for item in &mut args {
+        let last = args.len().saturating_sub(1);
+        for (i, item) in args.iter_mut().enumerate() {
             match item {
-                Node::Identifier(..) | Node::Initializer(..) => {}
+                Node::Identifier(..) | Node::Initializer(..) | Node::RestElement(..) => {}
+                // `(a, ...rest) =>` is parsed as a spread, the rest element must be last
+                Node::SpreadElement(e) if i == last => {
+                    if let Node::Identifier(name) = &**e {
+                        let rest = Node::RestElement(name.to_string());
+                        *item = rest;
+                    } else {
+                        return Err(Error::UnexpectedToken(None));
+                    }
+                }
                 Node::BinaryExpression(op, left, right) if *op == Operator::Assign => {
                     if let Node::Identifier(..) = &**left {
                         let init = Node::Initializer(
@@ -1816,26 +2715,62 @@ impl<'a> Parser<'a> {
                         );
                         std::mem::replace(item, init);
                     } else {
-                        return Err(Error::UnexpectedToken);
+                        return Err(Error::UnexpectedToken(None));
                     }
                 }
-                _ => return Err(Error::UnexpectedToken),
+                _ => return Err(Error::UnexpectedToken(None)),
             }
         }
+        // the parameters of an arrow function are parsed as expressions, so
+        // they are all attributed to the arrow.
+        let position = self.lexer.token_position();
+        let declared: HashMap<_, _> = args
+            .iter()
+            .filter_map(|arg| match arg {
+                Node::Identifier(name) | Node::RestElement(name) => Some(name.clone()),
+                Node::Initializer(name, _) => match &**name {
+                    Node::Identifier(name) => Some(name.clone()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .map(|name| (name, position))
+            .collect();
+        let repeated = declared.len() < args.len();
         let body = if self.peek(Token::LeftBrace) {
-            self.parse_block(match kind {
-                FunctionKind::Normal => ParseScope::Function,
-                FunctionKind::Async => ParseScope::AsyncFunction,
-                FunctionKind::Generator => ParseScope::GeneratorFunction,
-                _ => unreachable!(),
-            })?
+            self.parse_block_with(
+                match kind {
+                    FunctionKind::Normal => ParseScope::Function,
+                    FunctionKind::Async => ParseScope::AsyncFunction,
+                    FunctionKind::Generator => ParseScope::GeneratorFunction,
+                    _ => unreachable!(),
+                },
+                declared,
+            )?
         } else {
-            let expr = self.parse_assignment_expression()?;
+            let position = self.lexer.peek_position();
+            // a concise body is in the function's scope like a block body,
+            // for what may be awaited and what is only found at the top level
+            let saved = self.scope_bits;
+            self.scope_bits |= match kind {
+                FunctionKind::Async => ParseScope::AsyncFunction,
+                _ => ParseScope::Function,
+            } as u8;
+            let expr = self.parse_assignment_expression();
+            self.scope_bits = saved;
+            let expr = expr?;
+            let span = SourceSpan {
+                start: position,
+                end: self.lexer.token_end(),
+            };
             Node::Block(
-                Scope::new(ParseScope::Function),
+                Scope::new(ParseScope::Function).with_positions(vec![span]),
                 vec![Node::ReturnStatement(Some(Box::new(expr)))],
             )
         };
+        if repeated && (self.strict || is_strict_body(&body)) {
+            return Err(Error::DuplicateBinding(position));
+        }
         Ok(Node::ArrowFunctionExpression(
             kind | FunctionKind::Arrow,
             args,
@@ -1858,6 +2793,10 @@ impl<'a> Parser<'a> {
                 }
                 trailing = false;
             }
+            if self.eat(Token::Ellipsis) {
+                list.push(Node::SpreadElement(Box::new(self.parse_expression()?)));
+                continue;
+            }
             list.push(self.parse_expression()?);
         }
         Ok((list, trailing))
@@ -1880,8 +2819,22 @@ impl<'a> Parser<'a> {
         Ok(identifiers)
     }
 
-    fn parse_parameters(&mut self, close: Token) -> Result<Vec<Node>, Error> {
+    fn parse_parameters(
+        &mut self,
+        close: Token,
+    ) -> Result<
+        (
+            Vec<Node>,
+            HashMap<String, SourcePosition>,
+            Option<SourcePosition>,
+        ),
+        Error,
+    > {
         let mut parameters = Vec::new();
+        let mut declared = HashMap::new();
+        // where the first parameter which is repeated was declared, which is
+        // only an error in strict code, and that isn't known until the body
+        let mut repeated = None;
         let mut first = true;
         while !self.eat(close.clone()) {
             if first {
@@ -1892,7 +2845,24 @@ impl<'a> Parser<'a> {
                     break;
                 }
             }
+            if self.eat(Token::Ellipsis) {
+                // the rest element must be the last parameter
+                let ident = self.parse_identifier(false)?;
+                if let Some(original) = declared.get(&ident) {
+                    repeated = repeated.or(Some(*original));
+                } else {
+                    declared.insert(ident.clone(), self.lexer.token_position());
+                }
+                parameters.push(Node::RestElement(ident));
+                self.expect(close)?;
+                break;
+            }
             let ident = self.parse_identifier(false)?;
+            if let Some(original) = declared.get(&ident) {
+                repeated = repeated.or(Some(*original));
+            } else {
+                declared.insert(ident.clone(), self.lexer.token_position());
+            }
             if self.lexer.peek()? == &Token::Operator(Operator::Assign) {
                 self.lexer.next()?;
                 let init = self.parse_expression()?;
@@ -1904,6 +2874,26 @@ impl<'a> Parser<'a> {
                 parameters.push(Node::Identifier(ident));
             }
         }
-        Ok(parameters)
+        Ok((parameters, declared, repeated))
     }
 }
+
+#[test]
+fn test_function_kind() {
+    assert_eq!(FunctionKind::from_bits(0b0001), Some(FunctionKind::Normal));
+    assert_eq!(
+        FunctionKind::from_bits(0b1010),
+        Some(FunctionKind::Async | FunctionKind::Arrow)
+    );
+    assert_eq!(FunctionKind::from_bits(0), None);
+    assert_eq!(FunctionKind::from_bits(0b1000), None);
+    assert_eq!(FunctionKind::from_bits(0b0011), None);
+    assert_eq!(FunctionKind::from_bits(0xff), None);
+    assert_eq!(format!("{}", FunctionKind::Normal), "normal");
+    assert_eq!(format!("{}", FunctionKind::Async), "async");
+    assert_eq!(format!("{}", FunctionKind::Generator), "generator");
+    assert_eq!(
+        format!("{}", FunctionKind::Async | FunctionKind::Arrow),
+        "async arrow"
+    );
+}
@@ -0,0 +1,884 @@
+use crate::parser::{is_identifier_part, is_identifier_start, FunctionKind, Node, Operator, Scope};
+
+// binding power of each kind of expression, higher binds tighter.
+const PREC_ASSIGNMENT: u8 = 1;
+const PREC_CONDITIONAL: u8 = 2;
+const PREC_RANGE: u8 = 10;
+const PREC_ADDITIVE: u8 = 12;
+const PREC_MULTIPLICATIVE: u8 = 13;
+const PREC_UNARY: u8 = 15;
+const PREC_LHS: u8 = 16;
+const PREC_PRIMARY: u8 = 17;
+
+fn operator_str(op: Operator) -> &'static str {
+    match op {
+        Operator::Add => "+",
+        Operator::AddAssign => "+=",
+        Operator::Sub => "-",
+        Operator::SubAssign => "-=",
+        Operator::Mul => "*",
+        Operator::MulAssign => "*=",
+        Operator::Pow => "**",
+        Operator::PowAssign => "**=",
+        Operator::Div => "/",
+        Operator::DivAssign => "/=",
+        Operator::Mod => "%",
+        Operator::ModAssign => "%=",
+        Operator::LeftShift => "<<",
+        Operator::RightShift => ">>",
+        Operator::UnsignedRightShift => ">>>",
+        Operator::UnsignedRightShiftAssign => ">>>=",
+        Operator::GreaterThan => ">",
+        Operator::GreaterThanOrEqual => ">=",
+        Operator::Not => "!",
+        Operator::LessThan => "<",
+        Operator::LessThanOrEqual => "<=",
+        Operator::BitwiseAND => "&",
+        Operator::BitwiseOR => "|",
+        Operator::BitwiseXOR => "^",
+        Operator::BitwiseNOT => "~",
+        Operator::LogicalAND => "&&",
+        Operator::LogicalOR => "||",
+        Operator::Assign => "=",
+        Operator::Equal => "==",
+        Operator::NotEqual => "!=",
+        Operator::Typeof => "typeof",
+        Operator::Void => "void",
+        Operator::Delete => "delete",
+        Operator::Has => "has",
+        Operator::Range => "..",
+        Operator::RangeInclusive => "..=",
+    }
+}
+
+fn operator_precedence(op: Operator) -> u8 {
+    match op {
+        Operator::Assign
+        | Operator::AddAssign
+        | Operator::SubAssign
+        | Operator::MulAssign
+        | Operator::PowAssign
+        | Operator::DivAssign
+        | Operator::ModAssign
+        | Operator::UnsignedRightShiftAssign => PREC_ASSIGNMENT,
+        Operator::LogicalOR => 3,
+        Operator::LogicalAND => 4,
+        Operator::BitwiseOR => 5,
+        Operator::BitwiseXOR => 6,
+        Operator::BitwiseAND => 7,
+        Operator::Equal | Operator::NotEqual => 8,
+        Operator::LessThan
+        | Operator::GreaterThan
+        | Operator::LessThanOrEqual
+        | Operator::GreaterThanOrEqual
+        | Operator::Has => 9,
+        Operator::Range | Operator::RangeInclusive => PREC_RANGE,
+        Operator::LeftShift | Operator::RightShift | Operator::UnsignedRightShift => 11,
+        Operator::Add | Operator::Sub => PREC_ADDITIVE,
+        Operator::Mul | Operator::Div | Operator::Mod => PREC_MULTIPLICATIVE,
+        Operator::Pow => 14,
+        Operator::Not
+        | Operator::BitwiseNOT
+        | Operator::Typeof
+        | Operator::Void
+        | Operator::Delete => PREC_UNARY,
+    }
+}
+
+fn precedence(node: &Node) -> u8 {
+    match node {
+        // negative and non-finite numbers can't be written as a single literal,
+        // so they are printed as the arithmetic that the parser folds back.
+        Node::NumberLiteral(n) if n.is_nan() || n.is_infinite() => {
+            if n.is_sign_negative() {
+                PREC_ADDITIVE
+            } else {
+                PREC_MULTIPLICATIVE
+            }
+        }
+        Node::NumberLiteral(n) if *n < 0.0 => PREC_ADDITIVE,
+        Node::BinaryExpression(op, ..) => operator_precedence(*op),
+        Node::UnaryExpression(..) | Node::AwaitExpression(..) => PREC_UNARY,
+        // properties can be read from `new A()`, but not from `new A`
+        Node::NewExpression(expr) => match **expr {
+            Node::CallExpression(..) => PREC_LHS,
+            _ => PREC_UNARY,
+        },
+        Node::ConditionalExpression(..) => PREC_CONDITIONAL,
+        Node::YieldExpression(..) | Node::ArrowFunctionExpression(..) | Node::Initializer(..) => {
+            PREC_ASSIGNMENT
+        }
+        Node::MemberExpression(..)
+        | Node::ComputedMemberExpression(..)
+        | Node::CallExpression(..)
+        | Node::TailCallExpression(..) => PREC_LHS,
+        _ => PREC_PRIMARY,
+    }
+}
+
+// whether the property accesses leading to `node` include a call
+fn has_call(node: &Node) -> bool {
+    match node {
+        Node::CallExpression(..) | Node::TailCallExpression(..) => true,
+        Node::MemberExpression(base, _) | Node::ComputedMemberExpression(base, _) => has_call(base),
+        _ => false,
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if is_identifier_start(c) => chars.all(is_identifier_part),
+        _ => false,
+    }
+}
+
+fn number_str(n: f64) -> String {
+    if n.is_nan() {
+        "0 / 0".to_string()
+    } else if n.is_infinite() {
+        if n.is_sign_negative() {
+            "0 - 1 / 0".to_string()
+        } else {
+            "1 / 0".to_string()
+        }
+    } else if n < 0.0 {
+        format!("0 - {}", -n)
+    } else {
+        format!("{}", n)
+    }
+}
+
+fn string_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                out.push_str(&format!("\\u{{{:04X}}}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn template_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '`' => out.push_str("\\`"),
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push_str("\\${");
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// a decorated function is desugared into `const name = d1(d2(function() {}))`
+fn decorators(init: &Node) -> Option<(Vec<&Node>, &Node)> {
+    let mut decorators = Vec::new();
+    let mut top = init;
+    while let Node::CallExpression(callee, args) = top {
+        if args.len() != 1 {
+            return None;
+        }
+        decorators.push(&**callee);
+        top = &args[0];
+    }
+    match top {
+        Node::FunctionExpression(_, None, ..) if !decorators.is_empty() => Some((decorators, top)),
+        _ => None,
+    }
+}
+
+struct Printer<'a> {
+    out: String,
+    indent: usize,
+    scopes: Vec<&'a Scope>,
+}
+
+impl<'a> Printer<'a> {
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn mutable(&self, name: &str) -> bool {
+        for scope in self.scopes.iter().rev() {
+            if let Some(mutable) = scope.bindings.get(name) {
+                return *mutable;
+            }
+        }
+        true
+    }
+
+    fn block(&mut self, node: &'a Node) {
+        match node {
+            Node::Block(scope, stmts) => {
+                self.scopes.push(scope);
+                if stmts.is_empty() {
+                    self.push("{}");
+                } else {
+                    self.push("{");
+                    self.indent += 1;
+                    for stmt in stmts {
+                        self.newline();
+                        self.statement(stmt);
+                    }
+                    self.indent -= 1;
+                    self.newline();
+                    self.push("}");
+                }
+                self.scopes.pop();
+            }
+            _ => {
+                self.push("{");
+                self.indent += 1;
+                self.newline();
+                self.statement(node);
+                self.indent -= 1;
+                self.newline();
+                self.push("}");
+            }
+        }
+    }
+
+    fn statement(&mut self, node: &'a Node) {
+        match node {
+            Node::Block(..) => self.block(node),
+            // folded away `if false {}`
+            Node::NullLiteral => self.push("if false {}"),
+            Node::ErrorNode => self.push("/* syntax error */"),
+            Node::IfStatement(..) => self.if_statement(node),
+            Node::WhileLoop(test, body) => {
+                self.push("while ");
+                self.expression(test, 0);
+                self.push(" ");
+                self.block(body);
+            }
+            Node::ForLoop(r#async, binding, target, body) => {
+                self.push("for ");
+                if *r#async {
+                    self.push("await ");
+                }
+                self.push(binding);
+                self.push(" in ");
+                self.expression(target, PREC_ASSIGNMENT);
+                self.push(" ");
+                self.block(body);
+            }
+            Node::ExpressionStatement(expr) => {
+                self.expression(expr, 0);
+                self.push(";");
+            }
+            Node::FunctionDeclaration(kind, name, params, body) => {
+                self.function(*kind, Some(name.as_str()), params, body)
+            }
+            Node::ClassDeclaration(name, extends, fields) => self.class(name, extends, fields),
+            Node::LexicalInitialization(name, init) => {
+                if let Some((decorators, function)) = decorators(init) {
+                    for d in decorators {
+                        self.push("@");
+                        self.expression(d, PREC_LHS);
+                        self.newline();
+                    }
+                    if let Node::FunctionExpression(kind, _, params, body) = function {
+                        self.function(*kind, Some(name.as_str()), params, body);
+                    }
+                } else {
+                    self.push(if self.mutable(name) { "let " } else { "const " });
+                    self.push(name);
+                    self.push(" = ");
+                    self.expression(init, PREC_ASSIGNMENT);
+                    self.push(";");
+                }
+            }
+            Node::ReturnStatement(expr) => {
+                self.push("return");
+                if let Some(expr) = expr {
+                    self.push(" ");
+                    self.expression(expr, 0);
+                }
+                self.push(";");
+            }
+            Node::ThrowStatement(expr) => {
+                self.push("throw ");
+                self.expression(expr, 0);
+                self.push(";");
+            }
+            Node::BreakStatement(label) | Node::ContinueStatement(label) => {
+                self.push(match node {
+                    Node::BreakStatement(..) => "break",
+                    _ => "continue",
+                });
+                if let Some(label) = label {
+                    self.push(" ");
+                    self.push(label);
+                }
+                self.push(";");
+            }
+            Node::LabeledStatement(label, body) => {
+                self.push(label);
+                self.push(": ");
+                self.statement(body);
+            }
+            Node::TryStatement(try_clause, binding, catch_clause, finally_clause) => {
+                self.push("try ");
+                self.block(try_clause);
+                if let Some(catch_clause) = catch_clause {
+                    self.push(" catch ");
+                    if let Some(binding) = binding {
+                        self.push(binding);
+                        self.push(" ");
+                    }
+                    self.block(catch_clause);
+                }
+                if let Some(finally_clause) = finally_clause {
+                    self.push(" finally ");
+                    self.block(finally_clause);
+                }
+            }
+            Node::ImportDeclaration(specifier) => {
+                self.push("import ");
+                self.push(&string_str(specifier));
+                self.push(";");
+            }
+            Node::ImportNamedDeclaration(specifier, names) => {
+                self.push("import { ");
+                self.push(&names.join(", "));
+                self.push(" } from ");
+                self.push(&string_str(specifier));
+                self.push(";");
+            }
+            Node::ImportDefaultDeclaration(specifier, name, kind) => {
+                self.push("import ");
+                self.push(name);
+                self.push(" from ");
+                self.push(&string_str(specifier));
+                if let Some(kind) = kind {
+                    self.push(" as ");
+                    self.push(kind);
+                }
+                self.push(";");
+            }
+            Node::ImportStandardDeclaration(namespace, names) => {
+                self.push("import { ");
+                self.push(&names.join(", "));
+                self.push(" } from standard:");
+                self.push(namespace);
+                self.push(";");
+            }
+            Node::ExportDeclaration(decl) => {
+                self.push("export ");
+                self.statement(decl);
+            }
+            _ => {
+                self.expression(node, 0);
+                self.push(";");
+            }
+        }
+    }
+
+    fn if_statement(&mut self, node: &'a Node) {
+        if let Node::IfStatement(test, consequent, alternative) = node {
+            self.push("if ");
+            self.expression(test, 0);
+            self.push(" ");
+            self.block(consequent);
+            if let Some(alternative) = alternative {
+                self.push(" else ");
+                match &**alternative {
+                    Node::IfStatement(..) => self.if_statement(alternative),
+                    Node::NullLiteral => self.push("if false {}"),
+                    _ => self.block(alternative),
+                }
+            }
+        }
+    }
+
+    fn parameters(&mut self, params: &'a [Node]) {
+        self.push("(");
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            self.expression(param, PREC_ASSIGNMENT);
+        }
+        self.push(")");
+    }
+
+    fn function(
+        &mut self,
+        kind: FunctionKind,
+        name: Option<&'a str>,
+        params: &'a [Node],
+        body: &'a Node,
+    ) {
+        self.push(match kind {
+            FunctionKind::Async => "async function",
+            FunctionKind::Generator => "gen function",
+            _ => "function",
+        });
+        if let Some(name) = name {
+            self.push(" ");
+            self.push(name);
+        }
+        self.parameters(params);
+        self.push(" ");
+        self.block(body);
+    }
+
+    fn method(&mut self, key: &'a Node, value: &'a Node) {
+        match key {
+            Node::StringLiteral(s) if is_identifier(s) => self.push(s),
+            _ => {
+                self.push("[");
+                self.expression(key, PREC_ASSIGNMENT);
+                self.push("]");
+            }
+        }
+        match value {
+            Node::FunctionExpression(FunctionKind::Normal, None, params, body) => {
+                self.parameters(params);
+                self.push(" ");
+                self.block(body);
+            }
+            _ => {
+                self.push(": ");
+                self.expression(value, PREC_ASSIGNMENT);
+            }
+        }
+    }
+
+    fn class(&mut self, name: &'a str, extends: &'a Option<Box<Node>>, fields: &'a [Node]) {
+        self.push("class ");
+        self.push(name);
+        if let Some(extends) = extends {
+            self.push(" extends ");
+            self.expression(extends, PREC_LHS);
+        }
+        if fields.is_empty() {
+            self.push(" {}");
+            return;
+        }
+        self.push(" {");
+        self.indent += 1;
+        for field in fields {
+            self.newline();
+            if let Node::Initializer(key, value) = field {
+                self.method(key, value);
+            }
+        }
+        self.indent -= 1;
+        self.newline();
+        self.push("}");
+    }
+
+    fn list(&mut self, items: &'a [Node]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            self.expression(item, PREC_ASSIGNMENT);
+        }
+    }
+
+    fn pattern(&mut self, node: &'a Node) {
+        match node {
+            Node::ObjectPattern(patterns, wildcard) => {
+                self.push("{ ");
+                for (i, (name, pattern)) in patterns.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    self.push(name);
+                    match pattern {
+                        Node::Identifier(n) if n == name => {}
+                        _ => {
+                            self.push(": ");
+                            self.pattern(pattern);
+                        }
+                    }
+                }
+                if *wildcard {
+                    if !patterns.is_empty() {
+                        self.push(", ");
+                    }
+                    self.push("...");
+                }
+                self.push(" }");
+            }
+            Node::ArrayPattern(patterns, wildcard) => {
+                self.push("[");
+                for (i, pattern) in patterns.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    self.pattern(pattern);
+                }
+                if *wildcard {
+                    if !patterns.is_empty() {
+                        self.push(", ");
+                    }
+                    self.push("...");
+                }
+                self.push("]");
+            }
+            Node::TuplePattern(patterns) => {
+                self.push("(");
+                for (i, pattern) in patterns.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    self.pattern(pattern);
+                }
+                if patterns.len() == 1 {
+                    self.push(",");
+                }
+                self.push(")");
+            }
+            // a pattern can't be arithmetic, but it can be a negative literal
+            Node::NumberLiteral(n) if *n < 0.0 && n.is_finite() => {
+                self.push(&format!("-{}", -n));
+            }
+            _ => self.expression(node, PREC_ASSIGNMENT),
+        }
+    }
+
+    fn expression(&mut self, node: &'a Node, min_precedence: u8) {
+        if precedence(node) < min_precedence {
+            self.push("(");
+            self.expression(node, 0);
+            self.push(")");
+            return;
+        }
+        match node {
+            Node::NullLiteral => self.push("null"),
+            Node::TrueLiteral => self.push("true"),
+            Node::FalseLiteral => self.push("false"),
+            Node::NumberLiteral(n) => self.push(&number_str(*n)),
+            Node::BigIntLiteral(n) => self.push(&format!("{}n", n)),
+            Node::StringLiteral(s) => self.push(&string_str(s)),
+            Node::SymbolLiteral(s) => {
+                self.push(":");
+                self.push(s);
+            }
+            Node::RegexLiteral(s) => {
+                self.push("/");
+                self.push(s);
+                self.push("/");
+            }
+            Node::ObjectLiteral(fields) => {
+                if fields.is_empty() {
+                    self.push("{}");
+                    return;
+                }
+                self.push("{ ");
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    match field {
+                        Node::Initializer(key, value) => self.method(key, value),
+                        Node::ObjectSpread(expr) => {
+                            self.push("...");
+                            self.expression(expr, PREC_ASSIGNMENT);
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                self.push(" }");
+            }
+            Node::ArrayLiteral(items) => {
+                self.push("[");
+                self.list(items);
+                self.push("]");
+            }
+            Node::TupleLiteral(items) => {
+                self.push("(");
+                self.list(items);
+                if items.len() == 1 {
+                    self.push(",");
+                }
+                self.push(")");
+            }
+            Node::TemplateLiteral(quasis, expressions) => {
+                self.push("`");
+                for (i, quasi) in quasis.iter().enumerate() {
+                    self.push(&template_str(quasi));
+                    if let Some(expr) = expressions.get(i) {
+                        self.push("${");
+                        self.expression(expr, 0);
+                        self.push("}");
+                    }
+                }
+                self.push("`");
+            }
+            Node::Identifier(name) => self.push(name),
+            Node::RestElement(name) => {
+                self.push("...");
+                self.push(name);
+            }
+            Node::SpreadElement(expr) => {
+                self.push("...");
+                self.expression(expr, PREC_ASSIGNMENT);
+            }
+            Node::ConditionalExpression(test, consequent, alternative) => {
+                self.expression(test, PREC_CONDITIONAL + 1);
+                self.push(" ? ");
+                self.expression(consequent, PREC_ASSIGNMENT);
+                self.push(" : ");
+                self.expression(alternative, PREC_ASSIGNMENT);
+            }
+            Node::UnaryExpression(op, expr) => {
+                self.push(operator_str(*op));
+                match op {
+                    Operator::Typeof | Operator::Void | Operator::Delete => self.push(" "),
+                    _ => {
+                        if let Node::UnaryExpression(..) = &**expr {
+                            self.push(" ");
+                        }
+                    }
+                }
+                self.expression(expr, PREC_UNARY);
+            }
+            Node::BinaryExpression(op, left, right) => {
+                // binary productions in the parser are right recursive, so
+                // only the left side needs to bind strictly tighter.
+                let precedence = operator_precedence(*op);
+                self.expression(left, precedence + 1);
+                self.push(" ");
+                self.push(operator_str(*op));
+                self.push(" ");
+                // ranges don't chain at all
+                if precedence == PREC_RANGE {
+                    self.expression(right, precedence + 1);
+                } else {
+                    self.expression(right, precedence);
+                }
+            }
+            Node::ParenthesizedExpression(expr) => {
+                self.push("(");
+                self.expression(expr, 0);
+                self.push(")");
+            }
+            Node::YieldExpression(expr) => {
+                self.push("yield");
+                if let Some(expr) = expr {
+                    self.push(" ");
+                    self.expression(expr, PREC_ASSIGNMENT);
+                }
+            }
+            Node::AwaitExpression(expr) => {
+                self.push("await ");
+                self.expression(expr, PREC_UNARY);
+            }
+            Node::ThisExpression => self.push("this"),
+            Node::ImportMeta => self.push("import.meta"),
+            Node::NewExpression(expr) => {
+                self.push("new ");
+                match &**expr {
+                    Node::CallExpression(callee, args) => {
+                        // a call in the callee would take the arguments
+                        if has_call(callee) {
+                            self.push("(");
+                            self.expression(callee, 0);
+                            self.push(")");
+                        } else {
+                            self.expression(callee, PREC_LHS);
+                        }
+                        self.push("(");
+                        self.list(args);
+                        self.push(")");
+                    }
+                    _ if has_call(expr) => {
+                        self.push("(");
+                        self.expression(expr, 0);
+                        self.push(")");
+                    }
+                    _ => self.expression(expr, PREC_LHS),
+                }
+            }
+            Node::MatchExpression(expr, arms) => {
+                self.push("match ");
+                self.expression(expr, 0);
+                self.push(" {");
+                self.indent += 1;
+                for arm in arms {
+                    self.newline();
+                    if let Node::MatchArm(pattern, consequent) = arm {
+                        self.pattern(pattern);
+                        self.push(" => ");
+                        match &**consequent {
+                            Node::Block(..) => self.block(consequent),
+                            _ => self.expression(consequent, PREC_ASSIGNMENT),
+                        }
+                    }
+                    self.push(",");
+                }
+                self.indent -= 1;
+                self.newline();
+                self.push("}");
+            }
+            Node::MatchArm(pattern, consequent) => {
+                self.pattern(pattern);
+                self.push(" => ");
+                self.expression(consequent, PREC_ASSIGNMENT);
+            }
+            Node::ObjectPattern(..) | Node::ArrayPattern(..) | Node::TuplePattern(..) => {
+                self.pattern(node)
+            }
+            Node::MemberExpression(base, property) => {
+                self.expression(base, PREC_LHS);
+                if is_identifier(property) {
+                    self.push(".");
+                    self.push(property);
+                } else {
+                    self.push("[");
+                    self.push(&string_str(property));
+                    self.push("]");
+                }
+            }
+            Node::ComputedMemberExpression(base, property) => {
+                self.expression(base, PREC_LHS);
+                self.push("[");
+                self.expression(property, 0);
+                self.push("]");
+            }
+            Node::CallExpression(callee, args) | Node::TailCallExpression(callee, args) => {
+                self.expression(callee, PREC_LHS);
+                self.push("(");
+                self.list(args);
+                self.push(")");
+            }
+            Node::FunctionExpression(kind, name, params, body) => {
+                self.function(*kind, name.as_ref().map(String::as_str), params, body)
+            }
+            Node::ArrowFunctionExpression(kind, params, body) => {
+                if *kind & FunctionKind::Async == FunctionKind::Async {
+                    self.push("async ");
+                }
+                self.parameters(params);
+                self.push(" => ");
+                match &**body {
+                    // `() => expr` is parsed as `() => { return expr; }`
+                    Node::Block(scope, stmts) if scope.bindings.is_empty() && stmts.len() == 1 => {
+                        match &stmts[0] {
+                            Node::ReturnStatement(Some(expr)) => match &**expr {
+                                Node::TailCallExpression(..) | Node::ObjectLiteral(..) => {
+                                    self.block(body)
+                                }
+                                _ => self.expression(expr, PREC_ASSIGNMENT),
+                            },
+                            _ => self.block(body),
+                        }
+                    }
+                    _ => self.block(body),
+                }
+            }
+            Node::ClassExpression(name, extends, fields) => self.class(name, extends, fields),
+            Node::Initializer(target, init) => {
+                self.expression(target, PREC_LHS);
+                self.push(" = ");
+                self.expression(init, PREC_ASSIGNMENT);
+            }
+            _ => self.statement(node),
+        }
+    }
+}
+
+/// Generate source code for `node`. The output is not byte for byte the
+/// original source (comments and formatting are lost), but parsing it again
+/// produces an equal tree. A `Block` at the root is printed as a script body.
+pub fn print_node(node: &Node) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        indent: 0,
+        scopes: Vec::new(),
+    };
+    match node {
+        Node::Block(scope, stmts) => {
+            printer.scopes.push(scope);
+            for (i, stmt) in stmts.iter().enumerate() {
+                if i > 0 {
+                    printer.newline();
+                }
+                match stmt {
+                    // undo the parser turning the trailing expression
+                    // statement into the completion value of the script
+                    Node::ParenthesizedExpression(expr) if i == stmts.len() - 1 => {
+                        printer.expression(expr, 0);
+                        printer.push(";");
+                    }
+                    _ => printer.statement(stmt),
+                }
+            }
+            printer.out.push('\n');
+        }
+        _ => printer.expression(node, 0),
+    }
+    printer.out
+}
+
+#[test]
+fn test_print_round_trip() {
+    let sources = [
+        include_str!("../test/exports.sl"),
+        include_str!("../test/fib.sl"),
+        include_str!("../test/finally.sl"),
+        include_str!("../test/module.sl"),
+        include_str!("../test/test.sl"),
+        "const a = 1; let b = a + 2 * 3 - (4 - 5); b -= 1; b;",
+        "const x = 0 - 1.5 + 0.25; x ** 2 ** 3;",
+        "let a = !true || typeof b == 'string' && void 0; a = c ? -d : ~e;",
+        "delete o.a; delete o[b + 1]; !delete o.c;",
+        "const s = `a ${b + `c ${d}`} \\\\ $x e`; s;",
+        "const t = (1, 'two', :three); const u = (t,); u[0][1];",
+        "const o = { a: 1, ['b c']: 2, [d]: 3, e, f(x) { return x; } }; o.a.b[c](d, e);",
+        "const f = async (a, b = 2) => a + b; const g = () => h(); const i = () => { return j; };",
+        "gen function g() { yield; yield 1; const x = yield g(); }",
+        "async function f() { for await x in y { await x; } }",
+        "for x in [1, 2] { if x { continue; } else if y { break; } else { x; } }",
+        "while a < b { a += 1; } if false {} if true { a; }",
+        "outer: while a { inner: for x in b { if x { continue outer; } break inner; } break; }",
+        "try { a(); } catch e { b(e); } finally { c(); } try { a(); } catch { b(); }",
+        "class A extends B.C { constructor(x) { this.x = x; } get() { return new A(this.x).x; } }",
+        "const m = match x { 1 => 'one', { a, b: [c, ...] } => c, { d: { e }, ... } => { e; }, y => y };",
+        "const n = match v { (a, [b, ...]) => a + b, (c,) => c, -1 => null, null => 0n, { ... } => 1, _ => 2 };",
+        "function x(v) { return (func) => () => v + func(); } @x('1') @x('2') function owo() { return '3'; }",
+        "import { readFile } from standard:fs; import a from './a'; import t from './t.txt' as text; import './b'; export const c = 1; export function d() {}",
+        "const r = /ab+c/; r.test('abbc');",
+        "function f(a, ...b) { return b; } const g = (...c) => c; const h = async (d, ...e) => e;",
+        "f(1, ...a, 2); o.m(...[b, c]); new A(...d);",
+        "new A().b(); new (a().b)(); new a.b(); new a.b; (new a).b;",
+        "const o = { ...a, b: 1, ...c ? d : e, ...f() };",
+        "const big = -(2n ** 64n) + 0xffn;",
+        "for i in 0..n + 1 { a < 1..=i; } const r = (0..2).rev(); [...a..b];",
+        "const π = 3; const $el = { café: π, ['a-b']: 1 }; $el.café + `${$el['a-b']}$`;",
+    ];
+    for source in sources.iter() {
+        let ast = crate::Parser::parse(source).unwrap();
+        let printed = print_node(&ast);
+        let reparsed = crate::Parser::parse(&printed)
+            .unwrap_or_else(|e| panic!("{:?} while reparsing:\n{}", e, printed));
+        assert_eq!(ast, reparsed, "printed source:\n{}", printed);
+        assert_eq!(printed, print_node(&reparsed));
+    }
+}
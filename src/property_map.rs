@@ -0,0 +1,95 @@
+//! The own properties of an object. Most objects have a handful, which are
+//! kept in a list that is searched from the front, since comparing a few
+//! keys is cheaper than hashing one. Objects which grow past `LIMIT`
+//! properties move them to a hash map.
+
+use crate::value::{ObjectKey, Value};
+use smallvec::SmallVec;
+use std::collections::HashMap;
+use std::fmt;
+
+// how many properties are stored in the object itself, and how many a list
+// holds before it is turned into a map.
+const INLINE: usize = 4;
+const LIMIT: usize = 8;
+
+#[derive(Clone, Finalize)]
+pub enum PropertyMap {
+    List(SmallVec<[(ObjectKey, Value); INLINE]>),
+    Map(HashMap<ObjectKey, Value>),
+}
+
+unsafe impl gc::Trace for PropertyMap {
+    custom_trace!(this, {
+        match this {
+            PropertyMap::List(list) => {
+                for (_, value) in list.iter() {
+                    mark(value);
+                }
+            }
+            PropertyMap::Map(map) => mark(map),
+        }
+    });
+}
+
+impl fmt::Debug for PropertyMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl PropertyMap {
+    pub fn new() -> PropertyMap {
+        PropertyMap::List(SmallVec::new())
+    }
+
+    pub fn get(&self, key: &ObjectKey) -> Option<&Value> {
+        match self {
+            PropertyMap::List(list) => list.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            PropertyMap::Map(map) => map.get(key),
+        }
+    }
+
+    pub fn contains_key(&self, key: &ObjectKey) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: ObjectKey, value: Value) {
+        match self {
+            PropertyMap::List(list) => {
+                if let Some(entry) = list.iter_mut().find(|(k, _)| *k == key) {
+                    entry.1 = value;
+                } else if list.len() < LIMIT {
+                    list.push((key, value));
+                } else {
+                    let mut map = list.drain(..).collect::<HashMap<ObjectKey, Value>>();
+                    map.insert(key, value);
+                    *self = PropertyMap::Map(map);
+                }
+            }
+            PropertyMap::Map(map) => {
+                map.insert(key, value);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &ObjectKey) {
+        match self {
+            PropertyMap::List(list) => {
+                if let Some(index) = list.iter().position(|(k, _)| k == key) {
+                    list.remove(index);
+                }
+            }
+            PropertyMap::Map(map) => {
+                map.remove(key);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&ObjectKey, &Value)> + '_> {
+        match self {
+            PropertyMap::List(list) => Box::new(list.iter().map(|(k, v)| (k, v))),
+            PropertyMap::Map(map) => Box::new(map.iter()),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::value::ObjectKey;
+use crate::value::{ObjectKind, ValueKind};
 use crate::{Agent, Value};
 
 macro_rules! intrinsics {
@@ -9,26 +9,17 @@ macro_rules! intrinsics {
             (GetAsyncIterator, get_async_iterator),
             (ObjectKeys, object_keys),
             (ListLength, list_length),
-            (ListPopFront, list_pop_front),
+            (TupleLength, tuple_length),
+            (ArrayLength, array_length),
+            (IsObject, is_object),
+            (ThrowNoMatch, throw_no_match),
         );
     };
 }
 
 fn to_string(agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
-    if accumulator.type_of() == "string" {
-        Ok(())
-    } else {
-        let ts = accumulator.get(agent, ObjectKey::well_known_symbol("toString"))?;
-        if ts.type_of() != "function" {
-            Err(Value::new_error(
-                agent,
-                "value does not provide a :toString",
-            ))
-        } else {
-            *accumulator = ts.call(agent, accumulator.clone(), vec![])?;
-            Ok(())
-        }
-    }
+    *accumulator = accumulator.to_string(agent)?;
+    Ok(())
 }
 
 fn get_iterator(agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
@@ -43,12 +34,12 @@ fn get_async_iterator(agent: &Agent, accumulator: &mut Value) -> Result<(), Valu
 
 fn object_keys(agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
     let keys = accumulator.keys(agent)?;
-    *accumulator = Value::new_list_from_iter(keys.iter().map(Value::from));
+    *accumulator = Value::new_list_from_iter(keys.iter().map(|key| key.to_value()));
     Ok(())
 }
 
 fn list_length(_agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
-    if let Value::List(list) = accumulator {
+    if let ValueKind::List(list) = accumulator.kind() {
         let len = list.borrow().len() as f64;
         *accumulator = Value::from(len);
     } else {
@@ -57,19 +48,39 @@ fn list_length(_agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
     Ok(())
 }
 
-fn list_pop_front(_agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
-    if let Value::List(list) = accumulator {
-        let item = list.borrow_mut().pop_front();
-        *accumulator = match item {
-            None => Value::Empty,
-            Some(v) => v,
-        };
-    } else {
-        unreachable!();
-    }
+// the length of a tuple, or -1 for anything else
+fn tuple_length(_agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
+    *accumulator = match accumulator.kind() {
+        ValueKind::Tuple(items) => Value::from(items.len() as f64),
+        _ => Value::from(-1.0),
+    };
     Ok(())
 }
 
+// the length of an array, or -1 for anything else
+fn array_length(_agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
+    *accumulator = match accumulator.kind() {
+        ValueKind::Object(o) => match &o.kind {
+            ObjectKind::Array(values) => Value::from(values.borrow().len() as f64),
+            _ => Value::from(-1.0),
+        },
+        _ => Value::from(-1.0),
+    };
+    Ok(())
+}
+
+fn is_object(_agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
+    *accumulator = Value::from(accumulator.is_object());
+    Ok(())
+}
+
+fn throw_no_match(agent: &Agent, accumulator: &mut Value) -> Result<(), Value> {
+    Err(Value::new_error(
+        agent,
+        &format!("no match arm for a value of type {}", accumulator.type_of()),
+    ))
+}
+
 macro_rules! declare_enum {
     ( $( ( $name:ident, $fn:ident ), )* ) => (
         #[derive(Debug, PartialEq, Clone, Copy)]
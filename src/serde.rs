@@ -372,7 +372,7 @@ impl<'a> serde::ser::SerializeStructVariant for MapSerializer<'a> {
     {
         let value = serialize(self.agent, value)?;
         self.object
-            .set(self.agent, ObjectKey::from(key), value)
+            .set(self.agent, ObjectKey::new(self.agent, key), value)
             .unwrap();
         Ok(())
     }
@@ -0,0 +1,162 @@
+//! Hooks for profiling scripts. An agent with a tracer reports every call,
+//! return and statement to it, see `Agent::set_tracer`. Without one the
+//! interpreter only pays for checking a flag.
+
+use crate::agent::Agent;
+use crate::value::{ObjectKey, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A range of assembled code, `start` inclusive and `end` exclusive. Nodes
+/// don't carry source positions, so this is what the interpreter can say
+/// about where it is; `disassemble` shows which code it covers.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+pub trait Tracer {
+    /// A function is called. `span` is the body of a bytecode function and
+    /// None for a builtin or an async function resuming.
+    fn enter_function(&mut self, name: &str, span: Option<Span>);
+
+    /// The innermost function returned or threw, `duration` after it was
+    /// entered. Async functions and generators only count until they first
+    /// suspend, except that async stack traces enter an async function again
+    /// each time it resumes.
+    fn exit_function(&mut self, name: &str, duration: Duration);
+
+    /// A statement is about to run.
+    fn on_statement(&mut self, _span: Span) {}
+
+    /// What the trace collected, returned to scripts by `stopProfile`.
+    fn report(&self, _agent: &Agent) -> Result<Value, Value> {
+        Ok(Value::Null)
+    }
+}
+
+#[derive(Default)]
+struct ProfileEntry {
+    calls: usize,
+    self_time: Duration,
+    total_time: Duration,
+}
+
+/// Counts calls and the time spent in each function by name. The total time
+/// of a function includes its callees, and counts a recursive call once per
+/// frame.
+#[derive(Default)]
+pub struct FlatProfiler {
+    entries: HashMap<String, ProfileEntry>,
+    // the time spent in callees of each function on the stack
+    children: Vec<Duration>,
+}
+
+impl FlatProfiler {
+    pub fn new() -> FlatProfiler {
+        FlatProfiler::default()
+    }
+}
+
+fn milliseconds(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1000.0 + f64::from(d.subsec_nanos()) / 1_000_000.0
+}
+
+impl Tracer for FlatProfiler {
+    fn enter_function(&mut self, _name: &str, _span: Option<Span>) {
+        self.children.push(Duration::default());
+    }
+
+    fn exit_function(&mut self, name: &str, duration: Duration) {
+        let children = self.children.pop().unwrap_or_default();
+        if let Some(parent) = self.children.last_mut() {
+            *parent += duration;
+        }
+        let entry = self.entries.entry(name.to_string()).or_default();
+        entry.calls += 1;
+        entry.self_time += duration.checked_sub(children).unwrap_or_default();
+        entry.total_time += duration;
+    }
+
+    /// An array of `{ name, calls, selfTime, totalTime }` with times in
+    /// milliseconds, sorted by self time, most first.
+    fn report(&self, agent: &Agent) -> Result<Value, Value> {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by(|(_, a), (_, b)| b.self_time.cmp(&a.self_time));
+        let mut items = Vec::with_capacity(entries.len());
+        for (name, entry) in entries {
+            let item = Value::new_object(agent.intrinsics.object_prototype.clone());
+            item.set(agent, ObjectKey::NAME, Value::from(name.as_str()))?;
+            item.set(
+                agent,
+                ObjectKey::new(agent, "calls"),
+                Value::from(entry.calls as f64),
+            )?;
+            item.set(
+                agent,
+                ObjectKey::new(agent, "selfTime"),
+                Value::from(milliseconds(entry.self_time)),
+            )?;
+            item.set(
+                agent,
+                ObjectKey::new(agent, "totalTime"),
+                Value::from(milliseconds(entry.total_time)),
+            )?;
+            items.push(item);
+        }
+        Ok(Value::new_array_from_vec(agent, items))
+    }
+}
+
+/// Records calls as duration events in the Trace Event Format, which
+/// chrome://tracing and other trace viewers load.
+pub struct ChromeTracer {
+    start: Instant,
+    events: Vec<serde_json::Value>,
+}
+
+impl ChromeTracer {
+    pub fn new() -> ChromeTracer {
+        ChromeTracer {
+            start: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    fn event(&mut self, name: &str, phase: &str) {
+        let elapsed = self.start.elapsed();
+        let ts =
+            elapsed.as_secs() as f64 * 1_000_000.0 + f64::from(elapsed.subsec_nanos()) / 1000.0;
+        self.events.push(serde_json::json!({
+            "name": name,
+            "ph": phase,
+            "ts": ts,
+            "pid": 1,
+            "tid": 1,
+        }));
+    }
+}
+
+impl Default for ChromeTracer {
+    fn default() -> ChromeTracer {
+        ChromeTracer::new()
+    }
+}
+
+impl Tracer for ChromeTracer {
+    fn enter_function(&mut self, name: &str, _span: Option<Span>) {
+        self.event(name, "B");
+    }
+
+    fn exit_function(&mut self, name: &str, _duration: Duration) {
+        self.event(name, "E");
+    }
+
+    /// The events as a JSON array, in a string.
+    fn report(&self, _agent: &Agent) -> Result<Value, Value> {
+        Ok(Value::from(
+            serde_json::Value::from(self.events.clone()).to_string(),
+        ))
+    }
+}
@@ -1,16 +1,27 @@
+use crate::interner::InternedString;
 use crate::interpreter::{AssemblerFunctionInfo, Context, Interpreter, Scope};
-use crate::intrinsics::{perform_await, promise::new_promise_capability};
+use crate::intrinsics::typed_array::{self, TypedArrayKind};
+use crate::intrinsics::url;
+use crate::intrinsics::{perform_await, promise::new_promise_capability, proxy};
 use crate::parser::FunctionKind;
+use crate::property_map::PropertyMap;
 use crate::{Agent, IntoValue};
-use gc::{Gc, GcCell};
-use indexmap::IndexMap;
+use gc::{Gc, GcCell, Trace};
+use num::{BigInt, Zero};
 use regex::Regex;
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-type BuiltinFunction = fn(&Agent, Vec<Value>, &Context) -> Result<Value, Value>;
+/// The signature of every function implemented in Rust, whether it is an
+/// intrinsic, part of a standard module, or registered by an embedder.
+pub type BuiltinFunction = fn(&Agent, Vec<Value>, &Context) -> Result<Value, Value>;
 
 static SYMBOL_COUNTER: AtomicUsize = AtomicUsize::new(0);
 #[derive(Debug, Clone, Trace, Finalize, Eq)]
@@ -88,14 +99,38 @@ impl Symbol {
     }
 }
 
-#[derive(Trace, Finalize, Debug, Eq, Clone)]
+/// A property name. Names which are array indices are always `Number`, so
+/// that `"1"` and `1` are the same key, and other strings are interned by
+/// the agent, so only keys made by the same agent can be compared.
+#[derive(Trace, Finalize, Debug, PartialEq, Eq, Hash, Clone)]
 pub enum ObjectKey {
     Number(usize),
-    String(String),
+    Interned(InternedString),
     Symbol(Symbol),
 }
 
 impl ObjectKey {
+    pub const LENGTH: ObjectKey = ObjectKey::Interned(InternedString::LENGTH);
+    pub const NAME: ObjectKey = ObjectKey::Interned(InternedString::NAME);
+
+    pub fn new(agent: &Agent, name: &str) -> ObjectKey {
+        if let Some(b'0'..=b'9') = name.as_bytes().first() {
+            match name.parse::<usize>() {
+                Ok(n) if n.to_string() == name => return ObjectKey::Number(n),
+                _ => {}
+            }
+        }
+        ObjectKey::Interned(agent.intern(name))
+    }
+
+    pub fn from_number(agent: &Agent, n: f64) -> ObjectKey {
+        if n >= 0f64 && n.fract() == 0f64 && n <= usize::MAX as f64 {
+            ObjectKey::Number(n as usize)
+        } else {
+            ObjectKey::new(agent, &crate::num_util::to_string(n))
+        }
+    }
+
     pub fn well_known_symbol(name: &str) -> ObjectKey {
         ObjectKey::Symbol(Symbol::new_registered(name))
     }
@@ -103,47 +138,41 @@ impl ObjectKey {
     fn to_number(&self) -> Option<usize> {
         match self {
             ObjectKey::Number(n) => Some(*n),
-            ObjectKey::String(s) => match s.parse::<usize>() {
-                Ok(n) => Some(n),
-                Err(_) => None,
-            },
-            ObjectKey::Symbol(..) => None,
+            ObjectKey::Interned(..) | ObjectKey::Symbol(..) => None,
         }
     }
-}
 
-impl PartialEq for ObjectKey {
-    fn eq(&self, other: &Self) -> bool {
+    /// Whether this is the string key `name`.
+    pub fn is(&self, name: &str) -> bool {
         match self {
-            ObjectKey::Number(n) => match other {
-                ObjectKey::Number(nv) => n == nv,
-                ObjectKey::String(s) => &n.to_string() == s,
-                ObjectKey::Symbol(..) => false,
-            },
-            ObjectKey::String(s) => match other {
-                ObjectKey::String(sv) => s == sv,
-                ObjectKey::Number(n) => &n.to_string() == s,
-                ObjectKey::Symbol(..) => false,
-            },
-            ObjectKey::Symbol(s) => match other {
-                ObjectKey::Symbol(sv) => s == sv,
-                _ => false,
-            },
+            ObjectKey::Interned(s) => &**s == name,
+            _ => false,
+        }
+    }
+
+    /// The key as a string or a symbol.
+    pub fn to_value(&self) -> Value {
+        match self {
+            ObjectKey::Number(n) => Value::from(n.to_string()),
+            ObjectKey::Interned(s) => Value::from(&**s),
+            ObjectKey::Symbol(s) => Value::Symbol(s.clone()),
         }
     }
 }
 
+// the order keys are listed in, which compares numbers with strings as
+// strings and puts symbols last.
 impl PartialOrd for ObjectKey {
     fn partial_cmp(&self, other: &ObjectKey) -> Option<std::cmp::Ordering> {
         match self {
             ObjectKey::Number(n) => match other {
                 ObjectKey::Number(nv) => n.partial_cmp(nv),
-                ObjectKey::String(s) => n.to_string().partial_cmp(s),
+                ObjectKey::Interned(s) => n.to_string().as_str().partial_cmp(&**s),
                 ObjectKey::Symbol(..) => Some(std::cmp::Ordering::Less),
             },
-            ObjectKey::String(s) => match other {
-                ObjectKey::String(sv) => s.partial_cmp(sv),
-                ObjectKey::Number(n) => n.to_string().partial_cmp(s),
+            ObjectKey::Interned(s) => match other {
+                ObjectKey::Interned(sv) => (**s).partial_cmp(&**sv),
+                ObjectKey::Number(n) => (**s).partial_cmp(n.to_string().as_str()),
                 ObjectKey::Symbol(..) => Some(std::cmp::Ordering::Less),
             },
             ObjectKey::Symbol(..) => match other {
@@ -160,79 +189,28 @@ impl Ord for ObjectKey {
     }
 }
 
-impl Hash for ObjectKey {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        match self {
-            ObjectKey::Number(n) => {
-                0.hash(state);
-                n.to_string().hash(state);
-            }
-            ObjectKey::String(s) => {
-                1.hash(state);
-                s.hash(state);
-            }
-            ObjectKey::Symbol(s) => {
-                2.hash(state);
-                s.hash(state);
-            }
-        }
-    }
-}
-
 impl std::fmt::Display for ObjectKey {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             ObjectKey::Number(n) => write!(fmt, "{}", n),
-            ObjectKey::String(s) => write!(fmt, "{}", s),
+            ObjectKey::Interned(s) => write!(fmt, "{}", &**s),
             ObjectKey::Symbol(s) => write!(fmt, "{}", s),
         }
     }
 }
 
-impl From<String> for ObjectKey {
-    fn from(s: String) -> Self {
-        ObjectKey::String(s)
-    }
-}
-
-impl From<&str> for ObjectKey {
-    fn from(s: &str) -> Self {
-        ObjectKey::String(s.to_string())
-    }
-}
-
 impl From<u32> for ObjectKey {
     fn from(n: u32) -> Self {
         ObjectKey::Number(n as usize)
     }
 }
 
-impl From<i32> for ObjectKey {
-    fn from(n: i32) -> Self {
-        if n >= 0 {
-            ObjectKey::Number(n as usize)
-        } else {
-            ObjectKey::String(n.to_string())
-        }
-    }
-}
-
 impl From<usize> for ObjectKey {
     fn from(n: usize) -> Self {
         ObjectKey::Number(n)
     }
 }
 
-impl From<f64> for ObjectKey {
-    fn from(n: f64) -> Self {
-        if n >= 0f64 {
-            ObjectKey::Number(n as usize)
-        } else {
-            ObjectKey::String(n.to_string())
-        }
-    }
-}
-
 #[derive(Finalize)]
 pub enum ObjectKind {
     Ordinary,
@@ -240,17 +218,47 @@ pub enum ObjectKind {
     Boolean(bool),
     String(Vec<char>),
     Number(f64),
+    BigInt(Rc<BigInt>),
     Symbol(Symbol),
     Regex(Regex),
     Buffer(GcCell<Vec<u8>>),
+    ArrayBuffer {
+        data: GcCell<Vec<u8>>,
+        detached: GcCell<bool>,
+    },
+    // a view of `length` elements starting `offset` bytes into `buffer`
+    TypedArray {
+        kind: TypedArrayKind,
+        buffer: Value,
+        offset: usize,
+        length: usize,
+    },
+    // `search_params` is the URLSearchParams which reads and writes the query
+    Url {
+        url: RefCell<::url::Url>,
+        search_params: Value,
+    },
+    // `mapped` holds the names of the parameters which alias the first indices
+    Arguments {
+        values: GcCell<Vec<Value>>,
+        mapped: Vec<String>,
+        scope: Gc<GcCell<Scope>>,
+    },
     BytecodeFunction {
         kind: FunctionKind,
+        name: String,
+        length: usize,
         parameters: Vec<String>,
+        rest: bool,
         position: usize,
         scope: Gc<GcCell<Scope>>,
     },
     BuiltinFunction(BuiltinFunction, GcCell<HashMap<String, Value>>),
     Custom(GcCell<HashMap<String, Value>>),
+    Proxy {
+        target: Value,
+        handler: Value,
+    },
 }
 
 unsafe impl gc::Trace for ObjectKind {
@@ -259,12 +267,26 @@ unsafe impl gc::Trace for ObjectKind {
             ObjectKind::Array(items) => {
                 mark(items);
             }
+            ObjectKind::Arguments { values, scope, .. } => {
+                mark(values);
+                mark(scope);
+            }
             ObjectKind::BytecodeFunction { scope, .. } => {
                 mark(scope);
             }
             ObjectKind::Custom(slots) | ObjectKind::BuiltinFunction(_, slots) => {
                 mark(slots);
             }
+            ObjectKind::Proxy { target, handler } => {
+                mark(target);
+                mark(handler);
+            }
+            ObjectKind::TypedArray { buffer, .. } => {
+                mark(buffer);
+            }
+            ObjectKind::Url { search_params, .. } => {
+                mark(search_params);
+            }
             _ => {}
         }
     });
@@ -278,10 +300,16 @@ impl std::fmt::Debug for ObjectKind {
             ObjectKind::Boolean(b) => format!("Boolean({})", b),
             ObjectKind::String(s) => format!("String({:?})", s),
             ObjectKind::Number(i) => format!("Number({})", i),
+            ObjectKind::BigInt(i) => format!("BigInt({})", i),
             ObjectKind::Regex(r) => format!("Regex({})", r),
             ObjectKind::Symbol(s) => format!("Symbol({:?})", s),
             ObjectKind::Buffer(b) => format!("Buffer({:?})", b),
+            ObjectKind::ArrayBuffer { .. } => "ArrayBuffer".to_string(),
+            ObjectKind::TypedArray { kind, .. } => kind.name().to_string(),
+            ObjectKind::Url { url, .. } => format!("Url({})", url.borrow()),
+            ObjectKind::Arguments { .. } => "Arguments".to_string(),
             ObjectKind::Custom(..) => "Custom".to_string(),
+            ObjectKind::Proxy { .. } => "Proxy".to_string(),
             ObjectKind::BytecodeFunction { position, .. } => {
                 format!("CompiledFunction @ {}", position)
             }
@@ -294,18 +322,38 @@ impl std::fmt::Debug for ObjectKind {
 #[derive(Debug, Trace, Finalize)]
 pub struct ObjectInfo {
     pub kind: ObjectKind,
-    properties: GcCell<IndexMap<ObjectKey, Value>>,
-    prototype: Value,
+    properties: GcCell<PropertyMap>,
+    prototype: GcCell<Value>,
+    extensible: GcCell<bool>,
+    // set for the intrinsics unless the agent allows changing them, its own
+    // properties can't be assigned or deleted, and none can be added.
+    frozen: GcCell<bool>,
 }
 
 impl ObjectInfo {
+    // the object a proxy forwards to, if this is a proxy with one
+    fn proxy_target(&self) -> Option<GcRef<'_, ObjectInfo>> {
+        match &self.kind {
+            ObjectKind::Proxy { target, .. } => match target.kind() {
+                ValueKind::Object(target) => Some(target),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     fn get(&self, property: ObjectKey) -> Value {
+        // a proxy reached through a prototype chain forwards to its target
+        // rather than calling its trap.
+        if let Some(target) = self.proxy_target() {
+            return target.get(property);
+        }
         if let ObjectInfo {
             kind: ObjectKind::Array(values),
             ..
         } = self
         {
-            if ObjectKey::from("length") == property {
+            if property == ObjectKey::LENGTH {
                 return Value::from(values.borrow().len() as f64);
             }
             if let Some(n) = property.to_number() {
@@ -317,11 +365,81 @@ impl ObjectInfo {
             ..
         } = self
         {
-            if ObjectKey::from("length") == property {
+            if property == ObjectKey::LENGTH {
                 return Value::from(values.borrow().len() as f64);
             }
             if let Some(n) = property.to_number() {
-                return Value::from(f64::from(*values.borrow().get(n).unwrap_or(&0)));
+                return match values.borrow().get(n) {
+                    Some(b) => Value::from(f64::from(*b)),
+                    None => Value::Null,
+                };
+            }
+        }
+        if let ObjectKind::ArrayBuffer { data, detached } = &self.kind {
+            if let ObjectKey::Interned(s) = &property {
+                match &**s {
+                    "byteLength" => return Value::from(data.borrow().len() as f64),
+                    "detached" => return Value::from(*detached.borrow()),
+                    _ => {}
+                }
+            }
+        }
+        if let ObjectKind::TypedArray {
+            kind,
+            buffer,
+            offset,
+            length,
+        } = &self.kind
+        {
+            let length = typed_array::view_length(*kind, buffer, *offset, *length);
+            if let Some(n) = property.to_number() {
+                if n < length {
+                    if let Some(n) = typed_array::get_element(*kind, buffer, *offset, n) {
+                        return Value::from(n);
+                    }
+                }
+                return Value::Null;
+            }
+            if let ObjectKey::Interned(s) = &property {
+                match &**s {
+                    "length" => return Value::from(length as f64),
+                    "byteLength" => return Value::from((length * kind.size()) as f64),
+                    "byteOffset" => return Value::from(*offset as f64),
+                    "buffer" => return buffer.clone(),
+                    _ => {}
+                }
+            }
+        }
+        if let ObjectKind::Url { url, search_params } = &self.kind {
+            if let ObjectKey::Interned(s) = &property {
+                let name: &str = s;
+                if name == "searchParams" {
+                    return search_params.clone();
+                }
+                if let Some(v) = url::get_component(&url.borrow(), name) {
+                    return v;
+                }
+            }
+        }
+        if let ObjectInfo {
+            kind:
+                ObjectKind::Arguments {
+                    values,
+                    mapped,
+                    scope,
+                },
+            ..
+        } = self
+        {
+            if let Some(n) = property.to_number() {
+                if let Some(name) = mapped.get(n) {
+                    if let Some(v) = scope.borrow().get_own(name) {
+                        return v;
+                    }
+                }
+                if let Some(v) = values.borrow().get(n) {
+                    return v.clone();
+                }
             }
         }
         if let ObjectInfo {
@@ -329,7 +447,7 @@ impl ObjectInfo {
             ..
         } = self
         {
-            if ObjectKey::from("length") == property {
+            if property == ObjectKey::LENGTH {
                 return Value::from(string.len() as f64);
             }
             if let Some(n) = property.to_number() {
@@ -341,13 +459,16 @@ impl ObjectInfo {
         match self.properties.borrow().get(&property) {
             Some(v) => v.clone(),
             _ => {
+                if let Some(v) = self.function_property(&property) {
+                    return v;
+                }
                 if let ObjectKey::Symbol(Symbol::Unregistered { private: true, .. }) = property {
                     // don't traverse for private symbol
                     Value::Null
                 } else {
-                    match &self.prototype {
-                        Value::Object(oo) => oo.get(property),
-                        Value::Null => Value::Null,
+                    match self.prototype.borrow().kind() {
+                        ValueKind::Object(oo) => oo.get(property),
+                        ValueKind::Null => Value::Null,
                         _ => unreachable!(),
                     }
                 }
@@ -355,6 +476,34 @@ impl ObjectInfo {
         }
     }
 
+    // `name` and `length` of functions are computed until they are overwritten.
+    fn function_property(&self, property: &ObjectKey) -> Option<Value> {
+        let is_name = *property == ObjectKey::NAME;
+        if !is_name && *property != ObjectKey::LENGTH {
+            return None;
+        }
+        let (name, length) = match &self.kind {
+            ObjectKind::BytecodeFunction { name, length, .. } => {
+                (Value::from(name.as_str()), Value::from(*length as f64))
+            }
+            ObjectKind::BuiltinFunction(_, slots) => {
+                let slots = slots.borrow();
+                (
+                    slots
+                        .get("function name")
+                        .cloned()
+                        .unwrap_or_else(|| Value::from("")),
+                    slots
+                        .get("function length")
+                        .cloned()
+                        .unwrap_or_else(|| Value::from(0)),
+                )
+            }
+            _ => return None,
+        };
+        Some(if is_name { name } else { length })
+    }
+
     pub fn set(
         &self,
         agent: &Agent,
@@ -362,13 +511,19 @@ impl ObjectInfo {
         value: Value,
         receiver: Gc<ObjectInfo>,
     ) -> Result<Value, Value> {
+        if let Some(target) = self.proxy_target() {
+            return target.set(agent, property, value, receiver);
+        }
+        if std::ptr::eq(self, &*receiver) {
+            self.check_not_frozen(agent, &property)?;
+        }
         if let ObjectInfo {
             kind: ObjectKind::Array(values),
             ..
         } = self
         {
-            if ObjectKey::from("length") == property {
-                if let Value::Number(len) = value {
+            if property == ObjectKey::LENGTH {
+                if let ValueKind::Number(len) = value.kind() {
                     values.borrow_mut().resize(len as usize, Value::Null);
                     return Ok(Value::Null);
                 } else {
@@ -378,6 +533,12 @@ impl ObjectInfo {
             if let Some(n) = property.to_number() {
                 let mut values = values.borrow_mut();
                 if values.len() <= n {
+                    if !*self.extensible.borrow() {
+                        return Err(Value::new_error(
+                            agent,
+                            &format!("cannot add property '{}', object is not extensible", n),
+                        ));
+                    }
                     values.resize(n + 1, Value::Null);
                 }
                 values[n] = value.clone();
@@ -389,11 +550,11 @@ impl ObjectInfo {
             ..
         } = self
         {
-            if ObjectKey::from("length") == property {
+            if property == ObjectKey::LENGTH {
                 return Ok(Value::Null);
             }
             if let Some(n) = property.to_number() {
-                if let Value::Number(v) = value {
+                if let ValueKind::Number(v) = value.kind() {
                     let mut values = values.borrow_mut();
                     if values.len() <= n {
                         return Err(Value::new_error(agent, "Buffer index out of range"));
@@ -405,33 +566,147 @@ impl ObjectInfo {
                 }
             }
         }
+        if let ObjectKind::ArrayBuffer { .. } = &self.kind {
+            if let ObjectKey::Interned(s) = &property {
+                if let "byteLength" | "detached" = &**s {
+                    return Ok(Value::Null);
+                }
+            }
+        }
+        if let ObjectKind::TypedArray {
+            kind,
+            buffer,
+            offset,
+            length,
+        } = &self.kind
+        {
+            if let Some(n) = property.to_number() {
+                if let ValueKind::Number(v) = value.kind() {
+                    if n >= *length || !typed_array::set_element(*kind, buffer, *offset, n, v) {
+                        return Err(Value::new_error(agent, "typed array index out of range"));
+                    }
+                    return Ok(value);
+                } else {
+                    return Err(Value::new_error(
+                        agent,
+                        "typed array values must be numbers",
+                    ));
+                }
+            }
+            if let ObjectKey::Interned(s) = &property {
+                if let "length" | "byteLength" | "byteOffset" | "buffer" = &**s {
+                    return Ok(Value::Null);
+                }
+            }
+        }
+        if let ObjectKind::Url { url, .. } = &self.kind {
+            if let ObjectKey::Interned(s) = &property {
+                let name = s.to_string();
+                if name == "searchParams" {
+                    return Ok(Value::Null);
+                }
+                if let Some(result) =
+                    url::set_component(agent, &mut url.borrow_mut(), &name, &value)
+                {
+                    return result.map(|_| value);
+                }
+            }
+        }
+        if let ObjectInfo {
+            kind:
+                ObjectKind::Arguments {
+                    values,
+                    mapped,
+                    scope,
+                },
+            ..
+        } = self
+        {
+            if let Some(n) = property.to_number() {
+                if let Some(name) = mapped.get(n) {
+                    scope.borrow_mut().overwrite(name, value.clone());
+                }
+                let mut values = values.borrow_mut();
+                if n < values.len() {
+                    values[n] = value.clone();
+                    return Ok(value);
+                }
+            }
+        }
         let own = if let ObjectKey::Symbol(Symbol::Unregistered { private: true, .. }) = property {
             true
         } else {
             false
         };
         if own || self.properties.borrow().contains_key(&property) {
-            receiver
-                .properties
-                .borrow_mut()
-                .insert(property, value.clone());
-            Ok(value)
+            receiver.insert(agent, property, value)
         } else {
-            match &self.prototype {
-                Value::Object(oo) => oo.set(agent, property, value, receiver),
-                Value::Null => {
-                    receiver
-                        .properties
-                        .borrow_mut()
-                        .insert(property, value.clone());
-                    Ok(value)
-                }
+            match self.prototype.borrow().kind() {
+                ValueKind::Object(oo) => oo.set(agent, property, value, receiver),
+                ValueKind::Null => receiver.insert(agent, property, value),
                 _ => unreachable!(),
             }
         }
     }
 
+    // adds or overwrites an own property, refusing to add new ones once
+    // extensions have been prevented.
+    fn insert(&self, agent: &Agent, property: ObjectKey, value: Value) -> Result<Value, Value> {
+        let private =
+            if let ObjectKey::Symbol(Symbol::Unregistered { private: true, .. }) = property {
+                true
+            } else {
+                false
+            };
+        self.check_not_frozen(agent, &property)?;
+        if !private
+            && !*self.extensible.borrow()
+            && !self.properties.borrow().contains_key(&property)
+        {
+            return Err(Value::new_error(
+                agent,
+                &format!(
+                    "cannot add property '{}', object is not extensible",
+                    property
+                ),
+            ));
+        }
+        self.properties.borrow_mut().insert(property, value.clone());
+        Ok(value)
+    }
+
+    fn check_not_frozen(&self, agent: &Agent, property: &ObjectKey) -> Result<(), Value> {
+        if let ObjectKey::Symbol(Symbol::Unregistered { private: true, .. }) = property {
+            return Ok(());
+        }
+        if *self.frozen.borrow() {
+            return Err(Value::new_error(
+                agent,
+                &format!("cannot assign to property '{}', object is frozen", property),
+            ));
+        }
+        Ok(())
+    }
+
     fn has(&self, key: ObjectKey) -> bool {
+        if let Some(target) = self.proxy_target() {
+            return target.has(key);
+        }
+        if self.has_own(&key) {
+            true
+        } else {
+            match self.prototype.borrow().kind() {
+                ValueKind::Object(o) => o.has(key),
+                ValueKind::Null => false,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn has_own(&self, key: &ObjectKey) -> bool {
+        if let Some(target) = self.proxy_target() {
+            return target.has_own(key);
+        }
         if let ObjectInfo {
             kind: ObjectKind::Array(values),
             ..
@@ -443,18 +718,128 @@ impl ObjectInfo {
                 }
             }
         }
-        if self.properties.borrow().contains_key(&key) {
-            true
+        if let ObjectInfo {
+            kind: ObjectKind::Buffer(values),
+            ..
+        } = self
+        {
+            if let Some(n) = key.to_number() {
+                if n < values.borrow().len() {
+                    return true;
+                }
+            }
+        }
+        if let ObjectInfo {
+            kind: ObjectKind::Arguments { values, .. },
+            ..
+        } = self
+        {
+            if let Some(n) = key.to_number() {
+                if n < values.borrow().len() {
+                    return true;
+                }
+            }
+        }
+        if let ObjectKind::TypedArray {
+            kind,
+            buffer,
+            offset,
+            length,
+        } = &self.kind
+        {
+            if let Some(n) = key.to_number() {
+                return n < typed_array::view_length(*kind, buffer, *offset, *length);
+            }
+        }
+        self.properties.borrow().contains_key(key)
+    }
+
+    fn get_prototype_of(&self) -> Value {
+        if let Some(target) = self.proxy_target() {
+            return target.get_prototype_of();
+        }
+        self.prototype.borrow().clone()
+    }
+
+    // returns false if the object is not extensible or if `prototype` would
+    // create a cycle.
+    fn set_prototype_of(&self, prototype: Value) -> bool {
+        if let Some(target) = self.proxy_target() {
+            return target.set_prototype_of(prototype);
+        }
+        if *self.prototype.borrow() == prototype {
+            return true;
+        }
+        if !*self.extensible.borrow() {
+            return false;
+        }
+        let mut p = prototype.clone();
+        while let ValueKind::Object(o) = p.kind() {
+            if std::ptr::eq(&**o, self) {
+                return false;
+            }
+            p = o.get_prototype_of();
+        }
+        *self.prototype.borrow_mut() = prototype;
+        true
+    }
+
+    fn is_extensible(&self) -> bool {
+        if let Some(target) = self.proxy_target() {
+            return target.is_extensible();
+        }
+        *self.extensible.borrow()
+    }
+
+    fn prevent_extensions(&self) {
+        if let Some(target) = self.proxy_target() {
+            target.prevent_extensions();
         } else {
-            match &self.prototype {
-                Value::Object(o) => o.has(key),
-                Value::Null => false,
-                _ => unreachable!(),
+            *self.extensible.borrow_mut() = false;
+        }
+    }
+
+    fn freeze(&self) {
+        if let Some(target) = self.proxy_target() {
+            target.freeze();
+        } else {
+            *self.extensible.borrow_mut() = false;
+            *self.frozen.borrow_mut() = true;
+        }
+    }
+
+    fn is_frozen(&self) -> bool {
+        if let Some(target) = self.proxy_target() {
+            return target.is_frozen();
+        }
+        *self.frozen.borrow()
+    }
+
+    fn delete(&self, key: &ObjectKey) -> bool {
+        if let Some(target) = self.proxy_target() {
+            return target.delete(key);
+        }
+        match &self.kind {
+            _ if *self.frozen.borrow() => false,
+            ObjectKind::Array(..)
+            | ObjectKind::Buffer(..)
+            | ObjectKind::Arguments { .. }
+            | ObjectKind::TypedArray { .. }
+                if key.to_number().is_some() =>
+            {
+                false
+            }
+            _ => {
+                self.properties.borrow_mut().remove(key);
+                true
             }
         }
     }
 
     fn keys(&self) -> Vec<ObjectKey> {
+        if let Some(target) = self.proxy_target() {
+            return target.keys();
+        }
         let mut keys = Vec::new();
         if let ObjectKind::Array(values) = &self.kind {
             for i in 0..(values.borrow().len()) {
@@ -466,8 +851,24 @@ impl ObjectInfo {
                 keys.push(ObjectKey::Number(i));
             }
         }
+        if let ObjectKind::Arguments { values, .. } = &self.kind {
+            for i in 0..(values.borrow().len()) {
+                keys.push(ObjectKey::Number(i));
+            }
+        }
+        if let ObjectKind::TypedArray {
+            kind,
+            buffer,
+            offset,
+            length,
+        } = &self.kind
+        {
+            for i in 0..typed_array::view_length(*kind, buffer, *offset, *length) {
+                keys.push(ObjectKey::Number(i));
+            }
+        }
         let entries = self.properties.borrow();
-        for key in entries.keys() {
+        for (key, _) in entries.iter() {
             if let ObjectKey::Symbol(Symbol::Unregistered { private: true, .. }) = key {
                 // private keys are unenumerable
             } else {
@@ -480,81 +881,336 @@ impl ObjectInfo {
     }
 }
 
-#[derive(Debug, Finalize, Clone)]
-pub enum Value {
+/// A value, NaN-boxed into 64 bits. Numbers are stored as their bits, with
+/// every NaN made the same quiet NaN, and other values are the NaNs whose
+/// top 16 bits are one of the tags below. Null, booleans and numbers which
+/// are 32 bit integers keep their payload in the low bits, objects and lists
+/// keep the bits of their `Gc`, and the rest point to a `Boxed` they own.
+///
+/// `kind` unpacks a value to match on. The functions named after the kinds
+/// of value, like `Value::Number`, make them.
+pub struct Value {
+    // a `Cell` because rooting or unrooting a `Gc` changes its bits
+    bits: Cell<u64>,
+}
+
+const TAG_SHIFT: u64 = 48;
+const PAYLOAD: u64 = (1 << TAG_SHIFT) - 1;
+// every NaN a number can be is stored as this one, so no number is mistaken
+// for a tagged value
+const CANONICAL_NAN: u64 = 0x7FF8_0000_0000_0000;
+const TAG_NULL: u64 = 0xFFF9;
+const TAG_EMPTY: u64 = 0xFFFA;
+const TAG_BOOLEAN: u64 = 0xFFFB;
+const TAG_INTEGER: u64 = 0xFFFC;
+const TAG_OBJECT: u64 = 0xFFFD;
+const TAG_LIST: u64 = 0xFFFE;
+const TAG_BOXED: u64 = 0xFFFF;
+
+// the values which don't fit in the payload of a NaN, which a value points
+// to and owns.
+#[derive(Clone, Finalize)]
+enum Boxed {
+    String(String),
+    BigInt(Rc<BigInt>),
+    Symbol(Symbol),
+    Tuple(Vec<Value>),
+    WrappedContext(Gc<GcCell<Context>>, Option<Box<Value>>),
+    Iterator(Box<Value>, Box<Value>),
+}
+
+unsafe impl gc::Trace for Boxed {
+    custom_trace!(this, {
+        match this {
+            Boxed::String(_) | Boxed::BigInt(_) | Boxed::Symbol(_) => {}
+            Boxed::Tuple(items) => mark(items),
+            Boxed::WrappedContext(c, p) => {
+                mark(c);
+                mark(p);
+            }
+            Boxed::Iterator(i, n) => {
+                mark(i);
+                mark(n);
+            }
+        }
+    });
+}
+
+/// A value unpacked by `Value::kind`.
+#[derive(Debug)]
+pub enum ValueKind<'a> {
     // Language types
     Null,
     Boolean(bool),
-    String(String),
+    String(&'a String),
     Number(f64),
-    Symbol(Symbol),
-    Object(Gc<ObjectInfo>),
-    Tuple(Vec<Value>),
+    BigInt(&'a Rc<BigInt>),
+    Symbol(&'a Symbol),
+    Object(GcRef<'a, ObjectInfo>),
+    Tuple(&'a Vec<Value>),
 
     // Internal types
     Empty,
-    List(Gc<GcCell<VecDeque<Value>>>),
-    WrappedContext(Gc<GcCell<Context>>, Option<Box<Value>>),
-    Iterator(Box<Value>, Box<Value>),
+    List(GcRef<'a, GcCell<VecDeque<Value>>>),
+    WrappedContext(&'a Gc<GcCell<Context>>, &'a Option<Box<Value>>),
+    Iterator(&'a Value, &'a Value),
+}
+
+/// The `Gc` of an object or list, borrowed from the value it was unpacked
+/// from. Cloning it through `Deref` gives a `Gc` of its own.
+pub struct GcRef<'a, T: Trace + 'static> {
+    gc: ManuallyDrop<Gc<T>>,
+    marker: PhantomData<&'a Value>,
+}
+
+impl<T: Trace + 'static> Deref for GcRef<'_, T> {
+    type Target = Gc<T>;
+
+    #[inline]
+    fn deref(&self) -> &Gc<T> {
+        &self.gc
+    }
+}
+
+impl<'a, T: Trace + 'static> GcRef<'a, T> {
+    /// What the `Gc` points to, for as long as the value it came from.
+    pub fn into_ref(self) -> &'a T {
+        unsafe { &*(&**self.gc as *const T) }
+    }
+}
+
+impl<T: Trace + std::fmt::Debug + 'static> std::fmt::Debug for GcRef<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.gc.fmt(f)
+    }
 }
 
 #[allow(non_upper_case_globals)]
 #[allow(clippy::declare_interior_mutable_const)]
 impl Value {
-    pub const True: Value = Value::Boolean(true);
-    pub const False: Value = Value::Boolean(false);
+    pub const True: Value = Value::tagged(TAG_BOOLEAN, 1);
+    pub const False: Value = Value::tagged(TAG_BOOLEAN, 0);
+    pub const Null: Value = Value::tagged(TAG_NULL, 0);
+    pub const Empty: Value = Value::tagged(TAG_EMPTY, 0);
 }
 
+#[allow(non_snake_case)]
+impl Value {
+    pub fn Boolean(b: bool) -> Value {
+        Value::tagged(TAG_BOOLEAN, b as u64)
+    }
+
+    pub fn Number(n: f64) -> Value {
+        let i = n as i32;
+        if f64::from(i) == n && (i != 0 || n.is_sign_positive()) {
+            Value::tagged(TAG_INTEGER, u64::from(i as u32))
+        } else if n.is_nan() {
+            Value::from_bits(CANONICAL_NAN)
+        } else {
+            Value::from_bits(n.to_bits())
+        }
+    }
+
+    pub fn String(s: String) -> Value {
+        Value::boxed(Boxed::String(s))
+    }
+
+    pub fn BigInt(n: Rc<BigInt>) -> Value {
+        Value::boxed(Boxed::BigInt(n))
+    }
+
+    pub fn Symbol(s: Symbol) -> Value {
+        Value::boxed(Boxed::Symbol(s))
+    }
+
+    pub fn Object(o: Gc<ObjectInfo>) -> Value {
+        Value::pointer(TAG_OBJECT, Gc::into_bits(o))
+    }
+
+    pub fn Tuple(items: Vec<Value>) -> Value {
+        Value::boxed(Boxed::Tuple(items))
+    }
+
+    pub fn List(list: Gc<GcCell<VecDeque<Value>>>) -> Value {
+        Value::pointer(TAG_LIST, Gc::into_bits(list))
+    }
+
+    pub fn WrappedContext(context: Gc<GcCell<Context>>, promise: Option<Box<Value>>) -> Value {
+        Value::boxed(Boxed::WrappedContext(context, promise))
+    }
+
+    pub fn Iterator(iterator: Box<Value>, next: Box<Value>) -> Value {
+        Value::boxed(Boxed::Iterator(iterator, next))
+    }
+}
+
+impl Value {
+    const fn from_bits(bits: u64) -> Value {
+        Value {
+            bits: Cell::new(bits),
+        }
+    }
+
+    const fn tagged(tag: u64, payload: u64) -> Value {
+        Value::from_bits(tag << TAG_SHIFT | payload)
+    }
+
+    fn boxed(boxed: Boxed) -> Value {
+        Value::pointer(TAG_BOXED, Box::into_raw(Box::new(boxed)) as usize)
+    }
+
+    // pointers fit in the payload on the 64 bit platforms in use, where
+    // addresses are 48 bits
+    fn pointer(tag: u64, bits: usize) -> Value {
+        debug_assert_eq!(bits as u64 & !PAYLOAD, 0);
+        Value::tagged(tag, bits as u64)
+    }
+
+    #[inline]
+    fn tag(&self) -> u64 {
+        self.bits.get() >> TAG_SHIFT
+    }
+
+    #[inline]
+    fn payload(&self) -> u64 {
+        self.bits.get() & PAYLOAD
+    }
+
+    #[inline]
+    fn gc<T: Trace + 'static>(&self) -> GcRef<'_, T> {
+        GcRef {
+            gc: ManuallyDrop::new(unsafe { Gc::from_bits(self.payload() as usize) }),
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn get_boxed(&self) -> &Boxed {
+        unsafe { &*(self.payload() as *const Boxed) }
+    }
+
+    // runs `f` on the `Gc` this value holds, keeping whether `f` rooted it
+    fn with_gc<T: Trace + 'static>(&self, f: impl FnOnce(&Gc<T>)) {
+        let gc = self.gc::<T>();
+        f(&gc);
+        self.bits
+            .set(self.tag() << TAG_SHIFT | Gc::bits(&gc) as u64);
+    }
+
+    /// The items of a tuple, to add to in place.
+    pub fn tuple_items_mut(&mut self) -> Option<&mut Vec<Value>> {
+        if self.tag() != TAG_BOXED {
+            return None;
+        }
+        match unsafe { &mut *(self.payload() as *mut Boxed) } {
+            Boxed::Tuple(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// This value unpacked, to match on.
+    #[inline]
+    pub fn kind(&self) -> ValueKind<'_> {
+        let bits = self.bits.get();
+        if bits < TAG_NULL << TAG_SHIFT {
+            return ValueKind::Number(f64::from_bits(bits));
+        }
+        match bits >> TAG_SHIFT {
+            TAG_NULL => ValueKind::Null,
+            TAG_EMPTY => ValueKind::Empty,
+            TAG_BOOLEAN => ValueKind::Boolean(bits & 1 != 0),
+            TAG_INTEGER => ValueKind::Number(f64::from(bits as u32 as i32)),
+            TAG_OBJECT => ValueKind::Object(self.gc()),
+            TAG_LIST => ValueKind::List(self.gc()),
+            _ => match self.get_boxed() {
+                Boxed::String(s) => ValueKind::String(s),
+                Boxed::BigInt(n) => ValueKind::BigInt(n),
+                Boxed::Symbol(s) => ValueKind::Symbol(s),
+                Boxed::Tuple(items) => ValueKind::Tuple(items),
+                Boxed::WrappedContext(c, p) => ValueKind::WrappedContext(c, p),
+                Boxed::Iterator(i, n) => ValueKind::Iterator(i, n),
+            },
+        }
+    }
+}
+
+impl Clone for Value {
+    #[inline]
+    fn clone(&self) -> Value {
+        match self.tag() {
+            TAG_OBJECT => Value::Object((*self.gc::<ObjectInfo>()).clone()),
+            TAG_LIST => Value::List((*self.gc::<GcCell<VecDeque<Value>>>()).clone()),
+            TAG_BOXED => Value::boxed(self.get_boxed().clone()),
+            _ => Value::from_bits(self.bits.get()),
+        }
+    }
+}
+
+impl Drop for Value {
+    #[inline]
+    fn drop(&mut self) {
+        match self.tag() {
+            TAG_OBJECT => drop(ManuallyDrop::into_inner(self.gc::<ObjectInfo>().gc)),
+            TAG_LIST => drop(ManuallyDrop::into_inner(
+                self.gc::<GcCell<VecDeque<Value>>>().gc,
+            )),
+            TAG_BOXED => drop(unsafe { Box::from_raw(self.payload() as *mut Boxed) }),
+            _ => {}
+        }
+    }
+}
+
+impl gc::Finalize for Value {}
+
+// `mark` is an unsafe fn, which can't be passed where a closure is expected
+#[allow(clippy::redundant_closure)]
 unsafe impl gc::Trace for Value {
     custom_trace!(this, {
-        match this {
-            Value::Null
-            | Value::Boolean(_)
-            | Value::String(_)
-            | Value::Number(_)
-            | Value::Symbol(_) => {}
-            Value::Object(o) => mark(o),
-            Value::Tuple(items, ..) => mark(items),
-
-            Value::Empty => {}
-            Value::List(list) => mark(list),
-            Value::WrappedContext(c, p) => {
-                mark(c);
-                mark(p);
-            }
-            Value::Iterator(i, n) => {
-                mark(i);
-                mark(n);
-            }
+        match this.tag() {
+            TAG_OBJECT => this.with_gc::<ObjectInfo>(|gc| mark(gc)),
+            TAG_LIST => this.with_gc::<GcCell<VecDeque<Value>>>(|gc| mark(gc)),
+            TAG_BOXED => mark(this.get_boxed()),
+            _ => {}
         }
     });
 }
 
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.kind().fmt(f)
+    }
+}
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self {
-            Value::Null => match other {
-                Value::Null => Some(std::cmp::Ordering::Equal),
+        match self.kind() {
+            ValueKind::Null => match other.kind() {
+                ValueKind::Null => Some(std::cmp::Ordering::Equal),
+                _ => None,
+            },
+            ValueKind::Boolean(..) => match other.kind() {
+                ValueKind::Boolean(..) => Some(std::cmp::Ordering::Equal),
                 _ => None,
             },
-            Value::Boolean(..) => match other {
-                Value::Boolean(..) => Some(std::cmp::Ordering::Equal),
+            ValueKind::Number(n) => match other.kind() {
+                ValueKind::Number(bn) => {
+                    Some(n.partial_cmp(&bn).unwrap_or(std::cmp::Ordering::Equal))
+                }
                 _ => None,
             },
-            Value::Number(n) => match other {
-                Value::Number(bn) => Some(n.partial_cmp(bn).unwrap_or(std::cmp::Ordering::Equal)),
+            ValueKind::BigInt(n) => match other.kind() {
+                ValueKind::BigInt(bn) => Some(n.cmp(bn)),
                 _ => None,
             },
-            Value::String(s) => match other {
-                Value::String(bs) => Some(s.cmp(bs)),
+            ValueKind::String(s) => match other.kind() {
+                ValueKind::String(bs) => Some(s.cmp(bs)),
                 _ => None,
             },
-            Value::Symbol(..) => match other {
-                Value::Symbol(..) => Some(std::cmp::Ordering::Equal),
+            ValueKind::Symbol(..) => match other.kind() {
+                ValueKind::Symbol(..) => Some(std::cmp::Ordering::Equal),
                 _ => None,
             },
-            Value::Object(..) | Value::Tuple(..) => match other {
-                Value::Object(..) | Value::Tuple(..) => Some(std::cmp::Ordering::Equal),
+            ValueKind::Object(..) | ValueKind::Tuple(..) => match other.kind() {
+                ValueKind::Object(..) | ValueKind::Tuple(..) => Some(std::cmp::Ordering::Equal),
                 _ => panic!(),
             },
             _ => None,
@@ -567,56 +1223,179 @@ impl Value {
         Value::Symbol(Symbol::new_unregistered(false, desc))
     }
 
-    pub fn new_private_symbol(desc: Option<String>) -> Value {
-        Value::Symbol(Symbol::new_unregistered(true, desc))
+    pub fn new_private_symbol(desc: Option<String>) -> Value {
+        Value::Symbol(Symbol::new_unregistered(true, desc))
+    }
+
+    pub fn new_well_known_symbol(desc: &str) -> Value {
+        Value::Symbol(Symbol::new_registered(desc))
+    }
+
+    pub fn new_object(prototype: Value) -> Value {
+        Value::Object(Gc::new(ObjectInfo {
+            kind: ObjectKind::Ordinary,
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(prototype),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
+        }))
+    }
+
+    /// A new ordinary object with the own properties `template` has now.
+    pub fn new_object_like(prototype: Value, template: &Value) -> Value {
+        let properties = match template.kind() {
+            ValueKind::Object(o) => o.properties.borrow().clone(),
+            _ => PropertyMap::new(),
+        };
+        Value::Object(Gc::new(ObjectInfo {
+            kind: ObjectKind::Ordinary,
+            properties: GcCell::new(properties),
+            prototype: GcCell::new(prototype),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
+        }))
+    }
+
+    pub fn new_custom_object(prototype: Value) -> Value {
+        Value::Object(Gc::new(ObjectInfo {
+            kind: ObjectKind::Custom(GcCell::new(HashMap::new())),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(prototype),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
+        }))
     }
 
-    pub fn new_well_known_symbol(desc: &str) -> Value {
-        Value::Symbol(Symbol::new_registered(desc))
+    pub fn new_array_buffer(agent: &Agent, data: Vec<u8>) -> Value {
+        Value::Object(Gc::new(ObjectInfo {
+            kind: ObjectKind::ArrayBuffer {
+                data: GcCell::new(data),
+                detached: GcCell::new(false),
+            },
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.array_buffer_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
+        }))
     }
 
-    pub fn new_object(prototype: Value) -> Value {
+    pub fn new_url(agent: &Agent, parsed: ::url::Url) -> Value {
+        let search_params = url::new_search_params(agent, Value::Null);
+        let u = Value::Object(Gc::new(ObjectInfo {
+            kind: ObjectKind::Url {
+                url: RefCell::new(parsed),
+                search_params: search_params.clone(),
+            },
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.url_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
+        }));
+        search_params.set_slot("url search params url", u.clone());
+        u
+    }
+
+    pub fn new_typed_array(
+        prototype: Value,
+        kind: TypedArrayKind,
+        buffer: Value,
+        offset: usize,
+        length: usize,
+    ) -> Value {
         Value::Object(Gc::new(ObjectInfo {
-            kind: ObjectKind::Ordinary,
-            properties: GcCell::new(IndexMap::new()),
-            prototype,
+            kind: ObjectKind::TypedArray {
+                kind,
+                buffer,
+                offset,
+                length,
+            },
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(prototype),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
-    pub fn new_custom_object(prototype: Value) -> Value {
+    pub fn new_proxy(target: Value, handler: Value) -> Value {
         Value::Object(Gc::new(ObjectInfo {
-            kind: ObjectKind::Custom(GcCell::new(HashMap::new())),
-            properties: GcCell::new(IndexMap::new()),
-            prototype,
+            kind: ObjectKind::Proxy { target, handler },
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(Value::Null),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
+    #[track_caller]
     pub fn new_error(agent: &Agent, message: &str) -> Value {
-        let mut properties = IndexMap::new();
+        let mut properties = PropertyMap::new();
         properties.insert(
-            ObjectKey::from("message"),
+            ObjectKey::new(agent, "message"),
             Value::String(message.to_string()),
         );
+        if agent.capture_native_frames() {
+            let caller = std::panic::Location::caller();
+            let location = format!("{}:{}", caller.file(), caller.line());
+            let frame = match agent.native_frame() {
+                Some(name) => format!("{} ({})", name, location),
+                None => location,
+            };
+            properties.insert(ObjectKey::new(agent, "nativeFrame"), Value::from(frame));
+        }
+        if let Some(stack) = agent.stack_trace(message) {
+            properties.insert(ObjectKey::new(agent, "stack"), Value::from(stack));
+        }
         Value::Object(Gc::new(ObjectInfo {
             kind: ObjectKind::Ordinary,
             properties: GcCell::new(properties),
-            prototype: agent.intrinsics.error_prototype.clone(),
+            prototype: GcCell::new(agent.intrinsics.error_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
     pub fn new_array(agent: &Agent) -> Value {
         Value::Object(Gc::new(ObjectInfo {
             kind: ObjectKind::Array(GcCell::new(Vec::new())),
-            properties: GcCell::new(IndexMap::new()),
-            prototype: agent.intrinsics.array_prototype.clone(),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.array_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
     pub fn new_array_from_vec(agent: &Agent, values: Vec<Value>) -> Value {
         Value::Object(Gc::new(ObjectInfo {
             kind: ObjectKind::Array(GcCell::new(values)),
-            properties: GcCell::new(IndexMap::new()),
-            prototype: agent.intrinsics.array_prototype.clone(),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.array_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
+        }))
+    }
+
+    // the first `args.len()` parameters stay aliased to their binding in `scope`
+    pub fn new_arguments(
+        agent: &Agent,
+        args: Vec<Value>,
+        callee: Value,
+        scope: Gc<GcCell<Scope>>,
+        parameters: &[String],
+    ) -> Value {
+        let mut properties = PropertyMap::new();
+        properties.insert(ObjectKey::LENGTH, Value::from(args.len() as f64));
+        properties.insert(ObjectKey::new(agent, "callee"), callee);
+        let mapped = parameters.iter().take(args.len()).cloned().collect();
+        Value::Object(Gc::new(ObjectInfo {
+            kind: ObjectKind::Arguments {
+                values: GcCell::new(args),
+                mapped,
+                scope,
+            },
+            properties: GcCell::new(properties),
+            prototype: GcCell::new(agent.intrinsics.object_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
@@ -629,16 +1408,20 @@ impl Value {
         };
         Ok(Value::Object(Gc::new(ObjectInfo {
             kind: ObjectKind::Regex(re),
-            properties: GcCell::new(IndexMap::new()),
-            prototype: agent.intrinsics.regex_prototype.clone(),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.regex_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         })))
     }
 
     pub fn new_buffer_from_vec(agent: &Agent, vec: Vec<u8>) -> Value {
         Value::Object(Gc::new(ObjectInfo {
             kind: ObjectKind::Buffer(GcCell::new(vec)),
-            properties: GcCell::new(IndexMap::new()),
-            prototype: agent.intrinsics.array_prototype.clone(),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.bytes_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
@@ -665,27 +1448,52 @@ impl Value {
         Value::Object(Gc::new(ObjectInfo {
             kind: ObjectKind::BytecodeFunction {
                 kind: info.kind,
+                name: info.name.clone().unwrap_or_default(),
+                length: info.length,
                 position: info.position,
                 parameters: info.parameters.clone(),
+                rest: info.rest,
                 scope,
             },
-            properties: GcCell::new(IndexMap::new()),
-            prototype: agent.intrinsics.function_prototype.clone(),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.function_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
     pub fn new_builtin_function(agent: &Agent, f: BuiltinFunction) -> Value {
         Value::Object(Gc::new(ObjectInfo {
             kind: ObjectKind::BuiltinFunction(f, GcCell::new(HashMap::new())),
-            properties: GcCell::new(IndexMap::new()),
-            prototype: agent.intrinsics.function_prototype.clone(),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.function_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
+        }))
+    }
+
+    pub fn new_named_builtin_function(
+        agent: &Agent,
+        f: BuiltinFunction,
+        name: &str,
+        length: usize,
+    ) -> Value {
+        let mut slots = HashMap::new();
+        slots.insert("function name".to_string(), Value::from(name));
+        slots.insert("function length".to_string(), Value::from(length as f64));
+        Value::Object(Gc::new(ObjectInfo {
+            kind: ObjectKind::BuiltinFunction(f, GcCell::new(slots)),
+            properties: GcCell::new(PropertyMap::new()),
+            prototype: GcCell::new(agent.intrinsics.function_prototype.clone()),
+            extensible: GcCell::new(true),
+            frozen: GcCell::new(false),
         }))
     }
 
     pub fn new_iter_result(agent: &Agent, value: Value, done: bool) -> Result<Value, Value> {
         let o = Value::new_object(agent.intrinsics.object_prototype.clone());
-        o.set(agent, ObjectKey::from("value"), value)?;
-        o.set(agent, ObjectKey::from("done"), Value::from(done))?;
+        o.set(agent, ObjectKey::new(agent, "value"), value)?;
+        o.set(agent, ObjectKey::new(agent, "done"), Value::from(done))?;
         Ok(o)
     }
 
@@ -698,43 +1506,61 @@ impl Value {
 }
 
 impl Value {
+    /// The result of the `typeof` operator and the `type` builtin. Arrays,
+    /// promises, bytes and every other kind of object are "object".
     pub fn type_of(&self) -> &str {
-        match &self {
-            Value::Null => "null",
-            Value::Boolean(..) => "boolean",
-            Value::Number(..) => "number",
-            Value::String(..) => "string",
-            Value::Symbol(..) => "symbol",
-            Value::Object(o) => match o.kind {
+        match self.kind() {
+            ValueKind::Null => "null",
+            ValueKind::Boolean(..) => "boolean",
+            ValueKind::Number(..) => "number",
+            ValueKind::BigInt(..) => "bigint",
+            ValueKind::String(..) => "string",
+            ValueKind::Symbol(..) => "symbol",
+            ValueKind::Object(o) => match o.kind {
                 ObjectKind::BytecodeFunction { .. } => "function",
                 ObjectKind::BuiltinFunction(..) => "function",
+                ObjectKind::Regex(..) => "regex",
                 _ => "object",
             },
-            Value::Tuple(..) => "tuple",
+            ValueKind::Tuple(..) => "tuple",
             _ => unreachable!(),
         }
     }
 
+    /// Whether this is an object of any kind, including functions.
+    pub fn is_object(&self) -> bool {
+        match self.kind() {
+            ValueKind::Object(..) => true,
+            _ => false,
+        }
+    }
+
     pub fn to_bool(&self) -> bool {
-        match &self {
-            Value::Null => false,
-            Value::Boolean(b) => *b,
-            Value::Number(n) => *n != 0.0,
-            Value::String(s) => !s.is_empty(),
-            Value::Symbol(..) => true,
-            Value::Object(..) => true,
-            Value::Tuple(..) => true,
+        match self.kind() {
+            ValueKind::Null => false,
+            ValueKind::Boolean(b) => b,
+            ValueKind::Number(n) => n != 0.0,
+            ValueKind::BigInt(n) => !n.is_zero(),
+            ValueKind::String(s) => !s.is_empty(),
+            ValueKind::Symbol(..) => true,
+            ValueKind::Object(..) => true,
+            ValueKind::Tuple(..) => true,
             _ => unreachable!(),
         }
     }
 
     pub fn get(&self, agent: &Agent, key: ObjectKey) -> Result<Value, Value> {
-        match self {
-            Value::Object(o) => Ok(o.get(key)),
-            Value::Tuple(t, ..) => {
+        match self.kind() {
+            ValueKind::Object(o) => match &o.kind {
+                ObjectKind::Proxy { target, handler } => {
+                    proxy::get(agent, target, handler, key, self.clone())
+                }
+                _ => Ok(o.get(key)),
+            },
+            ValueKind::Tuple(t, ..) => {
                 if let Some(n) = key.to_number() {
                     Ok(t.get(n).unwrap_or(&Value::Null).clone())
-                } else if key == ObjectKey::from("length") {
+                } else if key == ObjectKey::LENGTH {
                     Ok(Value::from(t.len() as f64))
                 } else {
                     Ok(Value::Null)
@@ -745,16 +1571,33 @@ impl Value {
     }
 
     pub fn set(&self, agent: &Agent, key: ObjectKey, value: Value) -> Result<Value, Value> {
-        match self {
-            Value::Object(o) => o.set(agent, key, value, o.clone()),
+        match self.kind() {
+            ValueKind::Object(o) => match &o.kind {
+                ObjectKind::Proxy { target, handler } => {
+                    proxy::set(agent, target, handler, key, value, self.clone())
+                }
+                _ => o.set(agent, key, value, o.clone()),
+            },
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
+    pub fn delete(&self, agent: &Agent, key: ObjectKey) -> Result<bool, Value> {
+        match self.kind() {
+            ValueKind::Object(o) => match &o.kind {
+                ObjectKind::Proxy { target, handler } => {
+                    proxy::delete_property(agent, target, handler, key)
+                }
+                _ => Ok(o.delete(&key)),
+            },
             _ => Err(Value::new_error(agent, "base must be an object")),
         }
     }
 
     pub fn keys(&self, agent: &Agent) -> Result<Vec<ObjectKey>, Value> {
-        match self {
-            Value::Object(o) => Ok(o.keys()),
-            Value::Tuple(vec) => Ok((0..vec.len())
+        match self.kind() {
+            ValueKind::Object(o) => Ok(o.keys()),
+            ValueKind::Tuple(vec) => Ok((0..vec.len())
                 .map(ObjectKey::from)
                 .collect::<Vec<ObjectKey>>()),
             _ => Err(Value::new_error(agent, "base must be an object")),
@@ -762,9 +1605,12 @@ impl Value {
     }
 
     pub fn has(&self, agent: &Agent, key: ObjectKey) -> Result<bool, Value> {
-        match self {
-            Value::Object(o) => Ok(o.has(key)),
-            Value::Tuple(vec) => match key.to_number() {
+        match self.kind() {
+            ValueKind::Object(o) => match &o.kind {
+                ObjectKind::Proxy { target, handler } => proxy::has(agent, target, handler, key),
+                _ => Ok(o.has(key)),
+            },
+            ValueKind::Tuple(vec) => match key.to_number() {
                 Some(n) => Ok(vec.len() < n),
                 None => Ok(false),
             },
@@ -772,8 +1618,79 @@ impl Value {
         }
     }
 
+    pub fn has_own_property(&self, agent: &Agent, key: ObjectKey) -> Result<bool, Value> {
+        match self.kind() {
+            ValueKind::Object(o) => Ok(o.has_own(&key)),
+            ValueKind::Tuple(vec) => match key.to_number() {
+                Some(n) => Ok(n < vec.len()),
+                None => Ok(false),
+            },
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
+    pub fn get_prototype_of(&self, agent: &Agent) -> Result<Value, Value> {
+        match self.kind() {
+            ValueKind::Object(o) => Ok(o.get_prototype_of()),
+            ValueKind::Tuple(..) => Ok(Value::Null),
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
+    pub fn set_prototype_of(&self, agent: &Agent, prototype: Value) -> Result<bool, Value> {
+        match (self.kind(), prototype.kind()) {
+            (ValueKind::Object(o), ValueKind::Object(..))
+            | (ValueKind::Object(o), ValueKind::Null) => Ok(o.set_prototype_of(prototype)),
+            (ValueKind::Object(..), _) => Err(Value::new_error(
+                agent,
+                "prototype must be an object or null",
+            )),
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
+    pub fn is_extensible(&self, agent: &Agent) -> Result<bool, Value> {
+        match self.kind() {
+            ValueKind::Object(o) => Ok(o.is_extensible()),
+            ValueKind::Tuple(..) => Ok(false),
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
+    pub fn prevent_extensions(&self, agent: &Agent) -> Result<(), Value> {
+        match self.kind() {
+            ValueKind::Object(o) => {
+                o.prevent_extensions();
+                Ok(())
+            }
+            ValueKind::Tuple(..) => Ok(()),
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
+    /// Prevent extensions, and make the own properties of the object
+    /// impossible to assign or delete.
+    pub fn freeze(&self, agent: &Agent) -> Result<(), Value> {
+        match self.kind() {
+            ValueKind::Object(o) => {
+                o.freeze();
+                Ok(())
+            }
+            ValueKind::Tuple(..) => Ok(()),
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
+    pub fn is_frozen(&self, agent: &Agent) -> Result<bool, Value> {
+        match self.kind() {
+            ValueKind::Object(o) => Ok(o.is_frozen()),
+            ValueKind::Tuple(..) => Ok(true),
+            _ => Err(Value::new_error(agent, "base must be an object")),
+        }
+    }
+
     pub fn get_slot(&self, key: &str) -> Value {
-        if let Value::Object(o) = self {
+        if let ValueKind::Object(o) = self.kind() {
             match &o.kind {
                 ObjectKind::Custom(slots) | ObjectKind::BuiltinFunction(_, slots) => {
                     match slots.borrow().get(key) {
@@ -789,7 +1706,7 @@ impl Value {
     }
 
     pub fn set_slot(&self, key: &str, value: Value) {
-        if let Value::Object(o) = self {
+        if let ValueKind::Object(o) = self.kind() {
             match &o.kind {
                 ObjectKind::Custom(slots) | ObjectKind::BuiltinFunction(_, slots) => {
                     slots.borrow_mut().insert(key.to_string(), value);
@@ -802,7 +1719,7 @@ impl Value {
     }
 
     pub fn has_slot(&self, property: &str) -> bool {
-        if let Value::Object(o) = self {
+        if let ValueKind::Object(o) = self.kind() {
             match &o.kind {
                 ObjectKind::Custom(slots) | ObjectKind::BuiltinFunction(_, slots) => {
                     slots.borrow().contains_key(property)
@@ -815,67 +1732,121 @@ impl Value {
     }
 
     pub fn to_object(&self, agent: &Agent) -> Result<Value, Value> {
-        match self {
-            Value::Null => Err(Value::new_error(agent, "cannot convert null to object")),
-            Value::Boolean(b) => Ok(Value::Object(Gc::new(ObjectInfo {
-                kind: ObjectKind::Boolean(*b),
-                properties: GcCell::new(IndexMap::new()),
-                prototype: agent.intrinsics.boolean_prototype.clone(),
+        match self.kind() {
+            ValueKind::Null => Err(Value::new_error(agent, "cannot convert null to object")),
+            ValueKind::Boolean(b) => Ok(Value::Object(Gc::new(ObjectInfo {
+                kind: ObjectKind::Boolean(b),
+                properties: GcCell::new(PropertyMap::new()),
+                prototype: GcCell::new(agent.intrinsics.boolean_prototype.clone()),
+                extensible: GcCell::new(true),
+                frozen: GcCell::new(false),
             }))),
-            Value::Object(_) => Ok(self.clone()),
-            Value::Number(n) => Ok(Value::Object(Gc::new(ObjectInfo {
-                kind: ObjectKind::Number(*n),
-                properties: GcCell::new(IndexMap::new()),
-                prototype: agent.intrinsics.number_prototype.clone(),
+            ValueKind::Object(_) => Ok(self.clone()),
+            ValueKind::Number(n) => Ok(Value::Object(Gc::new(ObjectInfo {
+                kind: ObjectKind::Number(n),
+                properties: GcCell::new(PropertyMap::new()),
+                prototype: GcCell::new(agent.intrinsics.number_prototype.clone()),
+                extensible: GcCell::new(true),
+                frozen: GcCell::new(false),
             }))),
-            Value::String(s) => Ok(Value::Object(Gc::new(ObjectInfo {
+            ValueKind::BigInt(n) => Ok(Value::Object(Gc::new(ObjectInfo {
+                kind: ObjectKind::BigInt(n.clone()),
+                properties: GcCell::new(PropertyMap::new()),
+                prototype: GcCell::new(agent.intrinsics.bigint_prototype.clone()),
+                extensible: GcCell::new(true),
+                frozen: GcCell::new(false),
+            }))),
+            ValueKind::String(s) => Ok(Value::Object(Gc::new(ObjectInfo {
                 kind: ObjectKind::String(s.chars().collect()),
-                properties: GcCell::new(IndexMap::new()),
-                prototype: agent.intrinsics.string_prototype.clone(),
+                properties: GcCell::new(PropertyMap::new()),
+                prototype: GcCell::new(agent.intrinsics.string_prototype.clone()),
+                extensible: GcCell::new(true),
+                frozen: GcCell::new(false),
             }))),
-            Value::Symbol(s) => Ok(Value::Object(Gc::new(ObjectInfo {
+            ValueKind::Symbol(s) => Ok(Value::Object(Gc::new(ObjectInfo {
                 kind: ObjectKind::Symbol(s.clone()),
-                properties: GcCell::new(IndexMap::new()),
-                prototype: agent.intrinsics.symbol_prototype.clone(),
+                properties: GcCell::new(PropertyMap::new()),
+                prototype: GcCell::new(agent.intrinsics.symbol_prototype.clone()),
+                extensible: GcCell::new(true),
+                frozen: GcCell::new(false),
             }))),
-            Value::Tuple(_) => Ok(self.clone()),
+            ValueKind::Tuple(_) => Ok(self.clone()),
             _ => unreachable!(),
         }
     }
 
     pub fn to_object_key(&self, agent: &Agent) -> Result<ObjectKey, Value> {
-        match self {
-            Value::Symbol(s) => Ok(ObjectKey::Symbol(s.clone())),
-            Value::String(s) => Ok(ObjectKey::from(s.to_string())),
-            Value::Number(n) => Ok(ObjectKey::from(*n)),
+        match self.kind() {
+            ValueKind::Symbol(s) => Ok(ObjectKey::Symbol(s.clone())),
+            ValueKind::String(s) => Ok(ObjectKey::new(agent, s)),
+            ValueKind::Number(n) => Ok(ObjectKey::from_number(agent, n)),
+            ValueKind::BigInt(n) => Ok(ObjectKey::new(agent, &n.to_string())),
+            ValueKind::Null => Ok(ObjectKey::new(agent, "null")),
+            ValueKind::Boolean(b) => Ok(ObjectKey::new(agent, &b.to_string())),
+            ValueKind::Object(..) | ValueKind::Tuple(..) => match self.to_string(agent)?.kind() {
+                ValueKind::String(s) => Ok(ObjectKey::new(agent, s)),
+                _ => Err(Value::new_error(agent, ":toString must return a string")),
+            },
             _ => Err(Value::new_error(agent, "cannot convert to object key")),
         }
     }
 
+    // converts a value to a string by calling its :toString method.
+    pub fn to_string(&self, agent: &Agent) -> Result<Value, Value> {
+        if self.type_of() == "string" {
+            return Ok(self.clone());
+        }
+        if let ValueKind::Symbol(..) = self.kind() {
+            // symbols are only converted explicitly, with `toString()`
+            return Err(Value::new_error(
+                agent,
+                "cannot convert a symbol to a string",
+            ));
+        }
+        let ts = self.get(agent, ObjectKey::well_known_symbol("toString"))?;
+        if ts.type_of() != "function" {
+            Err(Value::new_error(
+                agent,
+                "value does not provide a :toString",
+            ))
+        } else {
+            ts.call(agent, self.clone(), vec![])
+        }
+    }
+
     pub fn to_iterator(&self, agent: &Agent) -> Result<Value, Value> {
         let iterator = self.get(agent, ObjectKey::well_known_symbol("iterator"))?;
         let iterator = iterator.call(agent, self.clone(), vec![])?;
-        let next = iterator.get(agent, ObjectKey::from("next"))?;
+        let next = iterator.get(agent, ObjectKey::new(agent, "next"))?;
         Ok(Value::Iterator(Box::new(iterator), Box::new(next)))
     }
 
     pub fn to_async_iterator(&self, agent: &Agent) -> Result<Value, Value> {
-        let iterator = self.get(agent, ObjectKey::well_known_symbol("asyncIterator"))?;
-        let iterator = iterator.call(agent, self.clone(), vec![])?;
-        let next = iterator.get(agent, ObjectKey::from("next"))?;
+        let method = self.get(agent, ObjectKey::well_known_symbol("asyncIterator"))?;
+        if method == Value::Null {
+            // sync iterables are awaited a step at a time
+            return self.to_iterator(agent);
+        }
+        let iterator = method.call(agent, self.clone(), vec![])?;
+        let next = iterator.get(agent, ObjectKey::new(agent, "next"))?;
         Ok(Value::Iterator(Box::new(iterator), Box::new(next)))
     }
 
     pub fn call(&self, agent: &Agent, this: Value, args: Vec<Value>) -> Result<Value, Value> {
-        match self {
-            Value::Object(o) => match &o.kind {
+        match self.kind() {
+            ValueKind::Object(o) => match &o.kind {
                 ObjectKind::BytecodeFunction {
                     position,
                     kind,
                     scope,
                     parameters,
-                    ..
+                    rest,
+                    name,
+                    length,
                 } => {
+                    if let Err(e) = check_arity(agent, name, *length, args.len()) {
+                        return async_rejection(agent, *kind, e);
+                    }
                     let ctx = Context::new(Scope::new(Some(scope.clone())));
                     if *kind & FunctionKind::Arrow == FunctionKind::Arrow {
                         // FIXME: doesn't have `this` vs inherited `this` needs to be clarified
@@ -887,7 +1858,9 @@ impl Value {
                         });
                     }
                     ctx.borrow_mut().function = Some(self.clone());
-                    evaluate_body(agent, ctx, *position, *kind, args, parameters)
+                    traced(agent, name, Some(*position), || {
+                        evaluate_body(agent, ctx, *position, *kind, args, parameters, *rest)
+                    })
                 }
                 ObjectKind::BuiltinFunction(f, ..) => {
                     let c = Context::new(Scope::new(None));
@@ -898,7 +1871,7 @@ impl Value {
                         this.to_object(agent)?
                     });
                     b.function = Some(self.clone());
-                    f(agent, args, &b)
+                    call_builtin(agent, self, *f, args, &b)
                 }
                 _ => Err(Value::new_error(agent, "value is not a function")),
             },
@@ -912,30 +1885,37 @@ impl Value {
         args: Vec<Value>,
         new_target: Value,
     ) -> Result<Value, Value> {
-        match self {
-            Value::Object(o) => match &o.kind {
+        match self.kind() {
+            ValueKind::Object(o) => match &o.kind {
                 ObjectKind::BytecodeFunction {
                     position,
                     kind,
                     scope,
                     parameters,
-                    ..
+                    rest,
+                    name,
+                    length,
                 } => {
+                    check_arity(agent, name, *length, args.len())?;
                     if *kind != FunctionKind::Normal
                         || (*kind & FunctionKind::Arrow == FunctionKind::Arrow)
                     {
                         Err(Value::new_error(agent, "value is not a constructor"))
                     } else {
-                        let mut prototype = new_target.get(agent, ObjectKey::from("prototype"))?;
-                        if prototype.type_of() != "object" {
+                        let mut prototype =
+                            new_target.get(agent, ObjectKey::new(agent, "prototype"))?;
+                        if !prototype.is_object() {
                             prototype = agent.intrinsics.object_prototype.clone();
                         }
                         let this = Value::new_object(prototype);
                         let ctx = Context::new(Scope::new(Some(scope.clone())));
                         ctx.borrow().scope.borrow_mut().this = Some(this.clone());
                         ctx.borrow_mut().function = Some(self.clone());
-                        let r = evaluate_body(agent, ctx, *position, *kind, args, parameters)?;
-                        if r.type_of() == "object" {
+                        ctx.borrow_mut().new_target = Some(new_target);
+                        let r = traced(agent, name, Some(*position), || {
+                            evaluate_body(agent, ctx, *position, *kind, args, parameters, *rest)
+                        })?;
+                        if r.is_object() {
                             Ok(r)
                         } else {
                             Ok(this)
@@ -943,8 +1923,9 @@ impl Value {
                     }
                 }
                 ObjectKind::BuiltinFunction(f, ..) => {
-                    let mut prototype = new_target.get(agent, ObjectKey::from("prototype"))?;
-                    if prototype.type_of() != "object" {
+                    let mut prototype =
+                        new_target.get(agent, ObjectKey::new(agent, "prototype"))?;
+                    if !prototype.is_object() {
                         prototype = agent.intrinsics.object_prototype.clone();
                     }
                     let this = Value::new_object(prototype);
@@ -952,8 +1933,9 @@ impl Value {
                     let mut cb = c.borrow_mut();
                     cb.scope.borrow_mut().this = Some(this.clone());
                     cb.function = Some(self.clone());
-                    let r = f(agent, args, &cb)?;
-                    if r.type_of() == "object" {
+                    cb.new_target = Some(new_target);
+                    let r = call_builtin(agent, self, *f, args, &cb)?;
+                    if r.is_object() {
                         Ok(r)
                     } else {
                         Ok(this)
@@ -971,23 +1953,61 @@ impl Value {
     }
 }
 
-fn evaluate_body(
+// async functions report every error through the promise they return, even
+// the ones from before their body starts running.
+fn async_rejection(agent: &Agent, kind: FunctionKind, e: Value) -> Result<Value, Value> {
+    if kind & FunctionKind::Async == FunctionKind::Async {
+        let promise = new_promise_capability(agent, agent.intrinsics.promise.clone())?;
+        promise
+            .get_slot("reject")
+            .call(agent, Value::Null, vec![e])?;
+        Ok(promise)
+    } else {
+        Err(e)
+    }
+}
+
+fn bind_parameters(
     agent: &Agent,
-    ctx: Gc<GcCell<Context>>,
-    position: usize,
+    ctx: &Gc<GcCell<Context>>,
     kind: FunctionKind,
     args: Vec<Value>,
     params: &[String],
-) -> Result<Value, Value> {
+    rest: bool,
+) -> Result<(), Value> {
     for (i, param) in params.iter().enumerate() {
         ctx.borrow()
             .scope
             .borrow_mut()
             .create(agent, param, false)?;
-        ctx.borrow()
-            .scope
-            .borrow_mut()
-            .initialize(param, args.get(i).unwrap_or(&Value::Empty).clone());
+        let value = if rest && i == params.len() - 1 {
+            Value::new_array_from_vec(agent, args.get(i..).unwrap_or(&[]).to_vec())
+        } else {
+            args.get(i).unwrap_or(&Value::Empty).clone()
+        };
+        ctx.borrow().scope.borrow_mut().initialize(param, value);
+    }
+
+    if kind & FunctionKind::Arrow != FunctionKind::Arrow {
+        let ctx = ctx.borrow();
+        let callee = ctx.callee();
+        let simple = &params[..params.len() - rest as usize];
+        declare_arguments(agent, &ctx.scope, args, callee, simple)?;
+    }
+    Ok(())
+}
+
+fn evaluate_body(
+    agent: &Agent,
+    ctx: Gc<GcCell<Context>>,
+    position: usize,
+    kind: FunctionKind,
+    args: Vec<Value>,
+    params: &[String],
+    rest: bool,
+) -> Result<Value, Value> {
+    if let Err(e) = bind_parameters(agent, &ctx, kind, args, params, rest) {
+        return async_rejection(agent, kind, e);
     }
 
     let mut interpreter = Interpreter::new(position, ctx.clone());
@@ -995,6 +2015,12 @@ fn evaluate_body(
     if kind & FunctionKind::Normal == FunctionKind::Normal {
         interpreter.run(agent).unwrap()
     } else if kind & FunctionKind::Generator == FunctionKind::Generator {
+        // runs the parameter defaults, the generator suspends before its body
+        match interpreter.run(agent) {
+            Ok(Err(e)) => return Err(e),
+            Ok(Ok(_)) => unreachable!(),
+            Err(_) => {}
+        }
         ctx.borrow_mut().interpreter = Some(interpreter);
         let o = Value::new_custom_object(agent.intrinsics.generator_prototype.clone());
         o.set_slot("generator context", Value::WrappedContext(ctx, None));
@@ -1017,11 +2043,12 @@ fn evaluate_body(
             Err(mut c) => {
                 ctx.borrow_mut().interpreter = Some(interpreter);
                 let value = std::mem::replace(&mut c.0, Value::Null);
-                perform_await(
-                    agent,
-                    Value::WrappedContext(ctx, Some(Box::new(promise.clone()))),
-                    value,
-                )?;
+                let ctx = Value::WrappedContext(ctx, Some(Box::new(promise.clone())));
+                if let Err(e) = perform_await(agent, ctx, value) {
+                    promise
+                        .get_slot("reject")
+                        .call(agent, Value::Null, vec![e])?;
+                }
             }
         }
         Ok(promise)
@@ -1030,6 +2057,90 @@ fn evaluate_body(
     }
 }
 
+// with strict arity, calling a function with fewer arguments than its
+// `length` is an error instead of leaving the parameters empty.
+// runs a builtin, recording it as the innermost native frame while the agent
+// is capturing them.
+fn call_builtin(
+    agent: &Agent,
+    function: &Value,
+    f: BuiltinFunction,
+    args: Vec<Value>,
+    ctx: &Context,
+) -> Result<Value, Value> {
+    let capture = agent.capture_native_frames();
+    if !capture && !agent.tracing() {
+        return f(agent, args, ctx);
+    }
+    let name = match function
+        .get(agent, ObjectKey::NAME)
+        .as_ref()
+        .map(Value::kind)
+    {
+        Ok(ValueKind::String(name)) if !name.is_empty() => name.clone(),
+        _ => "<anonymous>".to_string(),
+    };
+    if capture {
+        agent.push_native_frame(name.clone());
+    }
+    let r = traced(agent, &name, None, || f(agent, args, ctx));
+    if capture {
+        agent.pop_native_frame();
+    }
+    r
+}
+
+// runs a call, telling the agent's tracer about it if there is one. the
+// position of a bytecode function's body becomes its span.
+fn traced(
+    agent: &Agent,
+    name: &str,
+    position: Option<usize>,
+    f: impl FnOnce() -> Result<Value, Value>,
+) -> Result<Value, Value> {
+    if !agent.tracing() {
+        return f();
+    }
+    agent.trace_enter(name, position.map(|p| agent.assembler.function_span(p)));
+    let r = f();
+    agent.trace_exit();
+    r
+}
+
+pub fn check_arity(agent: &Agent, name: &str, length: usize, argc: usize) -> Result<(), Value> {
+    if agent.strict_arity() && argc < length {
+        let name = if name.is_empty() { "anonymous" } else { name };
+        Err(Value::new_error(
+            agent,
+            &format!(
+                "{} expects at least {} arguments, got {}",
+                name, length, argc
+            ),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// arrow functions don't get their own `arguments`, they see the one of the
+// enclosing function through the scope chain.
+pub fn declare_arguments(
+    agent: &Agent,
+    scope: &Gc<GcCell<Scope>>,
+    args: Vec<Value>,
+    callee: Value,
+    parameters: &[String],
+) -> Result<(), Value> {
+    if parameters.iter().any(|p| p == "arguments") {
+        return Ok(());
+    }
+    let arguments = Value::new_arguments(agent, args, callee, scope.clone(), parameters);
+    let mut scope = scope.borrow_mut();
+    scope.create(agent, "arguments", false)?;
+    scope.initialize("arguments", arguments);
+    Ok(())
+}
+
 #[inline]
 pub fn ref_eq<T>(thing: &T, other: &T) -> bool {
     (thing as *const T) == (other as *const T)
@@ -1037,43 +2148,47 @@ pub fn ref_eq<T>(thing: &T, other: &T) -> bool {
 
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        match self {
-            Value::Null => match other {
-                Value::Null => true,
+        match self.kind() {
+            ValueKind::Null => match other.kind() {
+                ValueKind::Null => true,
+                _ => false,
+            },
+            ValueKind::Boolean(b) => match other.kind() {
+                ValueKind::Boolean(vb) => b == vb,
                 _ => false,
             },
-            Value::Boolean(b) => match other {
-                Value::Boolean(vb) => b == vb,
+            ValueKind::String(s) => match other.kind() {
+                ValueKind::String(vs) => s == vs,
                 _ => false,
             },
-            Value::String(s) => match &other {
-                Value::String(vs) => s == vs,
+            ValueKind::Number(n) => match other.kind() {
+                ValueKind::Number(vn) => n == vn,
                 _ => false,
             },
-            Value::Number(n) => match &other {
-                Value::Number(vn) => n == vn,
+            ValueKind::BigInt(n) => match other.kind() {
+                ValueKind::BigInt(vn) => n == vn,
                 _ => false,
             },
-            Value::Symbol(s) => match &other {
-                Value::Symbol(vs) => s == vs,
+            ValueKind::Symbol(s) => match other.kind() {
+                ValueKind::Symbol(vs) => s == vs,
                 _ => false,
             },
-            Value::Object(o) => match &other {
-                Value::Object(vo) => ref_eq(&*o.properties.borrow(), &*vo.properties.borrow()),
+            ValueKind::Object(o) => match other.kind() {
+                ValueKind::Object(vo) => ref_eq(&*o.properties.borrow(), &*vo.properties.borrow()),
                 _ => false,
             },
-            Value::Tuple(i) => match &other {
-                Value::Tuple(vi) => {
+            ValueKind::Tuple(i) => match other.kind() {
+                ValueKind::Tuple(vi) => {
                     i.len() == vi.len() && i.iter().enumerate().all(|(i, v)| &vi[i] == v)
                 }
                 _ => false,
             },
-            Value::Empty => match other {
-                Value::Empty => true,
+            ValueKind::Empty => match other.kind() {
+                ValueKind::Empty => true,
                 _ => false,
             },
 
-            Value::List(..) | Value::WrappedContext(..) | Value::Iterator(..) => {
+            ValueKind::List(..) | ValueKind::WrappedContext(..) | ValueKind::Iterator(..) => {
                 ref_eq(self, other)
             }
         }
@@ -1084,33 +2199,37 @@ impl Eq for Value {}
 
 impl Hash for Value {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        match self {
-            Value::Null => 0.hash(state),
-            Value::Boolean(b) => {
+        match self.kind() {
+            ValueKind::Null => 0.hash(state),
+            ValueKind::Boolean(b) => {
                 1.hash(state);
                 b.hash(state);
             }
-            Value::String(s) => {
+            ValueKind::String(s) => {
                 2.hash(state);
                 s.hash(state);
             }
-            Value::Number(n) => {
+            ValueKind::Number(n) => {
                 3.hash(state);
                 n.to_bits().hash(state);
             }
-            Value::Symbol(s) => {
+            ValueKind::Symbol(s) => {
                 4.hash(state);
                 s.hash(state);
             }
-            Value::Object(o) => {
+            ValueKind::Object(o) => {
                 5.hash(state);
                 // hash the memory address of the map sigh
-                (&*o.properties.borrow() as *const IndexMap<ObjectKey, Value>).hash(state);
+                (&*o.properties.borrow() as *const PropertyMap).hash(state);
             }
-            Value::Tuple(items) => {
+            ValueKind::Tuple(items) => {
                 6.hash(state);
                 items.hash(state);
             }
+            ValueKind::BigInt(n) => {
+                7.hash(state);
+                n.hash(state);
+            }
             _ => unreachable!(),
         }
     }
@@ -1150,16 +2269,6 @@ impl From<bool> for Value {
     }
 }
 
-impl From<&ObjectKey> for Value {
-    fn from(k: &ObjectKey) -> Self {
-        match k {
-            ObjectKey::Number(n) => Value::from(n.to_string()),
-            ObjectKey::String(s) => Value::from(s.to_string()),
-            ObjectKey::Symbol(s) => Value::Symbol(s.clone()),
-        }
-    }
-}
-
 impl IntoValue for std::net::AddrParseError {
     fn into_value(&self, agent: &Agent) -> Value {
         Value::new_error(agent, &format!("{}", self))
@@ -1172,39 +2281,420 @@ impl IntoValue for std::io::Error {
     }
 }
 
+/// An agent independent copy of a value graph, so that it can be moved to
+/// another agent and turned back into a value there. Objects are numbered in
+/// the order they are first reached, later occurrences refer back to that
+/// number so cycles and shared references survive the trip.
+#[derive(Debug)]
+pub enum CloneData {
+    Null,
+    Boolean(bool),
+    String(String),
+    Number(f64),
+    BigInt(BigInt),
+    Tuple(Vec<CloneData>),
+    Object(usize, CloneKind, Vec<(String, CloneData)>),
+    Reference(usize),
+}
+
+#[derive(Debug)]
+pub enum CloneKind {
+    Ordinary,
+    Array(Vec<CloneData>),
+    Boolean(bool),
+    Number(f64),
+    String(Vec<char>),
+    Regex(String),
+    Buffer(Vec<u8>),
+    ArrayBuffer(Vec<u8>),
+    // the index of the buffer in the transfer list, until its bytes are moved
+    Transferred(usize),
+    TypedArray {
+        kind: TypedArrayKind,
+        buffer: Box<CloneData>,
+        offset: usize,
+        length: usize,
+    },
+    Time(i64, i64),
+}
+
+fn clone_path(path: &str, key: &ObjectKey) -> String {
+    match key {
+        ObjectKey::Number(n) => format!("{}[{}]", path, n),
+        ObjectKey::Interned(s) => {
+            let s: &str = s;
+            let mut chars = s.chars();
+            let identifier = match chars.next() {
+                Some('a'...'z') | Some('A'...'Z') | Some('_') => chars.all(|c| match c {
+                    'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => true,
+                    _ => false,
+                }),
+                _ => false,
+            };
+            if identifier {
+                format!("{}.{}", path, s)
+            } else {
+                format!("{}[{:?}]", path, s)
+            }
+        }
+        ObjectKey::Symbol(s) => format!("{}[{}]", path, s),
+    }
+}
+
+/// The error thrown for a value which can't be cloned, `path` being where it
+/// was found.
+pub fn data_clone_error(agent: &Agent, path: &str) -> Value {
+    let e = Value::new_error(agent, &format!("{} is not cloneable", path));
+    e.set(agent, ObjectKey::NAME, Value::from("DataCloneError"))
+        .unwrap();
+    e
+}
+
+type CloneSeen = HashMap<*const PropertyMap, usize>;
+
+impl CloneData {
+    /// Copy `value`, returning the path to the first value which can't be
+    /// copied (functions, symbols, promises, handles, etc) on failure.
+    pub fn new(value: &Value) -> Result<CloneData, String> {
+        CloneData::from_value(value, "value".to_string(), &mut HashMap::new(), &[])
+    }
+
+    /// Like `new`, but the bytes of the ArrayBuffers in `transfer` are moved
+    /// into the copy, leaving the buffers detached. Nothing is detached if
+    /// the copy fails.
+    pub fn with_transfer(value: &Value, transfer: &[Value]) -> Result<CloneData, String> {
+        let mut keys = Vec::with_capacity(transfer.len());
+        for (i, buffer) in transfer.iter().enumerate() {
+            let path = format!("transfer[{}]", i);
+            match buffer.kind() {
+                ValueKind::Object(o) => match &o.kind {
+                    ObjectKind::ArrayBuffer { detached, .. } if !*detached.borrow() => {
+                        let key = &*o.properties.borrow() as *const PropertyMap;
+                        if keys.contains(&key) {
+                            return Err(path);
+                        }
+                        keys.push(key);
+                    }
+                    _ => return Err(path),
+                },
+                _ => return Err(path),
+            }
+        }
+        let mut data =
+            CloneData::from_value(value, "value".to_string(), &mut HashMap::new(), &keys)?;
+        let mut moved = transfer
+            .iter()
+            .map(|buffer| match buffer.kind() {
+                ValueKind::Object(o) => match &o.kind {
+                    ObjectKind::ArrayBuffer { data, detached } => {
+                        *detached.borrow_mut() = true;
+                        Some(std::mem::take(&mut *data.borrow_mut()))
+                    }
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        data.fill_transferred(&mut moved);
+        Ok(data)
+    }
+
+    fn fill_transferred(&mut self, moved: &mut [Option<Vec<u8>>]) {
+        match self {
+            CloneData::Tuple(items) => {
+                for item in items {
+                    item.fill_transferred(moved);
+                }
+            }
+            CloneData::Object(_, kind, properties) => {
+                match kind {
+                    CloneKind::Transferred(i) => {
+                        *kind = CloneKind::ArrayBuffer(moved[*i].take().unwrap());
+                    }
+                    CloneKind::Array(items) => {
+                        for item in items {
+                            item.fill_transferred(moved);
+                        }
+                    }
+                    CloneKind::TypedArray { buffer, .. } => buffer.fill_transferred(moved),
+                    _ => {}
+                }
+                for (_, value) in properties {
+                    value.fill_transferred(moved);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn from_value(
+        value: &Value,
+        path: String,
+        seen: &mut CloneSeen,
+        transfer: &[*const PropertyMap],
+    ) -> Result<CloneData, String> {
+        match value.kind() {
+            ValueKind::Null => Ok(CloneData::Null),
+            ValueKind::Boolean(b) => Ok(CloneData::Boolean(b)),
+            ValueKind::String(s) => Ok(CloneData::String(s.clone())),
+            ValueKind::Number(n) => Ok(CloneData::Number(n)),
+            ValueKind::BigInt(n) => Ok(CloneData::BigInt((**n).clone())),
+            ValueKind::Tuple(items) => {
+                let mut data = Vec::with_capacity(items.len());
+                for (i, item) in items.iter().enumerate() {
+                    data.push(CloneData::from_value(
+                        item,
+                        format!("{}[{}]", path, i),
+                        seen,
+                        transfer,
+                    )?);
+                }
+                Ok(CloneData::Tuple(data))
+            }
+            ValueKind::Object(o) => {
+                let properties = o.properties.borrow();
+                let key = &*properties as *const PropertyMap;
+                if let Some(id) = seen.get(&key) {
+                    return Ok(CloneData::Reference(*id));
+                }
+                let id = seen.len();
+                seen.insert(key, id);
+                let kind = match &o.kind {
+                    ObjectKind::Ordinary => CloneKind::Ordinary,
+                    ObjectKind::Array(values) => {
+                        let mut data = Vec::new();
+                        for (i, item) in values.borrow().iter().enumerate() {
+                            data.push(CloneData::from_value(
+                                item,
+                                format!("{}[{}]", path, i),
+                                seen,
+                                transfer,
+                            )?);
+                        }
+                        CloneKind::Array(data)
+                    }
+                    ObjectKind::Boolean(b) => CloneKind::Boolean(*b),
+                    ObjectKind::Number(n) => CloneKind::Number(*n),
+                    ObjectKind::String(s) => CloneKind::String(s.clone()),
+                    ObjectKind::Regex(re) => CloneKind::Regex(re.as_str().to_string()),
+                    ObjectKind::Buffer(b) => CloneKind::Buffer(b.borrow().clone()),
+                    ObjectKind::ArrayBuffer { detached, .. } if *detached.borrow() => {
+                        return Err(path)
+                    }
+                    ObjectKind::ArrayBuffer { data, .. } => {
+                        match transfer.iter().position(|k| *k == key) {
+                            Some(i) => CloneKind::Transferred(i),
+                            None => CloneKind::ArrayBuffer(data.borrow().clone()),
+                        }
+                    }
+                    ObjectKind::TypedArray {
+                        kind,
+                        buffer,
+                        offset,
+                        length,
+                    } => CloneKind::TypedArray {
+                        kind: *kind,
+                        buffer: Box::new(CloneData::from_value(
+                            buffer,
+                            format!("{}.buffer", path),
+                            seen,
+                            transfer,
+                        )?),
+                        offset: *offset,
+                        length: *length,
+                    },
+                    ObjectKind::Custom(..) if value.has_slot("time value") => {
+                        match (
+                            value.get_slot("time value").kind(),
+                            value.get_slot("time offset").kind(),
+                        ) {
+                            (ValueKind::Number(ms), ValueKind::Number(offset)) => {
+                                CloneKind::Time(ms as i64, offset as i64)
+                            }
+                            _ => unreachable!(),
+                        }
+                    }
+                    _ => return Err(path),
+                };
+                let mut data = Vec::new();
+                for (key, value) in properties.iter() {
+                    // like the html algorithm, only string keys are copied
+                    if let ObjectKey::Symbol(..) = key {
+                        continue;
+                    }
+                    let path = clone_path(&path, key);
+                    data.push((
+                        key.to_string(),
+                        CloneData::from_value(value, path, seen, transfer)?,
+                    ));
+                }
+                Ok(CloneData::Object(id, kind, data))
+            }
+            _ => Err(path),
+        }
+    }
+
+    /// Create the values described by this copy in `agent`.
+    pub fn to_value(self, agent: &Agent) -> Value {
+        self.build(agent, &mut Vec::new())
+    }
+
+    fn build(self, agent: &Agent, objects: &mut Vec<Value>) -> Value {
+        match self {
+            CloneData::Null => Value::Null,
+            CloneData::Boolean(b) => Value::Boolean(b),
+            CloneData::String(s) => Value::String(s),
+            CloneData::Number(n) => Value::Number(n),
+            CloneData::BigInt(n) => Value::BigInt(Rc::new(n)),
+            CloneData::Tuple(items) => {
+                Value::Tuple(items.into_iter().map(|d| d.build(agent, objects)).collect())
+            }
+            CloneData::Reference(id) => objects[id].clone(),
+            CloneData::Object(id, kind, properties) => {
+                debug_assert_eq!(id, objects.len());
+                // a typed array's buffer comes after it
+                objects.push(Value::Null);
+                let (object, items) = match kind {
+                    CloneKind::Ordinary => (
+                        Value::new_object(agent.intrinsics.object_prototype.clone()),
+                        None,
+                    ),
+                    CloneKind::Array(items) => (Value::new_array(agent), Some(items)),
+                    CloneKind::Boolean(b) => (Value::from(b).to_object(agent).unwrap(), None),
+                    CloneKind::Number(n) => (Value::from(n).to_object(agent).unwrap(), None),
+                    CloneKind::String(s) => (
+                        Value::from(s.into_iter().collect::<String>())
+                            .to_object(agent)
+                            .unwrap(),
+                        None,
+                    ),
+                    CloneKind::Regex(re) => (Value::new_regex_object(agent, &re).unwrap(), None),
+                    CloneKind::Buffer(b) => (Value::new_buffer_from_vec(agent, b), None),
+                    CloneKind::ArrayBuffer(b) => (Value::new_array_buffer(agent, b), None),
+                    CloneKind::Transferred(..) => unreachable!(),
+                    CloneKind::TypedArray {
+                        kind,
+                        buffer,
+                        offset,
+                        length,
+                    } => {
+                        let buffer = buffer.build(agent, objects);
+                        let prototype = agent
+                            .intrinsics
+                            .global_object
+                            .get(agent, ObjectKey::new(agent, kind.name()))
+                            .and_then(|c| c.get(agent, ObjectKey::new(agent, "prototype")))
+                            .unwrap_or_else(|_| agent.intrinsics.typed_array_prototype.clone());
+                        (
+                            Value::new_typed_array(prototype, kind, buffer, offset, length),
+                            None,
+                        )
+                    }
+                    CloneKind::Time(ms, offset) => (
+                        crate::builtins::time::create_time(agent, ms, offset).unwrap(),
+                        None,
+                    ),
+                };
+                objects[id] = object.clone();
+                if let ValueKind::Object(o) = object.kind() {
+                    if let (Some(items), ObjectKind::Array(values)) = (items, &o.kind) {
+                        for item in items {
+                            let item = item.build(agent, objects);
+                            values.borrow_mut().push(item);
+                        }
+                    }
+                    for (name, data) in properties {
+                        let value = data.build(agent, objects);
+                        o.properties
+                            .borrow_mut()
+                            .insert(ObjectKey::new(agent, &name), value);
+                    }
+                }
+                object
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Deep copy this value into `target`, which may be a different agent.
+    pub fn structured_clone(&self, target: &Agent) -> Result<Value, Value> {
+        match CloneData::new(self) {
+            Ok(data) => Ok(data.to_value(target)),
+            Err(path) => Err(data_clone_error(target, &path)),
+        }
+    }
+}
+
 fn inspect(
     agent: &Agent,
     value: &Value,
     indent: usize,
-    inspected: &mut HashSet<*const IndexMap<ObjectKey, Value>>,
+    inspected: &mut HashSet<*const PropertyMap>,
 ) -> String {
-    match value {
-        Value::Null => "null".to_string(),
-        Value::Boolean(b) => b.to_string(),
-        Value::Number(n) => crate::num_util::to_string(*n),
-        Value::String(s) => format!("'{}'", s),
-        Value::Symbol(s) => format!("{}", s),
-        Value::Tuple(items) => {
+    match value.kind() {
+        ValueKind::Null => "null".to_string(),
+        ValueKind::Boolean(b) => b.to_string(),
+        ValueKind::Number(n) => crate::num_util::to_string(n),
+        ValueKind::BigInt(n) => format!("{}n", n),
+        ValueKind::String(s) => format!("'{}'", s),
+        ValueKind::Symbol(s) => format!("{}", s),
+        ValueKind::Tuple(items) => {
             let mut ins = Vec::new();
             for item in items {
                 ins.push(inspect(agent, item, indent, inspected));
             }
             format!("({})", ins.join(", "))
         }
-        Value::Object(o) => {
+        ValueKind::Object(o) => {
             if let ObjectKind::Regex(re) = &o.kind {
                 return format!("/{}/", re);
             }
-            if o.prototype == agent.intrinsics.error_prototype {
-                if let Ok(Value::String(s)) = o.get(ObjectKey::well_known_symbol("toString")).call(
-                    agent,
-                    value.clone(),
-                    vec![],
-                ) {
-                    return s;
+            if let ObjectKind::Buffer(b) = &o.kind {
+                let hex = b
+                    .borrow()
+                    .iter()
+                    .map(|b| format!(" {:02x}", b))
+                    .collect::<String>();
+                return format!("<Bytes{}>", hex);
+            }
+            if let ObjectKind::ArrayBuffer { data, .. } = &o.kind {
+                let hex = data
+                    .borrow()
+                    .iter()
+                    .map(|b| format!(" {:02x}", b))
+                    .collect::<String>();
+                return format!("<ArrayBuffer{}>", hex);
+            }
+            if let ObjectKind::TypedArray {
+                kind,
+                buffer,
+                offset,
+                length,
+            } = &o.kind
+            {
+                let items = (0..typed_array::view_length(*kind, buffer, *offset, *length))
+                    .map(|i| {
+                        let n = typed_array::get_element(*kind, buffer, *offset, i).unwrap_or(0.0);
+                        crate::num_util::to_string(n)
+                    })
+                    .collect::<Vec<String>>();
+                return format!("{} [{}]", kind.name(), items.join(", "));
+            }
+            if let ObjectKind::Url { url, .. } = &o.kind {
+                return format!("URL '{}'", url.borrow());
+            }
+            if *o.prototype.borrow() == agent.intrinsics.error_prototype {
+                if let Ok(ValueKind::String(s)) = o
+                    .get(ObjectKey::well_known_symbol("toString"))
+                    .call(agent, value.clone(), vec![])
+                    .as_ref()
+                    .map(Value::kind)
+                {
+                    return s.clone();
                 }
             }
-            let hash_key = &*o.properties.borrow() as *const IndexMap<ObjectKey, Value>;
+            let hash_key = &*o.properties.borrow() as *const PropertyMap;
             if inspected.contains(&hash_key) {
                 "[Circular]".to_string()
             } else {
@@ -1218,16 +2708,15 @@ fn inspect(
                 let mut out = String::new();
                 if function {
                     out += "[Function";
-                    if let Value::String(name) = o.get(ObjectKey::from("name")) {
-                        out += " ";
-                        out += name.as_str();
-                        if keys.len() == 1 {
-                            out += "]";
-                            return out;
+                    match o.get(ObjectKey::NAME).kind() {
+                        ValueKind::String(name) if !name.is_empty() => {
+                            out += " ";
+                            out += name.as_str();
                         }
+                        _ => {}
                     }
                     out += "]";
-                    if keys.is_empty() {
+                    if keys.iter().all(|k| *k == ObjectKey::NAME) {
                         return out;
                     }
                 }
@@ -1237,13 +2726,17 @@ fn inspect(
                     return out;
                 }
                 for key in keys {
-                    if function && key == ObjectKey::from("name") {
+                    if function && key == ObjectKey::NAME {
                         continue;
                     }
+                    let name = match &key {
+                        ObjectKey::Symbol(s) => format!("[{}]", s),
+                        key => key.to_string(),
+                    };
                     out += &format!(
                         "\n{}{}: {},",
                         "  ".repeat(indent + 1),
-                        key.clone(),
+                        name,
                         inspect(
                             agent,
                             &value.get(agent, key).unwrap(),
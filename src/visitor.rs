@@ -0,0 +1,269 @@
+use crate::parser::Node;
+
+#[derive(Debug, PartialEq)]
+pub enum VisitResult {
+    /// Walk into the children of the node.
+    Continue,
+    /// Don't walk into the children of the node, but keep walking its siblings.
+    Skip,
+    /// Stop walking entirely.
+    Stop,
+    /// Replace the node. Only meaningful for `VisitorMut`, the replacement is
+    /// not walked.
+    Replace(Node),
+}
+
+// calls $body once for every direct child of $node, binding it to $child.
+// the same match works for `&Node` and `&mut Node`.
+macro_rules! each_child {
+    ($node:expr, $child:ident => $body:expr) => {
+        match $node {
+            Node::NullLiteral
+            | Node::TrueLiteral
+            | Node::FalseLiteral
+            | Node::NumberLiteral(..)
+            | Node::BigIntLiteral(..)
+            | Node::StringLiteral(..)
+            | Node::SymbolLiteral(..)
+            | Node::RegexLiteral(..)
+            | Node::Identifier(..)
+            | Node::RestElement(..)
+            | Node::ThisExpression
+            | Node::ImportMeta
+            | Node::BreakStatement(..)
+            | Node::ContinueStatement(..)
+            | Node::ImportDeclaration(..)
+            | Node::ImportNamedDeclaration(..)
+            | Node::ImportDefaultDeclaration(..)
+            | Node::ImportStandardDeclaration(..)
+            | Node::ErrorNode => {}
+
+            Node::ObjectLiteral(list)
+            | Node::ArrayLiteral(list)
+            | Node::TupleLiteral(list)
+            | Node::TemplateLiteral(_, list)
+            | Node::Block(_, list)
+            | Node::ArrayPattern(list, _)
+            | Node::TuplePattern(list) => {
+                for $child in list {
+                    $body;
+                }
+            }
+
+            Node::ExpressionStatement($child)
+            | Node::UnaryExpression(_, $child)
+            | Node::ParenthesizedExpression($child)
+            | Node::AwaitExpression($child)
+            | Node::NewExpression($child)
+            | Node::LabeledStatement(_, $child)
+            | Node::SpreadElement($child)
+            | Node::ObjectSpread($child)
+            | Node::MemberExpression($child, _)
+            | Node::LexicalInitialization(_, $child)
+            | Node::ThrowStatement($child)
+            | Node::ExportDeclaration($child) => {
+                $body;
+            }
+
+            Node::YieldExpression(expr) | Node::ReturnStatement(expr) => {
+                if let Some($child) = expr {
+                    $body;
+                }
+            }
+
+            Node::WhileLoop(a, b)
+            | Node::ForLoop(_, _, a, b)
+            | Node::BinaryExpression(_, a, b)
+            | Node::MatchArm(a, b)
+            | Node::ComputedMemberExpression(a, b)
+            | Node::Initializer(a, b) => {
+                let $child = a;
+                $body;
+                let $child = b;
+                $body;
+            }
+
+            Node::ConditionalExpression(a, b, c) => {
+                let $child = a;
+                $body;
+                let $child = b;
+                $body;
+                let $child = c;
+                $body;
+            }
+
+            Node::IfStatement(test, consequent, alternative) => {
+                let $child = test;
+                $body;
+                let $child = consequent;
+                $body;
+                if let Some($child) = alternative {
+                    $body;
+                }
+            }
+
+            Node::MatchExpression(expr, list)
+            | Node::CallExpression(expr, list)
+            | Node::TailCallExpression(expr, list) => {
+                let $child = expr;
+                $body;
+                for $child in list {
+                    $body;
+                }
+            }
+
+            Node::ObjectPattern(map, _) => {
+                for (_, $child) in map {
+                    $body;
+                }
+            }
+
+            Node::FunctionExpression(_, _, list, body)
+            | Node::FunctionDeclaration(_, _, list, body)
+            | Node::ArrowFunctionExpression(_, list, body) => {
+                for $child in list {
+                    $body;
+                }
+                let $child = body;
+                $body;
+            }
+
+            Node::ClassExpression(_, extends, list) | Node::ClassDeclaration(_, extends, list) => {
+                if let Some($child) = extends {
+                    $body;
+                }
+                for $child in list {
+                    $body;
+                }
+            }
+
+            Node::TryStatement(try_clause, _, catch_clause, finally_clause) => {
+                let $child = try_clause;
+                $body;
+                if let Some($child) = catch_clause {
+                    $body;
+                }
+                if let Some($child) = finally_clause {
+                    $body;
+                }
+            }
+        }
+    };
+}
+
+pub trait Visitor {
+    fn visit_node(&mut self, node: &Node) -> VisitResult {
+        match node {
+            Node::Identifier(name) => self.visit_identifier(name),
+            Node::FunctionExpression(..)
+            | Node::FunctionDeclaration(..)
+            | Node::ArrowFunctionExpression(..) => self.visit_function(node),
+            Node::CallExpression(..) | Node::TailCallExpression(..) => self.visit_call(node),
+            Node::Block(..) => self.visit_block(node),
+            _ => VisitResult::Continue,
+        }
+    }
+
+    fn visit_identifier(&mut self, _name: &str) -> VisitResult {
+        VisitResult::Continue
+    }
+
+    fn visit_function(&mut self, _node: &Node) -> VisitResult {
+        VisitResult::Continue
+    }
+
+    fn visit_call(&mut self, _node: &Node) -> VisitResult {
+        VisitResult::Continue
+    }
+
+    fn visit_block(&mut self, _node: &Node) -> VisitResult {
+        VisitResult::Continue
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_node(&mut self, node: &mut Node) -> VisitResult;
+}
+
+fn walk_inner(visitor: &mut dyn Visitor, node: &Node) -> bool {
+    match visitor.visit_node(node) {
+        VisitResult::Stop => return false,
+        VisitResult::Skip | VisitResult::Replace(..) => return true,
+        VisitResult::Continue => {}
+    }
+    each_child!(node, child => if !walk_inner(visitor, child) {
+        return false;
+    });
+    true
+}
+
+fn walk_mut_inner(visitor: &mut dyn VisitorMut, node: &mut Node) -> bool {
+    match visitor.visit_node(node) {
+        VisitResult::Stop => return false,
+        VisitResult::Skip => return true,
+        VisitResult::Replace(replacement) => {
+            *node = replacement;
+            return true;
+        }
+        VisitResult::Continue => {}
+    }
+    each_child!(node, child => if !walk_mut_inner(visitor, child) {
+        return false;
+    });
+    true
+}
+
+/// Walk `node` and all of its descendants in source order.
+pub fn walk(visitor: &mut dyn Visitor, node: &Node) {
+    walk_inner(visitor, node);
+}
+
+/// Walk `node` and all of its descendants in source order, allowing the
+/// visitor to rewrite nodes as it goes.
+pub fn walk_mut(visitor: &mut dyn VisitorMut, node: &mut Node) {
+    walk_mut_inner(visitor, node);
+}
+
+#[test]
+fn test_visitor_count_identifiers() {
+    struct Counter(usize);
+    impl Visitor for Counter {
+        fn visit_identifier(&mut self, _name: &str) -> VisitResult {
+            self.0 += 1;
+            VisitResult::Continue
+        }
+    }
+
+    let ast = crate::Parser::parse(
+        r#"
+        const a = b + c;
+        function f(x, y = z) {
+          return x(y, a.b);
+        }
+        "#,
+    )
+    .unwrap();
+    let mut counter = Counter(0);
+    walk(&mut counter, &ast);
+    // b, c, x, y, z, x, y, a
+    assert_eq!(counter.0, 8);
+}
+
+#[test]
+fn test_visitor_mut_replace() {
+    struct Rename;
+    impl VisitorMut for Rename {
+        fn visit_node(&mut self, node: &mut Node) -> VisitResult {
+            match node {
+                Node::Identifier(name) if name == "a" => {
+                    VisitResult::Replace(Node::Identifier("b".to_string()))
+                }
+                _ => VisitResult::Continue,
+            }
+        }
+    }
+
+    let mut ast = crate::Parser::parse("a + 1;").unwrap();
+    walk_mut(&mut Rename, &mut ast);
+    assert_eq!(ast, crate::Parser::parse("b + 1;").unwrap());
+}
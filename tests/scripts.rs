@@ -0,0 +1,62 @@
+use slither::{Agent, Value};
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+// run a script in a fresh agent until its event loop is drained, returning
+// what went wrong, if anything.
+fn run(path: &Path) -> Result<(), String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let uncaught = Rc::new(RefCell::new(Vec::new()));
+    let mut agent = Agent::new();
+    {
+        let uncaught = uncaught.clone();
+        agent.set_uncaught_exception_handler(move |agent, e| {
+            uncaught.borrow_mut().push(Value::inspect(agent, &e));
+        });
+    }
+    let result = agent.run_to_completion(path.to_str().unwrap(), &source);
+    let mut errors = uncaught.borrow().clone();
+    if let Err(e) = result {
+        errors.push(Value::inspect(&agent, &e));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+// every `.sl` file in tests/scripts is a test, which passes if it runs to
+// completion without throwing. Scripts use standard:assert to check things.
+#[test]
+fn scripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let mut paths = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |e| e == "sl"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scripts in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let name = path.file_name().unwrap().to_string_lossy();
+        match run(path) {
+            Ok(()) => println!("ok {}", name),
+            Err(e) => {
+                println!("FAIL {}", name);
+                failures.push(format!("{}:\n{}", name, e));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} scripts failed\n\n{}",
+            failures.len(),
+            paths.len(),
+            failures.join("\n\n")
+        );
+    }
+}
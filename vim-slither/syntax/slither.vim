@@ -95,7 +95,7 @@ syntax region  slSwitchCase   contained matchgroup=slLabel start=/\<\%(case\|def
 syntax keyword slTry                    try             skipwhite skipempty nextgroup=slTryCatchBlock
 syntax keyword slFinally      contained finally         skipwhite skipempty nextgroup=slFinallyBlock
 syntax keyword slCatch        contained catch           skipwhite skipempty nextgroup=slParenCatch
-syntax keyword slException              throw
+syntax keyword slException              throw defer
 syntax keyword slAsyncKeyword           async await
 syntax match   slSwitchColon   contained /::\@!/        skipwhite skipempty nextgroup=slSwitchBlock
 